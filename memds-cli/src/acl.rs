@@ -0,0 +1,124 @@
+use std::io::{self, Error, ErrorKind};
+
+use memds_proto::memds_api::*;
+use memds_proto::memds_api_grpc::MemdsClient;
+
+use crate::util;
+
+// execute single ACL op, returning its result
+fn acl_exec(client: &MemdsClient, op: Operation, what: &str) -> io::Result<OpResult> {
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", what, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    Ok(result.clone())
+}
+
+fn user_op(otype: OpType, name: &str, rules: &[&str]) -> Operation {
+    let mut acl_req = AclOp::new();
+    acl_req.set_username(name.to_string());
+    for rule in rules.iter() {
+        acl_req.rules.push(rule.to_string());
+    }
+
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_acl(acl_req);
+    op
+}
+
+pub fn setuser(client: &MemdsClient, name: &str, rules: &[&str]) -> io::Result<()> {
+    acl_exec(client, user_op(OpType::ACL_SETUSER, name, rules), name)?;
+    println!("ok");
+    Ok(())
+}
+
+pub fn getuser(client: &MemdsClient, name: &str) -> io::Result<()> {
+    let result = acl_exec(client, user_op(OpType::ACL_GETUSER, name, &[]), name)?;
+
+    let user = &result.get_acl().get_users()[0];
+    println!("enabled: {}", user.enabled);
+    println!("nopass: {}", user.nopass);
+    println!("categories: {}", user.categories.join(" "));
+    for pattern in user.key_patterns.iter() {
+        println!("keys: {}", String::from_utf8_lossy(pattern));
+    }
+    Ok(())
+}
+
+pub fn deluser(client: &MemdsClient, name: &str) -> io::Result<()> {
+    acl_exec(client, user_op(OpType::ACL_DELUSER, name, &[]), name)?;
+    println!("ok");
+    Ok(())
+}
+
+pub fn list(client: &MemdsClient) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::ACL_LIST;
+    let result = acl_exec(client, op, "acl-list")?;
+
+    for user in result.get_acl().get_users() {
+        println!("user {} {}", user.username, user.rules.join(" "));
+    }
+    Ok(())
+}
+
+pub fn whoami(client: &MemdsClient) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::ACL_WHOAMI;
+    let result = acl_exec(client, op, "acl-whoami")?;
+
+    println!("{}", result.get_acl().username);
+    Ok(())
+}
+
+pub mod args {
+    use clap::{App, Arg, SubCommand};
+
+    pub fn acl_deluser() -> App<'static> {
+        SubCommand::with_name("acl-deluser")
+            .about("Acl.DelUser: Delete user")
+            .arg(Arg::with_name("name").help("User name").required(true))
+    }
+
+    pub fn acl_getuser() -> App<'static> {
+        SubCommand::with_name("acl-getuser")
+            .about("Acl.GetUser: Show user's permissions")
+            .arg(Arg::with_name("name").help("User name").required(true))
+    }
+
+    pub fn acl_list() -> App<'static> {
+        SubCommand::with_name("acl-list").about("Acl.List: List users, as ACL file rules")
+    }
+
+    pub fn acl_setuser() -> App<'static> {
+        SubCommand::with_name("acl-setuser")
+            .about("Acl.SetUser: Create or modify user, applying rules in order")
+            .arg(Arg::with_name("name").help("User name").required(true))
+            .arg(
+                Arg::with_name("rule")
+                    .help("Rules: on, off, >password, ~pattern, +@category, ...")
+                    .multiple(true)
+                    .allow_hyphen_values(true),
+            )
+    }
+
+    pub fn acl_whoami() -> App<'static> {
+        SubCommand::with_name("acl-whoami").about("Acl.WhoAmI: Show user authenticated as")
+    }
+}
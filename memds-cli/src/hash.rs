@@ -0,0 +1,467 @@
+use std::io::{self, Error, ErrorKind, Write};
+
+use memds_proto::memds_api::*;
+use memds_proto::memds_api_grpc::MemdsClient;
+
+use crate::util;
+
+// execute single hash operation, returning its result
+fn hash_exec(client: &MemdsClient, key: &str, op: Operation) -> io::Result<OpResult> {
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", key, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    Ok(result.clone())
+}
+
+pub fn set(
+    client: &MemdsClient,
+    key: &str,
+    field_values: &Vec<&str>,
+    only_new: bool,
+) -> io::Result<()> {
+    if field_values.len() % 2 != 0 {
+        let msg = format!("{}: field/value pairs required", key);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let mut op_req = HashSetOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    for pair in field_values.chunks(2) {
+        let mut fv = FieldValue::new();
+        fv.set_field(pair[0].as_bytes().to_vec());
+        fv.set_value(pair[1].as_bytes().to_vec());
+        op_req.pairs.push(fv);
+    }
+
+    let mut op = Operation::new();
+    op.otype = match only_new {
+        true => OpType::HASH_SETNX,
+        false => OpType::HASH_SET,
+    };
+    op.set_hset(op_req);
+
+    let result = hash_exec(client, key, op)?;
+
+    let count_res = result.get_count();
+    println!("{}", count_res.n);
+    Ok(())
+}
+
+pub fn get(client: &MemdsClient, key: &str, field: &str) -> io::Result<()> {
+    let mut op_req = KeyedListOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    op_req.elements.push(field.as_bytes().to_vec());
+
+    let mut op = Operation::new();
+    op.otype = OpType::HASH_GET;
+    op.set_keyed_list(op_req);
+
+    let result = hash_exec(client, key, op)?;
+
+    let get_res = result.get_get();
+    io::stdout().write_all(get_res.get_value())?;
+    Ok(())
+}
+
+pub fn mget(client: &MemdsClient, key: &str, fields: &Vec<&str>) -> io::Result<()> {
+    let mut op_req = KeyedListOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    for field in fields.iter() {
+        op_req.elements.push(field.as_bytes().to_vec());
+    }
+
+    let mut op = Operation::new();
+    op.otype = OpType::HASH_MGET;
+    op.set_keyed_list(op_req);
+
+    let result = hash_exec(client, key, op)?;
+
+    // output values, one per line, in request order.  absent fields
+    // output an empty line.
+    let hash_res = result.get_hash();
+    for fv in hash_res.pairs.iter() {
+        io::stdout().write_all(&fv.value)?;
+        io::stdout().write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+pub fn fields_query(
+    client: &MemdsClient,
+    key: &str,
+    fields: &Vec<&str>,
+    otype: OpType,
+) -> io::Result<()> {
+    let mut op_req = KeyedListOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    for field in fields.iter() {
+        op_req.elements.push(field.as_bytes().to_vec());
+    }
+
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_keyed_list(op_req);
+
+    let result = hash_exec(client, key, op)?;
+
+    let count_res = result.get_count();
+    println!("{}", count_res.n);
+    Ok(())
+}
+
+pub fn len(client: &MemdsClient, key: &str) -> io::Result<()> {
+    let mut key_req = KeyOp::new();
+    key_req.set_key(key.as_bytes().to_vec());
+
+    let mut op = Operation::new();
+    op.otype = OpType::HASH_LEN;
+    op.set_key(key_req);
+
+    let result = hash_exec(client, key, op)?;
+
+    let count_res = result.get_count();
+    println!("{}", count_res.n);
+    Ok(())
+}
+
+pub fn getall(client: &MemdsClient, key: &str, otype: OpType) -> io::Result<()> {
+    let mut key_req = KeyOp::new();
+    key_req.set_key(key.as_bytes().to_vec());
+
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_key(key_req);
+
+    let result = hash_exec(client, key, op)?;
+
+    // HASH_GETALL: output field, then value, one per line
+    if otype == OpType::HASH_GETALL {
+        let hash_res = result.get_hash();
+        for fv in hash_res.pairs.iter() {
+            io::stdout().write_all(&fv.field)?;
+            io::stdout().write_all(b"\n")?;
+            io::stdout().write_all(&fv.value)?;
+            io::stdout().write_all(b"\n")?;
+        }
+
+    // HASH_KEYS, HASH_VALS: output fields or values, one per line
+    } else {
+        let list_res = result.get_list();
+        for element in list_res.elements.iter() {
+            io::stdout().write_all(element)?;
+            io::stdout().write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+pub fn incrby(client: &MemdsClient, key: &str, field: &str, n: i64) -> io::Result<()> {
+    let mut op_req = HashIncrOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    op_req.set_field(field.as_bytes().to_vec());
+    op_req.n = n;
+
+    let mut op = Operation::new();
+    op.otype = OpType::HASH_INCRBY;
+    op.set_hincr(op_req);
+
+    let result = hash_exec(client, key, op)?;
+
+    // output new value
+    let num_res = result.get_num();
+    println!("{}", num_res.old_value + n);
+    Ok(())
+}
+
+pub fn incrbyfloat(client: &MemdsClient, key: &str, field: &str, n: f64) -> io::Result<()> {
+    let mut op_req = HashIncrOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    op_req.set_field(field.as_bytes().to_vec());
+    op_req.n_float = n;
+
+    let mut op = Operation::new();
+    op.otype = OpType::HASH_INCRBYFLOAT;
+    op.set_hincr(op_req);
+
+    let result = hash_exec(client, key, op)?;
+
+    // output new value
+    let num_res = result.get_num();
+    println!("{}", num_res.old_float + n);
+    Ok(())
+}
+
+pub fn scan(
+    client: &MemdsClient,
+    key: &str,
+    cursor: u64,
+    pattern: Option<&str>,
+    count: u32,
+) -> io::Result<()> {
+    let mut scan_req = KeyScanOp::new();
+    scan_req.set_key(key.as_bytes().to_vec());
+    scan_req.cursor = cursor;
+    if let Some(pat) = pattern {
+        scan_req.set_pattern(pat.as_bytes().to_vec());
+    }
+    scan_req.count = count;
+
+    let mut op = Operation::new();
+    op.otype = OpType::HASH_SCAN;
+    op.set_scan(scan_req);
+
+    let result = hash_exec(client, key, op)?;
+
+    // output next cursor, followed by field, then value, one per line
+    let scan_res = result.get_scan();
+    println!("{}", scan_res.cursor);
+    for fv in scan_res.pairs.iter() {
+        io::stdout().write_all(&fv.field)?;
+        io::stdout().write_all(b"\n")?;
+        io::stdout().write_all(&fv.value)?;
+        io::stdout().write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+pub mod args {
+    use clap::{App, Arg, SubCommand};
+
+    pub fn hdel() -> App<'static> {
+        SubCommand::with_name("hdel")
+            .about("Hash.Del: Remove fields from hash")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("field")
+                    .help("Field to remove")
+                    .required(true)
+                    .multiple(true),
+            )
+    }
+
+    pub fn hexists() -> App<'static> {
+        SubCommand::with_name("hexists")
+            .about("Hash.Exists: Test existence of fields in hash")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("field")
+                    .help("Field to test")
+                    .required(true)
+                    .multiple(true),
+            )
+    }
+
+    pub fn hget() -> App<'static> {
+        SubCommand::with_name("hget")
+            .about("Hash.Get: Retrieve value of hash field")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("field")
+                    .help("Field to retrieve")
+                    .required(true),
+            )
+    }
+
+    pub fn hgetall() -> App<'static> {
+        SubCommand::with_name("hgetall")
+            .about("Hash.GetAll: Retrieve all fields and values of hash")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to query")
+                    .required(true),
+            )
+    }
+
+    pub fn hincrby() -> App<'static> {
+        SubCommand::with_name("hincrby")
+            .about("Hash.IncrBy: Increment integer value of hash field")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("field")
+                    .help("Field to increment")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("n")
+                    .help("Amount of increment")
+                    .required(true)
+                    .allow_hyphen_values(true),
+            )
+    }
+
+    pub fn hincrbyfloat() -> App<'static> {
+        SubCommand::with_name("hincrbyfloat")
+            .about("Hash.IncrByFloat: Increment floating point value of hash field")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("field")
+                    .help("Field to increment")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("n")
+                    .help("Amount of increment")
+                    .required(true)
+                    .allow_hyphen_values(true),
+            )
+    }
+
+    pub fn hkeys() -> App<'static> {
+        SubCommand::with_name("hkeys")
+            .about("Hash.Keys: Retrieve all fields of hash")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to query")
+                    .required(true),
+            )
+    }
+
+    pub fn hlen() -> App<'static> {
+        SubCommand::with_name("hlen")
+            .about("Hash.Len: Number of fields in hash")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to query")
+                    .required(true),
+            )
+    }
+
+    pub fn hmget() -> App<'static> {
+        SubCommand::with_name("hmget")
+            .about("Hash.MGet: Retrieve values of multiple hash fields")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("field")
+                    .help("Field to retrieve")
+                    .required(true)
+                    .multiple(true),
+            )
+    }
+
+    pub fn hscan() -> App<'static> {
+        SubCommand::with_name("hscan")
+            .about("Hash.Scan: Incrementally iterate over hash fields")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("cursor")
+                    .help("Cursor returned by previous scan (default: 0, begin scan)"),
+            )
+            .arg(
+                Arg::with_name("match")
+                    .help("Return fields matching glob-style pattern")
+                    .long("match")
+                    .value_name("pattern"),
+            )
+            .arg(
+                Arg::with_name("count")
+                    .help("Number of fields to examine (default: 10)")
+                    .long("count")
+                    .value_name("n"),
+            )
+    }
+
+    pub fn hset() -> App<'static> {
+        SubCommand::with_name("hset")
+            .about("Hash.Set: Store fields in hash")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("field_value")
+                    .help("Field, followed by value, of each item to store")
+                    .required(true)
+                    .multiple(true),
+            )
+    }
+
+    pub fn hsetnx() -> App<'static> {
+        SubCommand::with_name("hsetnx")
+            .about("Hash.SetNX: Store field in hash, if field does not exist")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("field")
+                    .help("Field to store")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("value")
+                    .help("Value to store")
+                    .required(true),
+            )
+    }
+
+    pub fn hstrlen() -> App<'static> {
+        SubCommand::with_name("hstrlen")
+            .about("Hash.StrLen: Length of value of hash field")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("field")
+                    .help("Field to query")
+                    .required(true),
+            )
+    }
+
+    pub fn hvals() -> App<'static> {
+        SubCommand::with_name("hvals")
+            .about("Hash.Vals: Retrieve all values of hash")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of hash to query")
+                    .required(true),
+            )
+    }
+}
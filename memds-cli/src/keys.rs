@@ -25,7 +25,7 @@ pub fn del_exist(client: &MemdsClient, keys: &Vec<&str>, remove_it: bool) -> io:
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -43,6 +43,41 @@ pub fn del_exist(client: &MemdsClient, keys: &Vec<&str>, remove_it: bool) -> io:
     Ok(())
 }
 
+pub fn version(client: &MemdsClient, keys: &[&str]) -> io::Result<()> {
+    let mut op_req = KeyListOp::new();
+    for key in keys {
+        op_req.keys.push(key.as_bytes().to_vec());
+    }
+
+    let mut op = Operation::new();
+    op.otype = OpType::KEYS_VERSION;
+    op.set_key_list(op_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert_eq!(results.len(), 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}...: {}", keys[0], result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    for (key, version) in keys.iter().zip(result.get_versions()) {
+        println!("{}:{}", key, version);
+    }
+    Ok(())
+}
+
 pub fn rename(
     client: &MemdsClient,
     old_key: &str,
@@ -64,7 +99,7 @@ pub fn rename(
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -81,6 +116,50 @@ pub fn rename(
     Ok(())
 }
 
+pub fn move_copy(
+    client: &MemdsClient,
+    key: &str,
+    dest_db: &str,
+    dest_key: Option<&str>,
+    replace: bool,
+    otype: OpType,
+) -> io::Result<()> {
+    let mut key_req = KeyMoveOp::new();
+    key_req.set_key(key.as_bytes().to_vec());
+    key_req.set_dest_db(dest_db.as_bytes().to_vec());
+    if let Some(dest_key) = dest_key {
+        key_req.set_dest_key(dest_key.as_bytes().to_vec());
+    }
+    key_req.replace = replace;
+
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_key_move(key_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", key, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let count_res = results[0].get_count();
+    println!("{}", count_res.n);
+    Ok(())
+}
+
 pub fn dump(client: &MemdsClient, key: &str) -> io::Result<()> {
     let mut key_req = KeyOp::new();
     key_req.set_key(key.as_bytes().to_vec());
@@ -95,7 +174,7 @@ pub fn dump(client: &MemdsClient, key: &str) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -128,7 +207,7 @@ pub fn typ(client: &MemdsClient, key: &str) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -146,6 +225,186 @@ pub fn typ(client: &MemdsClient, key: &str) -> io::Result<()> {
     Ok(())
 }
 
+pub fn expire(client: &MemdsClient, key: &str, expire_ms: i64, absolute: bool) -> io::Result<()> {
+    let mut op_req = KeyExpireOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    op_req.expire_ms = expire_ms;
+    op_req.absolute = absolute;
+
+    let mut op = Operation::new();
+    op.otype = OpType::KEY_EXPIRE;
+    op.set_expire(op_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", key, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let count_res = results[0].get_count();
+    println!("{}", count_res.n);
+    Ok(())
+}
+
+pub fn ttl(client: &MemdsClient, key: &str, want_ms: bool) -> io::Result<()> {
+    let mut key_req = KeyOp::new();
+    key_req.set_key(key.as_bytes().to_vec());
+
+    let mut op = Operation::new();
+    op.otype = OpType::KEY_TTL;
+    op.set_key(key_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", key, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    // server returns milliseconds; round to nearest second if requested
+    let ttl_ms = results[0].get_ttl().ttl_ms;
+    if want_ms || ttl_ms < 0 {
+        println!("{}", ttl_ms);
+    } else {
+        println!("{}", (ttl_ms + 500) / 1000);
+    }
+    Ok(())
+}
+
+pub fn persist(client: &MemdsClient, key: &str) -> io::Result<()> {
+    let mut key_req = KeyOp::new();
+    key_req.set_key(key.as_bytes().to_vec());
+
+    let mut op = Operation::new();
+    op.otype = OpType::KEY_PERSIST;
+    op.set_key(key_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", key, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let count_res = results[0].get_count();
+    println!("{}", count_res.n);
+    Ok(())
+}
+
+fn scan_exec(
+    client: &MemdsClient,
+    cursor: u64,
+    pattern: Option<&str>,
+    count: u32,
+    typ: AtomType,
+) -> io::Result<KeyScanRes> {
+    let mut scan_req = KeyScanOp::new();
+    scan_req.cursor = cursor;
+    if let Some(pat) = pattern {
+        scan_req.set_pattern(pat.as_bytes().to_vec());
+    }
+    scan_req.count = count;
+    scan_req.typ = typ;
+
+    let mut op = Operation::new();
+    op.otype = OpType::KEYS_SCAN;
+    op.set_scan(scan_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("keys-scan: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    Ok(results[0].get_scan().clone())
+}
+
+pub fn scan(
+    client: &MemdsClient,
+    cursor: u64,
+    pattern: Option<&str>,
+    count: u32,
+    typ: AtomType,
+) -> io::Result<()> {
+    let scan_res = scan_exec(client, cursor, pattern, count, typ)?;
+
+    // output next cursor, followed by keys, one per line
+    println!("{}", scan_res.cursor);
+    for key in scan_res.keys.iter() {
+        io::stdout().write_all(key)?;
+        io::stdout().write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+pub fn keys(client: &MemdsClient, pattern: &str) -> io::Result<()> {
+    // scan entire keyspace, outputting matching keys, one per line
+    let mut cursor = 0;
+    loop {
+        let scan_res = scan_exec(client, cursor, Some(pattern), 1000, AtomType::NOTYPE)?;
+        for key in scan_res.keys.iter() {
+            io::stdout().write_all(key)?;
+            io::stdout().write_all(b"\n")?;
+        }
+
+        cursor = scan_res.cursor;
+        if cursor == 0 {
+            break;
+        }
+    }
+    Ok(())
+}
+
 fn read_file(in_fn: &str) -> io::Result<Vec<u8>> {
     let mut f = File::open(in_fn)?;
     let mut buffer = Vec::new();
@@ -172,7 +431,7 @@ pub fn restore(client: &MemdsClient, key: Option<&str>, restore_fn: &str) -> io:
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -203,6 +462,17 @@ pub mod args {
             )
     }
 
+    pub fn version() -> App<'static> {
+        SubCommand::with_name("version")
+            .about("Keys.Version: Retrieve versions of listed keys, for use with --watch")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key to query")
+                    .required(true)
+                    .multiple(true),
+            )
+    }
+
     pub fn dump() -> App<'static> {
         SubCommand::with_name("dump")
             .about("Keys.Dump: Dump listed key")
@@ -220,6 +490,137 @@ pub mod args {
             )
     }
 
+    pub fn expire() -> App<'static> {
+        SubCommand::with_name("expire")
+            .about("Keys.Expire: Set item time-to-live, in seconds")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("seconds")
+                    .help("Time-to-live, in seconds")
+                    .required(true),
+            )
+    }
+
+    pub fn expireat() -> App<'static> {
+        SubCommand::with_name("expireat")
+            .about("Keys.ExpireAt: Set item expiration, as unix time in seconds")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("timestamp")
+                    .help("Expiration time, in seconds since the unix epoch")
+                    .required(true),
+            )
+    }
+
+    pub fn persist() -> App<'static> {
+        SubCommand::with_name("persist")
+            .about("Keys.Persist: Remove item expiration")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to update")
+                    .required(true),
+            )
+    }
+
+    pub fn pexpire() -> App<'static> {
+        SubCommand::with_name("pexpire")
+            .about("Keys.PExpire: Set item time-to-live, in milliseconds")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("milliseconds")
+                    .help("Time-to-live, in milliseconds")
+                    .required(true),
+            )
+    }
+
+    pub fn pexpireat() -> App<'static> {
+        SubCommand::with_name("pexpireat")
+            .about("Keys.PExpireAt: Set item expiration, as unix time in milliseconds")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("timestamp")
+                    .help("Expiration time, in milliseconds since the unix epoch")
+                    .required(true),
+            )
+    }
+
+    pub fn pttl() -> App<'static> {
+        SubCommand::with_name("pttl")
+            .about("Keys.PTTL: Query item time-to-live, in milliseconds")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to query")
+                    .required(true),
+            )
+    }
+
+    pub fn keys() -> App<'static> {
+        SubCommand::with_name("keys")
+            .about("Keys.Keys: List all keys matching pattern")
+            .arg(
+                Arg::with_name("pattern")
+                    .help("Glob-style pattern")
+                    .required(true),
+            )
+    }
+
+    pub fn copy() -> App<'static> {
+        SubCommand::with_name("copy")
+            .about("Keys.Copy: Copy item to another database")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to copy")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("dest_db")
+                    .help("Destination database")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("dest_key")
+                    .help("Destination key of item (default: same key)")
+                    .long("dest-key")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("replace")
+                    .help("Replace destination item, if present")
+                    .long("replace"),
+            )
+    }
+
+    pub fn movekey() -> App<'static> {
+        SubCommand::with_name("move")
+            .about("Keys.Move: Move item to another database")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to move")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("dest_db")
+                    .help("Destination database")
+                    .required(true),
+            )
+    }
+
     pub fn rename() -> App<'static> {
         SubCommand::with_name("rename")
             .about("Keys.Rename: Rename item key")
@@ -267,6 +668,44 @@ pub mod args {
             )
     }
 
+    pub fn scan() -> App<'static> {
+        SubCommand::with_name("scan")
+            .about("Keys.Scan: Incrementally iterate over keys")
+            .arg(
+                Arg::with_name("cursor")
+                    .help("Cursor returned by previous scan (default: 0, begin scan)"),
+            )
+            .arg(
+                Arg::with_name("match")
+                    .help("Return keys matching glob-style pattern")
+                    .long("match")
+                    .value_name("pattern"),
+            )
+            .arg(
+                Arg::with_name("count")
+                    .help("Number of keys to examine (default: 10)")
+                    .long("count")
+                    .value_name("n"),
+            )
+            .arg(
+                Arg::with_name("type")
+                    .help("Return keys of this data type only")
+                    .long("type")
+                    .value_name("type")
+                    .possible_values(["string", "list", "set", "hash", "zset"]),
+            )
+    }
+
+    pub fn ttl() -> App<'static> {
+        SubCommand::with_name("ttl")
+            .about("Keys.TTL: Query item time-to-live, in seconds")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to query")
+                    .required(true),
+            )
+    }
+
     pub fn typ() -> App<'static> {
         SubCommand::with_name("type")
             .about("Keys.Type: Query item data type")
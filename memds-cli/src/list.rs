@@ -20,7 +20,7 @@ pub fn lindex(client: &MemdsClient, key: &str, index: i32) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -58,7 +58,7 @@ pub fn llen(client: &MemdsClient, key: &str) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -91,7 +91,7 @@ pub fn pop(client: &MemdsClient, key: &str, at_head: bool) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -140,7 +140,7 @@ pub fn push(
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
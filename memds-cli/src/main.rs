@@ -3,17 +3,27 @@ extern crate clap;
 use clap::value_t;
 
 use grpcio::*;
-use memds_proto::memds_api::OpType;
+use memds_proto::memds_api::{
+    AtomType, OpType, ShutdownOp_Mode, ZRangeOp_RangeBy, ZStoreOp_Aggregate,
+};
 use memds_proto::memds_api_grpc::MemdsClient;
 use std::io;
 use std::sync::Arc;
 
+use string::SetOptions;
+use zset::{ZAddOptions, ZRangeOptions};
+
+mod acl;
+mod hash;
 mod keys;
 mod list;
+mod script;
 mod server;
 mod set;
 mod string;
 mod util;
+mod wasm;
+mod zset;
 
 const APPNAME: &'static str = "memds-cli";
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -24,13 +34,131 @@ fn main() -> io::Result<()> {
     let cli_matches = clap::App::new(APPNAME)
         .version(VERSION)
         .about("Memds CLI")
+        .arg(
+            clap::Arg::with_name("db")
+                .help("Database key (default: server's default database)")
+                .long("db")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("socket")
+                .help("Connect to server's unix socket, rather than TCP")
+                .long("socket")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("tls")
+                .help("Connect with TLS")
+                .long("tls")
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("ca")
+                .help("TLS: CA certificates verifying server (default: system roots)")
+                .long("ca")
+                .value_name("PEM-FILE")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("tls-cert")
+                .help("TLS: client certificate")
+                .long("tls-cert")
+                .value_name("PEM-FILE")
+                .takes_value(true)
+                .requires("tls-key")
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("tls-key")
+                .help("TLS: client certificate's private key")
+                .long("tls-key")
+                .value_name("PEM-FILE")
+                .takes_value(true)
+                .requires("tls-cert")
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("tls-server-name")
+                .help("TLS: server name expected in certificate, if not the host")
+                .long("tls-server-name")
+                .value_name("NAME")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("user")
+                .help("Authenticate as user (default: default user)")
+                .long("user")
+                .value_name("NAME")
+                .takes_value(true)
+                .requires("password")
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("password")
+                .help("Authenticate with password")
+                .long("password")
+                .takes_value(true)
+                .conflicts_with("token")
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("token")
+                .help("Authenticate with token, identifying its user")
+                .long("token")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("watch")
+                .help("Execute only if key is at version, as KEY:VERSION (see version)")
+                .long("watch")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .global(true),
+        )
+        .subcommand(acl::args::acl_deluser())
+        .subcommand(acl::args::acl_getuser())
+        .subcommand(acl::args::acl_list())
+        .subcommand(acl::args::acl_setuser())
+        .subcommand(acl::args::acl_whoami())
+        .subcommand(hash::args::hdel())
+        .subcommand(hash::args::hexists())
+        .subcommand(hash::args::hget())
+        .subcommand(hash::args::hgetall())
+        .subcommand(hash::args::hincrby())
+        .subcommand(hash::args::hincrbyfloat())
+        .subcommand(hash::args::hkeys())
+        .subcommand(hash::args::hlen())
+        .subcommand(hash::args::hmget())
+        .subcommand(hash::args::hscan())
+        .subcommand(hash::args::hset())
+        .subcommand(hash::args::hsetnx())
+        .subcommand(hash::args::hstrlen())
+        .subcommand(hash::args::hvals())
+        .subcommand(keys::args::copy())
         .subcommand(keys::args::del())
         .subcommand(keys::args::dump())
         .subcommand(keys::args::exists())
+        .subcommand(keys::args::expire())
+        .subcommand(keys::args::expireat())
+        .subcommand(keys::args::keys())
+        .subcommand(keys::args::movekey())
+        .subcommand(keys::args::persist())
+        .subcommand(keys::args::pexpire())
+        .subcommand(keys::args::pexpireat())
+        .subcommand(keys::args::pttl())
         .subcommand(keys::args::rename())
         .subcommand(keys::args::renamenx())
         .subcommand(keys::args::restore())
+        .subcommand(keys::args::scan())
+        .subcommand(keys::args::ttl())
         .subcommand(keys::args::typ())
+        .subcommand(keys::args::version())
         .subcommand(list::args::lindex())
         .subcommand(list::args::llen())
         .subcommand(list::args::lpop())
@@ -39,10 +167,22 @@ fn main() -> io::Result<()> {
         .subcommand(list::args::rpop())
         .subcommand(list::args::rpush())
         .subcommand(list::args::rpushx())
+        .subcommand(script::args::eval())
+        .subcommand(script::args::evalsha())
+        .subcommand(script::args::script_exists())
+        .subcommand(script::args::script_flush())
+        .subcommand(script::args::script_load())
+        .subcommand(server::args::bgrewriteaof())
         .subcommand(server::args::bgsave())
+        .subcommand(server::args::command())
+        .subcommand(server::args::command_count())
         .subcommand(server::args::dbsize())
         .subcommand(server::args::flushall())
         .subcommand(server::args::flushdb())
+        .subcommand(server::args::lastsave())
+        .subcommand(server::args::save())
+        .subcommand(server::args::save_status())
+        .subcommand(server::args::shutdown())
         .subcommand(server::args::time())
         .subcommand(set::args::sadd())
         .subcommand(set::args::scard())
@@ -64,24 +204,111 @@ fn main() -> io::Result<()> {
         .subcommand(string::args::getset())
         .subcommand(string::args::incr())
         .subcommand(string::args::incrby())
+        .subcommand(string::args::psetex())
         .subcommand(string::args::set())
+        .subcommand(string::args::setex())
         .subcommand(string::args::setnx())
         .subcommand(string::args::strlen())
+        .subcommand(wasm::args::module_call())
+        .subcommand(wasm::args::module_list())
+        .subcommand(wasm::args::module_load())
+        .subcommand(wasm::args::module_unload())
+        .subcommand(zset::args::zadd())
+        .subcommand(zset::args::zcard())
+        .subcommand(zset::args::zcount())
+        .subcommand(zset::args::zinterstore())
+        .subcommand(zset::args::zpopmax())
+        .subcommand(zset::args::zpopmin())
+        .subcommand(zset::args::zrange())
+        .subcommand(zset::args::zrank())
+        .subcommand(zset::args::zrem())
+        .subcommand(zset::args::zrevrank())
+        .subcommand(zset::args::zscore())
+        .subcommand(zset::args::zunionstore())
         .get_matches();
 
     let endpoint = format!("{}:{}", DEF_BIND_HOST, memds_proto::DEF_PORT);
 
     let env = Arc::new(Environment::new(2));
-    let channel = ChannelBuilder::new(env).connect(&endpoint);
+    let tls = ["tls", "ca", "tls-cert"]
+        .iter()
+        .any(|arg| cli_matches.is_present(arg));
+    let channel = if tls {
+        let creds = util::channel_credentials(
+            cli_matches.value_of("ca"),
+            cli_matches.value_of("tls-cert"),
+            cli_matches.value_of("tls-key"),
+        )?;
+        let mut builder = ChannelBuilder::new(env);
+        if let Some(name) = cli_matches.value_of("tls-server-name") {
+            builder = builder.override_ssl_target(name);
+        }
+        builder.secure_connect(&endpoint, creds)
+    } else {
+        ChannelBuilder::new(env).connect(&endpoint)
+    };
     let client = MemdsClient::new(channel);
 
+    if let Some(path) = cli_matches.value_of("socket") {
+        util::connect_socket(path)?;
+    }
+    if cli_matches.is_present("password") || cli_matches.is_present("token") {
+        util::authenticate(
+            cli_matches.value_of("user"),
+            cli_matches.value_of("password"),
+            cli_matches.value_of("token"),
+        );
+    }
+    if let Some(db) = cli_matches.value_of("db") {
+        util::select_db(db);
+    }
+    if let Some(watches) = cli_matches.values_of("watch") {
+        for watch in watches {
+            util::watch(watch)?;
+        }
+    }
+
     match cli_matches.subcommand() {
+        Some(("acl-deluser", matches)) => {
+            let name = matches.value_of("name").unwrap();
+            acl::deluser(&client, name)
+        }
+        Some(("acl-getuser", matches)) => {
+            let name = matches.value_of("name").unwrap();
+            acl::getuser(&client, name)
+        }
+        Some(("acl-list", _matches)) => acl::list(&client),
+        Some(("acl-setuser", matches)) => {
+            let name = matches.value_of("name").unwrap();
+            let rules: Vec<_> = match matches.values_of("rule") {
+                None => Vec::new(),
+                Some(rules) => rules.collect(),
+            };
+            acl::setuser(&client, name, &rules)
+        }
+        Some(("acl-whoami", _matches)) => acl::whoami(&client),
         Some(("append", matches)) => {
             let key = matches.value_of("key").unwrap();
             let value = matches.value_of("value").unwrap();
-            string::set(&client, key, value, false, true, false)
+            string::set(&client, key, value, true, &SetOptions::default())
         }
+        Some(("bgrewriteaof", _matches)) => server::bgrewriteaof(&client),
         Some(("bgsave", _matches)) => server::bgsave(&client),
+        Some(("command", matches)) => {
+            let names: Vec<_> = match matches.values_of("name") {
+                None => Vec::new(),
+                Some(names) => names.collect(),
+            };
+            server::command(&client, &names)
+        }
+        Some(("command-count", _matches)) => server::command_count(&client),
+        Some(("copy", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let dest_db = matches.value_of("dest_db").unwrap();
+            let dest_key = matches.value_of("dest_key");
+            let replace = matches.is_present("replace");
+            keys::move_copy(&client, key, dest_db, dest_key, replace, OpType::KEY_COPY)
+        }
         Some(("dbsize", _matches)) => server::dbsize(&client),
         Some(("decr", matches)) => {
             let key = matches.value_of("key").unwrap();
@@ -100,12 +327,116 @@ fn main() -> io::Result<()> {
             let key = matches.value_of("key").unwrap();
             keys::dump(&client, key)
         }
+        Some(("eval", matches)) => {
+            let source = script::source(
+                matches.value_of("script").unwrap(),
+                matches.is_present("file"),
+            )?;
+            let key_args: Vec<_> = match matches.values_of("key_arg") {
+                None => Vec::new(),
+                Some(key_args) => key_args.collect(),
+            };
+            let numkeys = matches.value_of("numkeys").unwrap();
+            let (keys, args) = script::split_key_args(numkeys, &key_args)?;
+            script::eval(&client, OpType::SCRIPT_EVAL, &source, keys, args)
+        }
+        Some(("evalsha", matches)) => {
+            let sha1 = matches.value_of("sha1").unwrap();
+            let key_args: Vec<_> = match matches.values_of("key_arg") {
+                None => Vec::new(),
+                Some(key_args) => key_args.collect(),
+            };
+            let numkeys = matches.value_of("numkeys").unwrap();
+            let (keys, args) = script::split_key_args(numkeys, &key_args)?;
+            script::eval(&client, OpType::SCRIPT_EVALSHA, sha1, keys, args)
+        }
         Some(("exists", matches)) => {
             let keys: Vec<_> = matches.values_of("key").unwrap().collect();
             keys::del_exist(&client, &keys, false)
         }
+        Some(("expire", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let secs = value_t!(matches, "seconds", i64).unwrap();
+            keys::expire(&client, key, secs.saturating_mul(1000), false)
+        }
+        Some(("expireat", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let ts = value_t!(matches, "timestamp", i64).unwrap();
+            keys::expire(&client, key, ts.saturating_mul(1000), true)
+        }
         Some(("flushall", _matches)) => server::flush(&client, true),
         Some(("flushdb", _matches)) => server::flush(&client, false),
+        Some(("hdel", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let fields: Vec<_> = matches.values_of("field").unwrap().collect();
+            hash::fields_query(&client, key, &fields, OpType::HASH_DEL)
+        }
+        Some(("hexists", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let fields: Vec<_> = matches.values_of("field").unwrap().collect();
+            hash::fields_query(&client, key, &fields, OpType::HASH_EXISTS)
+        }
+        Some(("hget", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let field = matches.value_of("field").unwrap();
+            hash::get(&client, key, field)
+        }
+        Some(("hgetall", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            hash::getall(&client, key, OpType::HASH_GETALL)
+        }
+        Some(("hincrby", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let field = matches.value_of("field").unwrap();
+            let n = value_t!(matches, "n", i64).unwrap();
+            hash::incrby(&client, key, field, n)
+        }
+        Some(("hincrbyfloat", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let field = matches.value_of("field").unwrap();
+            let n = value_t!(matches, "n", f64).unwrap();
+            hash::incrbyfloat(&client, key, field, n)
+        }
+        Some(("hkeys", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            hash::getall(&client, key, OpType::HASH_KEYS)
+        }
+        Some(("hlen", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            hash::len(&client, key)
+        }
+        Some(("hmget", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let fields: Vec<_> = matches.values_of("field").unwrap().collect();
+            hash::mget(&client, key, &fields)
+        }
+        Some(("hscan", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let cursor = value_t!(matches, "cursor", u64).unwrap_or(0);
+            let pattern = matches.value_of("match");
+            let count = value_t!(matches, "count", u32).unwrap_or(0);
+            hash::scan(&client, key, cursor, pattern, count)
+        }
+        Some(("hset", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let field_values: Vec<_> = matches.values_of("field_value").unwrap().collect();
+            hash::set(&client, key, &field_values, false)
+        }
+        Some(("hsetnx", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let field = matches.value_of("field").unwrap();
+            let value = matches.value_of("value").unwrap();
+            hash::set(&client, key, &vec![field, value], true)
+        }
+        Some(("hstrlen", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let field = matches.value_of("field").unwrap();
+            hash::fields_query(&client, key, &vec![field], OpType::HASH_STRLEN)
+        }
+        Some(("hvals", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            hash::getall(&client, key, OpType::HASH_VALS)
+        }
         Some(("incr", matches)) => {
             let key = matches.value_of("key").unwrap();
             string::incrdecr(&client, OpType::STR_INCR, key, 1)
@@ -128,8 +459,17 @@ fn main() -> io::Result<()> {
         Some(("getset", matches)) => {
             let key = matches.value_of("key").unwrap();
             let value = matches.value_of("value").unwrap();
-            string::set(&client, key, value, true, false, false)
+            let opts = SetOptions {
+                return_old: true,
+                ..Default::default()
+            };
+            string::set(&client, key, value, false, &opts)
+        }
+        Some(("keys", matches)) => {
+            let pattern = matches.value_of("pattern").unwrap();
+            keys::keys(&client, pattern)
         }
+        Some(("lastsave", _matches)) => server::lastsave(&client),
         Some(("lindex", matches)) => {
             let key = matches.value_of("key").unwrap();
             let n = value_t!(matches, "index", i32).unwrap();
@@ -153,6 +493,59 @@ fn main() -> io::Result<()> {
             let elems: Vec<_> = matches.values_of("element").unwrap().collect();
             list::push(&client, key, &elems, true, true)
         }
+        Some(("module-call", matches)) => {
+            let method = matches.value_of("method").unwrap();
+            let key_args: Vec<_> = match matches.values_of("key_arg") {
+                None => Vec::new(),
+                Some(key_args) => key_args.collect(),
+            };
+            let numkeys = matches.value_of("numkeys").unwrap();
+            let (keys, args) = script::split_key_args(numkeys, &key_args)?;
+            wasm::call(&client, method, keys, args)
+        }
+        Some(("module-list", _matches)) => wasm::list(&client),
+        Some(("module-load", matches)) => {
+            let path = matches.value_of("path").unwrap();
+            let name = matches.value_of("name");
+            wasm::load(&client, path, name)
+        }
+        Some(("module-unload", matches)) => {
+            let name = matches.value_of("name").unwrap();
+            wasm::unload(&client, name)
+        }
+        Some(("move", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let dest_db = matches.value_of("dest_db").unwrap();
+            keys::move_copy(&client, key, dest_db, None, false, OpType::KEY_MOVE)
+        }
+        Some(("persist", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            keys::persist(&client, key)
+        }
+        Some(("pexpire", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let ms = value_t!(matches, "milliseconds", i64).unwrap();
+            keys::expire(&client, key, ms, false)
+        }
+        Some(("pexpireat", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let ts = value_t!(matches, "timestamp", i64).unwrap();
+            keys::expire(&client, key, ts, true)
+        }
+        Some(("psetex", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let ms = value_t!(matches, "milliseconds", i64).unwrap_or_else(|e| e.exit());
+            let value = matches.value_of("value").unwrap();
+            let opts = SetOptions {
+                expire_ms: ms,
+                ..Default::default()
+            };
+            string::set(&client, key, value, false, &opts)
+        }
+        Some(("pttl", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            keys::ttl(&client, key, true)
+        }
         Some(("rename", matches)) => {
             let old_key = matches.value_of("old_key").unwrap();
             let new_key = matches.value_of("new_key").unwrap();
@@ -187,10 +580,38 @@ fn main() -> io::Result<()> {
             let elems: Vec<_> = matches.values_of("element").unwrap().collect();
             set::add_del(&client, key, &elems, false)
         }
+        Some(("save", _matches)) => server::save(&client),
+        Some(("save-status", _matches)) => server::save_status(&client),
         Some(("scard", matches)) => {
             let key = matches.value_of("key").unwrap();
             set::info(&client, key)
         }
+        Some(("scan", matches)) => {
+            let cursor = value_t!(matches, "cursor", u64).unwrap_or(0);
+            let pattern = matches.value_of("match");
+            let count = value_t!(matches, "count", u32).unwrap_or(0);
+            let typ = match matches.value_of("type") {
+                Some("string") => AtomType::STRING,
+                Some("list") => AtomType::LIST,
+                Some("set") => AtomType::SET,
+                Some("hash") => AtomType::HASH,
+                Some("zset") => AtomType::ZSET,
+                _ => AtomType::NOTYPE,
+            };
+            keys::scan(&client, cursor, pattern, count, typ)
+        }
+        Some(("script-exists", matches)) => {
+            let sha1s: Vec<_> = matches.values_of("sha1").unwrap().collect();
+            script::exists(&client, &sha1s)
+        }
+        Some(("script-flush", _matches)) => script::flush(&client),
+        Some(("script-load", matches)) => {
+            let source = script::source(
+                matches.value_of("script").unwrap(),
+                matches.is_present("file"),
+            )?;
+            script::load(&client, &source)
+        }
         Some(("sdiff", matches)) => {
             let key1 = matches.value_of("key1").unwrap();
             let mut keys: Vec<_> = matches.values_of("keys").unwrap().collect();
@@ -219,6 +640,18 @@ fn main() -> io::Result<()> {
             keys.insert(0, key1);
             set::cmpstore(&client, &keys, &store_key, OpType::SET_INTERSECT)
         }
+        Some(("shutdown", matches)) => {
+            let mode = {
+                if matches.is_present("save") {
+                    ShutdownOp_Mode::SAVE
+                } else if matches.is_present("nosave") {
+                    ShutdownOp_Mode::NOSAVE
+                } else {
+                    ShutdownOp_Mode::DEFAULT
+                }
+            };
+            server::shutdown(&client, mode)
+        }
         Some(("sismember", matches)) => {
             let key = matches.value_of("key").unwrap();
             let elems: Vec<_> = matches.values_of("element").unwrap().collect();
@@ -236,12 +669,44 @@ fn main() -> io::Result<()> {
         Some(("set", matches)) => {
             let key = matches.value_of("key").unwrap();
             let value = matches.value_of("value").unwrap();
-            string::set(&client, key, value, false, false, false)
+            let expire_ms = {
+                if matches.is_present("ex") {
+                    value_t!(matches, "ex", i64)
+                        .unwrap_or_else(|e| e.exit())
+                        .saturating_mul(1000)
+                } else if matches.is_present("px") {
+                    value_t!(matches, "px", i64).unwrap_or_else(|e| e.exit())
+                } else {
+                    0
+                }
+            };
+            let opts = SetOptions {
+                return_old: matches.is_present("get"),
+                create_excl: matches.is_present("nx"),
+                require_exist: matches.is_present("xx"),
+                keep_ttl: matches.is_present("keepttl"),
+                expire_ms,
+            };
+            string::set(&client, key, value, false, &opts)
+        }
+        Some(("setex", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let secs = value_t!(matches, "seconds", i64).unwrap_or_else(|e| e.exit());
+            let value = matches.value_of("value").unwrap();
+            let opts = SetOptions {
+                expire_ms: secs.saturating_mul(1000),
+                ..Default::default()
+            };
+            string::set(&client, key, value, false, &opts)
         }
         Some(("setnx", matches)) => {
             let key = matches.value_of("key").unwrap();
             let value = matches.value_of("value").unwrap();
-            string::set(&client, key, value, false, false, true)
+            let opts = SetOptions {
+                create_excl: true,
+                ..Default::default()
+            };
+            string::set(&client, key, value, false, &opts)
         }
         Some(("smove", matches)) => {
             let src_key = matches.value_of("src_key").unwrap();
@@ -268,10 +733,152 @@ fn main() -> io::Result<()> {
             set::cmpstore(&client, &keys, &store_key, OpType::SET_UNION)
         }
         Some(("time", _matches)) => server::time(&client),
+        Some(("ttl", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            keys::ttl(&client, key, false)
+        }
         Some(("type", matches)) => {
             let key = matches.value_of("key").unwrap();
             keys::typ(&client, key)
         }
+        Some(("version", matches)) => {
+            let keys: Vec<_> = matches.values_of("key").unwrap().collect();
+            keys::version(&client, &keys)
+        }
+        Some(("zadd", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let score_members: Vec<_> = matches.values_of("score_member").unwrap().collect();
+            let opts = ZAddOptions {
+                create_excl: matches.is_present("nx"),
+                require_exist: matches.is_present("xx"),
+                only_greater: matches.is_present("gt"),
+                only_less: matches.is_present("lt"),
+                count_changed: matches.is_present("ch"),
+                incr: matches.is_present("incr"),
+            };
+            zset::add(&client, key, &score_members, &opts)
+        }
+        Some(("zcard", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            zset::card(&client, key)
+        }
+        Some(("zcount", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let opts = ZRangeOptions {
+                by: ZRangeOp_RangeBy::SCORE,
+                reverse: false,
+                limit: None,
+                with_scores: false,
+                min: matches.value_of("min").unwrap(),
+                max: matches.value_of("max").unwrap(),
+            };
+            zset::range(&client, key, &opts, OpType::ZSET_COUNT)
+        }
+        Some(("zinterstore", matches)) | Some(("zunionstore", matches)) => {
+            let otype = match cli_matches.subcommand_name() {
+                Some("zinterstore") => OpType::ZSET_INTERSTORE,
+                _ => OpType::ZSET_UNIONSTORE,
+            };
+            let store_key = matches.value_of("destination").unwrap();
+            let keys: Vec<_> = matches.values_of("keys").unwrap().collect();
+            let weights = match matches.values_of("weights") {
+                None => Vec::new(),
+                Some(values) => {
+                    let mut weights = Vec::new();
+                    for value in values {
+                        match value.parse::<f64>() {
+                            Ok(w) => weights.push(w),
+                            Err(_) => {
+                                let msg = format!("invalid weight: {}", value);
+                                return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+                            }
+                        }
+                    }
+                    weights
+                }
+            };
+            let aggregate = match matches.value_of("aggregate") {
+                Some("min") => ZStoreOp_Aggregate::MIN,
+                Some("max") => ZStoreOp_Aggregate::MAX,
+                _ => ZStoreOp_Aggregate::SUM,
+            };
+            zset::store(&client, store_key, &keys, &weights, aggregate, otype)
+        }
+        Some(("zpopmax", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let count = value_t!(matches, "count", u32).unwrap_or(1);
+            zset::pop(&client, key, count, OpType::ZSET_POPMAX)
+        }
+        Some(("zpopmin", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let count = value_t!(matches, "count", u32).unwrap_or(1);
+            zset::pop(&client, key, count, OpType::ZSET_POPMIN)
+        }
+        Some(("zrange", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let by = {
+                if matches.is_present("byscore") {
+                    ZRangeOp_RangeBy::SCORE
+                } else if matches.is_present("bylex") {
+                    ZRangeOp_RangeBy::LEX
+                } else {
+                    ZRangeOp_RangeBy::INDEX
+                }
+            };
+            let reverse = matches.is_present("rev");
+            let limit = match matches.values_of("limit") {
+                None => None,
+                Some(values) => {
+                    let values: Vec<_> = values.collect();
+                    match (values[0].parse::<u64>(), values[1].parse::<i64>()) {
+                        (Ok(offset), Ok(count)) => Some((offset, count)),
+                        _ => {
+                            let msg = String::from("invalid limit");
+                            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+                        }
+                    }
+                }
+            };
+
+            // score and lex ranges are given high bound first, if reversed
+            let (mut min, mut max) = (
+                matches.value_of("min").unwrap(),
+                matches.value_of("max").unwrap(),
+            );
+            if reverse && by != ZRangeOp_RangeBy::INDEX {
+                std::mem::swap(&mut min, &mut max);
+            }
+
+            let opts = ZRangeOptions {
+                by,
+                reverse,
+                limit,
+                with_scores: matches.is_present("withscores"),
+                min,
+                max,
+            };
+            zset::range(&client, key, &opts, OpType::ZSET_RANGE)
+        }
+        Some(("zrank", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let member = matches.value_of("member").unwrap();
+            zset::score(&client, key, member, OpType::ZSET_RANK)
+        }
+        Some(("zrem", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let members: Vec<_> = matches.values_of("member").unwrap().collect();
+            zset::rem(&client, key, &members)
+        }
+        Some(("zrevrank", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let member = matches.value_of("member").unwrap();
+            zset::score(&client, key, member, OpType::ZSET_REVRANK)
+        }
+        Some(("zscore", matches)) => {
+            let key = matches.value_of("key").unwrap();
+            let member = matches.value_of("member").unwrap();
+            zset::score(&client, key, member, OpType::ZSET_SCORE)
+        }
         Some((_, _)) | None => {
             println!("No subcommand specified.  Run with --help for help.");
             Ok(())
@@ -0,0 +1,279 @@
+use std::io::{self, Error, ErrorKind};
+
+use memds_proto::memds_api::*;
+use memds_proto::memds_api_grpc::MemdsClient;
+
+use crate::util;
+
+// print script value, nested values indented
+fn print_value(value: &ScriptValue, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value.vtype {
+        ScriptValue_Type::NIL => println!("{}(nil)", pad),
+        ScriptValue_Type::BOOL => println!("{}{}", pad, value.b),
+        ScriptValue_Type::INT => println!("{}(integer) {}", pad, value.i),
+        ScriptValue_Type::FLOAT => println!("{}{}", pad, value.f),
+        ScriptValue_Type::STRING => println!("{}{}", pad, String::from_utf8_lossy(&value.s)),
+        ScriptValue_Type::ARRAY => {
+            for (i, element) in value.elements.iter().enumerate() {
+                println!("{}{})", pad, i + 1);
+                print_value(element, indent + 2);
+            }
+        }
+        ScriptValue_Type::MAP => {
+            for (name, element) in value.names.iter().zip(value.elements.iter()) {
+                println!("{}{}:", pad, name);
+                print_value(element, indent + 2);
+            }
+        }
+    }
+}
+
+/// Script source:  as given, or read from the named file.
+pub fn source(script: &str, from_file: bool) -> io::Result<String> {
+    if from_file {
+        std::fs::read_to_string(script)
+    } else {
+        Ok(script.to_string())
+    }
+}
+
+/// Split arguments into NUMKEYS keys, then further arguments.
+pub fn split_key_args<'a>(
+    numkeys: &str,
+    key_args: &'a [&'a str],
+) -> io::Result<(&'a [&'a str], &'a [&'a str])> {
+    match numkeys.parse::<usize>() {
+        Ok(n) if n <= key_args.len() => Ok(key_args.split_at(n)),
+        _ => {
+            let msg = format!("Invalid numkeys {}", numkeys);
+            Err(Error::new(ErrorKind::Other, msg))
+        }
+    }
+}
+
+pub fn eval(
+    client: &MemdsClient,
+    otype: OpType,
+    script: &str,
+    keys: &[&str],
+    args: &[&str],
+) -> io::Result<()> {
+    let mut script_req = ScriptOp::new();
+    if otype == OpType::SCRIPT_EVALSHA {
+        script_req.sha1.push(script.to_string());
+    } else {
+        script_req.set_source(script.to_string());
+    }
+    for key in keys.iter() {
+        script_req.keys.push(key.as_bytes().to_vec());
+    }
+    for arg in args.iter() {
+        script_req.args.push(arg.as_bytes().to_vec());
+    }
+
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_script(script_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("eval: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    print_value(result.get_script().get_value(), 0);
+    Ok(())
+}
+
+pub fn load(client: &MemdsClient, source: &str) -> io::Result<()> {
+    let mut script_req = ScriptOp::new();
+    script_req.set_source(source.to_string());
+
+    let mut op = Operation::new();
+    op.otype = OpType::SCRIPT_LOAD;
+    op.set_script(script_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("script-load: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    println!("{}", result.get_script().sha1);
+    Ok(())
+}
+
+pub fn exists(client: &MemdsClient, sha1s: &[&str]) -> io::Result<()> {
+    let mut script_req = ScriptOp::new();
+    for sha1 in sha1s.iter() {
+        script_req.sha1.push(sha1.to_string());
+    }
+
+    let mut op = Operation::new();
+    op.otype = OpType::SCRIPT_EXISTS;
+    op.set_script(script_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("script-exists: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    for (sha1, exists) in sha1s.iter().zip(result.get_script().exists.iter()) {
+        println!("{}: {}", sha1, exists);
+    }
+    Ok(())
+}
+
+pub fn flush(client: &MemdsClient) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::SCRIPT_FLUSH;
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("script-flush: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    println!("ok");
+    Ok(())
+}
+
+pub mod args {
+    use clap::{App, Arg, SubCommand};
+
+    const KEYS_HELP: &str = "The script may access only the keys given as KEYS, and may not \
+                             call keyspace-wide or server-wide methods.";
+
+    pub fn eval() -> App<'static> {
+        SubCommand::with_name("eval")
+            .about("Script.Eval: Execute script")
+            .after_help(KEYS_HELP)
+            .arg(
+                Arg::with_name("file")
+                    .help("Read script from file named SCRIPT")
+                    .short('f')
+                    .long("file"),
+            )
+            .arg(
+                Arg::with_name("script")
+                    .help("Script source")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("numkeys")
+                    .help("Number of following arguments that are keys")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("key_arg")
+                    .help("Keys (KEYS), then further arguments (ARGV)")
+                    .multiple(true),
+            )
+    }
+
+    pub fn evalsha() -> App<'static> {
+        SubCommand::with_name("evalsha")
+            .about("Script.EvalSha: Execute cached script")
+            .after_help(KEYS_HELP)
+            .arg(
+                Arg::with_name("sha1")
+                    .help("SHA1 digest of script")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("numkeys")
+                    .help("Number of following arguments that are keys")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("key_arg")
+                    .help("Keys (KEYS), then further arguments (ARGV)")
+                    .multiple(true),
+            )
+    }
+
+    pub fn script_exists() -> App<'static> {
+        SubCommand::with_name("script-exists")
+            .about("Script.Exists: Query whether scripts are cached")
+            .arg(
+                Arg::with_name("sha1")
+                    .help("SHA1 digest of script")
+                    .required(true)
+                    .multiple(true),
+            )
+    }
+
+    pub fn script_flush() -> App<'static> {
+        SubCommand::with_name("script-flush").about("Script.Flush: Remove all cached scripts")
+    }
+
+    pub fn script_load() -> App<'static> {
+        SubCommand::with_name("script-load")
+            .about("Script.Load: Cache script, returning its SHA1 digest")
+            .arg(
+                Arg::with_name("file")
+                    .help("Read script from file named SCRIPT")
+                    .short('f')
+                    .long("file"),
+            )
+            .arg(
+                Arg::with_name("script")
+                    .help("Script source")
+                    .required(true),
+            )
+    }
+}
@@ -5,6 +5,33 @@ use memds_proto::memds_api_grpc::MemdsClient;
 
 use crate::util;
 
+pub fn bgrewriteaof(client: &MemdsClient) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::SRV_BGREWRITEAOF;
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("bgrewriteaof: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    println!("ok");
+    Ok(())
+}
+
 pub fn bgsave(client: &MemdsClient) -> io::Result<()> {
     let mut op = Operation::new();
     op.otype = OpType::SRV_BGSAVE;
@@ -15,7 +42,7 @@ pub fn bgsave(client: &MemdsClient) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -32,6 +59,94 @@ pub fn bgsave(client: &MemdsClient) -> io::Result<()> {
     Ok(())
 }
 
+pub fn command(client: &MemdsClient, names: &[&str]) -> io::Result<()> {
+    let mut op = Operation::new();
+    if names.is_empty() {
+        op.otype = OpType::SRV_COMMAND;
+    } else {
+        op.otype = OpType::SRV_COMMAND_INFO;
+        let mut cmd_req = CommandOp::new();
+        for name in names.iter() {
+            cmd_req.names.push(name.to_string());
+        }
+        op.set_command(cmd_req);
+    }
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("command: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    // summary line per command; full help if specific commands requested
+    let cmd_res = results[0].get_command();
+    for info in cmd_res.get_commands() {
+        if names.is_empty() {
+            println!(
+                "{} ({}) [{}]",
+                info.name,
+                info.otype as i32,
+                info.flags.join(",")
+            );
+            continue;
+        }
+
+        println!("{}", info.name);
+        println!("  operation type: {:?} ({})", info.otype, info.otype as i32);
+        if info.arg.is_empty() {
+            println!("  arguments: none");
+        } else {
+            println!("  arguments: Operation.{}", info.arg);
+        }
+        if !info.key_fields.is_empty() {
+            println!("  key fields: {}", info.key_fields.join(", "));
+        }
+        println!("  flags: {}", info.flags.join(", "));
+    }
+    Ok(())
+}
+
+pub fn command_count(client: &MemdsClient) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::SRV_COMMAND_COUNT;
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("command-count: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let count_res = results[0].get_count();
+    println!("{}", count_res.n);
+    Ok(())
+}
+
 pub fn dbsize(client: &MemdsClient) -> io::Result<()> {
     let mut op = Operation::new();
     op.otype = OpType::SRV_DBSIZE;
@@ -42,7 +157,7 @@ pub fn dbsize(client: &MemdsClient) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -73,7 +188,7 @@ pub fn flush(client: &MemdsClient, flush_all: bool) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -90,6 +205,122 @@ pub fn flush(client: &MemdsClient, flush_all: bool) -> io::Result<()> {
     Ok(())
 }
 
+pub fn lastsave(client: &MemdsClient) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::SRV_LASTSAVE;
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("lastsave: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let time_res = results[0].get_srv_time();
+    println!("{}", time_res.secs);
+    Ok(())
+}
+
+pub fn save_status(client: &MemdsClient) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::SRV_SAVE_STATUS;
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("save-status: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let status_res = results[0].get_save_status();
+    println!("in_progress: {}", status_res.in_progress);
+    println!("last_ok: {}", status_res.last_ok);
+    println!("last_save: {}", status_res.last_save);
+    println!("last_duration_ms: {}", status_res.last_duration_ms);
+    Ok(())
+}
+
+pub fn save(client: &MemdsClient) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::SRV_SAVE;
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("save: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    println!("ok");
+    Ok(())
+}
+
+pub fn shutdown(client: &MemdsClient, mode: ShutdownOp_Mode) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::SRV_SHUTDOWN;
+    let mut shutdown_req = ShutdownOp::new();
+    shutdown_req.mode = mode;
+    op.set_shutdown(shutdown_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("shutdown: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    println!("ok");
+    Ok(())
+}
+
 pub fn time(client: &MemdsClient) -> io::Result<()> {
     let mut op = Operation::new();
     op.otype = OpType::SRV_TIME;
@@ -100,7 +331,7 @@ pub fn time(client: &MemdsClient) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -119,12 +350,32 @@ pub fn time(client: &MemdsClient) -> io::Result<()> {
 }
 
 pub mod args {
-    use clap::{App, SubCommand};
+    use clap::{App, Arg, SubCommand};
+
+    pub fn bgrewriteaof() -> App<'static> {
+        SubCommand::with_name("bgrewriteaof")
+            .about("Server.BGRewriteAOF: Compact operation log in background")
+    }
 
     pub fn bgsave() -> App<'static> {
         SubCommand::with_name("bgsave").about("Server.BGSave: Dump entire database to filesystem")
     }
 
+    pub fn command() -> App<'static> {
+        SubCommand::with_name("command")
+            .about("Server.Command: List server operations, or describe named operations")
+            .arg(
+                Arg::with_name("name")
+                    .help("MODULE.METHOD name of operation to describe")
+                    .multiple(true),
+            )
+    }
+
+    pub fn command_count() -> App<'static> {
+        SubCommand::with_name("command-count")
+            .about("Server.CommandCount: Retrieve number of server operations")
+    }
+
     pub fn dbsize() -> App<'static> {
         SubCommand::with_name("dbsize")
             .about("Server.DBSize: Retrieve item count of current database")
@@ -138,6 +389,37 @@ pub mod args {
         SubCommand::with_name("flushall").about("Server.FlushAll: Empty all databases")
     }
 
+    pub fn lastsave() -> App<'static> {
+        SubCommand::with_name("lastsave")
+            .about("Server.LastSave: Retrieve time of last successful save")
+    }
+
+    pub fn save_status() -> App<'static> {
+        SubCommand::with_name("save-status")
+            .about("Server.SaveStatus: Retrieve status of last save")
+    }
+
+    pub fn save() -> App<'static> {
+        SubCommand::with_name("save")
+            .about("Server.Save: Dump entire database to filesystem, in foreground")
+    }
+
+    pub fn shutdown() -> App<'static> {
+        SubCommand::with_name("shutdown")
+            .about("Server.Shutdown: Stop server, optionally saving database")
+            .arg(
+                Arg::with_name("save")
+                    .help("Save database, even if no save rules are configured")
+                    .long("save")
+                    .conflicts_with("nosave"),
+            )
+            .arg(
+                Arg::with_name("nosave")
+                    .help("Do not save database")
+                    .long("nosave"),
+            )
+    }
+
     pub fn time() -> App<'static> {
         SubCommand::with_name("time").about("Server.Time: Retrieve server time")
     }
@@ -19,7 +19,7 @@ pub fn info(client: &MemdsClient, key: &str) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -62,7 +62,7 @@ pub fn add_del(
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -109,7 +109,7 @@ pub fn cmpstore(
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -153,7 +153,7 @@ pub fn is_member(client: &MemdsClient, key: &str, elems: &Vec<&str>) -> io::Resu
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -185,7 +185,7 @@ pub fn members(client: &MemdsClient, key: &str) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -222,7 +222,7 @@ pub fn mov(client: &MemdsClient, src_key: &str, dest_key: &str, member: &str) ->
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
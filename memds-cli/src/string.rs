@@ -19,7 +19,7 @@ pub fn get(client: &MemdsClient, key: &str) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -55,7 +55,7 @@ pub fn getrange(client: &MemdsClient, key: &str, start: i32, end: i32) -> io::Re
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -74,19 +74,31 @@ pub fn getrange(client: &MemdsClient, key: &str, start: i32, end: i32) -> io::Re
     }
 }
 
+/// Optional behaviors of String.Set, mirroring `StrSetOp`.
+#[derive(Default)]
+pub struct SetOptions {
+    pub return_old: bool,
+    pub create_excl: bool,
+    pub require_exist: bool,
+    pub keep_ttl: bool,
+    pub expire_ms: i64,
+}
+
 pub fn set(
     client: &MemdsClient,
     key: &str,
     value: &str,
-    return_old: bool,
     append: bool,
-    create_excl: bool,
+    opts: &SetOptions,
 ) -> io::Result<()> {
     let mut set_req = StrSetOp::new();
     set_req.set_key(key.as_bytes().to_vec());
     set_req.set_value(value.as_bytes().to_vec());
-    set_req.return_old = return_old;
-    set_req.create_excl = create_excl;
+    set_req.return_old = opts.return_old;
+    set_req.create_excl = opts.create_excl;
+    set_req.require_exist = opts.require_exist;
+    set_req.keep_ttl = opts.keep_ttl;
+    set_req.expire_ms = opts.expire_ms;
 
     let mut op = Operation::new();
     op.otype = match append {
@@ -101,7 +113,7 @@ pub fn set(
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -110,7 +122,7 @@ pub fn set(
 
     let result = &results[0];
     if result.ok {
-        if return_old {
+        if opts.return_old {
             let set_res = results[0].get_set();
             io::stdout().write_all(set_res.get_old_value())?;
         } else {
@@ -138,7 +150,7 @@ pub fn incrdecr(client: &MemdsClient, otype: OpType, key: &str, n: i64) -> io::R
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -172,7 +184,7 @@ pub fn strlen(client: &MemdsClient, key: &str) -> io::Result<()> {
     let resp = util::rpc_exec(&client, &req)?;
 
     if !resp.ok {
-        let msg = format!("Batch failure {}: {}", resp.err_code, resp.err_message);
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
         return Err(Error::new(ErrorKind::Other, msg));
     }
 
@@ -194,6 +206,14 @@ pub fn strlen(client: &MemdsClient, key: &str) -> io::Result<()> {
 pub mod args {
     use clap::{App, Arg, SubCommand};
 
+    // time-to-live:  a positive integer, as 0 would store no expiration
+    fn positive_ttl(s: &str) -> Result<(), String> {
+        match s.parse::<i64>() {
+            Ok(n) if n > 0 => Ok(()),
+            _ => Err("time-to-live must be a positive integer".to_string()),
+        }
+    }
+
     pub fn append() -> App<'static> {
         SubCommand::with_name("append")
             .about("String.Append: Append to item")
@@ -304,6 +324,27 @@ pub mod args {
             )
     }
 
+    pub fn psetex() -> App<'static> {
+        SubCommand::with_name("psetex")
+            .about("String.PSetEx: Store item, with time-to-live in milliseconds")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to store")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("milliseconds")
+                    .help("Time-to-live, in milliseconds")
+                    .required(true)
+                    .validator(positive_ttl),
+            )
+            .arg(
+                Arg::with_name("value")
+                    .help("Value of item to store")
+                    .required(true),
+            )
+    }
+
     pub fn set() -> App<'static> {
         SubCommand::with_name("set")
             .about("String.Set: Store item")
@@ -317,6 +358,64 @@ pub mod args {
                     .help("Value of item to store")
                     .required(true),
             )
+            .arg(
+                Arg::with_name("ex")
+                    .help("Set time-to-live, in seconds")
+                    .long("ex")
+                    .value_name("seconds")
+                    .validator(positive_ttl)
+                    .conflicts_with_all(&["px", "keepttl"]),
+            )
+            .arg(
+                Arg::with_name("px")
+                    .help("Set time-to-live, in milliseconds")
+                    .long("px")
+                    .value_name("milliseconds")
+                    .validator(positive_ttl)
+                    .conflicts_with("keepttl"),
+            )
+            .arg(
+                Arg::with_name("nx")
+                    .help("Store item, iff key does not exist")
+                    .long("nx")
+                    .conflicts_with("xx"),
+            )
+            .arg(
+                Arg::with_name("xx")
+                    .help("Store item, iff key exists")
+                    .long("xx"),
+            )
+            .arg(
+                Arg::with_name("keepttl")
+                    .help("Retain time-to-live of existing item")
+                    .long("keepttl"),
+            )
+            .arg(
+                Arg::with_name("get")
+                    .help("Return old value stored at key")
+                    .long("get"),
+            )
+    }
+
+    pub fn setex() -> App<'static> {
+        SubCommand::with_name("setex")
+            .about("String.SetEx: Store item, with time-to-live in seconds")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of item to store")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("seconds")
+                    .help("Time-to-live, in seconds")
+                    .required(true)
+                    .validator(positive_ttl),
+            )
+            .arg(
+                Arg::with_name("value")
+                    .help("Value of item to store")
+                    .required(true),
+            )
     }
 
     pub fn setnx() -> App<'static> {
@@ -1,10 +1,121 @@
 use futures::Future;
+use grpcio::{ChannelCredentials, ChannelCredentialsBuilder};
+use std::cell::RefCell;
 use std::io::{self, Error, ErrorKind};
+use std::os::unix::net::UnixStream;
 
 use memds_proto::memds_api::*;
 use memds_proto::memds_api_grpc::MemdsClient;
+use memds_proto::FramedClient;
+
+thread_local! {
+    // request options given on the command line (--db, --watch, --user,
+    // --password, --token), applied to each request
+    static REQ_OPTIONS: RefCell<RequestMsg> = RefCell::new(RequestMsg::new());
+
+    // unix socket connection (--socket); if present, carries requests
+    // in place of gRPC
+    static SOCKET: RefCell<Option<FramedClient<UnixStream>>> = const { RefCell::new(None) };
+}
+
+// read PEM file named on command line
+fn read_pem(path: &str) -> io::Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| {
+        let msg = format!("{}: {}", path, e);
+        Error::new(e.kind(), msg)
+    })
+}
+
+/// TLS credentials:  CA certificates verifying the server, if not the
+/// system's, and client certificate & key, if any.
+pub fn channel_credentials(
+    ca: Option<&str>,
+    cert: Option<&str>,
+    key: Option<&str>,
+) -> io::Result<ChannelCredentials> {
+    let mut builder = ChannelCredentialsBuilder::new();
+    if let Some(ca) = ca {
+        builder = builder.root_cert(read_pem(ca)?);
+    }
+    if let (Some(cert), Some(key)) = (cert, key) {
+        builder = builder.cert(read_pem(cert)?, read_pem(key)?);
+    }
+    Ok(builder.build())
+}
+
+/// Send requests via server's unix socket at `path`.
+pub fn connect_socket(path: &str) -> io::Result<()> {
+    let stream = UnixStream::connect(path).map_err(|e| {
+        let msg = format!("{}: {}", path, e);
+        Error::new(e.kind(), msg)
+    })?;
+    SOCKET.with(|sock| *sock.borrow_mut() = Some(FramedClient::new(stream)));
+    Ok(())
+}
+
+/// Authenticate each request, by user & password, or by token.
+pub fn authenticate(user: Option<&str>, password: Option<&str>, token: Option<&str>) {
+    let mut auth = AuthOp::new();
+    if let Some(user) = user {
+        auth.set_username(user.to_string());
+    }
+    if let Some(password) = password {
+        auth.set_password(password.as_bytes().to_vec());
+    }
+    if let Some(token) = token {
+        auth.set_token(token.as_bytes().to_vec());
+    }
+    REQ_OPTIONS.with(|opts| opts.borrow_mut().set_auth(auth));
+}
+
+pub fn select_db(db: &str) {
+    REQ_OPTIONS.with(|opts| opts.borrow_mut().set_db(db.as_bytes().to_vec()));
+}
+
+// parse KEY:VERSION
+fn parse_watch(arg: &str) -> Option<KeyVersion> {
+    let pos = arg.rfind(':')?;
+    let version = arg[pos + 1..].parse::<u64>().ok()?;
+
+    let mut watch = KeyVersion::new();
+    watch.set_key(arg.as_bytes()[..pos].to_vec());
+    watch.version = version;
+    Some(watch)
+}
+
+/// Add precondition KEY:VERSION to each request.
+pub fn watch(arg: &str) -> io::Result<()> {
+    match parse_watch(arg) {
+        None => {
+            let msg = format!("Invalid watch {}; expected KEY:VERSION", arg);
+            Err(Error::new(ErrorKind::Other, msg))
+        }
+        Some(watch) => {
+            REQ_OPTIONS.with(|opts| opts.borrow_mut().watch.push(watch));
+            Ok(())
+        }
+    }
+}
 
 pub fn rpc_exec(client: &MemdsClient, req: &RequestMsg) -> io::Result<ResponseMsg> {
+    let mut req = req.clone();
+    REQ_OPTIONS.with(|opts| {
+        let opts = opts.borrow();
+        if req.get_db().is_empty() {
+            req.set_db(opts.get_db().to_vec());
+        }
+        for watch in opts.get_watch() {
+            req.watch.push(watch.clone());
+        }
+        if opts.has_auth() {
+            req.set_auth(opts.get_auth().clone());
+        }
+    });
+
+    if let Some(resp) = SOCKET.with(|sock| sock.borrow_mut().as_mut().map(|c| c.exec(&req))) {
+        return resp;
+    }
+
     let exec = client.exec_async(&req).unwrap();
     match exec.wait() {
         Err(e) => {
@@ -0,0 +1,200 @@
+use std::io::{self, Error, ErrorKind, Write};
+
+use memds_proto::memds_api::*;
+use memds_proto::memds_api_grpc::MemdsClient;
+
+use crate::util;
+
+pub fn load(client: &MemdsClient, path: &str, name: Option<&str>) -> io::Result<()> {
+    let mut module_req = ModuleOp::new();
+    module_req.set_path(path.to_string());
+    if let Some(name) = name {
+        module_req.set_name(name.to_string());
+    }
+
+    let mut op = Operation::new();
+    op.otype = OpType::MODULE_LOAD;
+    op.set_module(module_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", path, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    println!("ok");
+    Ok(())
+}
+
+pub fn unload(client: &MemdsClient, name: &str) -> io::Result<()> {
+    let mut module_req = ModuleOp::new();
+    module_req.set_name(name.to_string());
+
+    let mut op = Operation::new();
+    op.otype = OpType::MODULE_UNLOAD;
+    op.set_module(module_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", name, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    println!("ok");
+    Ok(())
+}
+
+pub fn list(client: &MemdsClient) -> io::Result<()> {
+    let mut op = Operation::new();
+    op.otype = OpType::MODULE_LIST;
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("module-list: {}", result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    for info in result.get_module().get_modules() {
+        println!("{} ({})", info.name, info.path);
+        for (method, write) in info.methods.iter().zip(info.write.iter()) {
+            let flag = if *write { "write" } else { "readonly" };
+            println!("  {} [{}]", method, flag);
+        }
+    }
+    Ok(())
+}
+
+pub fn call(client: &MemdsClient, method: &str, keys: &[&str], args: &[&str]) -> io::Result<()> {
+    let mut call_req = ModuleCallOp::new();
+    call_req.set_method(method.to_string());
+    for key in keys.iter() {
+        call_req.keys.push(key.as_bytes().to_vec());
+    }
+    for arg in args.iter() {
+        call_req.args.push(arg.as_bytes().to_vec());
+    }
+
+    let mut op = Operation::new();
+    op.otype = OpType::MODULE_CALL;
+    op.set_module_call(call_req);
+
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", method, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    for reply in result.get_list().get_elements() {
+        io::stdout().write_all(reply)?;
+        println!();
+    }
+    Ok(())
+}
+
+pub mod args {
+    use clap::{App, Arg, SubCommand};
+
+    pub fn module_call() -> App<'static> {
+        SubCommand::with_name("module-call")
+            .about("Module.Call: Call method registered by module")
+            .after_help("The method may read and write only the keys given.")
+            .arg(
+                Arg::with_name("method")
+                    .help("NAMESPACE.METHOD name of method")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("numkeys")
+                    .help("Number of following arguments that are keys")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("key_arg")
+                    .help("Keys, then further arguments")
+                    .multiple(true),
+            )
+    }
+
+    pub fn module_list() -> App<'static> {
+        SubCommand::with_name("module-list")
+            .about("Module.List: List loaded modules and their methods")
+    }
+
+    pub fn module_load() -> App<'static> {
+        SubCommand::with_name("module-load")
+            .about("Module.Load: Load WASM module, from file local to server")
+            .arg(
+                Arg::with_name("name")
+                    .help("Namespace of module's methods (default: file stem)")
+                    .long("name")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("path")
+                    .help("Path of WASM module file")
+                    .required(true),
+            )
+    }
+
+    pub fn module_unload() -> App<'static> {
+        SubCommand::with_name("module-unload")
+            .about("Module.Unload: Unload module, and its methods")
+            .arg(
+                Arg::with_name("name")
+                    .help("Namespace of module")
+                    .required(true),
+            )
+    }
+}
@@ -0,0 +1,545 @@
+use std::io::{self, Error, ErrorKind, Write};
+
+use memds_proto::memds_api::*;
+use memds_proto::memds_api_grpc::MemdsClient;
+
+use crate::util;
+
+/// Optional behaviors of SortedSet.Add, mirroring `ZAddOp`.
+#[derive(Default)]
+pub struct ZAddOptions {
+    pub create_excl: bool,
+    pub require_exist: bool,
+    pub only_greater: bool,
+    pub only_less: bool,
+    pub count_changed: bool,
+    pub incr: bool,
+}
+
+/// Range query parameters of SortedSet.Range, mirroring `ZRangeOp`.
+pub struct ZRangeOptions<'a> {
+    pub by: ZRangeOp_RangeBy,
+    pub reverse: bool,
+    pub limit: Option<(u64, i64)>,
+    pub with_scores: bool,
+    pub min: &'a str, // index start, or min score/lex bound
+    pub max: &'a str, // index stop, or max score/lex bound
+}
+
+fn invalid_input(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, msg)
+}
+
+// parse score range bound: "1.5", "(1.5" (exclusive), "-inf", "+inf"
+fn parse_score_bound(s: &str) -> io::Result<(f64, bool)> {
+    let (num, exclusive) = match s.strip_prefix('(') {
+        Some(rest) => (rest, true),
+        None => (s, false),
+    };
+    match num.parse::<f64>() {
+        Ok(score) if !score.is_nan() => Ok((score, exclusive)),
+        _ => Err(invalid_input(format!("invalid score bound: {}", s))),
+    }
+}
+
+// execute single sorted set operation, returning its result
+fn zset_exec(client: &MemdsClient, key: &str, op: Operation) -> io::Result<OpResult> {
+    let mut req = RequestMsg::new();
+    req.ops.push(op);
+
+    let resp = util::rpc_exec(&client, &req)?;
+
+    if !resp.ok {
+        let msg = format!("Batch failure {:?}: {}", resp.err_code, resp.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    let results = resp.get_results();
+    assert!(results.len() == 1);
+
+    let result = &results[0];
+    if !result.ok {
+        let msg = format!("{}: {}", key, result.err_message);
+        return Err(Error::new(ErrorKind::Other, msg));
+    }
+
+    Ok(result.clone())
+}
+
+// output members, one per line, each optionally followed by its score
+fn print_members(zset_res: &ZSetRes, with_scores: bool) -> io::Result<()> {
+    for sm in zset_res.members.iter() {
+        io::stdout().write_all(&sm.member)?;
+        io::stdout().write_all(b"\n")?;
+        if with_scores {
+            println!("{}", sm.score);
+        }
+    }
+    Ok(())
+}
+
+pub fn add(
+    client: &MemdsClient,
+    key: &str,
+    score_members: &Vec<&str>,
+    opts: &ZAddOptions,
+) -> io::Result<()> {
+    if score_members.len() % 2 != 0 {
+        return Err(invalid_input(format!(
+            "{}: score/member pairs required",
+            key
+        )));
+    }
+
+    let mut op_req = ZAddOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    for pair in score_members.chunks(2) {
+        let score = match pair[0].parse::<f64>() {
+            Ok(score) => score,
+            Err(_) => {
+                return Err(invalid_input(format!("invalid score: {}", pair[0])));
+            }
+        };
+
+        let mut sm = ScoredMember::new();
+        sm.set_member(pair[1].as_bytes().to_vec());
+        sm.score = score;
+        op_req.members.push(sm);
+    }
+    op_req.create_excl = opts.create_excl;
+    op_req.require_exist = opts.require_exist;
+    op_req.only_greater = opts.only_greater;
+    op_req.only_less = opts.only_less;
+    op_req.count_changed = opts.count_changed;
+    op_req.incr = opts.incr;
+
+    let mut op = Operation::new();
+    op.otype = OpType::ZSET_ADD;
+    op.set_zadd(op_req);
+
+    let result = zset_exec(client, key, op)?;
+
+    // INCR: output new score, if updated.  otherwise output count.
+    if opts.incr {
+        for sm in result.get_zset().members.iter() {
+            println!("{}", sm.score);
+        }
+    } else {
+        println!("{}", result.get_count().n);
+    }
+    Ok(())
+}
+
+pub fn rem(client: &MemdsClient, key: &str, members: &Vec<&str>) -> io::Result<()> {
+    let mut op_req = KeyedListOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    for member in members.iter() {
+        op_req.elements.push(member.as_bytes().to_vec());
+    }
+
+    let mut op = Operation::new();
+    op.otype = OpType::ZSET_REM;
+    op.set_keyed_list(op_req);
+
+    let result = zset_exec(client, key, op)?;
+
+    println!("{}", result.get_count().n);
+    Ok(())
+}
+
+pub fn card(client: &MemdsClient, key: &str) -> io::Result<()> {
+    let mut key_req = KeyOp::new();
+    key_req.set_key(key.as_bytes().to_vec());
+
+    let mut op = Operation::new();
+    op.otype = OpType::ZSET_CARD;
+    op.set_key(key_req);
+
+    let result = zset_exec(client, key, op)?;
+
+    println!("{}", result.get_count().n);
+    Ok(())
+}
+
+pub fn score(client: &MemdsClient, key: &str, member: &str, otype: OpType) -> io::Result<()> {
+    let mut op_req = KeyedListOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    op_req.elements.push(member.as_bytes().to_vec());
+
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_keyed_list(op_req);
+
+    let result = zset_exec(client, key, op)?;
+
+    // ZSET_SCORE: output score.  ZSET_RANK, ZSET_REVRANK: output rank.
+    if otype == OpType::ZSET_SCORE {
+        println!("{}", result.get_zset().members[0].score);
+    } else {
+        println!("{}", result.get_count().n);
+    }
+    Ok(())
+}
+
+pub fn range(
+    client: &MemdsClient,
+    key: &str,
+    opts: &ZRangeOptions,
+    otype: OpType,
+) -> io::Result<()> {
+    let mut op_req = ZRangeOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    op_req.by = opts.by;
+    op_req.reverse = opts.reverse;
+
+    match opts.by {
+        ZRangeOp_RangeBy::INDEX => {
+            op_req.start = match opts.min.parse::<i64>() {
+                Ok(n) => n,
+                Err(_) => return Err(invalid_input(format!("invalid index: {}", opts.min))),
+            };
+            op_req.stop = match opts.max.parse::<i64>() {
+                Ok(n) => n,
+                Err(_) => return Err(invalid_input(format!("invalid index: {}", opts.max))),
+            };
+        }
+        ZRangeOp_RangeBy::SCORE => {
+            let (min, min_exclusive) = parse_score_bound(opts.min)?;
+            let (max, max_exclusive) = parse_score_bound(opts.max)?;
+            op_req.min = min;
+            op_req.min_exclusive = min_exclusive;
+            op_req.max = max;
+            op_req.max_exclusive = max_exclusive;
+        }
+        ZRangeOp_RangeBy::LEX => {
+            op_req.set_min_lex(opts.min.as_bytes().to_vec());
+            op_req.set_max_lex(opts.max.as_bytes().to_vec());
+        }
+    }
+
+    if let Some((offset, count)) = opts.limit {
+        op_req.has_limit = true;
+        op_req.offset = offset;
+        op_req.count = count;
+    }
+    op_req.with_scores = opts.with_scores;
+
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_zrange(op_req);
+
+    let result = zset_exec(client, key, op)?;
+
+    if otype == OpType::ZSET_COUNT {
+        println!("{}", result.get_count().n);
+        Ok(())
+    } else {
+        print_members(result.get_zset(), opts.with_scores)
+    }
+}
+
+pub fn pop(client: &MemdsClient, key: &str, count: u32, otype: OpType) -> io::Result<()> {
+    let mut op_req = ZPopOp::new();
+    op_req.set_key(key.as_bytes().to_vec());
+    op_req.count = count;
+
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_zpop(op_req);
+
+    let result = zset_exec(client, key, op)?;
+
+    print_members(result.get_zset(), true)
+}
+
+pub fn store(
+    client: &MemdsClient,
+    store_key: &str,
+    keys: &Vec<&str>,
+    weights: &Vec<f64>,
+    aggregate: ZStoreOp_Aggregate,
+    otype: OpType,
+) -> io::Result<()> {
+    let mut op_req = ZStoreOp::new();
+    for key in keys.iter() {
+        op_req.keys.push(key.as_bytes().to_vec());
+    }
+    for weight in weights.iter() {
+        op_req.weights.push(*weight);
+    }
+    op_req.aggregate = aggregate;
+    op_req.set_store_key(store_key.as_bytes().to_vec());
+
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_zstore(op_req);
+
+    let result = zset_exec(client, store_key, op)?;
+
+    println!("{}", result.get_count().n);
+    Ok(())
+}
+
+pub mod args {
+    use clap::{App, Arg, SubCommand};
+
+    fn store_args(app: App<'static>) -> App<'static> {
+        app.arg(
+            Arg::with_name("destination")
+                .help("Sorted set receiving results")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("keys")
+                .help("Input sorted sets (or sets)")
+                .required(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("weights")
+                .help("Comma-separated score multiplier of each input")
+                .long("weights")
+                .value_name("w1,w2,...")
+                .takes_value(true)
+                .multiple_values(true)
+                .require_value_delimiter(true)
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::with_name("aggregate")
+                .help("Combination of scores of member in multiple inputs (default: sum)")
+                .long("aggregate")
+                .value_name("fn")
+                .possible_values(["sum", "min", "max"]),
+        )
+    }
+
+    pub fn zadd() -> App<'static> {
+        SubCommand::with_name("zadd")
+            .about("SortedSet.Add: Store scored members in sorted set")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("score_member")
+                    .help("Score, followed by member, of each item to store")
+                    .required(true)
+                    .multiple(true)
+                    .allow_hyphen_values(true),
+            )
+            .arg(
+                Arg::with_name("nx")
+                    .help("Add new members only")
+                    .long("nx")
+                    .conflicts_with_all(&["xx", "gt", "lt"]),
+            )
+            .arg(
+                Arg::with_name("xx")
+                    .help("Update existing members only")
+                    .long("xx"),
+            )
+            .arg(
+                Arg::with_name("gt")
+                    .help("Update members only if new score is greater")
+                    .long("gt")
+                    .conflicts_with("lt"),
+            )
+            .arg(
+                Arg::with_name("lt")
+                    .help("Update members only if new score is less")
+                    .long("lt"),
+            )
+            .arg(
+                Arg::with_name("ch")
+                    .help("Count updated members, as well as added members")
+                    .long("ch"),
+            )
+            .arg(
+                Arg::with_name("incr")
+                    .help("Increment score of member; output new score")
+                    .long("incr"),
+            )
+    }
+
+    pub fn zcard() -> App<'static> {
+        SubCommand::with_name("zcard")
+            .about("SortedSet.Card: Number of members in sorted set")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to query")
+                    .required(true),
+            )
+    }
+
+    pub fn zcount() -> App<'static> {
+        SubCommand::with_name("zcount")
+            .about("SortedSet.Count: Number of members within score range")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("min")
+                    .help("Minimum score; \"(\" prefix for exclusive, or -inf")
+                    .required(true)
+                    .allow_hyphen_values(true),
+            )
+            .arg(
+                Arg::with_name("max")
+                    .help("Maximum score; \"(\" prefix for exclusive, or +inf")
+                    .required(true)
+                    .allow_hyphen_values(true),
+            )
+    }
+
+    pub fn zinterstore() -> App<'static> {
+        store_args(
+            SubCommand::with_name("zinterstore")
+                .about("SortedSet.InterStore: Intersect sorted sets, and store result"),
+        )
+    }
+
+    pub fn zpopmax() -> App<'static> {
+        SubCommand::with_name("zpopmax")
+            .about("SortedSet.PopMax: Remove and return highest scored members")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to update")
+                    .required(true),
+            )
+            .arg(Arg::with_name("count").help("Number of members to pop (default: 1)"))
+    }
+
+    pub fn zpopmin() -> App<'static> {
+        SubCommand::with_name("zpopmin")
+            .about("SortedSet.PopMin: Remove and return lowest scored members")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to update")
+                    .required(true),
+            )
+            .arg(Arg::with_name("count").help("Number of members to pop (default: 1)"))
+    }
+
+    pub fn zrange() -> App<'static> {
+        SubCommand::with_name("zrange")
+            .about("SortedSet.Range: Query members within index, score or lex range")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("min")
+                    .help("Start index; or min score/lex bound (max, with --rev)")
+                    .required(true)
+                    .allow_hyphen_values(true),
+            )
+            .arg(
+                Arg::with_name("max")
+                    .help("Stop index; or max score/lex bound (min, with --rev)")
+                    .required(true)
+                    .allow_hyphen_values(true),
+            )
+            .arg(
+                Arg::with_name("byscore")
+                    .help("Range is score bounds: 1.5, (1.5, -inf, +inf")
+                    .long("byscore")
+                    .conflicts_with("bylex"),
+            )
+            .arg(
+                Arg::with_name("bylex")
+                    .help("Range is lex bounds: [member, (member, -, +")
+                    .long("bylex"),
+            )
+            .arg(
+                Arg::with_name("rev")
+                    .help("Return members in descending order")
+                    .long("rev"),
+            )
+            .arg(
+                Arg::with_name("limit")
+                    .help("Skip offset members; return at most count (negative: all)")
+                    .long("limit")
+                    .number_of_values(2)
+                    .value_names(&["offset", "count"])
+                    .allow_hyphen_values(true),
+            )
+            .arg(
+                Arg::with_name("withscores")
+                    .help("Output score following each member")
+                    .long("withscores"),
+            )
+    }
+
+    pub fn zrank() -> App<'static> {
+        SubCommand::with_name("zrank")
+            .about("SortedSet.Rank: Position of member, lowest score first")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("member")
+                    .help("Member to query")
+                    .required(true),
+            )
+    }
+
+    pub fn zrem() -> App<'static> {
+        SubCommand::with_name("zrem")
+            .about("SortedSet.Remove: Remove members from sorted set")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to update")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("member")
+                    .help("Member to remove")
+                    .required(true)
+                    .multiple(true),
+            )
+    }
+
+    pub fn zrevrank() -> App<'static> {
+        SubCommand::with_name("zrevrank")
+            .about("SortedSet.RevRank: Position of member, highest score first")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("member")
+                    .help("Member to query")
+                    .required(true),
+            )
+    }
+
+    pub fn zscore() -> App<'static> {
+        SubCommand::with_name("zscore")
+            .about("SortedSet.Score: Score of member")
+            .arg(
+                Arg::with_name("key")
+                    .help("Key of sorted set to query")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("member")
+                    .help("Member to query")
+                    .required(true),
+            )
+    }
+
+    pub fn zunionstore() -> App<'static> {
+        store_args(
+            SubCommand::with_name("zunionstore")
+                .about("SortedSet.UnionStore: Union sorted sets, and store result"),
+        )
+    }
+}
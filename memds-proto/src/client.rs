@@ -0,0 +1,88 @@
+// Client transport for the framed protocol:  requests and responses
+// carried in MemdsCodec frames, over any byte stream.
+//
+// Requests may be pipelined:  several sent before their responses are
+// received.  Each request is assigned an id, echoed by its response.
+//
+
+use bytes::BytesMut;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::codec::MemdsCodec;
+use crate::memds_api::{MemdsMessage, MemdsMessage_MsgType, RequestMsg, ResponseMsg};
+
+pub struct FramedClient<S: Read + Write> {
+    stream: S,
+    codec: MemdsCodec,
+    accum: BytesMut,
+    next_id: u64,
+}
+
+impl FramedClient<TcpStream> {
+    /// Connect to framed listener at `addr`.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<FramedClient<TcpStream>> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(FramedClient::new(stream))
+    }
+}
+
+impl<S: Read + Write> FramedClient<S> {
+    pub fn new(stream: S) -> FramedClient<S> {
+        FramedClient {
+            stream,
+            codec: MemdsCodec::new(),
+            accum: BytesMut::with_capacity(4096),
+            next_id: 1,
+        }
+    }
+
+    /// Send request without awaiting its response, returning its id.
+    pub fn send(&mut self, req: &RequestMsg) -> io::Result<u64> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut msg = MemdsMessage::new();
+        msg.mtype = MemdsMessage_MsgType::REQUEST;
+        msg.id = id;
+        msg.set_req(req.clone());
+
+        let mut frame = BytesMut::new();
+        self.codec.encode(msg, &mut frame)?;
+        self.stream.write_all(&frame)?;
+        Ok(id)
+    }
+
+    /// Receive next response, and the id of its request.
+    pub fn recv(&mut self) -> io::Result<(u64, ResponseMsg)> {
+        let mut buf = [0; 16384];
+        loop {
+            if let Some(mut msg) = self.codec.decode(&mut self.accum)? {
+                if msg.mtype != MemdsMessage_MsgType::RESPONSE {
+                    return Err(Error::new(ErrorKind::InvalidData, "expected response"));
+                }
+                return Ok((msg.id, msg.take_resp()));
+            }
+
+            let n_read = self.stream.read(&mut buf)?;
+            if n_read == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "connection closed"));
+            }
+            self.accum.extend_from_slice(&buf[0..n_read]);
+        }
+    }
+
+    /// Execute request, awaiting its response.  Responses to requests
+    /// pipelined earlier, and not yet received, are discarded.
+    pub fn exec(&mut self, req: &RequestMsg) -> io::Result<ResponseMsg> {
+        let id = self.send(req)?;
+        loop {
+            let (resp_id, resp) = self.recv()?;
+            if resp_id == id {
+                return Ok(resp);
+            }
+        }
+    }
+}
@@ -23,6 +23,7 @@ use crate::error::*;
 use crate::memds_api::MemdsMessage;
 
 const HDR_SIZE: usize = 4; // [1 byte magic][3 byte size]
+const CRC_SIZE: usize = 4;
 const MAGIC: u32 = 0x4D; // ASCII 'M'
 const MAGIC_SHIFT: usize = 24;
 const MSG_SIZE_MASK: u32 = 0xffffff; // lower 24 bits; thus max msg sz = 16M
@@ -54,9 +55,28 @@ impl MemdsCodec {
         }
     }
 
+    /// Create codec continuing an existing checksum chain, such as a
+    /// file being appended to, or being decoded from a mid-file offset.
+    pub fn with_chain(crc: u32) -> MemdsCodec {
+        let mut codec = MemdsCodec::new();
+        codec.last_dec_crc = crc;
+        codec.last_enc_crc = crc;
+        codec
+    }
+
+    /// Checksum chain value following the last frame encoded.
+    pub fn enc_chain(&self) -> u32 {
+        self.last_enc_crc
+    }
+
+    /// Checksum chain value following the last frame decoded.
+    pub fn dec_chain(&self) -> u32 {
+        self.last_dec_crc
+    }
+
     fn decode_head(&mut self, src: &mut BytesMut) -> Result<Option<usize>, MemdsError> {
-        // check hdr len + 1 (+1 for "something else follows")
-        if src.len() <= HDR_SIZE {
+        // check hdr + checksum len
+        if src.len() < HDR_SIZE + CRC_SIZE {
             return Ok(None);
         }
 
@@ -71,7 +91,7 @@ impl MemdsCodec {
                 return Err(MemdsError::InvalidFrame);
             }
 
-            let crc = src.get_uint(HDR_SIZE) as u32;
+            let crc = src.get_uint(CRC_SIZE) as u32;
             self.expect_crc = crc;
 
             (header & MSG_SIZE_MASK) as usize
@@ -79,7 +99,7 @@ impl MemdsCodec {
 
         // advance cursor past header
         let hdr_buf = src.split_to(HDR_SIZE);
-        src.advance(4); // skip crc
+        src.advance(CRC_SIZE); // skip crc
 
         // remember top HDR_SIZE bytes, by copying hdr_bytes -> self.hdr_buf
         let hdr_bytes = hdr_buf.clone().freeze();
@@ -188,9 +208,9 @@ impl Encoder for MemdsCodec {
         self.last_enc_crc = digest.finalize();
 
         // assemble frame parts in linear buffer
-        dst.reserve(HDR_SIZE + 4 + msg_len as usize);
+        dst.reserve(HDR_SIZE + CRC_SIZE + msg_len as usize);
         dst.put_uint(header as u64, HDR_SIZE);
-        dst.put_uint(self.last_enc_crc as u64, 4);
+        dst.put_uint(self.last_enc_crc as u64, CRC_SIZE);
         dst.extend_from_slice(&msg_bytes);
 
         Ok(())
@@ -239,6 +259,28 @@ mod tests {
         assert_eq!(enc_msg, dec_msg);
     }
 
+    #[test]
+    fn chain_resume() {
+        let mut codec = MemdsCodec::new();
+
+        // encode two messages
+        let mut enc_msg = MemdsMessage::new();
+        enc_msg.mtype = MemdsMessage_MsgType::DBVAL;
+        let enc_msg_raw = &mut BytesMut::new();
+        codec.encode(enc_msg.clone(), enc_msg_raw).unwrap();
+        let offset = enc_msg_raw.len();
+        let chain = codec.enc_chain();
+        enc_msg.mtype = MemdsMessage_MsgType::END;
+        codec.encode(enc_msg.clone(), enc_msg_raw).unwrap();
+
+        // decode second message only, resuming chain at its offset
+        let mut tail = enc_msg_raw.split_off(offset);
+        let mut dec_codec = MemdsCodec::with_chain(chain);
+        let dec_msg = dec_codec.decode(&mut tail).unwrap().unwrap();
+        assert_eq!(enc_msg, dec_msg);
+        assert_eq!(dec_codec.dec_chain(), codec.enc_chain());
+    }
+
     #[test]
     fn invalid_checksum() {
         let mut codec = MemdsCodec::new();
@@ -11,3 +11,12 @@ impl From<std::io::Error> for MemdsError {
         MemdsError::IO(err)
     }
 }
+
+impl From<MemdsError> for std::io::Error {
+    fn from(err: MemdsError) -> std::io::Error {
+        match err {
+            MemdsError::IO(e) => e,
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:?}", e)),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use crate::scan::ScanIndex;
+
+/// The fields of a hash, each with its value.
+///
+/// Field lookup is O(1), via a hash map; fields are also indexed in
+/// scan order, so that HSCAN examines only the fields it returns.
+#[derive(Clone, Debug, Default)]
+pub struct FieldMap {
+    map: HashMap<Vec<u8>, Vec<u8>>,
+    scan_index: ScanIndex,
+}
+
+impl PartialEq for FieldMap {
+    fn eq(&self, other: &FieldMap) -> bool {
+        self.map == other.map
+    }
+}
+
+impl FieldMap {
+    pub fn new() -> FieldMap {
+        FieldMap {
+            map: HashMap::new(),
+            scan_index: ScanIndex::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&Vec<u8>> {
+        self.map.get(field)
+    }
+
+    pub fn contains_key(&self, field: &[u8]) -> bool {
+        self.map.contains_key(field)
+    }
+
+    /// Set field to value.  Returns the previous value, if any.
+    pub fn insert(&mut self, field: Vec<u8>, value: Vec<u8>) -> Option<Vec<u8>> {
+        if !self.map.contains_key(&field) {
+            self.scan_index.insert(&field);
+        }
+        self.map.insert(field, value)
+    }
+
+    /// Remove field, returning its value.
+    pub fn remove(&mut self, field: &[u8]) -> Option<Vec<u8>> {
+        let value = self.map.remove(field)?;
+        self.scan_index.remove(field);
+        Some(value)
+    }
+
+    /// Iterate over (field, value), in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &Vec<u8>)> {
+        self.map.iter()
+    }
+
+    /// Iterate over (scan hash, (field, value)), in scan order, from
+    /// `cursor`.
+    pub fn scan(&self, cursor: u64) -> impl Iterator<Item = (u64, (&Vec<u8>, &Vec<u8>))> {
+        self.scan_index
+            .range(cursor)
+            .map(move |(h, field)| (h, (field, &self.map[field])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fieldmap::FieldMap;
+    use crate::scan::scan_hash;
+
+    #[test]
+    fn scan_order() {
+        let mut hm = FieldMap::new();
+        for i in 0..10 {
+            hm.insert(format!("f{}", i).into_bytes(), b"v".to_vec());
+        }
+        assert_eq!(
+            hm.insert(b"f1".to_vec(), b"w".to_vec()),
+            Some(b"v".to_vec())
+        );
+        assert_eq!(hm.remove(b"f2"), Some(b"v".to_vec()));
+        assert_eq!(hm.remove(b"f2"), None);
+
+        // fields, with current values, in scan order
+        let fields: Vec<_> = hm.scan(0).collect();
+        assert_eq!(fields.len(), 9);
+        assert!(fields.windows(2).all(|w| w[0].0 <= w[1].0));
+        let (h, (_, value)) = fields.iter().find(|(_, (f, _))| *f == b"f1").unwrap();
+        assert_eq!((*h, value.as_slice()), (scan_hash(b"f1"), b"w".as_ref()));
+        assert_eq!(hm.scan(fields[5].0).count(), 4);
+    }
+}
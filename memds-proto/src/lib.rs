@@ -1,20 +1,30 @@
 use std::collections::HashSet;
 
+mod client;
 mod codec;
 mod error;
+mod fieldmap;
+mod scan;
+mod zset;
 
 pub const DEF_PORT: u16 = 16900;
+pub const DEF_FRAMED_PORT: u16 = 16901;
 
 #[derive(Clone)]
 pub enum Atom {
     String(Vec<u8>),
     List(Vec<Vec<u8>>),
     Set(HashSet<Vec<u8>>),
+    Hash(FieldMap),
+    SortedSet(SortedSet),
 }
 
 pub mod memds_api;
 pub mod memds_api_grpc;
-pub mod util;
 
+pub use client::FramedClient;
 pub use codec::MemdsCodec;
 pub use error::MemdsError;
+pub use fieldmap::FieldMap;
+pub use scan::{scan_hash, ScanIndex};
+pub use zset::SortedSet;
@@ -0,0 +1,74 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+
+/// Position of key within the scan order.  Fixed for the life of the
+/// process, independent of the layout or capacity of any HashMap.
+pub fn scan_hash(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The keys of a collection, ordered by `scan_hash()`, so that
+/// cursor-based iteration examines only the keys it returns.
+#[derive(Clone, Debug, Default)]
+pub struct ScanIndex {
+    keys: BTreeSet<(u64, Vec<u8>)>,
+}
+
+impl ScanIndex {
+    pub fn new() -> ScanIndex {
+        ScanIndex {
+            keys: BTreeSet::new(),
+        }
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        self.keys.insert((scan_hash(key), key.to_vec()));
+    }
+
+    pub fn remove(&mut self, key: &[u8]) {
+        self.keys.remove(&(scan_hash(key), key.to_vec()));
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    /// Iterate over (scan hash, key), in scan order, from `cursor`.
+    pub fn range(&self, cursor: u64) -> impl Iterator<Item = (u64, &Vec<u8>)> {
+        self.keys
+            .range((cursor, Vec::new())..)
+            .map(|(h, key)| (*h, key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scan::{scan_hash, ScanIndex};
+
+    #[test]
+    fn range() {
+        let mut index = ScanIndex::new();
+        for i in 0..100 {
+            index.insert(format!("key{}", i).as_bytes());
+        }
+        index.remove(b"key7");
+        index.remove(b"nope");
+
+        // every key, in scan order
+        let all: Vec<(u64, &Vec<u8>)> = index.range(0).collect();
+        assert_eq!(all.len(), 99);
+        assert!(all.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert!(all.iter().all(|(h, key)| *h == scan_hash(key)));
+
+        // from cursor:  those at or after it
+        let cursor = all[50].0;
+        assert_eq!(index.range(cursor).count(), 49);
+        assert_eq!(index.range(cursor).next().unwrap().1, all[50].1);
+
+        index.clear();
+        assert_eq!(index.range(0).count(), 0);
+    }
+}
@@ -0,0 +1,274 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+type Link = Option<Box<Node>>;
+
+// treap node, ordered by (score, member), with max-heap priority
+#[derive(Clone)]
+struct Node {
+    score: f64,
+    member: Vec<u8>,
+    prio: u64,
+    size: usize, // number of nodes in subtree rooted here
+    left: Link,
+    right: Link,
+}
+
+impl Node {
+    fn new(member: Vec<u8>, score: f64, prio: u64) -> Node {
+        Node {
+            score,
+            member,
+            prio,
+            size: 1,
+            left: None,
+            right: None,
+        }
+    }
+
+    fn update(&mut self) {
+        self.size = 1 + size(&self.left) + size(&self.right);
+    }
+}
+
+fn size(t: &Link) -> usize {
+    match t {
+        None => 0,
+        Some(n) => n.size,
+    }
+}
+
+/// Total order of sorted set members:  by score, then by member bytes.
+/// Scores are never NaN.
+pub fn cmp_scored(score_a: f64, member_a: &[u8], score_b: f64, member_b: &[u8]) -> Ordering {
+    match score_a.partial_cmp(&score_b) {
+        Some(Ordering::Equal) | None => member_a.cmp(member_b),
+        Some(ord) => ord,
+    }
+}
+
+// split t into (nodes for which goes_left is true, remainder).
+// goes_left must be monotone over the treap order.
+fn split(t: Link, goes_left: &dyn Fn(f64, &[u8]) -> bool) -> (Link, Link) {
+    match t {
+        None => (None, None),
+        Some(mut n) => {
+            if goes_left(n.score, &n.member) {
+                let (l, r) = split(n.right.take(), goes_left);
+                n.right = l;
+                n.update();
+                (Some(n), r)
+            } else {
+                let (l, r) = split(n.left.take(), goes_left);
+                n.left = r;
+                n.update();
+                (l, Some(n))
+            }
+        }
+    }
+}
+
+// join two treaps; every node of a orders before every node of b
+fn merge(a: Link, b: Link) -> Link {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(mut a), Some(mut b)) => {
+            if a.prio > b.prio {
+                a.right = merge(a.right.take(), Some(b));
+                a.update();
+                Some(a)
+            } else {
+                b.left = merge(Some(a), b.left.take());
+                b.update();
+                Some(b)
+            }
+        }
+    }
+}
+
+/// A set of unique members, each with a floating point score, ordered
+/// by (score, member).
+///
+/// Member lookup is O(1), via a hash map; insertion, removal, rank and
+/// positional access are O(log n), via an order-statistic treap.
+#[derive(Clone)]
+pub struct SortedSet {
+    scores: HashMap<Vec<u8>, f64>,
+    root: Link,
+    seed: u64, // treap priority generator state
+}
+
+impl Default for SortedSet {
+    fn default() -> Self {
+        SortedSet::new()
+    }
+}
+
+impl SortedSet {
+    pub fn new() -> SortedSet {
+        SortedSet {
+            scores: HashMap::new(),
+            root: None,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    // xorshift64* pseudo-random treap priority
+    fn next_prio(&mut self) -> u64 {
+        self.seed ^= self.seed >> 12;
+        self.seed ^= self.seed << 25;
+        self.seed ^= self.seed >> 27;
+        self.seed.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Add member, or update score of existing member.  Returns the
+    /// previous score, if any.  Score must not be NaN.
+    pub fn insert(&mut self, member: Vec<u8>, score: f64) -> Option<f64> {
+        assert!(!score.is_nan());
+
+        let old_score = self.remove(&member);
+
+        let prio = self.next_prio();
+        let (l, r) = split(self.root.take(), &|s, m| {
+            cmp_scored(s, m, score, &member) == Ordering::Less
+        });
+        let node = Some(Box::new(Node::new(member.clone(), score, prio)));
+        self.root = merge(merge(l, node), r);
+        self.scores.insert(member, score);
+
+        old_score
+    }
+
+    /// Remove member, returning its score.
+    pub fn remove(&mut self, member: &[u8]) -> Option<f64> {
+        let score = self.scores.remove(member)?;
+
+        let (l, r) = split(self.root.take(), &|s, m| {
+            cmp_scored(s, m, score, member) == Ordering::Less
+        });
+        let (_, r) = split(r, &|s, m| {
+            cmp_scored(s, m, score, member) != Ordering::Greater
+        });
+        self.root = merge(l, r);
+
+        Some(score)
+    }
+
+    /// Number of members, in order, for which `pred` is true.  `pred`
+    /// must be monotone:  true for a prefix of the order, false after.
+    pub fn count_while(&self, pred: &dyn Fn(f64, &[u8]) -> bool) -> usize {
+        let mut count = 0;
+        let mut t = &self.root;
+        while let Some(n) = t {
+            if pred(n.score, &n.member) {
+                count += size(&n.left) + 1;
+                t = &n.right;
+            } else {
+                t = &n.left;
+            }
+        }
+
+        count
+    }
+
+    /// Zero-based position of member, in ascending order.
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        let score = self.score(member)?;
+        Some(self.count_while(&|s, m| cmp_scored(s, m, score, member) == Ordering::Less))
+    }
+
+    /// Member and score at zero-based position, in ascending order.
+    pub fn get_index(&self, mut index: usize) -> Option<(&Vec<u8>, f64)> {
+        let mut t = &self.root;
+        while let Some(n) = t {
+            let left_size = size(&n.left);
+            match index.cmp(&left_size) {
+                Ordering::Less => t = &n.left,
+                Ordering::Equal => return Some((&n.member, n.score)),
+                Ordering::Greater => {
+                    index -= left_size + 1;
+                    t = &n.right;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Iterate over (member, score), in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, f64)> {
+        let mut out = Vec::with_capacity(self.len());
+        let mut stack: Vec<&Node> = Vec::new();
+        let mut t = &self.root;
+        loop {
+            while let Some(n) = t {
+                stack.push(n);
+                t = &n.left;
+            }
+            match stack.pop() {
+                None => break,
+                Some(n) => {
+                    out.push((&n.member, n.score));
+                    t = &n.right;
+                }
+            }
+        }
+
+        out.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zset::SortedSet;
+
+    #[test]
+    fn order_and_rank() {
+        let mut zs = SortedSet::new();
+        for i in 0..100 {
+            let member = format!("m{:03}", i).into_bytes();
+            assert!(zs.insert(member, (i % 10) as f64).is_none());
+        }
+        assert_eq!(zs.len(), 100);
+
+        // ascending by score, then member
+        let order: Vec<_> = zs.iter().map(|(m, s)| (m.clone(), s)).collect();
+        for pair in order.windows(2) {
+            assert!(pair[0].1 < pair[1].1 || (pair[0].1 == pair[1].1 && pair[0].0 < pair[1].0));
+        }
+
+        // rank and positional access agree
+        for (i, (member, _)) in order.iter().enumerate() {
+            assert_eq!(zs.rank(member), Some(i));
+            assert_eq!(zs.get_index(i).unwrap().0, member);
+        }
+        assert!(zs.get_index(100).is_none());
+
+        // update score moves member
+        assert_eq!(zs.insert(b"m000".to_vec(), 100.0), Some(0.0));
+        assert_eq!(zs.rank(b"m000"), Some(99));
+        assert_eq!(zs.len(), 100);
+
+        // remove
+        assert_eq!(zs.remove(b"m000"), Some(100.0));
+        assert_eq!(zs.remove(b"m000"), None);
+        assert_eq!(zs.len(), 99);
+        assert_eq!(zs.iter().count(), 99);
+
+        // count scores below 5.0
+        assert_eq!(zs.count_while(&|s, _| s < 5.0), 49);
+    }
+}
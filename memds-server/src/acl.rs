@@ -0,0 +1,624 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use memds_proto::memds_api::{
+    AclOp, AclRes, AclUser, AuthOp, ErrorCode, OpResult, OpType, Operation,
+};
+
+use crate::error::OpError;
+use crate::registry::{
+    Access, Handler, KeyScope, Method, ServerCtx, ADMIN, ARG_ACL, ARG_AUTH, DANGEROUS, NO_AUTH,
+};
+use crate::util;
+
+/// User as whom unauthenticated clients act, if it requires no password.
+pub const DEFAULT_USER: &str = "default";
+
+// operation categories, granted to users as a set
+pub const CAT_READ: u32 = 1 << 0;
+pub const CAT_WRITE: u32 = 1 << 1;
+pub const CAT_ADMIN: u32 = 1 << 2;
+pub const CAT_DANGEROUS: u32 = 1 << 3;
+pub const CAT_ALL: u32 = CAT_READ | CAT_WRITE | CAT_ADMIN | CAT_DANGEROUS;
+
+const CATEGORIES: &[(&str, u32)] = &[
+    ("read", CAT_READ),
+    ("write", CAT_WRITE),
+    ("admin", CAT_ADMIN),
+    ("dangerous", CAT_DANGEROUS),
+];
+
+/// Categories an operation falls within; a user must be granted each.
+pub fn categories(method: &Method, op: &Operation) -> u32 {
+    let mut cats = if method.is_write(op) {
+        CAT_WRITE
+    } else {
+        CAT_READ
+    };
+    if method.has_flag(ADMIN) {
+        cats |= CAT_ADMIN;
+    }
+    if method.has_flag(DANGEROUS) {
+        cats |= CAT_DANGEROUS;
+    }
+    cats
+}
+
+/// A user:  its credentials, and the operations and keys permitted it.
+/// Passwords and tokens are held only as SHA-256 digests.
+#[derive(Clone, Debug)]
+pub struct User {
+    pub name: String,
+    pub enabled: bool,
+    pub nopass: bool, // authenticates with any password, or none
+    passwords: Vec<String>,
+    tokens: Vec<String>,
+    pub categories: u32,
+    pub key_patterns: Vec<Vec<u8>>, // glob patterns
+}
+
+impl User {
+    /// New user, disabled, and permitted nothing.
+    pub fn new(name: &str) -> User {
+        User {
+            name: name.to_string(),
+            enabled: false,
+            nopass: false,
+            passwords: Vec::new(),
+            tokens: Vec::new(),
+            categories: 0,
+            key_patterns: Vec::new(),
+        }
+    }
+
+    /// Apply one rule, as in the ACL file:
+    ///
+    /// * `on`, `off`:  enable or disable user
+    /// * `>password`, `<password`:  add or remove password
+    /// * `#digest`, `!digest`:  add or remove password, by its digest
+    /// * `nopass`, `resetpass`:  permit any password, or none
+    /// * `token:secret`, `token#digest`, `resettokens`:  bearer tokens
+    /// * `~pattern`, `allkeys`, `resetkeys`:  key patterns permitted
+    /// * `+@category`, `-@category`:  grant or revoke read, write, admin,
+    ///   dangerous or all; `allcommands` and `nocommands` likewise
+    /// * `reset`:  return to the state of a new user
+    pub fn apply(&mut self, rule: &str) -> Result<(), String> {
+        let bad_rule = || format!("Error in ACL rule '{}'", rule);
+
+        if let Some(pw) = rule.strip_prefix('>') {
+            add_digest(&mut self.passwords, util::sha256_hex(pw.as_bytes()));
+            self.nopass = false;
+        } else if let Some(pw) = rule.strip_prefix('<') {
+            remove_digest(&mut self.passwords, &util::sha256_hex(pw.as_bytes()))?;
+        } else if let Some(digest) = rule.strip_prefix('#') {
+            add_digest(
+                &mut self.passwords,
+                parse_digest(digest).ok_or_else(bad_rule)?,
+            );
+            self.nopass = false;
+        } else if let Some(digest) = rule.strip_prefix('!') {
+            remove_digest(
+                &mut self.passwords,
+                &parse_digest(digest).ok_or_else(bad_rule)?,
+            )?;
+        } else if let Some(token) = rule.strip_prefix("token:") {
+            add_digest(&mut self.tokens, util::sha256_hex(token.as_bytes()));
+        } else if let Some(digest) = rule.strip_prefix("token#") {
+            add_digest(&mut self.tokens, parse_digest(digest).ok_or_else(bad_rule)?);
+        } else if let Some(pattern) = rule.strip_prefix('~') {
+            let pattern = pattern.as_bytes().to_vec();
+            if !self.key_patterns.contains(&pattern) {
+                self.key_patterns.push(pattern);
+            }
+        } else if let Some(cat) = rule.strip_prefix("+@") {
+            self.categories |= parse_category(cat).ok_or_else(bad_rule)?;
+        } else if let Some(cat) = rule.strip_prefix("-@") {
+            self.categories &= !parse_category(cat).ok_or_else(bad_rule)?;
+        } else {
+            match rule.to_ascii_lowercase().as_str() {
+                "on" => self.enabled = true,
+                "off" => self.enabled = false,
+                "nopass" => {
+                    self.nopass = true;
+                    self.passwords.clear();
+                }
+                "resetpass" => {
+                    self.nopass = false;
+                    self.passwords.clear();
+                }
+                "resettokens" => self.tokens.clear(),
+                "allkeys" => self.key_patterns = vec![b"*".to_vec()],
+                "resetkeys" => self.key_patterns.clear(),
+                "allcommands" => self.categories = CAT_ALL,
+                "nocommands" => self.categories = 0,
+                "reset" => *self = User::new(&self.name),
+                _ => return Err(bad_rule()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Rules recreating this user, from the state of a new user.
+    pub fn rules(&self) -> Vec<String> {
+        let mut rules = vec![if self.enabled { "on" } else { "off" }.to_string()];
+        if self.nopass {
+            rules.push("nopass".to_string());
+        }
+        for digest in self.passwords.iter() {
+            rules.push(format!("#{}", digest));
+        }
+        for digest in self.tokens.iter() {
+            rules.push(format!("token#{}", digest));
+        }
+        for pattern in self.key_patterns.iter() {
+            rules.push(format!("~{}", String::from_utf8_lossy(pattern)));
+        }
+        if self.categories == CAT_ALL {
+            rules.push("+@all".to_string());
+        } else {
+            for name in category_names(self.categories) {
+                rules.push(format!("+@{}", name));
+            }
+        }
+        rules
+    }
+
+    pub fn permits_key(&self, key: &[u8]) -> bool {
+        self.key_patterns.iter().any(|p| util::glob_match(p, key))
+    }
+
+    /// Check user is permitted the operation, and each key it names.
+    pub fn check(&self, method: &Method, op: &Operation) -> Result<(), OpError> {
+        if categories(method, op) & !self.categories != 0 {
+            let msg = format!(
+                "User {} has no permissions to run {}",
+                self.name,
+                method.full_name()
+            );
+            return Err(OpError::new(ErrorCode::NOPERM, &msg));
+        }
+
+        if let Some(arg) = &method.arg {
+            for key in (arg.key_values)(op) {
+                self.check_key(key)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_key(&self, key: &[u8]) -> Result<(), OpError> {
+        if self.permits_key(key) {
+            Ok(())
+        } else {
+            let msg = format!("User {} has no permissions to access a key", self.name);
+            Err(OpError::new(ErrorCode::NOPERM, &msg))
+        }
+    }
+
+    fn info(&self) -> AclUser {
+        let mut info = AclUser::new();
+        info.set_username(self.name.clone());
+        info.enabled = self.enabled;
+        info.nopass = self.nopass;
+        for name in category_names(self.categories) {
+            info.categories.push(name.to_string());
+        }
+        for pattern in self.key_patterns.iter() {
+            info.key_patterns.push(pattern.clone());
+        }
+        for rule in self.rules() {
+            info.rules.push(rule);
+        }
+        info
+    }
+}
+
+fn parse_category(name: &str) -> Option<u32> {
+    if name.eq_ignore_ascii_case("all") {
+        return Some(CAT_ALL);
+    }
+    CATEGORIES
+        .iter()
+        .find(|(cat_name, _)| cat_name.eq_ignore_ascii_case(name))
+        .map(|(_, cat)| *cat)
+}
+
+fn category_names(cats: u32) -> impl Iterator<Item = &'static str> {
+    CATEGORIES
+        .iter()
+        .filter(move |(_, cat)| cats & cat != 0)
+        .map(|(name, _)| *name)
+}
+
+// SHA-256 digest, as 64 hex digits
+fn parse_digest(s: &str) -> Option<String> {
+    if s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Some(s.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+// true if `digest` is amongst `digests`.  every one is compared, each
+// in constant time, so that the time taken reveals nothing of them
+fn has_digest(digests: &[String], digest: &str) -> bool {
+    digests.iter().fold(false, |found, d| {
+        util::ct_eq(d.as_bytes(), digest.as_bytes()) | found
+    })
+}
+
+fn add_digest(digests: &mut Vec<String>, digest: String) {
+    if !digests.contains(&digest) {
+        digests.push(digest);
+    }
+}
+
+fn remove_digest(digests: &mut Vec<String>, digest: &str) -> Result<(), String> {
+    match digests.iter().position(|d| d == digest) {
+        None => Err("No such password".to_string()),
+        Some(pos) => {
+            digests.remove(pos);
+            Ok(())
+        }
+    }
+}
+
+/// Users, by name.  Initially the default user alone, which requires no
+/// password and is permitted everything; absent other configuration,
+/// every client acts as it.
+pub struct Acl {
+    users: BTreeMap<String, User>,
+}
+
+impl Acl {
+    pub fn new() -> Acl {
+        let mut default = User::new(DEFAULT_USER);
+        for rule in ["on", "nopass", "allkeys", "allcommands"] {
+            default.apply(rule).unwrap();
+        }
+
+        let mut users = BTreeMap::new();
+        users.insert(DEFAULT_USER.to_string(), default);
+        Acl { users }
+    }
+
+    /// Parse ACL file:  a `user <name> <rule>...` line per user, applied
+    /// in turn, starting from the default user alone.  Blank lines and
+    /// `#` comments are ignored.
+    pub fn parse(text: &str) -> Result<Acl, String> {
+        let mut acl = Acl::new();
+        for (n, line) in text.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                None => continue,
+                Some(word) if word.starts_with('#') => continue,
+                Some("user") => {}
+                Some(_) => return Err(format!("line {}: expected 'user'", n + 1)),
+            }
+            let name = match words.next() {
+                None => return Err(format!("line {}: user name required", n + 1)),
+                Some(name) => name,
+            };
+            let rules: Vec<&str> = words.collect();
+            acl.set_user(name, &rules)
+                .map_err(|e| format!("line {}: {}", n + 1, e))?;
+        }
+        Ok(acl)
+    }
+
+    pub fn load(path: &Path) -> Result<Acl, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Acl::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // create or modify user, applying rules in order; upon failure, the
+    // user is unchanged
+    fn set_user<S: AsRef<str>>(&mut self, name: &str, rules: &[S]) -> Result<(), String> {
+        let mut user = match self.users.get(name) {
+            Some(user) => user.clone(),
+            None => User::new(name),
+        };
+        for rule in rules.iter() {
+            user.apply(rule.as_ref())?;
+        }
+
+        // tokens identify their user, so may not be shared
+        let shared = self
+            .users
+            .values()
+            .filter(|other| other.name != name)
+            .any(|other| other.tokens.iter().any(|t| user.tokens.contains(t)));
+        if shared {
+            return Err("Token in use by another user".to_string());
+        }
+
+        self.users.insert(name.to_string(), user);
+        Ok(())
+    }
+
+    /// Name of user the credentials authenticate, if enabled.
+    pub fn authenticate(&self, creds: &AuthOp) -> Result<String, OpError> {
+        let user = if !creds.token.is_empty() {
+            let digest = util::sha256_hex(&creds.token);
+            self.users.values().fold(None, |found, u| {
+                if has_digest(&u.tokens, &digest) {
+                    Some(u)
+                } else {
+                    found
+                }
+            })
+        } else {
+            let name = match creds.get_username() {
+                "" => DEFAULT_USER,
+                name => name,
+            };
+            let digest = util::sha256_hex(&creds.password);
+            self.users
+                .get(name)
+                .filter(|u| has_digest(&u.passwords, &digest) || u.nopass)
+        };
+
+        match user {
+            Some(user) if user.enabled => Ok(user.name.clone()),
+            _ => Err(OpError::new(
+                ErrorCode::NOAUTH,
+                "Invalid username-password pair or user is disabled",
+            )),
+        }
+    }
+
+    /// User as whom a client acts:  the user it authenticated as, else the
+    /// default user, if that requires no password.
+    pub fn user(&self, name: Option<&str>) -> Result<&User, OpError> {
+        let user = match name {
+            Some(name) => self.users.get(name),
+            None => self.users.get(DEFAULT_USER).filter(|u| u.nopass),
+        };
+        match user {
+            Some(user) if user.enabled => Ok(user),
+            _ => Err(OpError::new(ErrorCode::NOAUTH, "Authentication required")),
+        }
+    }
+
+    /// Check the client, authenticated as user `name` if any, is permitted
+    /// the operation.
+    pub fn check(
+        &self,
+        name: Option<&str>,
+        method: &Method,
+        op: &Operation,
+    ) -> Result<(), OpError> {
+        if method.has_flag(NO_AUTH) {
+            return Ok(());
+        }
+        self.user(name)?.check(method, op)
+    }
+
+    /// Check the client, authenticated as user `name` if any, is permitted
+    /// access to key, as by watching it or reading its version.
+    pub fn check_key(&self, name: Option<&str>, key: &[u8]) -> Result<(), OpError> {
+        self.user(name)?.check_key(key)
+    }
+}
+
+fn acl_result(otype: OpType, acl_res: AclRes) -> OpResult {
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = otype;
+    op_res.set_acl(acl_res);
+
+    op_res
+}
+
+pub fn auth(ctx: &mut ServerCtx, req: &AuthOp) -> OpResult {
+    let name = match ctx.acl.read().unwrap().authenticate(req) {
+        Ok(name) => name,
+        Err(err) => return err.into(),
+    };
+    *ctx.user = Some(name.clone());
+
+    let mut acl_res = AclRes::new();
+    acl_res.set_username(name);
+    acl_result(OpType::ACL_AUTH, acl_res)
+}
+
+pub fn whoami(ctx: &ServerCtx) -> OpResult {
+    let name = ctx.user.as_deref().unwrap_or(DEFAULT_USER);
+
+    let mut acl_res = AclRes::new();
+    acl_res.set_username(name.to_string());
+    acl_result(OpType::ACL_WHOAMI, acl_res)
+}
+
+pub fn setuser(acl: &mut Acl, req: &AclOp) -> OpResult {
+    if req.get_username().is_empty() {
+        return OpError::invalid_args("User name required").into();
+    }
+    if let Err(msg) = acl.set_user(req.get_username(), &req.rules) {
+        return OpError::invalid_args(&msg).into();
+    }
+    acl_result(OpType::ACL_SETUSER, AclRes::new())
+}
+
+pub fn getuser(acl: &Acl, req: &AclOp) -> OpResult {
+    let user = match acl.users.get(req.get_username()) {
+        None => return OpError::not_found().into(),
+        Some(user) => user,
+    };
+
+    let mut acl_res = AclRes::new();
+    acl_res.users.push(user.info());
+    acl_result(OpType::ACL_GETUSER, acl_res)
+}
+
+pub fn deluser(acl: &mut Acl, req: &AclOp) -> OpResult {
+    if req.get_username() == DEFAULT_USER {
+        return OpError::invalid_args("The 'default' user cannot be removed").into();
+    }
+    if acl.users.remove(req.get_username()).is_none() {
+        return OpError::not_found().into();
+    }
+    acl_result(OpType::ACL_DELUSER, AclRes::new())
+}
+
+pub fn list(acl: &Acl) -> OpResult {
+    let mut acl_res = AclRes::new();
+    for user in acl.users.values() {
+        acl_res.users.push(user.info());
+    }
+    acl_result(OpType::ACL_LIST, acl_res)
+}
+
+/// Acl module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "Acl",
+        name: "Auth",
+        otype: OpType::ACL_AUTH,
+        arg: Some(ARG_AUTH),
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: NO_AUTH,
+        handler: Handler::Server(|ctx, op| auth(ctx, op.get_auth())),
+    },
+    Method {
+        module: "Acl",
+        name: "SetUser",
+        otype: OpType::ACL_SETUSER,
+        arg: Some(ARG_ACL),
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Server(|ctx, op| setuser(&mut ctx.acl.write().unwrap(), op.get_acl())),
+    },
+    Method {
+        module: "Acl",
+        name: "GetUser",
+        otype: OpType::ACL_GETUSER,
+        arg: Some(ARG_ACL),
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: ADMIN,
+        handler: Handler::Server(|ctx, op| getuser(&ctx.acl.read().unwrap(), op.get_acl())),
+    },
+    Method {
+        module: "Acl",
+        name: "DelUser",
+        otype: OpType::ACL_DELUSER,
+        arg: Some(ARG_ACL),
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Server(|ctx, op| deluser(&mut ctx.acl.write().unwrap(), op.get_acl())),
+    },
+    Method {
+        module: "Acl",
+        name: "List",
+        otype: OpType::ACL_LIST,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: ADMIN,
+        handler: Handler::Server(|ctx, _op| list(&ctx.acl.read().unwrap())),
+    },
+    Method {
+        module: "Acl",
+        name: "WhoAmI",
+        otype: OpType::ACL_WHOAMI,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: NO_AUTH,
+        handler: Handler::Server(|ctx, _op| whoami(ctx)),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{Acl, DEFAULT_USER};
+    use crate::registry;
+    use memds_proto::memds_api::{AuthOp, ErrorCode, OpType, Operation};
+
+    fn get_op(key: &[u8]) -> Operation {
+        let mut op = Operation::new();
+        op.otype = OpType::STR_GET;
+        op.mut_get().set_key(key.to_vec());
+        op
+    }
+
+    fn creds(username: &str, password: &str) -> AuthOp {
+        let mut creds = AuthOp::new();
+        creds.set_username(username.to_string());
+        creds.set_password(password.as_bytes().to_vec());
+        creds
+    }
+
+    #[test]
+    fn rules() {
+        let acl = Acl::parse(
+            "# comment\n\
+             \n\
+             user default off\n\
+             user alice on >secret ~app:* +@read\n\
+             user bob on token:t0k +@all -@dangerous ~*\n",
+        )
+        .unwrap();
+        let get = registry::lookup(OpType::STR_GET).unwrap();
+        let set = registry::lookup(OpType::STR_SET).unwrap();
+        let flushall = registry::lookup(OpType::SRV_FLUSHALL).unwrap();
+
+        // default user disabled:  authentication required
+        let err = acl.check(None, get, &get_op(b"app:1")).unwrap_err();
+        assert_eq!(err.code, ErrorCode::NOAUTH);
+        let whoami = registry::lookup(OpType::ACL_WHOAMI).unwrap();
+        assert!(acl.check(None, whoami, &Operation::new()).is_ok());
+
+        // passwords
+        assert_eq!(
+            acl.authenticate(&creds("alice", "secret")).unwrap(),
+            "alice"
+        );
+        assert!(acl.authenticate(&creds("alice", "wrong")).is_err());
+        assert!(acl.authenticate(&creds(DEFAULT_USER, "")).is_err());
+
+        // tokens identify their user
+        let mut token = AuthOp::new();
+        token.set_token(b"t0k".to_vec());
+        assert_eq!(acl.authenticate(&token).unwrap(), "bob");
+
+        // categories, and key patterns
+        let alice = Some("alice");
+        assert!(acl.check(alice, get, &get_op(b"app:1")).is_ok());
+        let err = acl.check(alice, get, &get_op(b"other")).unwrap_err();
+        assert_eq!(err.code, ErrorCode::NOPERM);
+        let mut op = Operation::new();
+        op.otype = OpType::STR_SET;
+        op.mut_set().set_key(b"app:1".to_vec());
+        assert!(acl.check(alice, set, &op).is_err());
+        assert!(acl.check(Some("bob"), set, &op).is_ok());
+        assert!(acl.check(Some("bob"), flushall, &Operation::new()).is_err());
+
+        // rules recreate user
+        let rules = acl.users["alice"].rules();
+        assert_eq!(rules[0], "on");
+        assert!(rules[1].starts_with('#'));
+        assert_eq!(rules[1].len(), 65);
+        assert_eq!(&rules[2..], &["~app:*", "+@read"]);
+        let mut copy = Acl::new();
+        copy.set_user("alice", &rules).unwrap();
+        assert_eq!(
+            copy.authenticate(&creds("alice", "secret")).unwrap(),
+            "alice"
+        );
+
+        // failed rules leave user unchanged
+        let mut acl = acl;
+        assert!(acl.set_user("alice", &["off", "+@bogus"]).is_err());
+        assert!(acl.users["alice"].enabled);
+        assert!(acl.set_user("alice", &["token:t0k"]).is_err());
+
+        assert!(Acl::parse("group x").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use memds_proto::memds_api::{
+    OpCondition, OpCondition_Test, OpRef, OpRef_Source, OpRef_Target, OpResult, Operation,
+};
+
+use crate::error::OpError;
+
+// result of earlier op in batch
+fn earlier_result(results: &[OpResult], op_index: u32) -> Result<&OpResult, OpError> {
+    match results.get(op_index as usize) {
+        None => Err(OpError::invalid_args("Reference to later op")),
+        Some(res) => Ok(res),
+    }
+}
+
+// count of result:  count.n, else number of list elements
+fn result_count(res: &OpResult) -> Option<u64> {
+    if res.has_count() {
+        Some(res.get_count().n)
+    } else if res.has_list() {
+        Some(res.get_list().elements.len() as u64)
+    } else {
+        None
+    }
+}
+
+/// Does op's condition of execution, if any, hold?  `results` are those
+/// of the ops preceding it in the batch.
+pub fn condition_holds(op: &Operation, results: &[OpResult]) -> Result<bool, OpError> {
+    if !op.has_cond() {
+        return Ok(true);
+    }
+
+    let cond: &OpCondition = op.get_cond();
+    let res = earlier_result(results, cond.op_index)?;
+    let succeeded = res.ok && !res.skipped;
+    let holds = match cond.test {
+        OpCondition_Test::SUCCEEDED => succeeded,
+        OpCondition_Test::FAILED => !res.ok,
+        OpCondition_Test::COUNT_NONZERO | OpCondition_Test::COUNT_ZERO => {
+            let n = match result_count(res) {
+                Some(n) if succeeded => n,
+                _ => return Err(OpError::invalid_args("Condition op returned no count")),
+            };
+            (n != 0) == (cond.test == OpCondition_Test::COUNT_NONZERO)
+        }
+    };
+
+    Ok(holds)
+}
+
+// values of a referenced result
+fn source_values(r: &OpRef, results: &[OpResult]) -> Result<Vec<Vec<u8>>, OpError> {
+    let res = earlier_result(results, r.op_index)?;
+    if !res.ok || res.skipped {
+        return Err(OpError::invalid_args("Reference to failed or skipped op"));
+    }
+
+    let values = match r.source {
+        OpRef_Source::RES_VALUE if res.has_get() => vec![res.get_get().value.clone()],
+        OpRef_Source::RES_ELEMENTS if res.has_list() => res.get_list().elements.to_vec(),
+        OpRef_Source::RES_COUNT => match result_count(res) {
+            Some(n) => vec![n.to_string().into_bytes()],
+            None => vec![],
+        },
+        _ => vec![],
+    };
+    if values.is_empty() {
+        return Err(OpError::invalid_args("Referenced op returned no value"));
+    }
+
+    Ok(values)
+}
+
+// set key of op's argument
+fn set_key(op: &mut Operation, key: Vec<u8>) -> bool {
+    if op.has_get() {
+        op.mut_get().set_key(key);
+    } else if op.has_set() {
+        op.mut_set().set_key(key);
+    } else if op.has_num() {
+        op.mut_num().set_key(key);
+    } else if op.has_lpush() {
+        op.mut_lpush().set_key(key);
+    } else if op.has_lpop() {
+        op.mut_lpop().set_key(key);
+    } else if op.has_lindex() {
+        op.mut_lindex().set_key(key);
+    } else if op.has_key() {
+        op.mut_key().set_key(key);
+    } else if op.has_keyed_list() {
+        op.mut_keyed_list().set_key(key);
+    } else if op.has_expire() {
+        op.mut_expire().set_key(key);
+    } else if op.has_hset() {
+        op.mut_hset().set_key(key);
+    } else if op.has_hincr() {
+        op.mut_hincr().set_key(key);
+    } else if op.has_zadd() {
+        op.mut_zadd().set_key(key);
+    } else if op.has_zrange() {
+        op.mut_zrange().set_key(key);
+    } else if op.has_zpop() {
+        op.mut_zpop().set_key(key);
+    } else if op.has_key_move() {
+        op.mut_key_move().set_key(key);
+    } else {
+        return false;
+    }
+
+    true
+}
+
+// set value of op's argument
+fn set_value(op: &mut Operation, value: Vec<u8>) -> bool {
+    if op.has_set() {
+        op.mut_set().set_value(value);
+    } else if op.has_set_move() {
+        op.mut_set_move().set_member(value);
+    } else {
+        return false;
+    }
+
+    true
+}
+
+// append to element list of op's argument
+fn push_elements(op: &mut Operation, values: Vec<Vec<u8>>) -> bool {
+    let elements = if op.has_lpush() {
+        &mut op.mut_lpush().elements
+    } else if op.has_keyed_list() {
+        &mut op.mut_keyed_list().elements
+    } else if op.has_key_list() {
+        &mut op.mut_key_list().keys
+    } else {
+        return false;
+    };
+
+    for value in values {
+        elements.push(value);
+    }
+
+    true
+}
+
+/// Operation with its references to earlier results resolved:  values
+/// copied into its arguments, and references and condition removed, so
+/// that the operation may be executed or logged on its own.
+pub fn resolve(op: &Operation, results: &[OpResult]) -> Result<Operation, OpError> {
+    let mut resolved = op.clone();
+    resolved.clear_refs();
+    resolved.clear_cond();
+
+    for r in op.get_refs() {
+        let mut values = source_values(r, results)?;
+        let ok = match r.target {
+            OpRef_Target::ARG_KEY => set_key(&mut resolved, values.swap_remove(0)),
+            OpRef_Target::ARG_VALUE => set_value(&mut resolved, values.swap_remove(0)),
+            OpRef_Target::ARG_ELEMENTS => push_elements(&mut resolved, values),
+        };
+        if !ok {
+            return Err(OpError::invalid_args(
+                "Reference target not in op arguments",
+            ));
+        }
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::batch;
+    use memds_proto::memds_api::{
+        CountRes, KeyedListOp, ListRes, OpCondition, OpCondition_Test, OpRef, OpRef_Source,
+        OpRef_Target, OpResult, OpType, Operation,
+    };
+
+    #[test]
+    fn refs_conditions() {
+        // results:  pop returning "x", failure, count of 0
+        let mut pop_res = OpResult::new();
+        pop_res.ok = true;
+        let mut list_res = ListRes::new();
+        list_res.elements.push(b"x".to_vec());
+        pop_res.set_list(list_res);
+        let failed_res = OpResult::new();
+        let mut count_res = OpResult::new();
+        count_res.ok = true;
+        count_res.set_count(CountRes::new());
+        let results = vec![pop_res, failed_res, count_res];
+
+        // popped value, added to set
+        let mut op = Operation::new();
+        op.otype = OpType::SET_ADD;
+        let mut req = KeyedListOp::new();
+        req.set_key(b"b".to_vec());
+        op.set_keyed_list(req);
+        let mut r = OpRef::new();
+        r.op_index = 0;
+        r.source = OpRef_Source::RES_ELEMENTS;
+        r.target = OpRef_Target::ARG_ELEMENTS;
+        op.refs.push(r.clone());
+
+        let resolved = batch::resolve(&op, &results).unwrap();
+        assert_eq!(
+            resolved.get_keyed_list().elements.to_vec(),
+            vec![b"x".to_vec()]
+        );
+        assert!(resolved.refs.is_empty());
+
+        // references to failed or later ops, or absent values
+        r.op_index = 1;
+        op.refs[0] = r.clone();
+        assert!(batch::resolve(&op, &results).is_err());
+        r.op_index = 3;
+        op.refs[0] = r.clone();
+        assert!(batch::resolve(&op, &results[..2]).is_err());
+        r.op_index = 0;
+        r.source = OpRef_Source::RES_VALUE;
+        op.refs[0] = r;
+        assert!(batch::resolve(&op, &results).is_err());
+
+        // conditions
+        let mut cond = OpCondition::new();
+        assert_eq!(batch::condition_holds(&op, &results).unwrap(), true);
+        cond.op_index = 1;
+        cond.test = OpCondition_Test::SUCCEEDED;
+        op.set_cond(cond.clone());
+        assert_eq!(batch::condition_holds(&op, &results).unwrap(), false);
+        cond.test = OpCondition_Test::FAILED;
+        op.set_cond(cond.clone());
+        assert_eq!(batch::condition_holds(&op, &results).unwrap(), true);
+        cond.op_index = 2;
+        cond.test = OpCondition_Test::COUNT_ZERO;
+        op.set_cond(cond.clone());
+        assert_eq!(batch::condition_holds(&op, &results).unwrap(), true);
+        cond.op_index = 0;
+        cond.test = OpCondition_Test::COUNT_NONZERO;
+        op.set_cond(cond);
+        assert_eq!(batch::condition_holds(&op, &results).unwrap(), true);
+    }
+}
@@ -3,41 +3,158 @@ extern crate clap;
 use clap::value_t;
 use serde_derive::Deserialize;
 
+use std::path::PathBuf;
+
+use crate::resp;
+use crate::script;
+use crate::server::SaveRule;
+use crate::shard;
+use crate::unix;
+use crate::wal::{self, FsyncPolicy};
+
 const APPNAME: &'static str = "memds-server";
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
 const DEF_BIND_ADDR: &'static str = "127.0.0.1";
 const DEF_CONFIG_FN: &'static str = "memds.conf";
+const DEF_DIR: &'static str = ".";
+const DEF_DBFILENAME: &'static str = "memds-export.dat";
 
 #[derive(Deserialize)]
 struct TomlConfig {
     network: Option<TomlNetworkConfig>,
+    keyspace: Option<TomlKeyspaceConfig>,
     fs: Option<TomlFsConfig>,
+    script: Option<TomlScriptConfig>,
+    resp: Option<TomlRespConfig>,
+    framed: Option<TomlFramedConfig>,
+    tls: Option<TomlTlsConfig>,
+    acl: Option<TomlAclConfig>,
 }
 
 #[derive(Deserialize)]
 struct TomlNetworkConfig {
     bind_addr: Option<String>,
     bind_port: Option<u16>,
+    unix_sockets: Option<Vec<TomlUnixSocket>>,
+    cq_count: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct TomlUnixSocket {
+    path: String,
+    mode: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct TomlKeyspaceConfig {
+    shards: Option<usize>,
 }
 
 #[derive(Deserialize)]
 struct TomlFsConfig {
     import: Option<String>,
+    dir: Option<String>,
+    dbfilename: Option<String>,
+    save: Option<Vec<String>>,
+    aof: Option<String>,
+    aof_fsync: Option<String>,
+    aof_rewrite_pct: Option<u64>,
+    aof_rewrite_min_size: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct TomlScriptConfig {
+    time_limit_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct TomlRespConfig {
+    enabled: Option<bool>,
+    bind_addr: Option<String>,
+    bind_port: Option<u16>,
+}
+
+#[derive(Deserialize)]
+struct TomlFramedConfig {
+    enabled: Option<bool>,
+    bind_addr: Option<String>,
+    bind_port: Option<u16>,
+}
+
+#[derive(Deserialize)]
+struct TomlTlsConfig {
+    cert: String,
+    key: String,
+    ca: Option<String>,
+    require_client_cert: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct TomlAclConfig {
+    file: String,
 }
 
 pub struct Config {
     pub network: NetworkConfig,
+    pub keyspace: KeyspaceConfig,
     pub fs: FsConfig,
+    pub script: ScriptConfig,
+    pub resp: Option<RespConfig>,     // RESP listener; None if disabled
+    pub framed: Option<FramedConfig>, // framed listener; None if disabled
+    pub tls: Option<TlsConfig>,       // gRPC endpoint TLS; None if plaintext
+    pub acl: Option<AclConfig>,       // users; None if only the default user
 }
 
 pub struct NetworkConfig {
     pub bind_addr: String,
     pub bind_port: u16,
+    pub unix_sockets: Vec<UnixSocketConfig>,
+    pub cq_count: usize, // gRPC completion queues, each polled by a thread
+}
+
+pub struct UnixSocketConfig {
+    pub path: PathBuf,
+    pub mode: u32, // file permissions
+}
+
+pub struct KeyspaceConfig {
+    pub shards: usize,
 }
 
 pub struct FsConfig {
     pub import: Option<String>,
+    pub snapshot: PathBuf,
+    pub save: Vec<SaveRule>,
+    pub aof: Option<String>,
+    pub aof_fsync: FsyncPolicy,
+    pub aof_rewrite_pct: u64,
+    pub aof_rewrite_min_size: u64,
+}
+
+pub struct ScriptConfig {
+    pub time_limit_ms: u64,
+}
+
+pub struct RespConfig {
+    pub bind_addr: String,
+    pub bind_port: u16,
+}
+
+pub struct FramedConfig {
+    pub bind_addr: String,
+    pub bind_port: u16,
+}
+
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub ca: Option<PathBuf>, // CA verifying client certificates
+    pub require_client_cert: bool,
+}
+
+pub struct AclConfig {
+    pub file: PathBuf,
 }
 
 pub fn get() -> Config {
@@ -93,7 +210,13 @@ pub fn get() -> Config {
         } else {
             f_cfg = TomlConfig {
                 network: None,
+                keyspace: None,
                 fs: None,
+                script: None,
+                resp: None,
+                framed: None,
+                tls: None,
+                acl: None,
             };
         }
 
@@ -102,12 +225,23 @@ pub fn get() -> Config {
             f_cfg.network = Some(TomlNetworkConfig {
                 bind_addr: None,
                 bind_port: None,
+                unix_sockets: None,
+                cq_count: None,
             });
         }
 
         // if fs section missing, create default one
         if f_cfg.fs.is_none() {
-            f_cfg.fs = Some(TomlFsConfig { import: None });
+            f_cfg.fs = Some(TomlFsConfig {
+                import: None,
+                dir: None,
+                dbfilename: None,
+                save: None,
+                aof: None,
+                aof_fsync: None,
+                aof_rewrite_pct: None,
+                aof_rewrite_min_size: None,
+            });
         }
 
         let f_fs_cfg = f_cfg.fs.as_mut().unwrap();
@@ -137,14 +271,122 @@ pub fn get() -> Config {
 
     let f_net_cfg = f_cfg.network.unwrap();
     let f_fs_cfg = f_cfg.fs.unwrap();
+    let time_limit_ms = f_cfg.script.and_then(|s| s.time_limit_ms);
+
+    // RESP listener: enabled by its section; binds network address
+    // unless configured
+    let resp = match f_cfg.resp {
+        Some(r) if r.enabled.unwrap_or(true) => Some(RespConfig {
+            bind_addr: r
+                .bind_addr
+                .unwrap_or_else(|| f_net_cfg.bind_addr.clone().unwrap()),
+            bind_port: r.bind_port.unwrap_or(resp::DEF_PORT),
+        }),
+        _ => None,
+    };
+
+    // framed protocol listener: likewise
+    let framed = match f_cfg.framed {
+        Some(r) if r.enabled.unwrap_or(true) => Some(FramedConfig {
+            bind_addr: r
+                .bind_addr
+                .unwrap_or_else(|| f_net_cfg.bind_addr.clone().unwrap()),
+            bind_port: r.bind_port.unwrap_or(memds_proto::DEF_FRAMED_PORT),
+        }),
+        _ => None,
+    };
+
+    // gRPC endpoint TLS; client certificates require a CA to verify them
+    let tls = f_cfg.tls.map(|t| {
+        let require_client_cert = t.require_client_cert.unwrap_or(false);
+        if require_client_cert && t.ca.is_none() {
+            panic!("tls: require_client_cert requires ca");
+        }
+        TlsConfig {
+            cert: PathBuf::from(t.cert),
+            key: PathBuf::from(t.key),
+            ca: t.ca.map(PathBuf::from),
+            require_client_cert,
+        }
+    });
+
+    // users and their permissions, from ACL file
+    let acl = f_cfg.acl.map(|a| AclConfig {
+        file: PathBuf::from(a.file),
+    });
+
+    // unix socket listeners, serving the framed protocol
+    let unix_sockets = f_net_cfg
+        .unix_sockets
+        .unwrap_or_default()
+        .into_iter()
+        .map(|sock| UnixSocketConfig {
+            path: PathBuf::from(sock.path),
+            mode: sock.mode.unwrap_or(unix::DEF_SOCKET_MODE),
+        })
+        .collect();
+
+    // gRPC completion queues; default one per CPU
+    let cq_count = match f_net_cfg.cq_count {
+        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        Some(0) => panic!("cq_count must be at least 1"),
+        Some(n) => n,
+    };
+
+    // keyspace shards, each locked independently
+    let shards = match f_cfg.keyspace.and_then(|k| k.shards) {
+        None => shard::DEF_SHARDS,
+        Some(0) => panic!("keyspace shards must be at least 1"),
+        Some(n) => n,
+    };
+
+    // snapshot path: dir + dbfilename
+    let mut snapshot = PathBuf::from(f_fs_cfg.dir.as_deref().unwrap_or(DEF_DIR));
+    snapshot.push(f_fs_cfg.dbfilename.as_deref().unwrap_or(DEF_DBFILENAME));
+
+    // automatic snapshot rules; default none
+    let mut save = Vec::new();
+    for s in f_fs_cfg.save.iter().flatten() {
+        match SaveRule::parse(s) {
+            Some(rule) => save.push(rule),
+            None => panic!("invalid save rule: {}", s),
+        }
+    }
+
+    // operation log fsync policy; default every second
+    let aof_fsync = match f_fs_cfg.aof_fsync {
+        None => FsyncPolicy::EverySec,
+        Some(ref s) => match FsyncPolicy::parse(s) {
+            Some(policy) => policy,
+            None => panic!("invalid aof_fsync policy: {}", s),
+        },
+    };
 
     Config {
         network: NetworkConfig {
             bind_addr: f_net_cfg.bind_addr.unwrap(),
             bind_port: f_net_cfg.bind_port.unwrap(),
+            unix_sockets,
+            cq_count,
         },
+        keyspace: KeyspaceConfig { shards },
         fs: FsConfig {
             import: f_fs_cfg.import,
+            snapshot,
+            save,
+            aof: f_fs_cfg.aof,
+            aof_fsync,
+            aof_rewrite_pct: f_fs_cfg.aof_rewrite_pct.unwrap_or(wal::DEF_REWRITE_PCT),
+            aof_rewrite_min_size: f_fs_cfg
+                .aof_rewrite_min_size
+                .unwrap_or(wal::DEF_REWRITE_MIN_SIZE),
+        },
+        script: ScriptConfig {
+            time_limit_ms: time_limit_ms.unwrap_or(script::DEF_TIME_LIMIT_MS),
         },
+        resp,
+        framed,
+        tls,
+        acl,
     }
 }
@@ -0,0 +1,767 @@
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use memds_proto::{Atom, ScanIndex};
+
+use crate::util::scan_select;
+
+/// Milliseconds between runs of the active expiry cycle.
+pub const ACTIVE_EXPIRE_CYCLE_MS: u64 = 100;
+
+/// Maximum number of keys reaped per run of the active expiry cycle.
+pub const ACTIVE_EXPIRE_CYCLE_KEYS: usize = 1000;
+
+/// Current wall clock time, in milliseconds since the unix epoch.
+pub fn now_ms() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    now.as_millis() as u64
+}
+
+thread_local! {
+    // time against which expiry is judged, if fixed; see `with_clock()`
+    static CLOCK: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Call `f`, judging expiry as of unix time `now` (ms) throughout, so
+/// that a key expires before `f`, or not at all.  Operation log replay
+/// passes 0:  keys do not expire while loading, as their expiry was
+/// logged.
+pub fn with_clock<R>(now: u64, f: impl FnOnce() -> R) -> R {
+    let prior = CLOCK.with(|c| c.replace(Some(now)));
+    let res = f();
+    CLOCK.with(|c| c.set(prior));
+    res
+}
+
+/// Time (unix time, ms) against which expiry is judged:  that fixed by
+/// `with_clock()`, if any, else the wall clock.
+pub fn clock() -> u64 {
+    CLOCK.with(|c| c.get()).unwrap_or_else(now_ms)
+}
+
+// true if expiration time `t` has passed
+fn expired(t: u64) -> bool {
+    t <= clock()
+}
+
+// source of key versions, shared by all databases so that a version is
+// never reused, even if a key moves between databases or a database is
+// dropped and recreated.  versions are not persisted, so are seeded from
+// the clock upon first use:  they increase across restarts, unless over
+// 2^20 are taken per millisecond.
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(0);
+
+fn next_version() -> u64 {
+    if NEXT_VERSION.load(Ordering::Relaxed) == 0 {
+        let seed = now_ms() << 20;
+        let _ = NEXT_VERSION.compare_exchange(0, seed, Ordering::Relaxed, Ordering::Relaxed);
+    }
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Database key of the default database, used when neither operation nor
+/// request selects one.
+pub const DEF_DB: &[u8] = b"0";
+
+/// Item address:  database key and key.
+pub type ItemKey = (Vec<u8>, Vec<u8>);
+
+/// A value stored in the database, plus its per-key metadata.
+#[derive(Clone)]
+struct Entry {
+    atom: Atom,
+    expires: Option<u64>, // unix time (ms) at which key expires, if any
+    version: u64,         // changes upon each modification of key
+}
+
+/// A keyspace:  map of key -> Atom, with optional per-key expiration.
+///
+/// Expired keys are removed lazily, when accessed, and actively, by
+/// periodic calls to `active_expire()`.
+#[derive(Clone)]
+pub struct Db {
+    map: HashMap<Vec<u8>, Entry>,
+    expires: BTreeSet<(u64, Vec<u8>)>, // expiration index, soonest first
+    scan_index: ScanIndex,             // keys, in scan order
+    deleted: u64,                      // version of absent keys
+}
+
+impl Db {
+    pub fn new() -> Db {
+        Db {
+            map: HashMap::new(),
+            expires: BTreeSet::new(),
+            scan_index: ScanIndex::new(),
+            deleted: 0,
+        }
+    }
+
+    // record deletion of a key:  absent keys change version
+    fn note_deleted(&mut self) {
+        self.deleted = next_version();
+    }
+
+    // remove key, if it has expired.  returns true if key was removed.
+    fn expire_if_needed(&mut self, key: &[u8]) -> bool {
+        let expires = match self.map.get(key) {
+            Some(Entry {
+                expires: Some(t), ..
+            }) => *t,
+            _ => return false,
+        };
+
+        if !expired(expires) {
+            return false;
+        }
+
+        self.remove_entry(key);
+        self.note_deleted();
+
+        true
+    }
+
+    // remove key and its metadata, returning the entry
+    fn remove_entry(&mut self, key: &[u8]) -> Option<Entry> {
+        let ent = self.map.remove(key)?;
+        if let Some(t) = ent.expires {
+            self.expires.remove(&(t, key.to_vec()));
+        }
+        self.scan_index.remove(key);
+
+        Some(ent)
+    }
+
+    // store entry, with its metadata, at key; replaces any previous entry
+    fn insert_entry(&mut self, key: Vec<u8>, ent: Entry) {
+        self.remove_entry(&key);
+        if let Some(t) = ent.expires {
+            self.expires.insert((t, key.clone()));
+        }
+        self.scan_index.insert(&key);
+        self.map.insert(key, ent);
+    }
+
+    pub fn get(&mut self, key: &[u8]) -> Option<&Atom> {
+        self.expire_if_needed(key);
+        self.map.get(key).map(|ent| &ent.atom)
+    }
+
+    /// Mutable lookup.  Counts as a modification of the key.
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Atom> {
+        self.expire_if_needed(key);
+        self.map.get_mut(key).map(|ent| {
+            ent.version = next_version();
+            &mut ent.atom
+        })
+    }
+
+    pub fn contains_key(&mut self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        self.map.contains_key(key)
+    }
+
+    /// Read-only lookup, for callers without mutable access.  Expired
+    /// keys are treated as absent, but not removed.
+    pub fn peek(&self, key: &[u8]) -> Option<&Atom> {
+        match self.map.get(key) {
+            None => None,
+            Some(ent) => match ent.expires {
+                Some(t) if expired(t) => None,
+                _ => Some(&ent.atom),
+            },
+        }
+    }
+
+    /// Version of key:  changes whenever the key is modified, and is never
+    /// reused.  Keys absent share a version, changed by every deletion
+    /// from the database.
+    pub fn version(&self, key: &[u8]) -> u64 {
+        match self.map.get(key) {
+            None => self.deleted,
+            Some(ent) => match ent.expires {
+                Some(t) if expired(t) => self.deleted,
+                _ => ent.version,
+            },
+        }
+    }
+
+    /// Store value at key, overwriting any previous value and expiration.
+    pub fn insert(&mut self, key: Vec<u8>, atom: Atom) -> Option<Atom> {
+        self.expire_if_needed(&key);
+        let previous = self.remove_entry(&key);
+        self.insert_entry(
+            key,
+            Entry {
+                atom,
+                expires: None,
+                version: next_version(),
+            },
+        );
+
+        previous.map(|ent| ent.atom)
+    }
+
+    /// Store value at key, retaining the expiration of any previous value.
+    pub fn insert_keepttl(&mut self, key: Vec<u8>, atom: Atom) -> Option<Atom> {
+        match self.get_mut(&key) {
+            Some(old_atom) => Some(std::mem::replace(old_atom, atom)),
+            None => self.insert(key, atom),
+        }
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Atom> {
+        self.expire_if_needed(key);
+        let ent = self.remove_entry(key)?;
+        self.note_deleted();
+        Some(ent.atom)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.expires.clear();
+        self.scan_index.clear();
+        self.note_deleted();
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.map.keys()
+    }
+
+    /// Expiration time (unix time, ms) of key; None if key does not
+    /// exist or does not expire.
+    pub fn expire_time(&self, key: &[u8]) -> Option<u64> {
+        match self.map.get(key) {
+            None => None,
+            Some(ent) => ent.expires,
+        }
+    }
+
+    /// Set expiration time (unix time, ms) of key.  Returns false if key
+    /// does not exist.
+    pub fn set_expire(&mut self, key: &[u8], expires: u64) -> bool {
+        self.expire_if_needed(key);
+        let ent = match self.map.get_mut(key) {
+            None => return false,
+            Some(ent) => ent,
+        };
+
+        ent.version = next_version();
+        if let Some(t) = ent.expires.replace(expires) {
+            self.expires.remove(&(t, key.to_vec()));
+        }
+        self.expires.insert((expires, key.to_vec()));
+
+        true
+    }
+
+    /// Remove expiration from key.  Returns true if an expiration was
+    /// removed.
+    pub fn persist(&mut self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        let ent = match self.map.get_mut(key) {
+            None => return false,
+            Some(ent) => ent,
+        };
+
+        match ent.expires.take() {
+            None => false,
+            Some(t) => {
+                ent.version = next_version();
+                self.expires.remove(&(t, key.to_vec()));
+                true
+            }
+        }
+    }
+
+    /// Active expiry:  remove up to max_keys keys that expired at or
+    /// before `now`, soonest-expiring first.  Returns the keys removed.
+    pub fn active_expire(&mut self, now: u64, max_keys: usize) -> Vec<Vec<u8>> {
+        let mut expired = Vec::new();
+        while expired.len() < max_keys {
+            let key = match self.expires.iter().next() {
+                Some((t, key)) if *t <= now => key.clone(),
+                _ => break,
+            };
+
+            self.remove_entry(&key);
+            self.note_deleted();
+            expired.push(key);
+        }
+
+        expired
+    }
+}
+
+/// Incremental iteration over a keyspace, held in one or more parts
+/// `dbs`, such as keyspace shards.  Examines roughly `count` keys at or
+/// after `cursor`, returning the unexpired entries found and the cursor
+/// at which to resume; 0 when iteration is complete.  Each part is read
+/// from `cursor`, in scan order.  See `util::scan_select()`.
+pub fn scan<'a>(
+    dbs: impl Iterator<Item = &'a Db>,
+    cursor: u64,
+    count: usize,
+) -> (u64, Vec<(&'a Vec<u8>, &'a Atom)>) {
+    let parts = dbs.map(|db| {
+        db.scan_index.range(cursor).filter_map(move |(h, key)| {
+            let ent = &db.map[key];
+            match ent.expires {
+                Some(t) if expired(t) => None,
+                _ => Some((h, (key, &ent.atom))),
+            }
+        })
+    });
+
+    scan_select(parts, count)
+}
+
+/// The set of logical databases:  keyspaces, each addressed by a
+/// database key.  Databases are created upon first use, and dropped
+/// once empty.
+///
+/// The version of absent keys, changed by deletions, outlives dropped
+/// databases:  it is kept by the set, and no database's is older.
+#[derive(Clone)]
+pub struct DbSet {
+    dbs: HashMap<Vec<u8>, Db>,
+    deleted: u64, // version of absent keys, in databases dropped
+}
+
+impl DbSet {
+    pub fn new() -> DbSet {
+        DbSet {
+            dbs: HashMap::new(),
+            deleted: 0,
+        }
+    }
+
+    pub fn get(&self, name: &[u8]) -> Option<&Db> {
+        self.dbs.get(name)
+    }
+
+    /// Database `name`, created if missing.
+    pub fn db_mut(&mut self, name: &[u8]) -> &mut Db {
+        let deleted = self.deleted;
+        self.dbs.entry(name.to_vec()).or_insert_with(|| {
+            let mut db = Db::new();
+            db.deleted = deleted;
+            db
+        })
+    }
+
+    /// An empty database, as any missing from the set.
+    pub fn empty_db(&self) -> Db {
+        let mut db = Db::new();
+        db.deleted = self.deleted;
+        db
+    }
+
+    /// Version of key, in database `name`.  See `Db::version()`.
+    pub fn version(&self, name: &[u8], key: &[u8]) -> u64 {
+        match self.dbs.get(name) {
+            None => self.deleted,
+            Some(db) => db.version(key),
+        }
+    }
+
+    // raise the version of absent keys, in every database, to `deleted`
+    fn note_deleted(&mut self, deleted: u64) {
+        if deleted > self.deleted {
+            self.deleted = deleted;
+            for db in self.dbs.values_mut() {
+                db.deleted = db.deleted.max(deleted);
+            }
+        }
+    }
+
+    // drop empty databases, keeping the version of their absent keys
+    fn drop_empty(&mut self) {
+        let mut deleted = 0;
+        self.dbs.retain(|_, db| {
+            deleted = deleted.max(db.deleted);
+            db.len() > 0
+        });
+        self.note_deleted(deleted);
+    }
+
+    /// Drop database `name`, if empty.
+    pub fn prune(&mut self, name: &[u8]) {
+        if let Some(db) = self.dbs.get(name) {
+            if db.len() == 0 {
+                let deleted = db.deleted;
+                self.dbs.remove(name);
+                self.note_deleted(deleted);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &Db)> {
+        self.dbs.iter()
+    }
+
+    /// Total item count, across all databases.
+    pub fn len(&self) -> usize {
+        self.dbs.values().map(|db| db.len()).sum()
+    }
+
+    /// Drop every database:  a deletion of every key.
+    pub fn clear(&mut self) {
+        self.dbs.clear();
+        self.note_deleted(next_version());
+    }
+
+    /// Move the items at `keys`, in every database, into a new set.
+    /// Items keep their expiration and version, as do absent keys.
+    pub fn extract(&mut self, keys: &[&[u8]]) -> DbSet {
+        let mut out = DbSet::new();
+        out.deleted = self.deleted;
+        for (db_key, db) in self.dbs.iter_mut() {
+            for key in keys.iter() {
+                if let Some(ent) = db.remove_entry(key) {
+                    let out_db = out.db_mut(db_key);
+                    out_db.deleted = db.deleted;
+                    out_db.insert_entry(key.to_vec(), ent);
+                }
+            }
+        }
+        self.drop_empty();
+
+        out
+    }
+
+    /// Copy the items at `keys`, in every database, into a new set.
+    /// Items keep their expiration and version, as do absent keys.
+    pub fn copy(&self, keys: &[&[u8]]) -> DbSet {
+        let mut out = DbSet::new();
+        out.deleted = self.deleted;
+        for (db_key, db) in self.dbs.iter() {
+            for key in keys.iter() {
+                if let Some(ent) = db.map.get(*key) {
+                    let out_db = out.db_mut(db_key);
+                    out_db.deleted = db.deleted;
+                    out_db.insert_entry(key.to_vec(), ent.clone());
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Move every item of `other` into this set, replacing any items at
+    /// the same keys.  Deletions from `other` change the version of
+    /// absent keys, in this set.
+    pub fn absorb(&mut self, other: DbSet) {
+        self.note_deleted(other.deleted);
+        for (db_key, mut db) in other.dbs {
+            match self.dbs.get_mut(&db_key) {
+                None if db.len() == 0 => self.note_deleted(db.deleted),
+                None => {
+                    db.deleted = db.deleted.max(self.deleted);
+                    self.dbs.insert(db_key, db);
+                }
+                Some(dest) => {
+                    dest.deleted = dest.deleted.max(db.deleted);
+                    for (key, ent) in db.map {
+                        dest.insert_entry(key, ent);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Move every item into one of `n` new sets, chosen by `part(key)`.
+    /// Each keeps the version of absent keys.
+    pub fn split(self, n: usize, part: impl Fn(&[u8]) -> usize) -> Vec<DbSet> {
+        let mut parts: Vec<DbSet> = (0..n).map(|_| DbSet::new()).collect();
+        for dbs in parts.iter_mut() {
+            dbs.deleted = self.deleted;
+        }
+        for (db_key, db) in self.dbs {
+            let mut db_parts: Vec<Db> = (0..n).map(|_| Db::new()).collect();
+            for (key, ent) in db.map {
+                db_parts[part(&key)].insert_entry(key, ent);
+            }
+            for (dbs, mut db_part) in parts.iter_mut().zip(db_parts) {
+                db_part.deleted = db.deleted;
+                dbs.dbs.insert(db_key.clone(), db_part);
+            }
+        }
+        for dbs in parts.iter_mut() {
+            dbs.drop_empty();
+        }
+
+        parts
+    }
+
+    /// Remove the items at `keys`, in every database, that have expired.
+    /// Returns the database key and key of each item removed.
+    pub fn expire_keys(&mut self, keys: &[&[u8]]) -> Vec<ItemKey> {
+        let mut expired = Vec::new();
+        for (db_key, db) in self.dbs.iter_mut() {
+            for key in keys.iter() {
+                if db.expire_if_needed(key) {
+                    expired.push((db_key.clone(), key.to_vec()));
+                }
+            }
+        }
+        self.drop_empty();
+
+        expired
+    }
+
+    /// Active expiry, across all databases.  Returns the database key and
+    /// key of each item removed.
+    pub fn active_expire(&mut self, now: u64, max_keys: usize) -> Vec<ItemKey> {
+        let mut expired = Vec::new();
+        for (db_key, db) in self.dbs.iter_mut() {
+            if expired.len() >= max_keys {
+                break;
+            }
+            let keys = db.active_expire(now, max_keys - expired.len());
+            expired.extend(keys.into_iter().map(|key| (db_key.clone(), key)));
+        }
+        self.drop_empty();
+
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{self, now_ms, Db, DbSet};
+    use memds_proto::Atom;
+    use std::collections::HashSet;
+
+    #[test]
+    fn lazy_expire() {
+        let mut db = Db::new();
+        db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+        db.insert(b"name".to_vec(), Atom::String(b"Jane Doe".to_vec()));
+
+        // expire in the past; key disappears upon access, unless expiry
+        // is judged at an earlier time
+        assert!(db.set_expire(b"foo", now_ms() - 1));
+        assert_eq!(db.len(), 2);
+        db::with_clock(0, || assert!(db.get(b"foo").is_some()));
+        let mut dbs = DbSet::new();
+        dbs.db_mut(b"db")
+            .insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+        dbs.db_mut(b"db").set_expire(b"foo", now_ms() - 1);
+        let expired = dbs.expire_keys(&[b"foo", b"bar"]);
+        assert_eq!(expired, [(b"db".to_vec(), b"foo".to_vec())]);
+        assert!(dbs.get(b"db").is_none());
+        assert!(db.peek(b"foo").is_none());
+        assert!(db.get(b"foo").is_none());
+        assert_eq!(db.len(), 1);
+
+        // expire in the future; key remains
+        assert!(db.set_expire(b"name", now_ms() + 60000));
+        assert!(db.get(b"name").is_some());
+
+        // overwrite clears expiration; keepttl retains it
+        db.insert_keepttl(b"name".to_vec(), Atom::String(b"John Doe".to_vec()));
+        assert!(db.expire_time(b"name").is_some());
+        db.insert(b"name".to_vec(), Atom::String(b"Jane Doe".to_vec()));
+        assert!(db.expire_time(b"name").is_none());
+
+        // persist
+        assert!(db.set_expire(b"name", now_ms() + 60000));
+        assert!(db.persist(b"name"));
+        assert!(!db.persist(b"name"));
+        assert!(!db.set_expire(b"does-not-exist", now_ms()));
+    }
+
+    #[test]
+    fn active_expire() {
+        let mut db = Db::new();
+        let now = now_ms();
+        for i in 0..10 {
+            let key = format!("key{}", i).into_bytes();
+            db.insert(key.clone(), Atom::String(b"value".to_vec()));
+            db.set_expire(&key, now + i);
+        }
+
+        // keys 0..=4 are due
+        assert_eq!(db.active_expire(now + 4, 3), [b"key0", b"key1", b"key2"]);
+        assert_eq!(db.active_expire(now + 4, 100).len(), 2);
+        assert_eq!(db.len(), 5);
+        assert!(db.peek(b"key5").is_some());
+
+        db.clear();
+        assert_eq!(db.len(), 0);
+        assert!(db.active_expire(now + 100, 100).is_empty());
+    }
+
+    #[test]
+    fn scan() {
+        let mut db = Db::new();
+        for i in 0..100 {
+            let key = format!("key{}", i).into_bytes();
+            db.insert(key, Atom::String(b"value".to_vec()));
+        }
+
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        let mut n_calls = 0;
+        loop {
+            let (next_cursor, entries) = db::scan(std::iter::once(&db), cursor, 7);
+            for (key, _) in entries {
+                assert!(seen.insert(key.clone()));
+            }
+            n_calls += 1;
+
+            // grow the map mid-iteration, forcing resizes
+            if n_calls == 3 {
+                for i in 0..1000 {
+                    let key = format!("extra{}", i).into_bytes();
+                    db.insert(key, Atom::String(b"value".to_vec()));
+                }
+            }
+
+            if next_cursor == 0 {
+                break;
+            }
+            cursor = next_cursor;
+        }
+
+        // every original key seen exactly once
+        for i in 0..100 {
+            let key = format!("key{}", i).into_bytes();
+            assert!(seen.contains(&key));
+        }
+    }
+
+    #[test]
+    fn versions() {
+        let mut db = Db::new();
+        assert_eq!(db.version(b"foo"), 0);
+
+        db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+        let v1 = db.version(b"foo");
+        assert!(v1 > 0);
+
+        // reads leave version unchanged; modifications advance it
+        db.get(b"foo");
+        assert_eq!(db.version(b"foo"), v1);
+        db.get_mut(b"foo");
+        let v2 = db.version(b"foo");
+        assert!(v2 > v1);
+        db.set_expire(b"foo", now_ms() + 60000);
+        assert!(db.version(b"foo") > v2);
+
+        // deleted and recreated key never reuses a version; deletion
+        // changes the version of absent keys
+        let v3 = db.version(b"foo");
+        db.remove(b"foo");
+        let v4 = db.version(b"foo");
+        assert!(v4 > v3);
+        assert_eq!(db.version(b"other"), v4);
+        db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+        assert!(db.version(b"foo") > v4);
+    }
+
+    #[test]
+    fn absent_versions() {
+        let mut dbs = DbSet::new();
+        let v0 = dbs.version(b"1", b"foo");
+
+        // create then delete, dropping the database
+        dbs.db_mut(b"1")
+            .insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+        dbs.db_mut(b"1").remove(b"foo");
+        dbs.prune(b"1");
+        assert!(dbs.get(b"1").is_none());
+        let v1 = dbs.version(b"1", b"foo");
+        assert!(v1 > v0);
+        assert_eq!(dbs.db_mut(b"1").version(b"foo"), v1);
+        assert_eq!(dbs.empty_db().version(b"foo"), v1);
+
+        // moved sets keep it; deletions in them change it
+        dbs.db_mut(b"1")
+            .insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+        let mut out = dbs.extract(&[b"foo"]);
+        assert_eq!(dbs.version(b"1", b"foo"), v1);
+        out.db_mut(b"1").remove(b"foo");
+        let v2 = out.version(b"1", b"foo");
+        assert!(v2 > v1);
+        let mut parts = out.split(2, |_| 0);
+        assert_eq!(parts[1].version(b"1", b"foo"), v2);
+        dbs.absorb(parts.remove(0));
+        assert_eq!(dbs.version(b"1", b"foo"), v2);
+
+        dbs.clear();
+        assert!(dbs.version(b"1", b"foo") > v2);
+    }
+
+    #[test]
+    fn dbset() {
+        let mut dbs = DbSet::new();
+        dbs.db_mut(b"0")
+            .insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+        dbs.db_mut(b"users")
+            .insert(b"foo".to_vec(), Atom::String(b"baz".to_vec()));
+        assert_eq!(dbs.len(), 2);
+        assert_eq!(dbs.iter().count(), 2);
+
+        // same key, distinct databases
+        match dbs.db_mut(b"users").get(b"foo") {
+            Some(Atom::String(s)) => assert_eq!(s, b"baz"),
+            _ => panic!("not a string"),
+        }
+
+        // empty databases are dropped
+        dbs.db_mut(b"empty");
+        assert_eq!(dbs.iter().count(), 3);
+        dbs.prune(b"empty");
+        assert!(dbs.get(b"empty").is_none());
+
+        // active expiry spans databases
+        dbs.db_mut(b"users").set_expire(b"foo", now_ms() - 1);
+        assert_eq!(dbs.active_expire(now_ms(), 10).len(), 1);
+        assert!(dbs.get(b"users").is_none());
+        assert_eq!(dbs.len(), 1);
+
+        dbs.clear();
+        assert_eq!(dbs.len(), 0);
+    }
+
+    #[test]
+    fn move_items() {
+        let mut dbs = DbSet::new();
+        for i in 0..10 {
+            let key = format!("key{}", i).into_bytes();
+            dbs.db_mut(b"0")
+                .insert(key.clone(), Atom::String(b"value".to_vec()));
+            dbs.db_mut(b"1")
+                .insert(key, Atom::String(b"value".to_vec()));
+        }
+        dbs.db_mut(b"1").set_expire(b"key1", now_ms() + 60000);
+        let version = dbs.db_mut(b"1").version(b"key1");
+
+        // extract, from every database, keeping metadata
+        let mut out = dbs.extract(&[b"key1", b"key2", b"nope"]);
+        assert_eq!(out.len(), 4);
+        assert_eq!(dbs.len(), 16);
+        assert_eq!(out.db_mut(b"1").version(b"key1"), version);
+        assert!(out.db_mut(b"1").expire_time(b"key1").is_some());
+
+        // split by key, then absorb back
+        let parts = out.split(2, |key| if key == b"key1" { 0 } else { 1 });
+        assert_eq!(parts[0].len(), 2);
+        assert_eq!(parts[1].len(), 2);
+        for part in parts {
+            dbs.absorb(part);
+        }
+        assert_eq!(dbs.len(), 20);
+        assert_eq!(dbs.db_mut(b"1").version(b"key1"), version);
+        assert_eq!(dbs.active_expire(now_ms() + 120000, 10).len(), 1);
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+
+use memds_proto::memds_api::{ErrorCode, OpResult};
+
+/// Operation failure:  an error code, upon which clients may branch, and
+/// a human-readable message.
+#[derive(Debug)]
+pub struct OpError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl OpError {
+    pub fn new(code: ErrorCode, message: &str) -> OpError {
+        OpError {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn invalid_op() -> OpError {
+        OpError::new(ErrorCode::INVALID_OP, "Invalid op")
+    }
+
+    pub fn invalid_args(message: &str) -> OpError {
+        OpError::new(ErrorCode::INVALID_ARGUMENT, message)
+    }
+
+    pub fn not_found() -> OpError {
+        OpError::new(ErrorCode::NOT_FOUND, "Not Found")
+    }
+
+    /// Item exists, but is not of the type the operation requires.
+    pub fn wrong_type(type_name: &str) -> OpError {
+        OpError::new(ErrorCode::WRONGTYPE, &format!("not a {}", type_name))
+    }
+
+    pub fn internal(message: &str) -> OpError {
+        OpError::new(ErrorCode::INTERNAL, message)
+    }
+}
+
+impl fmt::Display for OpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl From<OpError> for OpResult {
+    fn from(err: OpError) -> OpResult {
+        let mut res = OpResult::new();
+        res.ok = false;
+        res.err_code = err.code;
+        res.err_message = err.message;
+        res
+    }
+}
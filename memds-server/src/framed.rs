@@ -0,0 +1,114 @@
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use memds_proto::memds_api::{ErrorCode, MemdsMessage, MemdsMessage_MsgType, ResponseMsg};
+use memds_proto::MemdsCodec;
+
+use crate::rpcservice::MemdsService;
+
+// execute request message, on behalf of the connection's user,
+// returning its response message
+fn exec_msg(svc: &MemdsService, msg: MemdsMessage, user: &mut Option<String>) -> MemdsMessage {
+    let out_resp = match msg.mtype {
+        MemdsMessage_MsgType::REQUEST => svc.exec_request(msg.get_req(), user),
+        _ => {
+            let mut out_resp = ResponseMsg::new();
+            out_resp.err_code = ErrorCode::INVALID_OP;
+            out_resp.set_err_message("Expected request message".to_string());
+            out_resp
+        }
+    };
+
+    let mut resp_msg = MemdsMessage::new();
+    resp_msg.mtype = MemdsMessage_MsgType::RESPONSE;
+    resp_msg.id = msg.id;
+    resp_msg.set_resp(out_resp);
+    resp_msg
+}
+
+/// Serve one client connection, until closed or a frame fails to decode.
+pub fn serve<S: Read + Write>(svc: MemdsService, mut stream: S) -> io::Result<()> {
+    let mut codec = MemdsCodec::new();
+    let mut accum = BytesMut::with_capacity(4096);
+    let mut buf = [0; 16384];
+    let mut user = None; // authenticated user, if any
+
+    loop {
+        let n_read = stream.read(&mut buf)?;
+        if n_read == 0 {
+            return Ok(());
+        }
+        accum.extend_from_slice(&buf[0..n_read]);
+
+        // execute each complete request, in order; responses to
+        // pipelined requests are written together
+        let mut out = BytesMut::new();
+        while let Some(msg) = codec.decode(&mut accum)? {
+            codec.encode(exec_msg(&svc, msg, &mut user), &mut out)?;
+        }
+        stream.write_all(&out)?;
+    }
+}
+
+/// Accept framed protocol clients on `listener`, each served by its own
+/// thread.
+pub fn listen(svc: MemdsService, listener: TcpListener) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let svc = svc.clone();
+                std::thread::spawn(move || {
+                    let _ = stream.set_nodelay(true);
+                    if let Err(e) = serve(svc, stream) {
+                        debug!("framed client: {:?}", e);
+                    }
+                });
+            }
+            Err(e) => error!("framed accept failed: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rpcservice::tests::test_service;
+    use memds_proto::memds_api::{NumOp, OpType, Operation, RequestMsg};
+    use memds_proto::FramedClient;
+    use std::net::TcpListener;
+
+    fn incr_req(key: &[u8]) -> RequestMsg {
+        let mut num = NumOp::new();
+        num.set_key(key.to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::STR_INCR;
+        op.set_num(num);
+        let mut req = RequestMsg::new();
+        req.ops.push(op);
+        req
+    }
+
+    #[test]
+    fn pipelined() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let svc = test_service();
+        std::thread::spawn(move || super::listen(svc, listener));
+
+        let mut client = FramedClient::connect(addr).unwrap();
+
+        // responses arrive in request order, with request ids
+        let id1 = client.send(&incr_req(b"a")).unwrap();
+        let id2 = client.send(&incr_req(b"a")).unwrap();
+        let (resp_id, resp) = client.recv().unwrap();
+        assert_eq!((resp_id, resp.results[0].get_num().old_value), (id1, 0));
+        let (resp_id, resp) = client.recv().unwrap();
+        assert_eq!((resp_id, resp.results[0].get_num().old_value), (id2, 1));
+
+        let resp = client.exec(&incr_req(b"a")).unwrap();
+        assert!(resp.ok);
+        assert_eq!(resp.results[0].get_num().old_value, 2);
+    }
+}
@@ -0,0 +1,841 @@
+use std::str;
+
+use memds_proto::memds_api::{
+    CountRes, ErrorCode, FieldValue, HashIncrOp, HashRes, HashSetOp, KeyOp, KeyScanOp, KeyScanRes,
+    KeyedListOp, ListRes, NumRes, OpResult, OpType, StrGetRes,
+};
+use memds_proto::{Atom, FieldMap};
+
+use crate::db::Db;
+use crate::error::OpError;
+use crate::keys::DEF_SCAN_COUNT;
+use crate::registry::{
+    Access, Handler, KeyScope, Method, ARG_HINCR, ARG_HSET, ARG_KEY, ARG_KEYED_LIST, ARG_SCAN,
+};
+use crate::util::{glob_match, scan_select};
+
+pub fn set(db: &mut Db, req: &HashSetOp, otype: OpType) -> OpResult {
+    let only_new = match otype {
+        OpType::HASH_SETNX => true,
+        _ => false,
+    };
+
+    // validate request
+    if req.pairs.is_empty() || (only_new && req.pairs.len() != 1) {
+        return OpError::invalid_args("Invalid args").into();
+    }
+
+    // get hash to mutate.
+    let hm = {
+        let key = req.get_key();
+        match db.get_mut(key) {
+            None => {
+                // does not exist; create empty hash
+                db.insert(key.to_vec(), Atom::Hash(FieldMap::new()));
+                match db.get_mut(key) {
+                    None => unreachable!(),
+                    Some(atom) => match atom {
+                        Atom::Hash(hm) => hm,
+                        _ => unreachable!(),
+                    },
+                }
+            }
+
+            // found the key.  grab ref.
+            Some(atom) => match atom {
+                Atom::Hash(hm) => hm,
+                _ => {
+                    return OpError::wrong_type("hash").into();
+                }
+            },
+        }
+    };
+
+    // iterate through each field/value pair in request, storing
+    let mut n_new = 0;
+    for fv in req.pairs.iter() {
+        let field = fv.get_field();
+        if only_new && hm.contains_key(field) {
+            continue;
+        }
+        if hm.insert(field.to_vec(), fv.get_value().to_vec()).is_none() {
+            n_new += 1;
+        }
+    }
+
+    // return number of fields added (not number of fields updated)
+    let mut count_res = CountRes::new();
+    count_res.n = n_new as u64;
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn get(db: &Db, req: &KeyedListOp, otype: OpType) -> OpResult {
+    let want_single = match otype {
+        OpType::HASH_GET => true,
+        _ => false,
+    };
+
+    // validate request
+    if req.elements.is_empty() || (want_single && req.elements.len() != 1) {
+        return OpError::invalid_args("Invalid args").into();
+    }
+
+    // get hash to query
+    let empty_hm = FieldMap::new();
+    let hm = {
+        match db.peek(req.get_key()) {
+            None => {
+                // single-field queries of missing hash fail below;
+                // multi-field queries return all fields absent
+                &empty_hm
+            }
+            Some(atom) => match atom {
+                Atom::Hash(hm) => hm,
+                _ => {
+                    return OpError::wrong_type("hash").into();
+                }
+            },
+        }
+    };
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+
+    // single field:  return value, or not-found error
+    if want_single {
+        let mut get_res = StrGetRes::new();
+        match hm.get(&req.elements[0]) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(value) => {
+                get_res.set_value(value.to_vec());
+            }
+        }
+        op_res.set_get(get_res);
+
+    // multiple fields:  return field/value pairs, in request order
+    } else {
+        let mut hash_res = HashRes::new();
+        for field in req.elements.iter() {
+            let mut fv = FieldValue::new();
+            fv.set_field(field.to_vec());
+            if let Some(value) = hm.get(field) {
+                fv.set_value(value.to_vec());
+                fv.exists = true;
+            }
+            hash_res.pairs.push(fv);
+        }
+        op_res.set_hash(hash_res);
+    }
+
+    op_res
+}
+
+pub fn del(db: &mut Db, req: &KeyedListOp) -> OpResult {
+    // get hash to mutate
+    let hm = {
+        match db.get_mut(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::Hash(hm) => hm,
+                _ => {
+                    return OpError::wrong_type("hash").into();
+                }
+            },
+        }
+    };
+
+    // iterate through each field in request, removing
+    let mut n_removed = 0;
+    for field in req.elements.iter() {
+        if hm.remove(field).is_some() {
+            n_removed += 1;
+        }
+    }
+
+    // return number of fields removed
+    let mut count_res = CountRes::new();
+    count_res.n = n_removed as u64;
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::HASH_DEL;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn query(db: &Db, req: &KeyedListOp, otype: OpType) -> OpResult {
+    // validate request
+    if otype == OpType::HASH_STRLEN && req.elements.len() != 1 {
+        return OpError::invalid_args("Invalid args").into();
+    }
+
+    // get hash to query
+    let hm = {
+        match db.peek(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::Hash(hm) => hm,
+                _ => {
+                    return OpError::wrong_type("hash").into();
+                }
+            },
+        }
+    };
+
+    // HASH_EXISTS: number of requested fields present in hash.
+    // HASH_STRLEN: length of requested field's value (0 if absent)
+    let mut count_res = CountRes::new();
+    for field in req.elements.iter() {
+        if let Some(value) = hm.get(field) {
+            count_res.n += match otype {
+                OpType::HASH_STRLEN => value.len() as u64,
+                _ => 1,
+            };
+        }
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn len(db: &Db, req: &KeyOp) -> OpResult {
+    // get hash to query
+    let hm = {
+        match db.peek(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::Hash(hm) => hm,
+                _ => {
+                    return OpError::wrong_type("hash").into();
+                }
+            },
+        }
+    };
+
+    // return number of fields in hash
+    let mut count_res = CountRes::new();
+    count_res.n = hm.len() as u64;
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::HASH_LEN;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn getall(db: &Db, req: &KeyOp, otype: OpType) -> OpResult {
+    // get hash to query
+    let hm = {
+        match db.peek(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::Hash(hm) => hm,
+                _ => {
+                    return OpError::wrong_type("hash").into();
+                }
+            },
+        }
+    };
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+
+    match otype {
+        // return field/value pairs
+        OpType::HASH_GETALL => {
+            let mut hash_res = HashRes::new();
+            for (field, value) in hm.iter() {
+                let mut fv = FieldValue::new();
+                fv.set_field(field.to_vec());
+                fv.set_value(value.to_vec());
+                fv.exists = true;
+                hash_res.pairs.push(fv);
+            }
+            op_res.set_hash(hash_res);
+        }
+
+        // return fields, or values
+        OpType::HASH_KEYS | OpType::HASH_VALS => {
+            let mut list_res = ListRes::new();
+            for (field, value) in hm.iter() {
+                if otype == OpType::HASH_KEYS {
+                    list_res.elements.push(field.to_vec());
+                } else {
+                    list_res.elements.push(value.to_vec());
+                }
+            }
+            op_res.set_list(list_res);
+        }
+
+        _ => unreachable!(),
+    }
+
+    op_res
+}
+
+pub fn incrby(db: &mut Db, req: &HashIncrOp, otype: OpType) -> OpResult {
+    let is_float = match otype {
+        OpType::HASH_INCRBYFLOAT => true,
+        _ => false,
+    };
+
+    // get hash to mutate.
+    let hm = {
+        let key = req.get_key();
+        match db.get_mut(key) {
+            None => {
+                // does not exist; create empty hash
+                db.insert(key.to_vec(), Atom::Hash(FieldMap::new()));
+                match db.get_mut(key) {
+                    None => unreachable!(),
+                    Some(atom) => match atom {
+                        Atom::Hash(hm) => hm,
+                        _ => unreachable!(),
+                    },
+                }
+            }
+
+            // found the key.  grab ref.
+            Some(atom) => match atom {
+                Atom::Hash(hm) => hm,
+                _ => {
+                    return OpError::wrong_type("hash").into();
+                }
+            },
+        }
+    };
+
+    // get old field value as string, or init
+    let field = req.get_field();
+    let old_str = match hm.get(field) {
+        None => "0",
+        Some(value) => match str::from_utf8(value) {
+            Ok(s) => s,
+            Err(_) => {
+                return OpError::new(ErrorCode::WRONGTYPE, "value not a string").into();
+            }
+        },
+    };
+
+    let mut num_res = NumRes::new();
+
+    // perform increment, and store result in hash as string
+    let new_str = match is_float {
+        true => {
+            let old_val = match old_str.parse::<f64>() {
+                Ok(v) => v,
+                Err(_) => {
+                    return OpError::new(ErrorCode::WRONGTYPE, "value not f64").into();
+                }
+            };
+            let new_val = old_val + req.n_float;
+            if !new_val.is_finite() {
+                return OpError::new(
+                    ErrorCode::OVERFLOW,
+                    "increment would produce NaN or Infinity",
+                )
+                .into();
+            }
+
+            num_res.old_float = old_val;
+            new_val.to_string()
+        }
+        false => {
+            let old_val = match old_str.parse::<i64>() {
+                Ok(v) => v,
+                Err(_) => {
+                    return OpError::new(ErrorCode::WRONGTYPE, "value not i64").into();
+                }
+            };
+            let new_val = match old_val.checked_add(req.n) {
+                Some(v) => v,
+                None => {
+                    return OpError::new(
+                        ErrorCode::OVERFLOW,
+                        "increment or decrement would overflow",
+                    )
+                    .into();
+                }
+            };
+
+            num_res.old_value = old_val;
+            new_val.to_string()
+        }
+    };
+
+    hm.insert(field.to_vec(), new_str.as_bytes().to_vec());
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+    op_res.set_num(num_res);
+
+    op_res
+}
+
+pub fn scan(db: &Db, req: &KeyScanOp) -> OpResult {
+    let count = {
+        if req.count == 0 {
+            DEF_SCAN_COUNT
+        } else {
+            req.count as usize
+        }
+    };
+
+    // get hash to query
+    let hm = {
+        match db.peek(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::Hash(hm) => hm,
+                _ => {
+                    return OpError::wrong_type("hash").into();
+                }
+            },
+        }
+    };
+
+    // examine next batch of fields
+    let (cursor, entries) = scan_select([hm.scan(req.cursor)], count);
+
+    // filter by pattern
+    let mut scan_res = KeyScanRes::new();
+    scan_res.cursor = cursor;
+    for (field, value) in entries {
+        if !req.pattern.is_empty() && !glob_match(&req.pattern, field) {
+            continue;
+        }
+
+        let mut fv = FieldValue::new();
+        fv.set_field(field.to_vec());
+        fv.set_value(value.to_vec());
+        fv.exists = true;
+        scan_res.pairs.push(fv);
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::HASH_SCAN;
+    op_res.set_scan(scan_res);
+
+    op_res
+}
+
+/// Hash module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "Hash",
+        name: "Set",
+        otype: OpType::HASH_SET,
+        arg: Some(ARG_HSET),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| set(db, op.get_hset(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "SetNX",
+        otype: OpType::HASH_SETNX,
+        arg: Some(ARG_HSET),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| set(db, op.get_hset(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "Get",
+        otype: OpType::HASH_GET,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| get(db, op.get_keyed_list(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "MGet",
+        otype: OpType::HASH_MGET,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| get(db, op.get_keyed_list(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "Del",
+        otype: OpType::HASH_DEL,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| del(db, op.get_keyed_list())),
+    },
+    Method {
+        module: "Hash",
+        name: "Exists",
+        otype: OpType::HASH_EXISTS,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| query(db, op.get_keyed_list(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "StrLen",
+        otype: OpType::HASH_STRLEN,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| query(db, op.get_keyed_list(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "Len",
+        otype: OpType::HASH_LEN,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| len(db, op.get_key())),
+    },
+    Method {
+        module: "Hash",
+        name: "GetAll",
+        otype: OpType::HASH_GETALL,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| getall(db, op.get_key(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "Keys",
+        otype: OpType::HASH_KEYS,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| getall(db, op.get_key(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "Vals",
+        otype: OpType::HASH_VALS,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| getall(db, op.get_key(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "IncrBy",
+        otype: OpType::HASH_INCRBY,
+        arg: Some(ARG_HINCR),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| incrby(db, op.get_hincr(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "IncrByFloat",
+        otype: OpType::HASH_INCRBYFLOAT,
+        arg: Some(ARG_HINCR),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| incrby(db, op.get_hincr(), op.otype)),
+    },
+    Method {
+        module: "Hash",
+        name: "Scan",
+        otype: OpType::HASH_SCAN,
+        arg: Some(ARG_SCAN),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| scan(db, op.get_scan())),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::db::Db;
+    use crate::hash;
+    use memds_proto::memds_api::{
+        ErrorCode, FieldValue, HashIncrOp, HashSetOp, KeyOp, KeyScanOp, KeyedListOp, OpType,
+    };
+    use memds_proto::{Atom, FieldMap};
+
+    fn get_test_db() -> Db {
+        let mut db = Db::new();
+        db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+
+        let mut hm = FieldMap::new();
+        hm.insert(b"name".to_vec(), b"Jane Doe".to_vec());
+        hm.insert(b"age".to_vec(), b"25".to_vec());
+        hm.insert(b"height".to_vec(), b"1.5".to_vec());
+        db.insert(b"user1".to_vec(), Atom::Hash(hm));
+
+        db
+    }
+
+    fn fv(field: &[u8], value: &[u8]) -> FieldValue {
+        let mut fv = FieldValue::new();
+        fv.set_field(field.to_vec());
+        fv.set_value(value.to_vec());
+        fv
+    }
+
+    #[test]
+    fn set() {
+        let mut db = get_test_db();
+
+        // set two fields, one new
+        let mut req = HashSetOp::new();
+        req.set_key(b"user1".to_vec());
+        req.pairs.push(fv(b"age", b"26"));
+        req.pairs.push(fv(b"city", b"Paris"));
+
+        let res = hash::set(&mut db, &req, OpType::HASH_SET);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.otype, OpType::HASH_SET);
+        assert_eq!(res.get_count().n, 1);
+
+        match db.get(b"user1") {
+            Some(Atom::Hash(hm)) => {
+                assert_eq!(hm.len(), 4);
+                assert_eq!(hm.get(b"age").unwrap(), b"26");
+            }
+            _ => panic!("not a hash"),
+        }
+
+        // setnx of existing field does not overwrite
+        let mut req = HashSetOp::new();
+        req.set_key(b"user1".to_vec());
+        req.pairs.push(fv(b"age", b"99"));
+
+        let res = hash::set(&mut db, &req, OpType::HASH_SETNX);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 0);
+
+        // wrong type
+        let mut req = HashSetOp::new();
+        req.set_key(b"foo".to_vec());
+        req.pairs.push(fv(b"age", b"99"));
+
+        let res = hash::set(&mut db, &req, OpType::HASH_SET);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::WRONGTYPE);
+    }
+
+    #[test]
+    fn get() {
+        let db = get_test_db();
+
+        // single field
+        let mut req = KeyedListOp::new();
+        req.set_key(b"user1".to_vec());
+        req.elements.push(b"name".to_vec());
+
+        let res = hash::get(&db, &req, OpType::HASH_GET);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_get().value, b"Jane Doe");
+
+        // missing field
+        let mut req = KeyedListOp::new();
+        req.set_key(b"user1".to_vec());
+        req.elements.push(b"city".to_vec());
+
+        let res = hash::get(&db, &req, OpType::HASH_GET);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
+
+        // multiple fields, in request order
+        let mut req = KeyedListOp::new();
+        req.set_key(b"user1".to_vec());
+        req.elements.push(b"age".to_vec());
+        req.elements.push(b"city".to_vec());
+
+        let res = hash::get(&db, &req, OpType::HASH_MGET);
+        assert_eq!(res.ok, true);
+        let pairs = &res.get_hash().pairs;
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].value, b"25");
+        assert_eq!(pairs[0].exists, true);
+        assert_eq!(pairs[1].field, b"city");
+        assert_eq!(pairs[1].exists, false);
+    }
+
+    #[test]
+    fn del_exists_strlen() {
+        let mut db = get_test_db();
+
+        let mut req = KeyedListOp::new();
+        req.set_key(b"user1".to_vec());
+        req.elements.push(b"name".to_vec());
+
+        let res = hash::query(&db, &req, OpType::HASH_STRLEN);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 8);
+
+        req.elements.push(b"city".to_vec());
+        let res = hash::query(&db, &req, OpType::HASH_EXISTS);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 1);
+
+        let res = hash::del(&mut db, &req);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 1);
+
+        let mut req = KeyOp::new();
+        req.set_key(b"user1".to_vec());
+        let res = hash::len(&db, &req);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 2);
+    }
+
+    #[test]
+    fn getall() {
+        let db = get_test_db();
+
+        let mut req = KeyOp::new();
+        req.set_key(b"user1".to_vec());
+
+        let res = hash::getall(&db, &req, OpType::HASH_GETALL);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_hash().pairs.len(), 3);
+
+        let res = hash::getall(&db, &req, OpType::HASH_KEYS);
+        assert_eq!(res.ok, true);
+        let mut fields = res.get_list().elements.to_vec();
+        fields.sort();
+        assert_eq!(
+            fields,
+            vec![b"age".to_vec(), b"height".to_vec(), b"name".to_vec()]
+        );
+
+        let res = hash::getall(&db, &req, OpType::HASH_VALS);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_list().elements.len(), 3);
+
+        req.set_key(b"does-not-exist".to_vec());
+        let res = hash::getall(&db, &req, OpType::HASH_GETALL);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn incrby() {
+        let mut db = get_test_db();
+
+        let mut req = HashIncrOp::new();
+        req.set_key(b"user1".to_vec());
+        req.set_field(b"age".to_vec());
+        req.n = 5;
+
+        let res = hash::incrby(&mut db, &req, OpType::HASH_INCRBY);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_num().old_value, 25);
+
+        // float increment
+        req.set_field(b"height".to_vec());
+        req.n_float = 0.25;
+
+        let res = hash::incrby(&mut db, &req, OpType::HASH_INCRBYFLOAT);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_num().old_float, 1.5);
+
+        match db.get(b"user1") {
+            Some(Atom::Hash(hm)) => {
+                assert_eq!(hm.get(b"age").unwrap(), b"30");
+                assert_eq!(hm.get(b"height").unwrap(), b"1.75");
+            }
+            _ => panic!("not a hash"),
+        }
+
+        // integer increment of non-integer
+        let res = hash::incrby(&mut db, &req, OpType::HASH_INCRBY);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::WRONGTYPE);
+    }
+
+    #[test]
+    fn scan() {
+        let db = get_test_db();
+
+        let mut found = Vec::new();
+        let mut req = KeyScanOp::new();
+        req.set_key(b"user1".to_vec());
+        req.count = 1;
+        loop {
+            let res = hash::scan(&db, &req);
+            assert_eq!(res.ok, true);
+            assert_eq!(res.otype, OpType::HASH_SCAN);
+
+            let scan_res = res.get_scan();
+            for fv in scan_res.pairs.iter() {
+                found.push(fv.field.clone());
+            }
+            if scan_res.cursor == 0 {
+                break;
+            }
+            req.cursor = scan_res.cursor;
+        }
+
+        found.sort();
+        assert_eq!(
+            found,
+            vec![b"age".to_vec(), b"height".to_vec(), b"name".to_vec()]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use memds_proto::memds_api::{OpType, Operation};
+use memds_proto::Atom;
+
+use crate::db::{Db, DbSet};
+use crate::error::OpError;
+use crate::registry::Method;
+use crate::shard::Shards;
+
+// state prior to an operation
+enum Undo {
+    Key {
+        db_key: Vec<u8>,
+        key: Vec<u8>,
+        prior: Option<(Atom, Option<u64>)>,
+    },
+    Db {
+        db_key: Vec<u8>,
+        prior: Vec<Option<Db>>, // by shard
+    },
+    All(Vec<DbSet>), // by shard
+}
+
+/// Undo journal of an atomic batch:  the prior state of everything the
+/// batch's write operations may modify, in the shards it locked.
+pub struct Journal {
+    entries: Vec<Undo>,
+}
+
+impl Journal {
+    pub fn new() -> Journal {
+        Journal {
+            entries: Vec::new(),
+        }
+    }
+
+    fn record_key(&mut self, shards: &Shards, db_key: &[u8], key: &[u8]) -> Result<(), OpError> {
+        let prior = shards
+            .shard(key)?
+            .get(db_key)
+            .and_then(|db| db.peek(key).map(|atom| (atom.clone(), db.expire_time(key))));
+        self.entries.push(Undo::Key {
+            db_key: db_key.to_vec(),
+            key: key.to_vec(),
+            prior,
+        });
+
+        Ok(())
+    }
+
+    /// Record the state an operation may modify, prior to its execution.
+    pub fn record(
+        &mut self,
+        shards: &Shards,
+        db_key: &[u8],
+        method: &Method,
+        op: &Operation,
+    ) -> Result<(), OpError> {
+        match op.otype {
+            OpType::SRV_FLUSHDB => {
+                let prior = shards
+                    .parts()
+                    .iter()
+                    .map(|dbs| dbs.get(db_key).cloned())
+                    .collect();
+                self.entries.push(Undo::Db {
+                    db_key: db_key.to_vec(),
+                    prior,
+                });
+            }
+
+            OpType::SRV_FLUSHALL => {
+                let prior = shards.parts().into_iter().cloned().collect();
+                self.entries.push(Undo::All(prior));
+            }
+
+            _ => {
+                if let Some(arg) = &method.arg {
+                    for key in (arg.key_values)(op) {
+                        self.record_key(shards, db_key, key)?;
+                    }
+                }
+
+                // MOVE/COPY destination lies in another database
+                if op.has_key_move() {
+                    let req = op.get_key_move();
+                    let dest_key = match req.dest_key.is_empty() {
+                        true => &req.key,
+                        false => &req.dest_key,
+                    };
+                    self.record_key(shards, &req.dest_db, dest_key)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restore recorded state, most recent first.
+    pub fn rollback(self, shards: &mut Shards) -> Result<(), OpError> {
+        for undo in self.entries.into_iter().rev() {
+            match undo {
+                Undo::Key { db_key, key, prior } => {
+                    shards.with_items(&[&key], |dbs| {
+                        let db = dbs.db_mut(&db_key);
+                        db.remove(&key);
+                        if let Some((atom, expires)) = prior {
+                            db.insert(key.clone(), atom);
+                            if let Some(t) = expires {
+                                db.set_expire(&key, t);
+                            }
+                        }
+                        dbs.prune(&db_key);
+                    })?;
+                }
+
+                Undo::Db { db_key, prior } => {
+                    for (dbs, db) in shards.parts_mut()?.into_iter().zip(prior) {
+                        match db {
+                            Some(db) => *dbs.db_mut(&db_key) = db,
+                            None => dbs.prune(&db_key),
+                        }
+                    }
+                }
+
+                Undo::All(prior) => {
+                    for (dbs, part) in shards.parts_mut()?.into_iter().zip(prior) {
+                        *dbs = part;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{DbSet, DEF_DB};
+    use crate::journal::Journal;
+    use crate::registry;
+    use crate::rpcservice::{exec_sharded, selected_db};
+    use crate::shard::{Keyspace, Shards};
+    use memds_proto::memds_api::{KeyListOp, KeyMoveOp, OpType, Operation, StrSetOp};
+    use memds_proto::Atom;
+
+    fn exec_journaled(journal: &mut Journal, shards: &mut Shards, op: &Operation) {
+        let method = registry::validate(op).unwrap();
+        let db_key = selected_db(op, b"");
+        journal.record(shards, db_key, method, op).unwrap();
+        let (op_res, _) = exec_sharded(shards, method, db_key, op).unwrap();
+        assert!(op_res.ok);
+    }
+
+    #[test]
+    fn rollback() {
+        let mut dbs = DbSet::new();
+        let db = dbs.db_mut(DEF_DB);
+        db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+        db.insert(b"ttl".to_vec(), Atom::String(b"x".to_vec()));
+        db.set_expire(b"ttl", 4102444800000);
+        let keyspace = Keyspace::new(dbs, 4);
+        let mut shards = keyspace.lock(None, true);
+
+        let mut journal = Journal::new();
+
+        let mut req = StrSetOp::new();
+        req.set_key(b"foo".to_vec());
+        req.set_value(b"baz".to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::STR_SET;
+        op.set_set(req);
+        exec_journaled(&mut journal, &mut shards, &op);
+
+        let mut req = KeyListOp::new();
+        req.keys.push(b"ttl".to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::KEYS_DEL;
+        op.set_key_list(req);
+        exec_journaled(&mut journal, &mut shards, &op);
+
+        let mut req = KeyMoveOp::new();
+        req.set_key(b"foo".to_vec());
+        req.set_dest_db(b"1".to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::KEY_MOVE;
+        op.set_key_move(req);
+        exec_journaled(&mut journal, &mut shards, &op);
+
+        let mut op = Operation::new();
+        op.otype = OpType::SRV_FLUSHDB;
+        op.set_db(b"1".to_vec());
+        exec_journaled(&mut journal, &mut shards, &op);
+
+        let mut op = Operation::new();
+        op.otype = OpType::SRV_FLUSHALL;
+        exec_journaled(&mut journal, &mut shards, &op);
+        assert!(shards.parts().iter().all(|dbs| dbs.len() == 0));
+
+        journal.rollback(&mut shards).unwrap();
+
+        let parts = shards.parts();
+        assert!(parts.iter().all(|dbs| dbs.get(b"1").is_none()));
+        assert_eq!(parts.iter().map(|dbs| dbs.len()).sum::<usize>(), 2);
+        let db = shards.shard(b"foo").unwrap().get(DEF_DB).unwrap();
+        match db.peek(b"foo") {
+            Some(Atom::String(s)) => assert_eq!(s, b"bar"),
+            _ => panic!("not a string"),
+        }
+        let db = shards.shard(b"ttl").unwrap().get(DEF_DB).unwrap();
+        assert_eq!(db.expire_time(b"ttl"), Some(4102444800000));
+    }
+}
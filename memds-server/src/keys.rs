@@ -1,35 +1,74 @@
-use std::collections::HashMap;
 use std::collections::HashSet;
 
 use bytes::{BufMut, Bytes, BytesMut};
 use memds_proto::memds_api::{
-    AtomType, CountRes, DbValue, KeyListOp, KeyOp, KeyRenameOp, MemdsMessage, MemdsMessage_MsgType,
-    OpResult, OpType, StrGetRes, StrSetOp, TypeRes,
+    AtomType, CountRes, DbValue, ErrorCode, FieldValue, KeyExpireOp, KeyListOp, KeyMoveOp, KeyOp,
+    KeyRenameOp, KeyScanOp, KeyScanRes, MemdsMessage, MemdsMessage_MsgType, OpResult, OpType,
+    Operation, RequestMsg, ScoredMember, StrGetRes, StrSetOp, TtlRes, TypeRes,
 };
-use memds_proto::util::result_err;
-use memds_proto::{Atom, MemdsCodec};
+use memds_proto::{Atom, FieldMap, MemdsCodec, SortedSet};
 use tokio_util::codec::{Decoder, Encoder};
 
-pub fn del_exist(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyListOp, remove_item: bool) -> OpResult {
+use crate::db::{self, Db, DbSet};
+use crate::error::OpError;
+use crate::registry::{
+    Access, Handler, KeyScope, Method, ARG_EXPIRE, ARG_KEY, ARG_KEY_LIST, ARG_KEY_MOVE, ARG_RENAME,
+    ARG_SCAN, ARG_SET, DANGEROUS,
+};
+use crate::util::glob_match;
+
+pub const DEF_SCAN_COUNT: usize = 10;
+
+pub fn atom_type(atom: &Atom) -> AtomType {
+    match atom {
+        Atom::String(_) => AtomType::STRING,
+        Atom::List(_) => AtomType::LIST,
+        Atom::Set(_) => AtomType::SET,
+        Atom::Hash(_) => AtomType::HASH,
+        Atom::SortedSet(_) => AtomType::ZSET,
+    }
+}
+
+pub fn del_exist(db: &mut Db, req: &KeyListOp, remove_item: bool) -> OpResult {
+    // if we're testing existence, no item is touched
+    if !remove_item {
+        return exist(db, req);
+    }
+
     let mut count: u64 = 0;
 
-    // iterate through provided key list
+    // iterate through provided key list, attempting to remove each item
     for key in req.get_keys().iter() {
-        // if we're deleting, attempt to remove item
-        if remove_item {
-            if db.remove(key).is_some() {
-                count += 1;
-            }
+        if db.remove(key).is_some() {
+            count += 1;
+        }
+    }
 
-        // if we're testing existence, do so
-        } else {
-            if db.contains_key(key) {
-                count += 1;
-            }
+    // return number of keys removed
+    let mut count_res = CountRes::new();
+    count_res.n = count;
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::KEYS_DEL;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn exist(db: &Db, req: &KeyListOp) -> OpResult {
+    let mut count: u64 = 0;
+
+    // iterate through provided key list, testing existence
+    for key in req.get_keys().iter() {
+        if db.peek(key).is_some() {
+            count += 1;
         }
     }
 
-    // return number of keys matched (== operations successful, for delete)
+    // return number of keys matched
     let mut count_res = CountRes::new();
     count_res.n = count;
 
@@ -37,35 +76,36 @@ pub fn del_exist(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyListOp, remove_item:
     let mut op_res = OpResult::new();
 
     op_res.ok = true;
-    op_res.otype = match remove_item {
-        true => OpType::KEYS_DEL,
-        false => OpType::KEYS_EXIST,
-    };
+    op_res.otype = OpType::KEYS_EXIST;
     op_res.set_count(count_res);
 
     op_res
 }
 
-pub fn rename(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyRenameOp) -> OpResult {
+pub fn rename(db: &mut Db, req: &KeyRenameOp) -> OpResult {
     let old_key = req.get_old_key();
     let new_key = req.get_new_key();
 
     if req.create_excl && db.contains_key(new_key) {
-        return result_err(-412, "Precondition failed: key exists");
+        return OpError::new(ErrorCode::EXISTS, "Precondition failed: key exists").into();
     }
 
     // remove value stored at old key
+    let expires = db.expire_time(old_key);
     let value = {
         let rm_res = db.remove(old_key);
         if rm_res.is_none() {
-            return result_err(-404, "Not Found");
+            return OpError::not_found().into();
         }
 
         rm_res.unwrap()
     };
 
-    // store value at new key
+    // store value at new key, carrying expiration along with it
     db.insert(new_key.to_vec(), value);
+    if let Some(t) = expires {
+        db.set_expire(new_key, t);
+    }
 
     // standard operation result assignment & final return
     let mut op_res = OpResult::new();
@@ -76,21 +116,80 @@ pub fn rename(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyRenameOp) -> OpResult {
     op_res
 }
 
-pub fn typ(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
+/// Move or copy key, from database `src_db` to another database and/or
+/// key.  Result count is 1 if moved/copied, or 0 if the destination key
+/// exists (and replacement was not requested).
+pub fn move_copy(dbs: &mut DbSet, src_db: &[u8], req: &KeyMoveOp, otype: OpType) -> OpResult {
+    let key = req.get_key();
+    let dest_db = req.get_dest_db();
+    let dest_key = match req.dest_key.is_empty() {
+        true => key,
+        false => req.get_dest_key(),
+    };
+
+    if dest_db.is_empty() {
+        return OpError::invalid_args("Invalid args").into();
+    }
+    if dest_db == src_db && dest_key == key {
+        return OpError::invalid_args("Source and destination are the same").into();
+    }
+    if !dbs.db_mut(src_db).contains_key(key) {
+        return OpError::not_found().into();
+    }
+
+    let mut count_res = CountRes::new();
+    let dest_exists = dbs.db_mut(dest_db).contains_key(dest_key);
+    if !dest_exists || req.replace {
+        // take value from source, carrying expiration along with it
+        let src = dbs.db_mut(src_db);
+        let expires = src.expire_time(key);
+        let value = match otype {
+            OpType::KEY_MOVE => src.remove(key).unwrap(),
+            _ => src.get(key).unwrap().clone(),
+        };
+
+        let dest = dbs.db_mut(dest_db);
+        dest.insert(dest_key.to_vec(), value);
+        if let Some(t) = expires {
+            dest.set_expire(dest_key, t);
+        }
+
+        count_res.n = 1;
+    }
+    dbs.prune(dest_db);
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn version(db: &Db, req: &KeyListOp) -> OpResult {
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::KEYS_VERSION;
+    for key in req.keys.iter() {
+        op_res.versions.push(db.version(key));
+    }
+
+    op_res
+}
+
+pub fn typ(db: &Db, req: &KeyOp) -> OpResult {
     let key = req.get_key();
 
     // get value stored at key
-    let typ = match db.get(key) {
+    let typ = match db.peek(key) {
         None => {
-            return result_err(-404, "Not Found");
+            return OpError::not_found().into();
         }
 
-        // match type
-        Some(atom) => match atom {
-            Atom::String(_) => AtomType::STRING,
-            Atom::List(_) => AtomType::LIST,
-            Atom::Set(_) => AtomType::SET,
-        },
+        Some(atom) => atom_type(atom),
     };
 
     // return type
@@ -107,7 +206,119 @@ pub fn typ(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
     op_res
 }
 
-pub fn import_dbv(db: &mut HashMap<Vec<u8>, Atom>, in_key: Option<&[u8]>, dbv: &DbValue) -> bool {
+pub fn expire(db: &mut Db, req: &KeyExpireOp) -> OpResult {
+    let key = req.get_key();
+    let now = db::clock() as i64;
+
+    // calculate absolute expiration time
+    let expires = {
+        if req.absolute {
+            req.expire_ms
+        } else {
+            now.saturating_add(req.expire_ms)
+        }
+    };
+
+    // expiration in the past deletes the key; otherwise, set expiration
+    let updated = {
+        if expires <= now {
+            db.remove(key).is_some()
+        } else {
+            db.set_expire(key, expires as u64)
+        }
+    };
+
+    // return number of keys updated
+    let mut count_res = CountRes::new();
+    count_res.n = updated as u64;
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::KEY_EXPIRE;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn ttl(db: &Db, req: &KeyOp) -> OpResult {
+    let key = req.get_key();
+
+    if db.peek(key).is_none() {
+        return OpError::not_found().into();
+    }
+
+    // calculate remaining time to live, or -1 if none
+    let mut ttl_res = TtlRes::new();
+    ttl_res.ttl_ms = match db.expire_time(key) {
+        None => -1,
+        Some(t) => t.saturating_sub(db::clock()) as i64,
+    };
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::KEY_TTL;
+    op_res.set_ttl(ttl_res);
+
+    op_res
+}
+
+pub fn persist(db: &mut Db, req: &KeyOp) -> OpResult {
+    // remove expiration, returning number of keys updated
+    let mut count_res = CountRes::new();
+    count_res.n = db.persist(req.get_key()) as u64;
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::KEY_PERSIST;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn scan(parts: &[&DbSet], db_key: &[u8], req: &KeyScanOp) -> OpResult {
+    let count = {
+        if req.count == 0 {
+            DEF_SCAN_COUNT
+        } else {
+            req.count as usize
+        }
+    };
+
+    // examine next batch of keys, across every part of the keyspace
+    let dbs = parts.iter().filter_map(|dbs| dbs.get(db_key));
+    let (cursor, entries) = db::scan(dbs, req.cursor, count);
+
+    // filter by pattern and type
+    let mut scan_res = KeyScanRes::new();
+    scan_res.cursor = cursor;
+    for (key, atom) in entries {
+        if !req.pattern.is_empty() && !glob_match(&req.pattern, key) {
+            continue;
+        }
+        if req.typ != AtomType::NOTYPE && req.typ != atom_type(atom) {
+            continue;
+        }
+
+        scan_res.keys.push(key.to_vec());
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::KEYS_SCAN;
+    op_res.set_scan(scan_res);
+
+    op_res
+}
+
+pub fn import_dbv(db: &mut Db, in_key: Option<&[u8]>, dbv: &DbValue) -> bool {
     let key = match in_key {
         None => &dbv.key,
         Some(k) => k,
@@ -132,20 +343,40 @@ pub fn import_dbv(db: &mut HashMap<Vec<u8>, Atom>, in_key: Option<&[u8]>, dbv: &
             }
             Atom::Set(hs)
         }
+        AtomType::HASH => {
+            let mut hm = FieldMap::new();
+            for fv in dbv.pairs.iter() {
+                hm.insert(fv.field.to_vec(), fv.value.to_vec());
+            }
+            Atom::Hash(hm)
+        }
+        AtomType::ZSET => {
+            let mut zs = SortedSet::new();
+            for sm in dbv.members.iter() {
+                if sm.score.is_nan() {
+                    return false;
+                }
+                zs.insert(sm.member.to_vec(), sm.score);
+            }
+            Atom::SortedSet(zs)
+        }
     };
 
     db.insert(key.to_vec(), value);
+    if dbv.expire_at != 0 {
+        db.set_expire(key, dbv.expire_at);
+    }
 
     true
 }
 
-pub fn export_dbv(db: &HashMap<Vec<u8>, Atom>, key: &[u8]) -> Option<DbValue> {
+pub fn export_dbv(db: &Db, key: &[u8]) -> Option<DbValue> {
     // create result DbValue
     let mut dbv = DbValue::new();
     dbv.set_key(key.to_vec());
 
     // encode DbValue value
-    match db.get(key) {
+    match db.peek(key) {
         None => return None,
 
         // match type
@@ -166,18 +397,42 @@ pub fn export_dbv(db: &HashMap<Vec<u8>, Atom>, key: &[u8]) -> Option<DbValue> {
                     dbv.elements.push(elem.clone());
                 }
             }
+            Atom::Hash(hm) => {
+                dbv.typ = AtomType::HASH;
+                for (field, value) in hm.iter() {
+                    let mut fv = FieldValue::new();
+                    fv.set_field(field.clone());
+                    fv.set_value(value.clone());
+                    fv.exists = true;
+                    dbv.pairs.push(fv);
+                }
+            }
+            Atom::SortedSet(zs) => {
+                dbv.typ = AtomType::ZSET;
+                for (member, score) in zs.iter() {
+                    let mut sm = ScoredMember::new();
+                    sm.set_member(member.clone());
+                    sm.score = score;
+                    dbv.members.push(sm);
+                }
+            }
         },
     };
 
+    // encode expiration, if any
+    if let Some(t) = db.expire_time(key) {
+        dbv.expire_at = t;
+    }
+
     Some(dbv)
 }
 
-pub fn dump(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
+pub fn dump(db: &Db, req: &KeyOp) -> OpResult {
     // create result DbValue
     let dbv = {
         match export_dbv(db, req.get_key()) {
             None => {
-                return result_err(-404, "Not Found");
+                return OpError::not_found().into();
             }
             Some(dbv) => dbv,
         }
@@ -207,38 +462,58 @@ pub fn dump(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
     op_res
 }
 
-pub fn restore(db: &mut HashMap<Vec<u8>, Atom>, req: &StrSetOp) -> OpResult {
-    let msg = {
-        let mut codec = MemdsCodec::new();
-        let buf = Bytes::from(req.value.clone());
-        let msg_raw = &mut BytesMut::new();
-        msg_raw.put(buf);
-        match codec.decode(msg_raw) {
-            Err(_) => {
-                return result_err(-400, "Deser failed");
-            }
-            Ok(None) => {
-                return result_err(-400, "Deser empty");
+// decode DUMP output
+fn decode_dump(value: &[u8]) -> Result<MemdsMessage, OpError> {
+    let mut codec = MemdsCodec::new();
+    let buf = Bytes::from(value.to_vec());
+    let msg_raw = &mut BytesMut::new();
+    msg_raw.put(buf);
+    match codec.decode(msg_raw) {
+        Err(_) => Err(OpError::invalid_args("Deser failed")),
+        Ok(None) => Err(OpError::invalid_args("Deser empty")),
+        Ok(Some(dec_msg)) => {
+            if (dec_msg.mtype != MemdsMessage_MsgType::DBVAL) || (!dec_msg.has_dbv()) {
+                return Err(OpError::invalid_args("not dbv"));
             }
-            Ok(Some(dec_msg)) => {
-                if (dec_msg.mtype != MemdsMessage_MsgType::DBVAL) || (!dec_msg.has_dbv()) {
-                    return result_err(-400, "not dbv");
-                }
 
-                dec_msg
-            }
+            Ok(dec_msg)
         }
+    }
+}
+
+/// RESTOREs without key restore the key within their dump:  a copy of
+/// the request naming it, if any omit it, so that it is locked, checked
+/// and journaled as any other op's keys.
+pub fn name_restore_keys(msg_req: &RequestMsg) -> Option<RequestMsg> {
+    let unnamed = |op: &Operation| {
+        op.otype == OpType::KEY_RESTORE && op.has_set() && op.get_set().key.is_empty()
     };
+    if !msg_req.get_ops().iter().any(unnamed) {
+        return None;
+    }
 
-    let key_opt = {
-        if req.key.len() > 0 {
-            Some(&req.key[..])
-        } else {
-            None
+    let mut named = msg_req.clone();
+    for op in named.ops.iter_mut().filter(|op| unnamed(op)) {
+        if let Ok(msg) = decode_dump(op.get_set().get_value()) {
+            op.mut_set().set_key(msg.get_dbv().key.clone());
         }
+    }
+    Some(named)
+}
+
+pub fn restore(db: &mut Db, req: &StrSetOp) -> OpResult {
+    // the key written must be named, so that it is locked and checked
+    // as any other op's keys
+    if req.key.is_empty() {
+        return OpError::invalid_args("Key required").into();
+    }
+
+    let msg = match decode_dump(&req.value) {
+        Ok(msg) => msg,
+        Err(err) => return err.into(),
     };
 
-    import_dbv(db, key_opt, msg.get_dbv());
+    import_dbv(db, Some(&req.key), msg.get_dbv());
 
     // standard operation result assignment & final return
     let mut op_res = OpResult::new();
@@ -249,17 +524,156 @@ pub fn restore(db: &mut HashMap<Vec<u8>, Atom>, req: &StrSetOp) -> OpResult {
     op_res
 }
 
+/// Keys module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "Keys",
+        name: "Del",
+        otype: OpType::KEYS_DEL,
+        arg: Some(ARG_KEY_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| del_exist(db, op.get_key_list(), true)),
+    },
+    Method {
+        module: "Keys",
+        name: "Exists",
+        otype: OpType::KEYS_EXIST,
+        arg: Some(ARG_KEY_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| exist(db, op.get_key_list())),
+    },
+    Method {
+        module: "Keys",
+        name: "Rename",
+        otype: OpType::KEYS_RENAME,
+        arg: Some(ARG_RENAME),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| rename(db, op.get_rename())),
+    },
+    Method {
+        module: "Keys",
+        name: "Type",
+        otype: OpType::KEYS_TYPE,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| typ(db, op.get_key())),
+    },
+    Method {
+        module: "Keys",
+        name: "Dump",
+        otype: OpType::KEY_DUMP,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| dump(db, op.get_key())),
+    },
+    Method {
+        module: "Keys",
+        name: "Restore",
+        otype: OpType::KEY_RESTORE,
+        arg: Some(ARG_SET),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| restore(db, op.get_set())),
+    },
+    Method {
+        module: "Keys",
+        name: "Expire",
+        otype: OpType::KEY_EXPIRE,
+        arg: Some(ARG_EXPIRE),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| expire(db, op.get_expire())),
+    },
+    Method {
+        module: "Keys",
+        name: "TTL",
+        otype: OpType::KEY_TTL,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| ttl(db, op.get_key())),
+    },
+    Method {
+        module: "Keys",
+        name: "Persist",
+        otype: OpType::KEY_PERSIST,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| persist(db, op.get_key())),
+    },
+    Method {
+        module: "Keys",
+        name: "Version",
+        otype: OpType::KEYS_VERSION,
+        arg: Some(ARG_KEY_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| version(db, op.get_key_list())),
+    },
+    Method {
+        module: "Keys",
+        name: "Scan",
+        otype: OpType::KEYS_SCAN,
+        arg: Some(ARG_SCAN),
+        scope: KeyScope::All,
+        access: Access::Read,
+        flags: DANGEROUS,
+        handler: Handler::KeyspaceRead(|parts, db_key, op| scan(parts, db_key, op.get_scan())),
+    },
+    Method {
+        module: "Keys",
+        name: "Move",
+        otype: OpType::KEY_MOVE,
+        arg: Some(ARG_KEY_MOVE),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Dbs(|dbs, db_key, op| {
+            move_copy(dbs, db_key, op.get_key_move(), op.otype)
+        }),
+    },
+    Method {
+        module: "Keys",
+        name: "Copy",
+        otype: OpType::KEY_COPY,
+        arg: Some(ARG_KEY_MOVE),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Dbs(|dbs, db_key, op| {
+            move_copy(dbs, db_key, op.get_key_move(), op.otype)
+        }),
+    },
+];
+
 #[cfg(test)]
 mod tests {
+    use crate::db::{now_ms, Db, DbSet, DEF_DB};
     use crate::{keys, string};
     use memds_proto::memds_api::{
-        AtomType, KeyListOp, KeyOp, KeyRenameOp, OpType, StrGetOp, StrSetOp,
+        AtomType, ErrorCode, KeyExpireOp, KeyListOp, KeyMoveOp, KeyOp, KeyRenameOp, KeyScanOp,
+        OpType, StrGetOp, StrSetOp,
     };
-    use memds_proto::Atom;
-    use std::collections::HashMap;
+    use memds_proto::{Atom, FieldMap, SortedSet};
 
-    fn get_test_db() -> HashMap<Vec<u8>, Atom> {
-        let mut db: HashMap<Vec<u8>, Atom> = HashMap::new();
+    fn get_test_db() -> Db {
+        let mut db = Db::new();
         db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
         db.insert(b"name".to_vec(), Atom::String(b"Jane Doe".to_vec()));
         db.insert(b"age".to_vec(), Atom::String(b"25".to_vec()));
@@ -323,12 +737,12 @@ mod tests {
 
     #[test]
     fn typ() {
-        let mut db = get_test_db();
+        let db = get_test_db();
 
         let mut req = KeyOp::new();
         req.set_key(b"foo".to_vec());
 
-        let res = keys::typ(&mut db, &req);
+        let res = keys::typ(&db, &req);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::KEYS_TYPE);
@@ -338,6 +752,60 @@ mod tests {
         assert_eq!(type_res.typ, AtomType::STRING);
     }
 
+    #[test]
+    fn move_copy() {
+        let mut dbs = DbSet::new();
+        *dbs.db_mut(b"0") = get_test_db();
+        dbs.db_mut(b"0").set_expire(b"foo", now_ms() + 60000);
+
+        // move "foo" to db "1", with its expiration
+        let mut req = KeyMoveOp::new();
+        req.set_key(b"foo".to_vec());
+        req.set_dest_db(b"1".to_vec());
+
+        let res = keys::move_copy(&mut dbs, b"0", &req, OpType::KEY_MOVE);
+
+        assert_eq!(res.ok, true);
+        assert_eq!(res.otype, OpType::KEY_MOVE);
+        assert_eq!(res.get_count().n, 1);
+        assert!(!dbs.db_mut(b"0").contains_key(b"foo"));
+        assert!(dbs.db_mut(b"1").get(b"foo").is_some());
+        assert!(dbs.db_mut(b"1").expire_time(b"foo").is_some());
+
+        // moving missing key fails
+        let res = keys::move_copy(&mut dbs, b"0", &req, OpType::KEY_MOVE);
+
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
+
+        // copy "name" to db "1":  source remains
+        req.set_key(b"name".to_vec());
+        let res = keys::move_copy(&mut dbs, b"0", &req, OpType::KEY_COPY);
+
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 1);
+        assert!(dbs.db_mut(b"0").contains_key(b"name"));
+        assert!(dbs.db_mut(b"1").contains_key(b"name"));
+
+        // existing destination:  not copied, unless replacing
+        req.set_key(b"age".to_vec());
+        req.set_dest_key(b"name".to_vec());
+        let res = keys::move_copy(&mut dbs, b"0", &req, OpType::KEY_COPY);
+
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 0);
+
+        req.replace = true;
+        let res = keys::move_copy(&mut dbs, b"0", &req, OpType::KEY_COPY);
+
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 1);
+        match dbs.db_mut(b"1").get(b"name") {
+            Some(Atom::String(s)) => assert_eq!(s, b"25"),
+            _ => panic!("not a string"),
+        }
+    }
+
     #[test]
     fn rename() {
         let mut db = get_test_db();
@@ -357,17 +825,17 @@ mod tests {
         let mut req = StrGetOp::new();
         req.set_key(b"foo".to_vec());
 
-        let res = string::get(&mut db, &req, OpType::STR_GET);
+        let res = string::get(&db, &req, OpType::STR_GET);
 
         assert_eq!(res.ok, false);
         assert_eq!(res.otype, OpType::NOOP);
-        assert_eq!(res.err_code, -404);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
 
         // get "food" == found
         let mut req = StrGetOp::new();
         req.set_key(b"food".to_vec());
 
-        let res = string::get(&mut db, &req, OpType::STR_GET);
+        let res = string::get(&db, &req, OpType::STR_GET);
 
         assert_eq!(res.ok, true);
 
@@ -383,7 +851,7 @@ mod tests {
         let mut req = KeyOp::new();
         req.set_key(b"foo".to_vec());
 
-        let res = keys::dump(&mut db, &req);
+        let res = keys::dump(&db, &req);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::KEY_DUMP);
@@ -406,11 +874,220 @@ mod tests {
         let mut req = StrGetOp::new();
         req.set_key(b"foo2".to_vec());
 
-        let res = string::get(&mut db, &req, OpType::STR_GET);
+        let res = string::get(&db, &req, OpType::STR_GET);
 
         assert_eq!(res.ok, true);
 
         let get_res = res.get_get();
         assert_eq!(get_res.value, b"bar".to_vec());
     }
+
+    #[test]
+    fn expire() {
+        let mut db = get_test_db();
+
+        // expire "foo" in 60 seconds
+        let mut req = KeyExpireOp::new();
+        req.set_key(b"foo".to_vec());
+        req.expire_ms = 60000;
+
+        let res = keys::expire(&mut db, &req);
+
+        assert_eq!(res.ok, true);
+        assert_eq!(res.otype, OpType::KEY_EXPIRE);
+        assert_eq!(res.get_count().n, 1);
+
+        // ttl("foo") in (0,60s]
+        let mut req = KeyOp::new();
+        req.set_key(b"foo".to_vec());
+
+        let res = keys::ttl(&db, &req);
+
+        assert_eq!(res.ok, true);
+        assert_eq!(res.otype, OpType::KEY_TTL);
+        assert!(res.has_ttl());
+
+        let ttl_res = res.get_ttl();
+        assert!(ttl_res.ttl_ms > 0 && ttl_res.ttl_ms <= 60000);
+
+        // rename carries expiration along
+        let mut req = KeyRenameOp::new();
+        req.set_old_key(b"foo".to_vec());
+        req.set_new_key(b"food".to_vec());
+
+        let res = keys::rename(&mut db, &req);
+        assert_eq!(res.ok, true);
+
+        // persist("food") removes expiration
+        let mut req = KeyOp::new();
+        req.set_key(b"food".to_vec());
+
+        let res = keys::persist(&mut db, &req);
+
+        assert_eq!(res.ok, true);
+        assert_eq!(res.otype, OpType::KEY_PERSIST);
+        assert_eq!(res.get_count().n, 1);
+
+        let res = keys::ttl(&db, &req);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_ttl().ttl_ms, -1);
+
+        // expiration in the past deletes key
+        let mut req = KeyExpireOp::new();
+        req.set_key(b"food".to_vec());
+        req.expire_ms = 1;
+        req.absolute = true;
+
+        let res = keys::expire(&mut db, &req);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 1);
+
+        let mut req = KeyOp::new();
+        req.set_key(b"food".to_vec());
+
+        let res = keys::ttl(&db, &req);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
+
+        // expire of missing key updates nothing
+        let mut req = KeyExpireOp::new();
+        req.set_key(b"does-not-exist".to_vec());
+        req.expire_ms = 60000;
+
+        let res = keys::expire(&mut db, &req);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 0);
+    }
+
+    #[test]
+    fn dump_with_expiration() {
+        let mut db = get_test_db();
+
+        let mut req = KeyExpireOp::new();
+        req.set_key(b"foo".to_vec());
+        req.expire_ms = 60000;
+        keys::expire(&mut db, &req);
+
+        // dump "foo", restore as "foo2"
+        let mut req = KeyOp::new();
+        req.set_key(b"foo".to_vec());
+
+        let res = keys::dump(&db, &req);
+        assert_eq!(res.ok, true);
+
+        let mut set_req = StrSetOp::new();
+        set_req.set_key(b"foo2".to_vec());
+        set_req.set_value(res.get_get().get_value().to_vec());
+
+        let res = keys::restore(&mut db, &set_req);
+        assert_eq!(res.ok, true);
+
+        // expiration survives round trip
+        let mut req = KeyOp::new();
+        req.set_key(b"foo2".to_vec());
+
+        let res = keys::ttl(&db, &req);
+        assert_eq!(res.ok, true);
+        assert!(res.get_ttl().ttl_ms > 0);
+    }
+
+    #[test]
+    fn dump_hash() {
+        let mut db = get_test_db();
+
+        let mut hm = FieldMap::new();
+        hm.insert(b"name".to_vec(), b"Jane Doe".to_vec());
+        hm.insert(b"age".to_vec(), b"25".to_vec());
+        db.insert(b"user1".to_vec(), Atom::Hash(hm.clone()));
+
+        // dump "user1", restore as "user2"
+        let mut req = KeyOp::new();
+        req.set_key(b"user1".to_vec());
+
+        let res = keys::dump(&db, &req);
+        assert_eq!(res.ok, true);
+
+        let mut set_req = StrSetOp::new();
+        set_req.set_key(b"user2".to_vec());
+        set_req.set_value(res.get_get().get_value().to_vec());
+
+        let res = keys::restore(&mut db, &set_req);
+        assert_eq!(res.ok, true);
+
+        match db.get(b"user2") {
+            Some(Atom::Hash(restored)) => assert_eq!(*restored, hm),
+            _ => panic!("not a hash"),
+        }
+    }
+
+    #[test]
+    fn dump_zset() {
+        let mut db = get_test_db();
+
+        let mut zs = SortedSet::new();
+        zs.insert(b"a".to_vec(), 1.5);
+        zs.insert(b"b".to_vec(), -2.0);
+        db.insert(b"zset1".to_vec(), Atom::SortedSet(zs));
+
+        // dump "zset1", restore as "zset2"
+        let mut req = KeyOp::new();
+        req.set_key(b"zset1".to_vec());
+
+        let res = keys::dump(&db, &req);
+        assert_eq!(res.ok, true);
+
+        let mut set_req = StrSetOp::new();
+        set_req.set_key(b"zset2".to_vec());
+        set_req.set_value(res.get_get().get_value().to_vec());
+
+        let res = keys::restore(&mut db, &set_req);
+        assert_eq!(res.ok, true);
+
+        match db.get(b"zset2") {
+            Some(Atom::SortedSet(restored)) => {
+                assert_eq!(restored.len(), 2);
+                assert_eq!(restored.score(b"a"), Some(1.5));
+                assert_eq!(restored.rank(b"b"), Some(0));
+            }
+            _ => panic!("not a sorted set"),
+        }
+    }
+
+    #[test]
+    fn scan() {
+        let mut dbs = DbSet::new();
+        *dbs.db_mut(DEF_DB) = get_test_db();
+        dbs.db_mut(DEF_DB)
+            .insert(b"lst".to_vec(), Atom::List(vec![b"a".to_vec()]));
+
+        // keyspace in parts, as sharded
+        let parts = dbs.split(3, |key| key.len() % 3);
+        let parts: Vec<&DbSet> = parts.iter().collect();
+
+        // full scan, in small steps, of string keys matching "*a*"
+        let mut found = Vec::new();
+        let mut req = KeyScanOp::new();
+        req.set_pattern(b"*a*".to_vec());
+        req.count = 1;
+        req.typ = AtomType::STRING;
+        loop {
+            let res = keys::scan(&parts, DEF_DB, &req);
+
+            assert_eq!(res.ok, true);
+            assert_eq!(res.otype, OpType::KEYS_SCAN);
+            assert!(res.has_scan());
+
+            let scan_res = res.get_scan();
+            for key in scan_res.keys.iter() {
+                found.push(key.clone());
+            }
+            if scan_res.cursor == 0 {
+                break;
+            }
+            req.cursor = scan_res.cursor;
+        }
+
+        found.sort();
+        assert_eq!(found, vec![b"age".to_vec(), b"name".to_vec()]);
+    }
 }
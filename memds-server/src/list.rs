@@ -1,23 +1,26 @@
-use std::collections::HashMap;
-
 use memds_proto::memds_api::{
     CountRes, KeyOp, ListIndexOp, ListInfoRes, ListPopOp, ListPushOp, ListRes, OpResult, OpType,
 };
-use memds_proto::util::result_err;
 use memds_proto::Atom;
 
-pub fn info(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
+use crate::db::Db;
+use crate::error::OpError;
+use crate::registry::{
+    Access, Handler, KeyScope, Method, ARG_KEY, ARG_LINDEX, ARG_LPOP, ARG_LPUSH,
+};
+
+pub fn info(db: &Db, req: &KeyOp) -> OpResult {
     // get list to query
     let l = {
         let key = req.get_key();
-        match db.get_mut(key) {
+        match db.peek(key) {
             None => {
-                return result_err(-404, "Not Found");
+                return OpError::not_found().into();
             }
             Some(atom) => match atom {
                 Atom::List(l) => l,
                 _ => {
-                    return result_err(-400, "not a list");
+                    return OpError::wrong_type("list").into();
                 }
             },
         }
@@ -39,14 +42,14 @@ pub fn info(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
     op_res
 }
 
-pub fn push(db: &mut HashMap<Vec<u8>, Atom>, req: &ListPushOp) -> OpResult {
+pub fn push(db: &mut Db, req: &ListPushOp) -> OpResult {
     // get list to mutate
     let l = {
         let key = req.get_key();
         match db.get_mut(key) {
             None => {
                 if req.if_exists {
-                    return result_err(-404, "Not Found");
+                    return OpError::not_found().into();
                 }
                 db.insert(key.to_vec(), Atom::List(Vec::new()));
                 match db.get_mut(key) {
@@ -60,7 +63,7 @@ pub fn push(db: &mut HashMap<Vec<u8>, Atom>, req: &ListPushOp) -> OpResult {
             Some(atom) => match atom {
                 Atom::List(l) => l,
                 _ => {
-                    return result_err(-400, "not a list");
+                    return OpError::wrong_type("list").into();
                 }
             },
         }
@@ -91,18 +94,18 @@ pub fn push(db: &mut HashMap<Vec<u8>, Atom>, req: &ListPushOp) -> OpResult {
     op_res
 }
 
-pub fn pop(db: &mut HashMap<Vec<u8>, Atom>, req: &ListPopOp) -> OpResult {
+pub fn pop(db: &mut Db, req: &ListPopOp) -> OpResult {
     // get list to mutate
     let l = {
         let key = req.get_key();
         match db.get_mut(key) {
             None => {
-                return result_err(-404, "Not Found");
+                return OpError::not_found().into();
             }
             Some(atom) => match atom {
                 Atom::List(l) => l,
                 _ => {
-                    return result_err(-400, "not a list");
+                    return OpError::wrong_type("list").into();
                 }
             },
         }
@@ -133,9 +136,9 @@ pub fn pop(db: &mut HashMap<Vec<u8>, Atom>, req: &ListPopOp) -> OpResult {
     op_res
 }
 
-pub fn index(db: &mut HashMap<Vec<u8>, Atom>, req: &ListIndexOp) -> OpResult {
+pub fn index(db: &Db, req: &ListIndexOp) -> OpResult {
     // get list to query
-    match db.get(req.get_key()) {
+    match db.peek(req.get_key()) {
         Some(atom) => match atom {
             Atom::List(l) => {
                 let mut index_res = ListRes::new();
@@ -168,21 +171,65 @@ pub fn index(db: &mut HashMap<Vec<u8>, Atom>, req: &ListIndexOp) -> OpResult {
 
                 op_res
             }
-            _ => result_err(-400, "not a list"),
+            _ => OpError::wrong_type("list").into(),
         },
-        None => result_err(-404, "Not Found"),
+        None => OpError::not_found().into(),
     }
 }
 
+/// List module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "List",
+        name: "Push",
+        otype: OpType::LIST_PUSH,
+        arg: Some(ARG_LPUSH),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| push(db, op.get_lpush())),
+    },
+    Method {
+        module: "List",
+        name: "Pop",
+        otype: OpType::LIST_POP,
+        arg: Some(ARG_LPOP),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| pop(db, op.get_lpop())),
+    },
+    Method {
+        module: "List",
+        name: "Index",
+        otype: OpType::LIST_INDEX,
+        arg: Some(ARG_LINDEX),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| index(db, op.get_lindex())),
+    },
+    Method {
+        module: "List",
+        name: "Info",
+        otype: OpType::LIST_INFO,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| info(db, op.get_key())),
+    },
+];
+
 #[cfg(test)]
 mod tests {
+    use crate::db::Db;
     use crate::list;
     use memds_proto::memds_api::{KeyOp, ListIndexOp, ListPopOp, ListPushOp, OpType};
     use memds_proto::Atom;
-    use std::collections::HashMap;
 
-    fn get_test_db() -> HashMap<Vec<u8>, Atom> {
-        let mut db: HashMap<Vec<u8>, Atom> = HashMap::new();
+    fn get_test_db() -> Db {
+        let mut db = Db::new();
         db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
         db.insert(b"name".to_vec(), Atom::String(b"Jane Doe".to_vec()));
         db.insert(b"age".to_vec(), Atom::String(b"25".to_vec()));
@@ -229,7 +276,7 @@ mod tests {
         req.set_key(b"lst".to_vec());
         req.index = 0;
 
-        let res = list::index(&mut db, &req);
+        let res = list::index(&db, &req);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::LIST_INDEX);
@@ -244,7 +291,7 @@ mod tests {
         req.set_key(b"lst".to_vec());
         req.index = 1;
 
-        let res = list::index(&mut db, &req);
+        let res = list::index(&db, &req);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::LIST_INDEX);
@@ -259,7 +306,7 @@ mod tests {
         req.set_key(b"lst".to_vec());
         req.index = -1;
 
-        let res = list::index(&mut db, &req);
+        let res = list::index(&db, &req);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::LIST_INDEX);
@@ -287,7 +334,7 @@ mod tests {
         let mut req = KeyOp::new();
         req.set_key(b"lst".to_vec());
 
-        let res = list::info(&mut db, &req);
+        let res = list::info(&db, &req);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::LIST_INFO);
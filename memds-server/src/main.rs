@@ -4,41 +4,54 @@ extern crate memds_proto;
 #[macro_use]
 extern crate log;
 
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, Error, ErrorKind, Read};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
 
 use bytes::BytesMut;
-use futures::sync::oneshot;
 use futures::Future;
 use grpcio::{Environment, ServerBuilder};
+use nix::sys::signal::{SigSet, Signal};
 use tokio_util::codec::Decoder;
 
-use memds_proto::memds_api::MemdsMessage_MsgType;
+use memds_proto::memds_api::{LogPos, MemdsMessage_MsgType, ShutdownOp_Mode};
 use memds_proto::memds_api_grpc;
-use memds_proto::{Atom, MemdsCodec};
+use memds_proto::MemdsCodec;
 
+mod acl;
+mod batch;
 mod config;
+mod db;
+mod error;
+mod framed;
+mod hash;
+mod journal;
 mod keys;
 mod list;
+mod registry;
+mod resp;
 mod rpcservice;
+mod script;
 mod server;
 mod set;
+mod shard;
 mod string;
+mod tls;
+mod unix;
+mod util;
+mod wal;
+mod wasm;
+mod zset;
 
-fn init_db(cfg: &config::Config) -> io::Result<HashMap<Vec<u8>, Atom>> {
-    let mut db = HashMap::new();
-
-    // get filename; if missing, return success
-    let import_fn = match &cfg.fs.import {
-        None => {
-            return Ok(db);
-        }
-        Some(n) => n,
-    };
-
+// import snapshot into dbs, returning the operation log position
+// recorded in the snapshot, if any
+fn import_snapshot(dbs: &mut db::DbSet, import_fn: &Path) -> io::Result<Option<LogPos>> {
     // open file and set up import & decode
     let mut codec = MemdsCodec::new();
     let mut f = File::open(import_fn)?;
@@ -73,15 +86,23 @@ fn init_db(cfg: &config::Config) -> io::Result<HashMap<Vec<u8>, Atom>> {
                 Ok(Some(msg)) => {
                     // EOF record; return success
                     if msg.mtype == MemdsMessage_MsgType::END {
-                        return Ok(db);
+                        if msg.has_log_pos() {
+                            return Ok(Some(msg.get_log_pos().clone()));
+                        }
+                        return Ok(None);
 
                     // require DBVAL records
                     } else if (msg.mtype != MemdsMessage_MsgType::DBVAL) || !msg.has_dbv() {
                         return Err(Error::new(ErrorKind::Other, "unexpected record type"));
                     }
 
-                    // import record
-                    if !keys::import_dbv(&mut db, None, msg.get_dbv()) {
+                    // import record, into its database
+                    let dbv = msg.get_dbv();
+                    let db_key = match dbv.get_db() {
+                        b"" => db::DEF_DB,
+                        db_key => db_key,
+                    };
+                    if !keys::import_dbv(dbs.db_mut(db_key), None, dbv) {
                         return Err(Error::new(ErrorKind::Other, "record import failed"));
                     }
                 }
@@ -95,31 +116,284 @@ fn init_db(cfg: &config::Config) -> io::Result<HashMap<Vec<u8>, Atom>> {
     ))
 }
 
+fn init_db(cfg: &config::Config) -> io::Result<(db::DbSet, Option<wal::Wal>)> {
+    let mut dbs = db::DbSet::new();
+
+    // import snapshot:  as specified, else configured snapshot if present
+    let log_pos = match &cfg.fs.import {
+        Some(import_fn) => import_snapshot(&mut dbs, Path::new(import_fn))?,
+        None => {
+            if cfg.fs.snapshot.exists() {
+                import_snapshot(&mut dbs, &cfg.fs.snapshot)?
+            } else {
+                None
+            }
+        }
+    };
+
+    // replay operation log on top of snapshot, if logging enabled
+    let wal = match &cfg.fs.aof {
+        None => None,
+        Some(aof_fn) => Some(wal::Wal::open(
+            Path::new(aof_fn),
+            cfg.fs.aof_fsync,
+            &mut dbs,
+            log_pos.as_ref(),
+        )?),
+    };
+
+    Ok((dbs, wal))
+}
+
 fn main() {
-    let env = Arc::new(Environment::new(1));
+    // termination and reload signals are blocked in all threads, and
+    // instead received by a dedicated thread.  must precede thread creation.
+    let mut sigs = SigSet::empty();
+    sigs.add(Signal::SIGTERM);
+    sigs.add(Signal::SIGINT);
+    sigs.add(Signal::SIGHUP);
+    sigs.thread_block().unwrap();
 
     let cfg = config::get();
 
-    let initial_db = init_db(&cfg).unwrap();
+    // completion queues, each polled by its own thread, serving requests
+    // in parallel
+    let env = Arc::new(Environment::new(cfg.network.cq_count));
+
+    let (initial_db, initial_wal) = init_db(&cfg).unwrap();
+
+    let keyspace = Arc::new(shard::Keyspace::new(initial_db, cfg.keyspace.shards));
+    let wal = Arc::new(Mutex::new(initial_wal));
+    let snap = Arc::new(Mutex::new(server::SaveState::new(
+        cfg.fs.snapshot.clone(),
+        cfg.fs.save.clone(),
+    )));
+
+    // periodic maintenance: operation log fsync and rewrite, automatic
+    // snapshots.  lock order matches rpcservice.
+    let cron_keyspace = keyspace.clone();
+    let cron_wal = wal.clone();
+    let cron_snap = snap.clone();
+    let rewrite_pct = cfg.fs.aof_rewrite_pct;
+    let rewrite_min_size = cfg.fs.aof_rewrite_min_size;
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_millis(wal::FSYNC_INTERVAL_MS));
+        let now = db::now_ms() / 1000;
+        let due = {
+            let mut wal = cron_wal.lock().unwrap();
+            let mut snap = cron_snap.lock().unwrap();
+            let (mut rewrite_due, mut rewriting) = (false, false);
+            if let Some(w) = wal.as_mut() {
+                w.cron();
+                rewrite_due = w.rewrite_due(rewrite_pct, rewrite_min_size);
+                rewriting = w.rewrite_in_progress();
+            }
+            snap.poll_child();
+
+            // one forked child at a time
+            (rewrite_due && !snap.in_progress()) || (snap.save_due(now) && !rewriting)
+        };
+        if !due {
+            continue;
+        }
+
+        // rewrite & snapshot are written by a forked child: every shard
+        // is read-locked across the fork, for a consistent dataset
+        let shards = cron_keyspace.read_all();
+        let parts: Vec<&db::DbSet> = shards.iter().map(|s| &**s).collect();
+        let mut wal = cron_wal.lock().unwrap();
+        let mut snap = cron_snap.lock().unwrap();
+
+        if let Some(w) = wal.as_mut() {
+            if !snap.in_progress() {
+                w.auto_rewrite(&parts, rewrite_pct, rewrite_min_size);
+            }
+        }
+
+        let rewriting = wal.as_ref().is_some_and(|w| w.rewrite_in_progress());
+        if snap.save_due(now) && !rewriting {
+            info!("{} changes since last save; saving", snap.dirty);
+            let res = server::bgsave(&parts, wal.as_mut(), &mut snap);
+            if !res.ok {
+                error!("automatic save failed: {}", res.err_message);
+            }
+        }
+    });
+
+    // active expiry: periodically remove expired keys not otherwise
+    // accessed, logging their removal
+    let expire_keyspace = keyspace.clone();
+    let expire_wal = wal.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_millis(db::ACTIVE_EXPIRE_CYCLE_MS));
+        let log = |expired: &[db::ItemKey]| {
+            if let Some(w) = expire_wal.lock().unwrap().as_mut() {
+                if let Err(e) = w.append_batch(&wal::expired_ops(expired)) {
+                    error!("operation log write failed: {:?}", e);
+                }
+            }
+        };
+        expire_keyspace.active_expire(db::now_ms(), db::ACTIVE_EXPIRE_CYCLE_KEYS, log);
+    });
+
+    // shutdown requests, from SHUTDOWN operation or signal
+    let (shutdown_tx, shutdown_rx) = mpsc::channel();
+
+    // gRPC endpoint TLS certificates, reloaded upon SIGHUP
+    let cert_fetcher = cfg.tls.map(tls::CertFetcher::new);
 
-    let service = memds_api_grpc::create_memds(rpcservice::MemdsService {
-        map: Arc::new(Mutex::new(initial_db)),
+    let sig_tx = shutdown_tx.clone();
+    let cert_reload = cert_fetcher.as_ref().map(|f| f.reload_flag());
+    std::thread::spawn(move || loop {
+        match sigs.wait() {
+            Ok(Signal::SIGHUP) => match &cert_reload {
+                Some(reload) => {
+                    info!("received SIGHUP; reloading TLS certificates");
+                    reload.store(true, Ordering::SeqCst);
+                }
+                None => info!("received SIGHUP; nothing to reload"),
+            },
+            Ok(sig) => {
+                info!("received {:?}", sig);
+                let req = server::ShutdownReq {
+                    mode: ShutdownOp_Mode::DEFAULT,
+                    reply: None,
+                };
+                if sig_tx.send(req).is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                error!("signal wait failed: {:?}", e);
+                break;
+            }
+        }
     });
-    let mut server = ServerBuilder::new(env)
-        .register_service(service)
-        .bind(cfg.network.bind_addr, cfg.network.bind_port)
-        .build()
-        .unwrap();
+
+    // users and their permissions; absent an ACL file, the default user
+    // alone, permitted everything
+    let acl = match &cfg.acl {
+        None => acl::Acl::new(),
+        Some(acl_cfg) => match acl::Acl::load(&acl_cfg.file) {
+            Ok(acl) => acl,
+            Err(e) => panic!("ACL file load failed: {}", e),
+        },
+    };
+
+    let svc = rpcservice::MemdsService {
+        keyspace: keyspace.clone(),
+        wal: wal.clone(),
+        snap: snap.clone(),
+        scripts: Arc::new(Mutex::new(script::ScriptCache::new(
+            cfg.script.time_limit_ms,
+        ))),
+        modules: Arc::new(Mutex::new(wasm::ModuleSet::new())),
+        acl: Arc::new(RwLock::new(acl)),
+        shutdown_tx,
+    };
+
+    // redis protocol clients, if configured
+    if let Some(resp_cfg) = &cfg.resp {
+        let addr = (resp_cfg.bind_addr.as_str(), resp_cfg.bind_port);
+        let listener = TcpListener::bind(addr).unwrap();
+        println!("RESP listening on {}", listener.local_addr().unwrap());
+        let resp_svc = svc.clone();
+        std::thread::spawn(move || resp::listen(resp_svc, listener));
+    }
+
+    // framed protocol clients, if configured
+    if let Some(framed_cfg) = &cfg.framed {
+        let addr = (framed_cfg.bind_addr.as_str(), framed_cfg.bind_port);
+        let listener = TcpListener::bind(addr).unwrap();
+        println!("framed listening on {}", listener.local_addr().unwrap());
+        let framed_svc = svc.clone();
+        std::thread::spawn(move || framed::listen(framed_svc, listener));
+    }
+
+    // unix socket clients, of the framed protocol
+    let mut socket_files = Vec::new();
+    for sock_cfg in cfg.network.unix_sockets.iter() {
+        let (listener, file) = unix::bind(&sock_cfg.path, sock_cfg.mode).unwrap();
+        println!("listening on {}", sock_cfg.path.display());
+        socket_files.push(file);
+        let unix_svc = svc.clone();
+        std::thread::spawn(move || unix::listen(unix_svc, listener));
+    }
+
+    let service = memds_api_grpc::create_memds(svc);
+    let builder = ServerBuilder::new(env).register_service(service);
+    let builder = match cert_fetcher {
+        Some(fetcher) => {
+            let cert_type = fetcher.cert_request_type();
+            builder.bind_with_fetcher(
+                cfg.network.bind_addr,
+                cfg.network.bind_port,
+                Box::new(fetcher),
+                cert_type,
+            )
+        }
+        None => builder.bind(cfg.network.bind_addr, cfg.network.bind_port),
+    };
+    let mut server = builder.build().unwrap();
     server.start();
     for (host, port) in server.bind_addrs() {
         println!("listening on {}:{}", host, port);
     }
-    let (tx, rx) = oneshot::channel();
-    std::thread::spawn(move || {
-        println!("Press ENTER to exit...");
-        let _ = io::stdin().read(&mut [0]).unwrap();
-        tx.send(())
-    });
-    let _ = rx.wait();
+
+    // wait for shutdown request, then write the final snapshot, if
+    // requested, while still serving.  if it fails, the request fails
+    // and the server continues running.
+    let mode = loop {
+        let req = shutdown_rx.recv().unwrap();
+        let res = {
+            let shards = keyspace.read_all();
+            let parts: Vec<&db::DbSet> = shards.iter().map(|s| &**s).collect();
+            let mut wal = wal.lock().unwrap();
+            let mut snap = snap.lock().unwrap();
+            server::shutdown_save(&parts, wal.as_mut(), &mut snap, req.mode)
+        };
+        if !res.ok {
+            error!("shutdown save failed; continuing to serve");
+        }
+
+        let ok = res.ok;
+        if let Some(reply) = req.reply {
+            let _ = reply.send(res);
+        }
+        if ok {
+            break req.mode;
+        }
+    };
+
+    // stop serving; in-flight requests are completed
+    println!("shutting down");
     let _ = server.shutdown().wait();
+    for file in socket_files.iter() {
+        file.remove();
+    }
+
+    let mut exit_code = 0;
+    let shards = keyspace.read_all();
+    let parts: Vec<&db::DbSet> = shards.iter().map(|s| &**s).collect();
+    let mut wal = wal.lock().unwrap();
+    let mut snap = snap.lock().unwrap();
+
+    // writes completed after the final snapshot, meanwhile
+    if server::shutdown_saves(mode, &snap) && snap.dirty > 0 {
+        let res = server::save(&parts, wal.as_mut(), &mut snap);
+        if !res.ok {
+            error!("shutdown save failed: {}", res.err_message);
+            exit_code = 1;
+        }
+    }
+
+    // flush operation log
+    if let Some(w) = wal.as_mut() {
+        if let Err(e) = w.sync() {
+            error!("operation log sync failed: {:?}", e);
+            exit_code = 1;
+        }
+    }
+
+    std::process::exit(exit_code);
 }
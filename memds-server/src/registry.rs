@@ -0,0 +1,356 @@
+use std::sync::mpsc::Sender;
+use std::sync::{Mutex, RwLock};
+
+use protobuf::RepeatedField;
+
+use memds_proto::memds_api::{ErrorCode, OpRef_Target, OpResult, OpType, Operation};
+
+use crate::acl::{self, Acl};
+use crate::db::{Db, DbSet};
+use crate::error::OpError;
+use crate::script::ScriptCache;
+use crate::server::{SaveState, ShutdownReq};
+use crate::wal::Wal;
+use crate::wasm::ModuleSet;
+use crate::{hash, keys, list, script, server, set, string, wasm, zset};
+
+/// Server state available to server-wide methods.  Shared state is
+/// locked by the methods using it, after the keyspace shards:  script
+/// cache, modules, operation log, then snapshot state, in that order.
+pub struct ServerCtx<'a> {
+    pub dbs: &'a mut DbSet,     // items at the operation's keys
+    pub parts: &'a [&'a DbSet], // every item, for keyspace-wide methods
+    pub db_key: &'a [u8],       // selected database
+    pub wal: &'a Mutex<Option<Wal>>,
+    pub snap: &'a Mutex<SaveState>,
+    pub scripts: &'a Mutex<ScriptCache>,
+    pub modules: &'a Mutex<ModuleSet>,
+    pub acl: &'a RwLock<Acl>,
+    pub user: &'a mut Option<String>, // user client authenticated as, if any
+    pub shutdown_tx: &'a Sender<ShutdownReq>,
+}
+
+/// Method implementation, by the scope of state it operates upon.
+pub enum Handler {
+    /// Confined to the selected database.
+    Db(fn(&mut Db, &Operation) -> OpResult),
+
+    /// Confined to the selected database, which it only reads.  Expired
+    /// keys are treated as absent, but not removed.
+    DbRead(fn(&Db, &Operation) -> OpResult),
+
+    /// Spans databases; called with the selected database key.
+    Dbs(fn(&mut DbSet, &[u8], &Operation) -> OpResult),
+
+    /// Spans the whole keyspace, held in parts such as keyspace shards;
+    /// called with the selected database key.
+    Keyspace(fn(&mut [&mut DbSet], &[u8], &Operation) -> OpResult),
+
+    /// Reads the whole keyspace, held in parts; called with the selected
+    /// database key.
+    KeyspaceRead(fn(&[&DbSet], &[u8], &Operation) -> OpResult),
+
+    /// Server-wide persistence & lifecycle.  Never logged or replayed.
+    Server(fn(&mut ServerCtx, &Operation) -> OpResult),
+}
+
+/// Argument schema:  the `Operation` field carrying a method's arguments.
+pub struct Arg {
+    pub name: &'static str,
+    pub present: fn(&Operation) -> bool,
+    pub keys: &'static [&'static str], // argument fields holding item keys
+    pub key_values: fn(&Operation) -> Vec<&[u8]>, // ...and their values
+}
+
+// argument field holding one or more item keys
+trait KeyField {
+    fn push_keys<'a>(&'a self, keys: &mut Vec<&'a [u8]>);
+}
+
+impl KeyField for Vec<u8> {
+    fn push_keys<'a>(&'a self, keys: &mut Vec<&'a [u8]>) {
+        if !self.is_empty() {
+            keys.push(self);
+        }
+    }
+}
+
+impl KeyField for RepeatedField<Vec<u8>> {
+    fn push_keys<'a>(&'a self, keys: &mut Vec<&'a [u8]>) {
+        for key in self.iter() {
+            keys.push(key);
+        }
+    }
+}
+
+macro_rules! arg_schemas {
+    ($($name:ident => $field:ident, $has:ident, $get:ident, [$($key:ident),*];)*) => {
+        $(
+            pub const $name: Arg = Arg {
+                name: stringify!($field),
+                present: Operation::$has,
+                keys: &[$(stringify!($key)),*],
+                #[allow(unused_variables, unused_mut)]
+                key_values: |op| {
+                    let arg = op.$get();
+                    let mut keys = Vec::new();
+                    $(arg.$key.push_keys(&mut keys);)*
+                    keys
+                },
+            };
+        )*
+    };
+}
+
+// argument schemas, by Operation field
+arg_schemas! {
+    ARG_GET => get, has_get, get_get, [key];
+    ARG_SET => set, has_set, get_set, [key];
+    ARG_NUM => num, has_num, get_num, [key];
+    ARG_LPUSH => lpush, has_lpush, get_lpush, [key];
+    ARG_LPOP => lpop, has_lpop, get_lpop, [key];
+    ARG_LINDEX => lindex, has_lindex, get_lindex, [key];
+    ARG_KEY => key, has_key, get_key, [key];
+    ARG_KEY_LIST => key_list, has_key_list, get_key_list, [keys];
+    ARG_RENAME => rename, has_rename, get_rename, [old_key, new_key];
+    ARG_KEYED_LIST => keyed_list, has_keyed_list, get_keyed_list, [key];
+    ARG_CMP_STOR => cmp_stor, has_cmp_stor, get_cmp_stor, [keys, store_key];
+    ARG_SET_MOVE => set_move, has_set_move, get_set_move, [src_key, dest_key];
+    ARG_EXPIRE => expire, has_expire, get_expire, [key];
+    ARG_SCAN => scan, has_scan, get_scan, [key];
+    ARG_HSET => hset, has_hset, get_hset, [key];
+    ARG_HINCR => hincr, has_hincr, get_hincr, [key];
+    ARG_ZADD => zadd, has_zadd, get_zadd, [key];
+    ARG_ZRANGE => zrange, has_zrange, get_zrange, [key];
+    ARG_ZPOP => zpop, has_zpop, get_zpop, [key];
+    ARG_ZSTORE => zstore, has_zstore, get_zstore, [keys, store_key];
+    ARG_KEY_MOVE => key_move, has_key_move, get_key_move, [key, dest_key];
+    ARG_COMMAND => command, has_command, get_command, [];
+    ARG_SCRIPT => script, has_script, get_script, [keys];
+    ARG_MODULE => module, has_module, get_module, [];
+    ARG_MODULE_CALL => module_call, has_module_call, get_module_call, [keys];
+    ARG_AUTH => auth, has_auth, get_auth, [];
+    ARG_ACL => acl, has_acl, get_acl, [];
+}
+
+/// Read/write classification of a method.
+pub enum Access {
+    Read,
+    Write,
+
+    /// Writes iff the predicate holds for the operation.
+    WriteIf(fn(&Operation) -> bool),
+}
+
+/// Extent of the keyspace an operation may touch, determining the
+/// keyspace shards locked by its batch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyScope {
+    /// No items.
+    None,
+
+    /// The items at the keys its arguments name, in any database.
+    Keys,
+
+    /// Any item, in any database.
+    All,
+}
+
+/// Method flag:  server administration, rather than data access.
+pub const ADMIN: u32 = 1 << 0;
+
+/// Method flag:  destructive or disruptive beyond the keys named, or
+/// revealing keys regardless of pattern.
+pub const DANGEROUS: u32 = 1 << 1;
+
+/// Method flag:  permitted any client, authenticated or not.
+pub const NO_AUTH: u32 = 1 << 2;
+
+// method flags, by name
+const FLAG_NAMES: &[(&str, u32)] = &[
+    ("admin", ADMIN),
+    ("dangerous", DANGEROUS),
+    ("no-auth", NO_AUTH),
+];
+
+/// A MODULE.METHOD registration.
+pub struct Method {
+    pub module: &'static str,
+    pub name: &'static str,
+    pub otype: OpType,
+    pub arg: Option<Arg>,
+    pub scope: KeyScope, // keyspace touched, by any operation
+    pub access: Access,
+    pub flags: u32,
+    pub handler: Handler,
+}
+
+impl Method {
+    pub fn full_name(&self) -> String {
+        format!("{}.{}", self.module, self.name)
+    }
+
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Names of the method's flags.
+    pub fn flag_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        FLAG_NAMES
+            .iter()
+            .filter(move |(_, flag)| self.has_flag(*flag))
+            .map(|(name, _)| *name)
+    }
+
+    /// Does this operation of the method mutate the database?
+    pub fn is_write(&self, op: &Operation) -> bool {
+        match self.access {
+            Access::Read => false,
+            Access::Write => true,
+            Access::WriteIf(pred) => pred(op),
+        }
+    }
+
+    /// May the method execute with the keyspace locked for reading
+    /// alone?
+    pub fn reads_only(&self) -> bool {
+        match self.handler {
+            Handler::DbRead(_) | Handler::KeyspaceRead(_) => true,
+            Handler::Db(_) | Handler::Dbs(_) | Handler::Keyspace(_) => false,
+            Handler::Server(_) => self.scope != KeyScope::Keys,
+        }
+    }
+
+    /// Extent of the keyspace this operation of the method may touch.
+    pub fn scope(&self, op: &Operation) -> KeyScope {
+        // keys taken from earlier results are unknown until execution
+        if self.scope == KeyScope::Keys {
+            for r in op.get_refs() {
+                match r.target {
+                    OpRef_Target::ARG_KEY => return KeyScope::All,
+                    OpRef_Target::ARG_ELEMENTS if op.has_key_list() => return KeyScope::All,
+                    _ => {}
+                }
+            }
+        }
+
+        self.scope
+    }
+}
+
+/// Method tables, as registered by each module.
+pub const MODULES: &[&[Method]] = &[
+    acl::METHODS,
+    hash::METHODS,
+    keys::METHODS,
+    list::METHODS,
+    script::METHODS,
+    server::METHODS,
+    set::METHODS,
+    string::METHODS,
+    wasm::METHODS,
+    zset::METHODS,
+];
+
+/// Iterate through all registered methods.
+pub fn methods() -> impl Iterator<Item = &'static Method> {
+    MODULES.iter().flat_map(|m| m.iter())
+}
+
+/// Find the method registered under a MODULE.METHOD name, ignoring case.
+pub fn lookup_name(name: &str) -> Option<&'static Method> {
+    methods().find(|m| m.full_name().eq_ignore_ascii_case(name))
+}
+
+/// Find the method registered for an operation type.
+pub fn lookup(otype: OpType) -> Option<&'static Method> {
+    methods().find(|m| m.otype == otype)
+}
+
+/// Find the method executing an operation, verifying the operation
+/// carries the arguments the method requires.
+pub fn validate(op: &Operation) -> Result<&'static Method, OpError> {
+    let method = match lookup(op.otype) {
+        Some(m) => m,
+        None => return Err(OpError::invalid_op()),
+    };
+
+    if let Some(arg) = &method.arg {
+        if !(arg.present)(op) {
+            let msg = format!("Invalid op: {} requires {}", method.full_name(), arg.name);
+            return Err(OpError::new(ErrorCode::INVALID_OP, &msg));
+        }
+    }
+
+    Ok(method)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::registry::{self, Access, Handler, KeyScope};
+    use memds_proto::memds_api::{OpRef, OpRef_Target, OpType, Operation};
+    use std::collections::HashSet;
+
+    #[test]
+    fn registry() {
+        // each operation type and name registered once
+        let mut otypes = HashSet::new();
+        let mut names = HashSet::new();
+        for m in registry::methods() {
+            assert!(otypes.insert(m.otype), "{:?}", m.otype);
+            assert!(names.insert(m.full_name()), "{}", m.full_name());
+        }
+
+        let m = registry::lookup(OpType::STR_SET).unwrap();
+        assert_eq!(m.full_name(), "String.Set");
+        assert!(m.is_write(&Operation::new()));
+        assert!(registry::lookup(OpType::NOOP).is_none());
+        let m = registry::lookup_name("keys.rename").unwrap();
+        assert_eq!(m.arg.as_ref().unwrap().keys, &["old_key", "new_key"]);
+
+        // server methods are never logged; methods touching keys name
+        // them in their arguments
+        for m in registry::methods() {
+            if let Handler::Server(_) = m.handler {
+                assert!(!m.is_write(&Operation::new()));
+            }
+            if m.scope == KeyScope::Keys {
+                assert!(!m.arg.as_ref().unwrap().keys.is_empty());
+            }
+
+            // read methods, other than server-wide, take read locks;
+            // keyspace-wide handlers span every shard
+            match (&m.access, &m.handler) {
+                (_, Handler::Server(_)) => {}
+                (Access::Read, _) => assert!(m.reads_only(), "{}", m.full_name()),
+                _ => assert!(!m.reads_only(), "{}", m.full_name()),
+            }
+            match m.handler {
+                Handler::Keyspace(_) | Handler::KeyspaceRead(_) => {
+                    assert_eq!(m.scope, KeyScope::All)
+                }
+                _ => {}
+            }
+        }
+
+        // missing arguments rejected
+        let mut op = Operation::new();
+        op.otype = OpType::STR_GET;
+        assert!(registry::validate(&op).is_err());
+        op.mut_get().set_key(b"foo".to_vec());
+        assert!(registry::validate(&op).is_ok());
+
+        // keyspace touched: named keys, none, or all
+        let m = registry::validate(&op).unwrap();
+        assert_eq!(m.scope(&op), KeyScope::Keys);
+        let m = registry::lookup(OpType::SRV_TIME).unwrap();
+        assert_eq!(m.scope(&op), KeyScope::None);
+        let m = registry::lookup(OpType::SRV_DBSIZE).unwrap();
+        assert_eq!(m.scope(&op), KeyScope::All);
+        let m = registry::lookup(OpType::STR_GET).unwrap();
+        let mut r = OpRef::new();
+        r.target = OpRef_Target::ARG_KEY;
+        op.refs.push(r);
+        assert_eq!(m.scope(&op), KeyScope::All);
+    }
+}
@@ -0,0 +1,1768 @@
+use std::io::{self, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::str;
+
+use memds_proto::memds_api::{
+    AclOp, AtomType, AuthOp, CmpStoreOp, ErrorCode, FieldValue, HashIncrOp, HashSetOp, KeyExpireOp,
+    KeyListOp, KeyOp, KeyRenameOp, KeyedListOp, ListIndexOp, ListPopOp, ListPushOp, NumOp,
+    OpResult, OpType, Operation, RequestMsg, ScoredMember, ScriptOp, ScriptValue, ScriptValue_Type,
+    SetMoveOp, StrGetOp, StrSetOp, ZAddOp, ZPopOp, ZRangeOp,
+};
+
+use crate::rpcservice::MemdsService;
+
+pub const DEF_PORT: u16 = 6379;
+
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+const MAX_ARRAY_LEN: usize = 1024 * 1024;
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// RESP reply value.  RESP3 types are downgraded when encoding for
+/// RESP2 clients.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Simple(String),
+    Error(String),
+    Int(i64),
+    Bulk(Vec<u8>),
+    Nil,
+    Array(Vec<Value>),
+    Double(f64),
+    Bool(bool),
+    Map(Vec<(Value, Value)>),
+    Set(Vec<Value>),
+}
+
+fn ok() -> Value {
+    Value::Simple("OK".to_string())
+}
+
+fn err(msg: &str) -> Value {
+    Value::Error(format!("ERR {}", msg))
+}
+
+fn bulk_str(s: &str) -> Value {
+    Value::Bulk(s.as_bytes().to_vec())
+}
+
+fn format_double(n: f64) -> String {
+    if n.is_infinite() {
+        if n > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else if n.is_nan() {
+        "nan".to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+impl Value {
+    /// Encode value for protocol version `proto` (2 or 3).
+    pub fn encode(&self, proto: u32, out: &mut Vec<u8>) {
+        match self {
+            Value::Simple(s) => out.extend_from_slice(format!("+{}\r\n", s).as_bytes()),
+            Value::Error(s) => out.extend_from_slice(format!("-{}\r\n", s).as_bytes()),
+            Value::Int(n) => out.extend_from_slice(format!(":{}\r\n", n).as_bytes()),
+            Value::Bulk(b) => {
+                out.extend_from_slice(format!("${}\r\n", b.len()).as_bytes());
+                out.extend_from_slice(b);
+                out.extend_from_slice(b"\r\n");
+            }
+            Value::Nil if proto >= 3 => out.extend_from_slice(b"_\r\n"),
+            Value::Nil => out.extend_from_slice(b"$-1\r\n"),
+            Value::Array(elements) => {
+                out.extend_from_slice(format!("*{}\r\n", elements.len()).as_bytes());
+                for element in elements.iter() {
+                    element.encode(proto, out);
+                }
+            }
+            Value::Double(n) if proto >= 3 => {
+                out.extend_from_slice(format!(",{}\r\n", format_double(*n)).as_bytes())
+            }
+            Value::Double(n) => bulk_str(&format_double(*n)).encode(proto, out),
+            Value::Bool(b) if proto >= 3 => {
+                out.extend_from_slice(if *b { b"#t\r\n" } else { b"#f\r\n" })
+            }
+            Value::Bool(b) => Value::Int(*b as i64).encode(proto, out),
+            Value::Map(pairs) => {
+                // RESP2: flattened into array of keys and values
+                let n = if proto >= 3 {
+                    pairs.len()
+                } else {
+                    pairs.len() * 2
+                };
+                let prefix = if proto >= 3 { '%' } else { '*' };
+                out.extend_from_slice(format!("{}{}\r\n", prefix, n).as_bytes());
+                for (k, v) in pairs.iter() {
+                    k.encode(proto, out);
+                    v.encode(proto, out);
+                }
+            }
+            Value::Set(elements) => {
+                let prefix = if proto >= 3 { '~' } else { '*' };
+                out.extend_from_slice(format!("{}{}\r\n", prefix, elements.len()).as_bytes());
+                for element in elements.iter() {
+                    element.encode(proto, out);
+                }
+            }
+        }
+    }
+}
+
+// find line terminated by CRLF at `pos`, returning it and the position
+// following
+fn line(buf: &[u8], pos: usize) -> Option<(&[u8], usize)> {
+    let rel = buf[pos..].windows(2).position(|w| w == b"\r\n")?;
+    Some((&buf[pos..pos + rel], pos + rel + 2))
+}
+
+fn parse_len(s: &[u8], max: usize) -> Result<Option<usize>, String> {
+    let n = str::from_utf8(s)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| "Protocol error: invalid length".to_string())?;
+    if n < 0 {
+        return Ok(None);
+    }
+    if n as u64 > max as u64 {
+        return Err("Protocol error: invalid length".to_string());
+    }
+    Ok(Some(n as usize))
+}
+
+/// Command arguments, and the number of bytes they were parsed from.
+pub type Parsed = (Vec<Vec<u8>>, usize);
+
+/// Parse command at the front of `buf`:  either an array of bulk strings,
+/// or an inline command.  Returns its arguments (empty if blank) and the
+/// number of bytes consumed, or None if incomplete.
+pub fn parse_command(buf: &[u8]) -> Result<Option<Parsed>, String> {
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    // inline command:  space-separated arguments
+    if buf[0] != b'*' {
+        return match line(buf, 0) {
+            None if buf.len() > MAX_INLINE_LEN => {
+                Err("Protocol error: too big inline request".to_string())
+            }
+            None => Ok(None),
+            Some((l, next)) => {
+                let args = l
+                    .split(|c| c.is_ascii_whitespace())
+                    .filter(|a| !a.is_empty())
+                    .map(|a| a.to_vec())
+                    .collect();
+                Ok(Some((args, next)))
+            }
+        };
+    }
+
+    let (l, mut pos) = match line(buf, 0) {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+    let n_args = parse_len(&l[1..], MAX_ARRAY_LEN)?.unwrap_or(0);
+
+    // the count is untrusted until its arguments arrive
+    let mut args = Vec::with_capacity(n_args.min(64));
+    for _ in 0..n_args {
+        let (l, next) = match line(buf, pos) {
+            None => return Ok(None),
+            Some(v) => v,
+        };
+        if l.first() != Some(&b'$') {
+            return Err("Protocol error: expected '$'".to_string());
+        }
+        let len = parse_len(&l[1..], MAX_BULK_LEN)?
+            .ok_or_else(|| "Protocol error: invalid bulk length".to_string())?;
+        if buf.len() < next + len + 2 {
+            return Ok(None);
+        }
+        if &buf[next + len..next + len + 2] != b"\r\n" {
+            return Err("Protocol error: expected CRLF".to_string());
+        }
+        args.push(buf[next..next + len].to_vec());
+        pos = next + len + 2;
+    }
+
+    Ok(Some((args, pos)))
+}
+
+// argument parsing helpers
+
+fn int_arg(arg: &[u8]) -> Result<i64, String> {
+    str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| "value is not an integer or out of range".to_string())
+}
+
+fn float_arg(arg: &[u8]) -> Result<f64, String> {
+    str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|n| !n.is_nan())
+        .ok_or_else(|| "value is not a valid float".to_string())
+}
+
+fn syntax_error<T>() -> Result<T, String> {
+    Err("syntax error".to_string())
+}
+
+// operation builders
+
+fn key_op(otype: OpType, key: &[u8]) -> Operation {
+    let mut req = KeyOp::new();
+    req.set_key(key.to_vec());
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_key(req);
+    op
+}
+
+fn key_list_op(otype: OpType, keys: &[Vec<u8>]) -> Operation {
+    let mut req = KeyListOp::new();
+    for key in keys.iter() {
+        req.keys.push(key.to_vec());
+    }
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_key_list(req);
+    op
+}
+
+fn keyed_list_op(otype: OpType, key: &[u8], elements: &[Vec<u8>]) -> Operation {
+    let mut req = KeyedListOp::new();
+    req.set_key(key.to_vec());
+    for element in elements.iter() {
+        req.elements.push(element.to_vec());
+    }
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_keyed_list(req);
+    op
+}
+
+fn cmp_stor_op(otype: OpType, store_key: &[u8], keys: &[Vec<u8>]) -> Operation {
+    let mut req = CmpStoreOp::new();
+    req.set_store_key(store_key.to_vec());
+    for key in keys.iter() {
+        req.keys.push(key.to_vec());
+    }
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_cmp_stor(req);
+    op
+}
+
+fn get_op(otype: OpType, key: &[u8]) -> Operation {
+    let mut req = StrGetOp::new();
+    req.set_key(key.to_vec());
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_get(req);
+    op
+}
+
+fn getrange_op(args: &[Vec<u8>]) -> Result<Operation, String> {
+    let mut op = get_op(OpType::STR_GETRANGE, &args[0]);
+    let req = op.mut_get();
+    req.substr = true;
+    req.range_start = int_arg(&args[1])? as i32;
+    req.range_end = int_arg(&args[2])? as i32;
+    Ok(op)
+}
+
+fn strlen_op(key: &[u8]) -> Operation {
+    let mut op = get_op(OpType::STR_GET, key);
+    op.mut_get().want_length = true;
+    op
+}
+
+fn str_set_op(otype: OpType, key: &[u8], value: &[u8]) -> Operation {
+    let mut req = StrSetOp::new();
+    req.set_key(key.to_vec());
+    req.set_value(value.to_vec());
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_set(req);
+    op
+}
+
+// SET key value [NX|XX] [EX seconds|PX milliseconds|KEEPTTL]
+fn set_op(args: &[Vec<u8>]) -> Result<Operation, String> {
+    let mut op = str_set_op(OpType::STR_SET, &args[0], &args[1]);
+    let req = op.mut_set();
+    let mut opts = args[2..].iter();
+    while let Some(opt) = opts.next() {
+        match opt.to_ascii_uppercase().as_slice() {
+            b"NX" => req.create_excl = true,
+            b"XX" => req.require_exist = true,
+            b"KEEPTTL" => req.keep_ttl = true,
+            b"EX" | b"PX" => {
+                let n = match opts.next() {
+                    None => return syntax_error(),
+                    Some(n) => int_arg(n)?,
+                };
+                if n <= 0 {
+                    return Err("invalid expire time in 'set' command".to_string());
+                }
+                req.expire_ms = match opt.to_ascii_uppercase().as_slice() {
+                    b"EX" => n.saturating_mul(1000),
+                    _ => n,
+                };
+            }
+            _ => return syntax_error(),
+        }
+    }
+    Ok(op)
+}
+
+// SETEX key seconds value, PSETEX key milliseconds value
+fn setex_op(args: &[Vec<u8>], unit_ms: i64) -> Result<Operation, String> {
+    let n = int_arg(&args[1])?;
+    if n <= 0 {
+        return Err("invalid expire time".to_string());
+    }
+    let mut op = str_set_op(OpType::STR_SET, &args[0], &args[2]);
+    op.mut_set().expire_ms = n.saturating_mul(unit_ms);
+    Ok(op)
+}
+
+fn append_op(args: &[Vec<u8>]) -> Operation {
+    let mut op = str_set_op(OpType::STR_APPEND, &args[0], &args[1]);
+    op.mut_set().return_old = true;
+    op
+}
+
+fn num_op(otype: OpType, key: &[u8], n: i64) -> Operation {
+    let mut req = NumOp::new();
+    req.set_key(key.to_vec());
+    req.n = n;
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_num(req);
+    op
+}
+
+fn rename_op(args: &[Vec<u8>], create_excl: bool) -> Operation {
+    let mut req = KeyRenameOp::new();
+    req.set_old_key(args[0].to_vec());
+    req.set_new_key(args[1].to_vec());
+    req.create_excl = create_excl;
+    let mut op = Operation::new();
+    op.otype = OpType::KEYS_RENAME;
+    op.set_rename(req);
+    op
+}
+
+fn expire_op(args: &[Vec<u8>], unit_ms: i64, absolute: bool) -> Result<Operation, String> {
+    let mut req = KeyExpireOp::new();
+    req.set_key(args[0].to_vec());
+    req.expire_ms = int_arg(&args[1])?.saturating_mul(unit_ms);
+    req.absolute = absolute;
+    let mut op = Operation::new();
+    op.otype = OpType::KEY_EXPIRE;
+    op.set_expire(req);
+    Ok(op)
+}
+
+fn push_op(args: &[Vec<u8>], at_head: bool, if_exists: bool) -> Operation {
+    let mut req = ListPushOp::new();
+    req.set_key(args[0].to_vec());
+    req.at_head = at_head;
+    req.if_exists = if_exists;
+    for element in args[1..].iter() {
+        req.elements.push(element.to_vec());
+    }
+    let mut op = Operation::new();
+    op.otype = OpType::LIST_PUSH;
+    op.set_lpush(req);
+    op
+}
+
+fn pop_op(key: &[u8], at_head: bool) -> Operation {
+    let mut req = ListPopOp::new();
+    req.set_key(key.to_vec());
+    req.at_head = at_head;
+    let mut op = Operation::new();
+    op.otype = OpType::LIST_POP;
+    op.set_lpop(req);
+    op
+}
+
+fn lindex_op(args: &[Vec<u8>]) -> Result<Operation, String> {
+    let mut req = ListIndexOp::new();
+    req.set_key(args[0].to_vec());
+    req.index = int_arg(&args[1])? as i32;
+    let mut op = Operation::new();
+    op.otype = OpType::LIST_INDEX;
+    op.set_lindex(req);
+    Ok(op)
+}
+
+fn smove_op(args: &[Vec<u8>]) -> Operation {
+    let mut req = SetMoveOp::new();
+    req.set_src_key(args[0].to_vec());
+    req.set_dest_key(args[1].to_vec());
+    req.set_member(args[2].to_vec());
+    let mut op = Operation::new();
+    op.otype = OpType::SET_MOVE;
+    op.set_set_move(req);
+    op
+}
+
+// HSET key field value [field value ...], HSETNX key field value
+fn hset_op(otype: OpType, args: &[Vec<u8>]) -> Result<Operation, String> {
+    if args.len() % 2 != 1 {
+        return Err("wrong number of arguments for 'hset' command".to_string());
+    }
+    let mut req = HashSetOp::new();
+    req.set_key(args[0].to_vec());
+    for pair in args[1..].chunks(2) {
+        let mut fv = FieldValue::new();
+        fv.set_field(pair[0].to_vec());
+        fv.set_value(pair[1].to_vec());
+        req.pairs.push(fv);
+    }
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_hset(req);
+    Ok(op)
+}
+
+fn hincr_op(otype: OpType, args: &[Vec<u8>]) -> Result<Operation, String> {
+    let mut req = HashIncrOp::new();
+    req.set_key(args[0].to_vec());
+    req.set_field(args[1].to_vec());
+    match otype {
+        OpType::HASH_INCRBYFLOAT => req.n_float = float_arg(&args[2])?,
+        _ => req.n = int_arg(&args[2])?,
+    }
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_hincr(req);
+    Ok(op)
+}
+
+// ZADD key [NX|XX] [GT|LT] [CH] [INCR] score member [score member ...]
+fn zadd_op(args: &[Vec<u8>]) -> Result<Operation, String> {
+    let mut req = ZAddOp::new();
+    req.set_key(args[0].to_vec());
+    let mut pos = 1;
+    while pos < args.len() {
+        match args[pos].to_ascii_uppercase().as_slice() {
+            b"NX" => req.create_excl = true,
+            b"XX" => req.require_exist = true,
+            b"GT" => req.only_greater = true,
+            b"LT" => req.only_less = true,
+            b"CH" => req.count_changed = true,
+            b"INCR" => req.incr = true,
+            _ => break,
+        }
+        pos += 1;
+    }
+    let pairs = &args[pos..];
+    if pairs.is_empty() || pairs.len() % 2 != 0 {
+        return syntax_error();
+    }
+    for pair in pairs.chunks(2) {
+        let mut sm = ScoredMember::new();
+        sm.score = float_arg(&pair[0])?;
+        sm.set_member(pair[1].to_vec());
+        req.members.push(sm);
+    }
+    let mut op = Operation::new();
+    op.otype = OpType::ZSET_ADD;
+    op.set_zadd(req);
+    Ok(op)
+}
+
+// ZRANGE key start stop [WITHSCORES], by index
+fn zrange_op(args: &[Vec<u8>], reverse: bool) -> Result<Operation, String> {
+    let mut req = ZRangeOp::new();
+    req.set_key(args[0].to_vec());
+    req.reverse = reverse;
+    req.start = int_arg(&args[1])?;
+    req.stop = int_arg(&args[2])?;
+    match &args[3..] {
+        [] => {}
+        [opt] if opt.eq_ignore_ascii_case(b"WITHSCORES") => req.with_scores = true,
+        _ => return syntax_error(),
+    }
+    let mut op = Operation::new();
+    op.otype = OpType::ZSET_RANGE;
+    op.set_zrange(req);
+    Ok(op)
+}
+
+fn zpop_op(otype: OpType, args: &[Vec<u8>]) -> Result<Operation, String> {
+    let mut req = ZPopOp::new();
+    req.set_key(args[0].to_vec());
+    req.count = match args.get(1) {
+        None => 1,
+        Some(n) => match int_arg(n)? {
+            n if n >= 0 && n <= u32::MAX as i64 => n as u32,
+            _ => return Err("value is out of range, must be positive".to_string()),
+        },
+    };
+    if args.len() > 2 {
+        return syntax_error();
+    }
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_zpop(req);
+    Ok(op)
+}
+
+// EVAL script numkeys [key ...] [arg ...], and EVALSHA
+fn script_op(otype: OpType, args: &[Vec<u8>]) -> Result<Operation, String> {
+    let script = str::from_utf8(&args[0]).map_err(|_| "invalid script".to_string())?;
+    let numkeys = int_arg(&args[1])?;
+    if numkeys < 0 || numkeys as usize > args.len() - 2 {
+        return Err("Number of keys can't be greater than number of args".to_string());
+    }
+    let (keys, argv) = args[2..].split_at(numkeys as usize);
+
+    let mut req = ScriptOp::new();
+    if otype == OpType::SCRIPT_EVALSHA {
+        req.sha1.push(script.to_ascii_lowercase());
+    } else {
+        req.set_source(script.to_string());
+    }
+    for key in keys.iter() {
+        req.keys.push(key.to_vec());
+    }
+    for arg in argv.iter() {
+        req.args.push(arg.to_vec());
+    }
+    let mut op = Operation::new();
+    op.otype = otype;
+    op.set_script(req);
+    Ok(op)
+}
+
+// AUTH [username] password
+fn auth_op(args: &[Vec<u8>]) -> Result<Operation, String> {
+    let (username, password) = match args {
+        [password] => (&[][..], password),
+        [username, password] => (&username[..], password),
+        _ => return Err("syntax error".to_string()),
+    };
+
+    let mut req = AuthOp::new();
+    req.set_username(String::from_utf8_lossy(username).to_string());
+    req.set_password(password.to_vec());
+    let mut op = Operation::new();
+    op.otype = OpType::ACL_AUTH;
+    op.set_auth(req);
+    Ok(op)
+}
+
+// ACL SETUSER username [rule ...], GETUSER username, DELUSER username,
+// LIST, WHOAMI
+fn acl_op(args: &[Vec<u8>]) -> Result<Operation, String> {
+    let sub = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+    let (otype, n_args) = match sub.as_str() {
+        "SETUSER" => (OpType::ACL_SETUSER, args.len().max(2)),
+        "GETUSER" => (OpType::ACL_GETUSER, 2),
+        "DELUSER" => (OpType::ACL_DELUSER, 2),
+        "LIST" => (OpType::ACL_LIST, 1),
+        "WHOAMI" => (OpType::ACL_WHOAMI, 1),
+        _ => return Err(format!("unknown subcommand '{}'", sub.to_ascii_lowercase())),
+    };
+    if args.len() != n_args {
+        return Err(format!(
+            "wrong number of arguments for 'acl|{}' command",
+            sub.to_ascii_lowercase()
+        ));
+    }
+
+    let mut op = nullary_op(otype);
+    if let Some(username) = args.get(1) {
+        let mut req = AclOp::new();
+        req.set_username(String::from_utf8_lossy(username).to_string());
+        for rule in args[2..].iter() {
+            req.rules.push(String::from_utf8_lossy(rule).to_string());
+        }
+        op.set_acl(req);
+    }
+    Ok(op)
+}
+
+fn nullary_op(otype: OpType) -> Operation {
+    let mut op = Operation::new();
+    op.otype = otype;
+    op
+}
+
+// reply builders:  called with the operation, and its result if
+// successful or failed with one of the command's `absent` codes
+
+fn ok_reply(_op: &Operation, res: &OpResult) -> Value {
+    if res.ok {
+        ok()
+    } else {
+        Value::Nil
+    }
+}
+
+fn bool_reply(_op: &Operation, res: &OpResult) -> Value {
+    Value::Int(res.ok as i64)
+}
+
+fn count_reply(_op: &Operation, res: &OpResult) -> Value {
+    Value::Int(res.get_count().n as i64)
+}
+
+fn count_or_nil_reply(op: &Operation, res: &OpResult) -> Value {
+    if res.ok {
+        count_reply(op, res)
+    } else {
+        Value::Nil
+    }
+}
+
+fn bulk_reply(_op: &Operation, res: &OpResult) -> Value {
+    if res.ok {
+        Value::Bulk(res.get_get().get_value().to_vec())
+    } else {
+        Value::Nil
+    }
+}
+
+fn element_reply(_op: &Operation, res: &OpResult) -> Value {
+    match res.get_list().get_elements().first() {
+        Some(element) => Value::Bulk(element.to_vec()),
+        None => Value::Nil,
+    }
+}
+
+fn elements(res: &OpResult) -> Vec<Value> {
+    res.get_list()
+        .get_elements()
+        .iter()
+        .map(|element| Value::Bulk(element.to_vec()))
+        .collect()
+}
+
+fn array_reply(_op: &Operation, res: &OpResult) -> Value {
+    Value::Array(elements(res))
+}
+
+fn set_reply(_op: &Operation, res: &OpResult) -> Value {
+    Value::Set(elements(res))
+}
+
+fn members_reply(with_scores: bool, res: &OpResult) -> Value {
+    let mut out = Vec::new();
+    for sm in res.get_zset().get_members() {
+        out.push(Value::Bulk(sm.get_member().to_vec()));
+        if with_scores {
+            out.push(Value::Double(sm.score));
+        }
+    }
+    Value::Array(out)
+}
+
+fn num_reply(op: &Operation, res: &OpResult) -> Value {
+    let old = res.get_num().old_value;
+    let n = op.get_num().n;
+    Value::Int(match op.otype {
+        OpType::STR_INCR => old.wrapping_add(1),
+        OpType::STR_INCRBY => old.wrapping_add(n),
+        OpType::STR_DECR => old.wrapping_sub(1),
+        _ => old.wrapping_sub(n),
+    })
+}
+
+fn ttl_reply(res: &OpResult, unit_ms: i64) -> Value {
+    if !res.ok {
+        return Value::Int(-2);
+    }
+    match res.get_ttl().ttl_ms {
+        -1 => Value::Int(-1),
+        ms => Value::Int((ms + unit_ms / 2) / unit_ms),
+    }
+}
+
+fn type_reply(_op: &Operation, res: &OpResult) -> Value {
+    let name = match res.get_typ().typ {
+        _ if !res.ok => "none",
+        AtomType::STRING => "string",
+        AtomType::LIST => "list",
+        AtomType::SET => "set",
+        AtomType::HASH => "hash",
+        AtomType::ZSET => "zset",
+        AtomType::NOTYPE => "none",
+    };
+    Value::Simple(name.to_string())
+}
+
+fn hmget_reply(_op: &Operation, res: &OpResult) -> Value {
+    let values = res
+        .get_hash()
+        .get_pairs()
+        .iter()
+        .map(|fv| match fv.exists {
+            true => Value::Bulk(fv.get_value().to_vec()),
+            false => Value::Nil,
+        })
+        .collect();
+    Value::Array(values)
+}
+
+fn hgetall_reply(_op: &Operation, res: &OpResult) -> Value {
+    let pairs = res
+        .get_hash()
+        .get_pairs()
+        .iter()
+        .map(|fv| {
+            (
+                Value::Bulk(fv.get_field().to_vec()),
+                Value::Bulk(fv.get_value().to_vec()),
+            )
+        })
+        .collect();
+    Value::Map(pairs)
+}
+
+fn zadd_reply(op: &Operation, res: &OpResult) -> Value {
+    if !op.get_zadd().incr {
+        return count_reply(op, res);
+    }
+    match res.get_zset().get_members().first() {
+        Some(sm) => Value::Double(sm.score),
+        None => Value::Nil,
+    }
+}
+
+fn zscore_reply(_op: &Operation, res: &OpResult) -> Value {
+    match res.get_zset().get_members().first() {
+        Some(sm) if res.ok => Value::Double(sm.score),
+        _ => Value::Nil,
+    }
+}
+
+fn time_reply(_op: &Operation, res: &OpResult) -> Value {
+    let t = res.get_srv_time();
+    Value::Array(vec![
+        bulk_str(&t.secs.to_string()),
+        bulk_str(&(t.nanosecs / 1000).to_string()),
+    ])
+}
+
+fn script_value(sv: &ScriptValue) -> Value {
+    match sv.vtype {
+        ScriptValue_Type::NIL => Value::Nil,
+        ScriptValue_Type::BOOL => Value::Bool(sv.b),
+        ScriptValue_Type::INT => Value::Int(sv.i),
+        ScriptValue_Type::FLOAT => Value::Double(sv.f),
+        ScriptValue_Type::STRING => Value::Bulk(sv.s.to_vec()),
+        ScriptValue_Type::ARRAY => Value::Array(sv.elements.iter().map(script_value).collect()),
+        ScriptValue_Type::MAP => Value::Map(
+            sv.names
+                .iter()
+                .zip(sv.elements.iter())
+                .map(|(name, element)| (bulk_str(name), script_value(element)))
+                .collect(),
+        ),
+    }
+}
+
+fn script_reply(_op: &Operation, res: &OpResult) -> Value {
+    script_value(res.get_script().get_value())
+}
+
+fn acl_reply(op: &Operation, res: &OpResult) -> Value {
+    let acl_res = res.get_acl();
+    match op.otype {
+        OpType::ACL_GETUSER => {
+            let user = &acl_res.get_users()[0];
+            let mut flags = vec![bulk_str(if user.enabled { "on" } else { "off" })];
+            if user.nopass {
+                flags.push(bulk_str("nopass"));
+            }
+            let keys = user.key_patterns.iter().map(|p| Value::Bulk(p.to_vec()));
+            let categories = user.categories.iter().map(|c| bulk_str(c));
+            Value::Map(vec![
+                (bulk_str("flags"), Value::Array(flags)),
+                (bulk_str("keys"), Value::Array(keys.collect())),
+                (bulk_str("categories"), Value::Array(categories.collect())),
+            ])
+        }
+        OpType::ACL_LIST => Value::Array(
+            acl_res
+                .get_users()
+                .iter()
+                .map(|u| bulk_str(&format!("user {} {}", u.username, u.rules.join(" "))))
+                .collect(),
+        ),
+        OpType::ACL_WHOAMI => bulk_str(acl_res.get_username()),
+        _ => ok(),
+    }
+}
+
+/// Command translated into an operation.
+struct Command {
+    name: &'static str,
+    arity: i32,                   // including command name; negative:  minimum
+    absent: &'static [ErrorCode], // failures replied to as absent items
+    op: fn(&[Vec<u8>]) -> Result<Operation, String>, // from arguments
+    reply: fn(&Operation, &OpResult) -> Value,
+}
+
+const NOT_FOUND: &[ErrorCode] = &[ErrorCode::NOT_FOUND];
+
+const COMMANDS: &[Command] = &[
+    // keys
+    Command {
+        name: "DEL",
+        arity: -2,
+        absent: &[],
+        op: |args| Ok(key_list_op(OpType::KEYS_DEL, args)),
+        reply: count_reply,
+    },
+    Command {
+        name: "EXISTS",
+        arity: -2,
+        absent: &[],
+        op: |args| Ok(key_list_op(OpType::KEYS_EXIST, args)),
+        reply: count_reply,
+    },
+    Command {
+        name: "EXPIRE",
+        arity: 3,
+        absent: &[],
+        op: |args| expire_op(args, 1000, false),
+        reply: count_reply,
+    },
+    Command {
+        name: "EXPIREAT",
+        arity: 3,
+        absent: &[],
+        op: |args| expire_op(args, 1000, true),
+        reply: count_reply,
+    },
+    Command {
+        name: "PEXPIRE",
+        arity: 3,
+        absent: &[],
+        op: |args| expire_op(args, 1, false),
+        reply: count_reply,
+    },
+    Command {
+        name: "PEXPIREAT",
+        arity: 3,
+        absent: &[],
+        op: |args| expire_op(args, 1, true),
+        reply: count_reply,
+    },
+    Command {
+        name: "PERSIST",
+        arity: 2,
+        absent: &[],
+        op: |args| Ok(key_op(OpType::KEY_PERSIST, &args[0])),
+        reply: count_reply,
+    },
+    Command {
+        name: "PTTL",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::KEY_TTL, &args[0])),
+        reply: |_op, res| ttl_reply(res, 1),
+    },
+    Command {
+        name: "RENAME",
+        arity: 3,
+        absent: &[],
+        op: |args| Ok(rename_op(args, false)),
+        reply: ok_reply,
+    },
+    Command {
+        name: "RENAMENX",
+        arity: 3,
+        absent: &[ErrorCode::EXISTS],
+        op: |args| Ok(rename_op(args, true)),
+        reply: bool_reply,
+    },
+    Command {
+        name: "TTL",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::KEY_TTL, &args[0])),
+        reply: |_op, res| ttl_reply(res, 1000),
+    },
+    Command {
+        name: "TYPE",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::KEYS_TYPE, &args[0])),
+        reply: type_reply,
+    },
+    // strings
+    Command {
+        name: "APPEND",
+        arity: 3,
+        absent: &[],
+        op: |args| Ok(append_op(args)),
+        reply: |op, res| {
+            let len = res.get_set().get_old_value().len() + op.get_set().get_value().len();
+            Value::Int(len as i64)
+        },
+    },
+    Command {
+        name: "DECR",
+        arity: 2,
+        absent: &[],
+        op: |args| Ok(num_op(OpType::STR_DECR, &args[0], 1)),
+        reply: num_reply,
+    },
+    Command {
+        name: "DECRBY",
+        arity: 3,
+        absent: &[],
+        op: |args| Ok(num_op(OpType::STR_DECRBY, &args[0], int_arg(&args[1])?)),
+        reply: num_reply,
+    },
+    Command {
+        name: "GET",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(get_op(OpType::STR_GET, &args[0])),
+        reply: bulk_reply,
+    },
+    Command {
+        name: "GETRANGE",
+        arity: 4,
+        absent: NOT_FOUND,
+        op: getrange_op,
+        reply: |_op, res| Value::Bulk(res.get_get().get_value().to_vec()),
+    },
+    Command {
+        name: "INCR",
+        arity: 2,
+        absent: &[],
+        op: |args| Ok(num_op(OpType::STR_INCR, &args[0], 1)),
+        reply: num_reply,
+    },
+    Command {
+        name: "INCRBY",
+        arity: 3,
+        absent: &[],
+        op: |args| Ok(num_op(OpType::STR_INCRBY, &args[0], int_arg(&args[1])?)),
+        reply: num_reply,
+    },
+    Command {
+        name: "PSETEX",
+        arity: 4,
+        absent: &[],
+        op: |args| setex_op(args, 1),
+        reply: ok_reply,
+    },
+    Command {
+        name: "SET",
+        arity: -3,
+        absent: &[ErrorCode::EXISTS, ErrorCode::NOT_FOUND],
+        op: set_op,
+        reply: ok_reply,
+    },
+    Command {
+        name: "SETEX",
+        arity: 4,
+        absent: &[],
+        op: |args| setex_op(args, 1000),
+        reply: ok_reply,
+    },
+    Command {
+        name: "SETNX",
+        arity: 3,
+        absent: &[ErrorCode::EXISTS],
+        op: |args| {
+            let mut op = str_set_op(OpType::STR_SET, &args[0], &args[1]);
+            op.mut_set().create_excl = true;
+            Ok(op)
+        },
+        reply: bool_reply,
+    },
+    Command {
+        name: "STRLEN",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(strlen_op(&args[0])),
+        reply: |_op, res| Value::Int(res.get_get().value_length as i64),
+    },
+    // lists
+    Command {
+        name: "LINDEX",
+        arity: 3,
+        absent: NOT_FOUND,
+        op: lindex_op,
+        reply: element_reply,
+    },
+    Command {
+        name: "LLEN",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::LIST_INFO, &args[0])),
+        reply: |_op, res| Value::Int(res.get_list_info().length as i64),
+    },
+    Command {
+        name: "LPOP",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(pop_op(&args[0], true)),
+        reply: element_reply,
+    },
+    Command {
+        name: "LPUSH",
+        arity: -3,
+        absent: &[],
+        op: |args| Ok(push_op(args, true, false)),
+        reply: count_reply,
+    },
+    Command {
+        name: "LPUSHX",
+        arity: -3,
+        absent: NOT_FOUND,
+        op: |args| Ok(push_op(args, true, true)),
+        reply: count_reply,
+    },
+    Command {
+        name: "RPOP",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(pop_op(&args[0], false)),
+        reply: element_reply,
+    },
+    Command {
+        name: "RPUSH",
+        arity: -3,
+        absent: &[],
+        op: |args| Ok(push_op(args, false, false)),
+        reply: count_reply,
+    },
+    Command {
+        name: "RPUSHX",
+        arity: -3,
+        absent: NOT_FOUND,
+        op: |args| Ok(push_op(args, false, true)),
+        reply: count_reply,
+    },
+    // sets
+    Command {
+        name: "SADD",
+        arity: -3,
+        absent: &[],
+        op: |args| Ok(keyed_list_op(OpType::SET_ADD, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    Command {
+        name: "SCARD",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::SET_INFO, &args[0])),
+        reply: |_op, res| Value::Int(res.get_set_info().length as i64),
+    },
+    Command {
+        name: "SDIFF",
+        arity: -2,
+        absent: &[],
+        op: |args| Ok(cmp_stor_op(OpType::SET_DIFF, b"", args)),
+        reply: set_reply,
+    },
+    Command {
+        name: "SDIFFSTORE",
+        arity: -3,
+        absent: &[],
+        op: |args| Ok(cmp_stor_op(OpType::SET_DIFF, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    Command {
+        name: "SINTER",
+        arity: -2,
+        absent: &[],
+        op: |args| Ok(cmp_stor_op(OpType::SET_INTERSECT, b"", args)),
+        reply: set_reply,
+    },
+    Command {
+        name: "SINTERSTORE",
+        arity: -3,
+        absent: &[],
+        op: |args| Ok(cmp_stor_op(OpType::SET_INTERSECT, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    Command {
+        name: "SISMEMBER",
+        arity: 3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::SET_ISMEMBER, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    Command {
+        name: "SMEMBERS",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::SET_MEMBERS, &args[0])),
+        reply: set_reply,
+    },
+    Command {
+        name: "SMOVE",
+        arity: 4,
+        absent: NOT_FOUND,
+        op: |args| Ok(smove_op(args)),
+        reply: count_reply,
+    },
+    Command {
+        name: "SREM",
+        arity: -3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::SET_DEL, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    Command {
+        name: "SUNION",
+        arity: -2,
+        absent: &[],
+        op: |args| Ok(cmp_stor_op(OpType::SET_UNION, b"", args)),
+        reply: set_reply,
+    },
+    Command {
+        name: "SUNIONSTORE",
+        arity: -3,
+        absent: &[],
+        op: |args| Ok(cmp_stor_op(OpType::SET_UNION, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    // hashes
+    Command {
+        name: "HDEL",
+        arity: -3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::HASH_DEL, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    Command {
+        name: "HEXISTS",
+        arity: 3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::HASH_EXISTS, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    Command {
+        name: "HGET",
+        arity: 3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::HASH_GET, &args[0], &args[1..])),
+        reply: bulk_reply,
+    },
+    Command {
+        name: "HGETALL",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::HASH_GETALL, &args[0])),
+        reply: hgetall_reply,
+    },
+    Command {
+        name: "HINCRBY",
+        arity: 4,
+        absent: &[],
+        op: |args| hincr_op(OpType::HASH_INCRBY, args),
+        reply: |op, res| Value::Int(res.get_num().old_value.wrapping_add(op.get_hincr().n)),
+    },
+    Command {
+        name: "HINCRBYFLOAT",
+        arity: 4,
+        absent: &[],
+        op: |args| hincr_op(OpType::HASH_INCRBYFLOAT, args),
+        reply: |op, res| {
+            let n = res.get_num().old_float + op.get_hincr().n_float;
+            bulk_str(&format_double(n))
+        },
+    },
+    Command {
+        name: "HKEYS",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::HASH_KEYS, &args[0])),
+        reply: array_reply,
+    },
+    Command {
+        name: "HLEN",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::HASH_LEN, &args[0])),
+        reply: count_reply,
+    },
+    Command {
+        name: "HMGET",
+        arity: -3,
+        absent: &[],
+        op: |args| Ok(keyed_list_op(OpType::HASH_MGET, &args[0], &args[1..])),
+        reply: hmget_reply,
+    },
+    Command {
+        name: "HSET",
+        arity: -4,
+        absent: &[],
+        op: |args| hset_op(OpType::HASH_SET, args),
+        reply: count_reply,
+    },
+    Command {
+        name: "HSETNX",
+        arity: 4,
+        absent: &[],
+        op: |args| hset_op(OpType::HASH_SETNX, args),
+        reply: count_reply,
+    },
+    Command {
+        name: "HSTRLEN",
+        arity: 3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::HASH_STRLEN, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    Command {
+        name: "HVALS",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::HASH_VALS, &args[0])),
+        reply: array_reply,
+    },
+    // sorted sets
+    Command {
+        name: "ZADD",
+        arity: -4,
+        absent: &[],
+        op: zadd_op,
+        reply: zadd_reply,
+    },
+    Command {
+        name: "ZCARD",
+        arity: 2,
+        absent: NOT_FOUND,
+        op: |args| Ok(key_op(OpType::ZSET_CARD, &args[0])),
+        reply: count_reply,
+    },
+    Command {
+        name: "ZPOPMAX",
+        arity: -2,
+        absent: NOT_FOUND,
+        op: |args| zpop_op(OpType::ZSET_POPMAX, args),
+        reply: |_op, res| members_reply(true, res),
+    },
+    Command {
+        name: "ZPOPMIN",
+        arity: -2,
+        absent: NOT_FOUND,
+        op: |args| zpop_op(OpType::ZSET_POPMIN, args),
+        reply: |_op, res| members_reply(true, res),
+    },
+    Command {
+        name: "ZRANGE",
+        arity: -4,
+        absent: NOT_FOUND,
+        op: |args| zrange_op(args, false),
+        reply: |op, res| members_reply(op.get_zrange().with_scores, res),
+    },
+    Command {
+        name: "ZRANK",
+        arity: 3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::ZSET_RANK, &args[0], &args[1..])),
+        reply: count_or_nil_reply,
+    },
+    Command {
+        name: "ZREM",
+        arity: -3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::ZSET_REM, &args[0], &args[1..])),
+        reply: count_reply,
+    },
+    Command {
+        name: "ZREVRANGE",
+        arity: -4,
+        absent: NOT_FOUND,
+        op: |args| zrange_op(args, true),
+        reply: |op, res| members_reply(op.get_zrange().with_scores, res),
+    },
+    Command {
+        name: "ZREVRANK",
+        arity: 3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::ZSET_REVRANK, &args[0], &args[1..])),
+        reply: count_or_nil_reply,
+    },
+    Command {
+        name: "ZSCORE",
+        arity: 3,
+        absent: NOT_FOUND,
+        op: |args| Ok(keyed_list_op(OpType::ZSET_SCORE, &args[0], &args[1..])),
+        reply: zscore_reply,
+    },
+    // server
+    Command {
+        name: "BGSAVE",
+        arity: 1,
+        absent: &[],
+        op: |_args| Ok(nullary_op(OpType::SRV_BGSAVE)),
+        reply: |_op, _res| Value::Simple("Background saving started".to_string()),
+    },
+    Command {
+        name: "DBSIZE",
+        arity: 1,
+        absent: &[],
+        op: |_args| Ok(nullary_op(OpType::SRV_DBSIZE)),
+        reply: count_reply,
+    },
+    Command {
+        name: "FLUSHALL",
+        arity: 1,
+        absent: &[],
+        op: |_args| Ok(nullary_op(OpType::SRV_FLUSHALL)),
+        reply: ok_reply,
+    },
+    Command {
+        name: "FLUSHDB",
+        arity: 1,
+        absent: &[],
+        op: |_args| Ok(nullary_op(OpType::SRV_FLUSHDB)),
+        reply: ok_reply,
+    },
+    Command {
+        name: "LASTSAVE",
+        arity: 1,
+        absent: &[],
+        op: |_args| Ok(nullary_op(OpType::SRV_LASTSAVE)),
+        reply: |_op, res| Value::Int(res.get_srv_time().secs as i64),
+    },
+    Command {
+        name: "SAVE",
+        arity: 1,
+        absent: &[],
+        op: |_args| Ok(nullary_op(OpType::SRV_SAVE)),
+        reply: ok_reply,
+    },
+    Command {
+        name: "TIME",
+        arity: 1,
+        absent: &[],
+        op: |_args| Ok(nullary_op(OpType::SRV_TIME)),
+        reply: time_reply,
+    },
+    // scripting
+    Command {
+        name: "EVAL",
+        arity: -3,
+        absent: &[],
+        op: |args| script_op(OpType::SCRIPT_EVAL, args),
+        reply: script_reply,
+    },
+    Command {
+        name: "EVALSHA",
+        arity: -3,
+        absent: &[],
+        op: |args| script_op(OpType::SCRIPT_EVALSHA, args),
+        reply: script_reply,
+    },
+    // authentication & access control
+    Command {
+        name: "AUTH",
+        arity: -2,
+        absent: &[],
+        op: auth_op,
+        reply: ok_reply,
+    },
+    Command {
+        name: "ACL",
+        arity: -2,
+        absent: &[],
+        op: acl_op,
+        reply: acl_reply,
+    },
+];
+
+fn lookup(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+// error reply for failed operation
+fn error_reply(res: &OpResult) -> Value {
+    let prefix = match res.err_code {
+        ErrorCode::WRONGTYPE => {
+            return Value::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            );
+        }
+        ErrorCode::NOT_FOUND => return err("no such key"),
+        ErrorCode::NOSCRIPT => "NOSCRIPT",
+        ErrorCode::NOAUTH => "NOAUTH",
+        ErrorCode::NOPERM => "NOPERM",
+        ErrorCode::BUSY => "BUSY",
+        ErrorCode::OOM => "OOM",
+        _ => "ERR",
+    };
+    Value::Error(format!("{} {}", prefix, res.err_message))
+}
+
+/// Connection state, and execution of its commands.
+pub struct Session {
+    proto: u32,           // protocol version: 2 or 3
+    db: Vec<u8>,          // selected database; empty if default
+    user: Option<String>, // authenticated user, if any
+    quit: bool,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session {
+            proto: 2,
+            db: Vec::new(),
+            user: None,
+            quit: false,
+        }
+    }
+
+    // HELLO [protover [AUTH username password] [SETNAME clientname]];
+    // the connection changes only if every option succeeds
+    fn hello(&mut self, svc: &MemdsService, args: &[Vec<u8>]) -> Value {
+        let mut proto = self.proto;
+        if let Some(ver) = args.first() {
+            match int_arg(ver) {
+                Ok(2) => proto = 2,
+                Ok(3) => proto = 3,
+                _ => return Value::Error("NOPROTO unsupported protocol version".to_string()),
+            }
+        }
+
+        let mut user = None;
+        let mut opts = args.get(1..).unwrap_or(&[]);
+        while !opts.is_empty() {
+            opts = match opts {
+                [opt, username, password, rest @ ..] if opt.eq_ignore_ascii_case(b"AUTH") => {
+                    let mut creds = AuthOp::new();
+                    creds.set_username(String::from_utf8_lossy(username).to_string());
+                    creds.set_password(password.to_vec());
+                    match svc.acl.read().unwrap().authenticate(&creds) {
+                        Ok(name) => user = Some(name),
+                        Err(_) => {
+                            return Value::Error(
+                                "WRONGPASS invalid username-password pair or user is disabled."
+                                    .to_string(),
+                            )
+                        }
+                    }
+                    rest
+                }
+                // client names are accepted, not retained
+                [opt, _name, rest @ ..] if opt.eq_ignore_ascii_case(b"SETNAME") => rest,
+                _ => return err("syntax error"),
+            };
+        }
+
+        self.proto = proto;
+        if user.is_some() {
+            self.user = user;
+        }
+
+        let pairs = vec![
+            (bulk_str("server"), bulk_str("memds")),
+            (bulk_str("version"), bulk_str(env!("CARGO_PKG_VERSION"))),
+            (bulk_str("proto"), Value::Int(self.proto as i64)),
+            (bulk_str("mode"), bulk_str("standalone")),
+            (bulk_str("role"), bulk_str("master")),
+            (bulk_str("modules"), Value::Array(Vec::new())),
+        ];
+        Value::Map(pairs)
+    }
+
+    // connection commands, not translated into operations
+    fn exec_conn(&mut self, svc: &MemdsService, name: &str, args: &[Vec<u8>]) -> Option<Value> {
+        let value = match (name.to_ascii_uppercase().as_str(), args) {
+            ("PING", []) => Value::Simple("PONG".to_string()),
+            ("PING", [msg]) | ("ECHO", [msg]) => Value::Bulk(msg.to_vec()),
+            ("HELLO", _) => self.hello(svc, args),
+            ("SELECT", [db]) => {
+                self.db = db.to_vec();
+                ok()
+            }
+            ("QUIT", _) => {
+                self.quit = true;
+                ok()
+            }
+            // client names and library info are accepted, not retained
+            ("CLIENT", [sub, ..])
+                if sub.eq_ignore_ascii_case(b"SETNAME") || sub.eq_ignore_ascii_case(b"SETINFO") =>
+            {
+                ok()
+            }
+            ("PING", _) | ("ECHO", _) | ("SELECT", _) => {
+                return Some(err(&format!(
+                    "wrong number of arguments for '{}' command",
+                    name.to_ascii_lowercase()
+                )))
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Execute command, returning its reply.
+    pub fn exec(&mut self, svc: &MemdsService, args: &[Vec<u8>]) -> Value {
+        let name = String::from_utf8_lossy(&args[0]).to_string();
+        if let Some(value) = self.exec_conn(svc, &name, &args[1..]) {
+            return value;
+        }
+
+        let cmd = match lookup(&name) {
+            None => return err(&format!("unknown command '{}'", name)),
+            Some(cmd) => cmd,
+        };
+        let n = args.len() as i32;
+        if (cmd.arity > 0 && n != cmd.arity) || (cmd.arity < 0 && n < -cmd.arity) {
+            return err(&format!(
+                "wrong number of arguments for '{}' command",
+                cmd.name.to_ascii_lowercase()
+            ));
+        }
+
+        let op = match (cmd.op)(&args[1..]) {
+            Ok(op) => op,
+            Err(msg) => return err(&msg),
+        };
+
+        let mut req = RequestMsg::new();
+        req.set_db(self.db.clone());
+        req.ops.push(op);
+        let resp = svc.exec_request(&req, &mut self.user);
+
+        let res = match resp.get_results().first() {
+            None => return err(&resp.err_message),
+            Some(res) => res,
+        };
+        if !res.ok && !cmd.absent.contains(&res.err_code) {
+            return error_reply(res);
+        }
+        (cmd.reply)(&req.ops[0], res)
+    }
+}
+
+// serve one client connection, until closed or QUIT
+fn serve(svc: MemdsService, mut stream: TcpStream) -> io::Result<()> {
+    let mut session = Session::new();
+    let mut buf = Vec::new();
+    let mut chunk = [0; 16384];
+    let mut writer = BufWriter::new(stream.try_clone()?);
+
+    loop {
+        let n_read = stream.read(&mut chunk)?;
+        if n_read == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n_read]);
+
+        // execute each complete command; replies to pipelined commands
+        // are written together
+        let mut out = Vec::new();
+        let mut consumed = 0;
+        loop {
+            match parse_command(&buf[consumed..]) {
+                Ok(None) => break,
+                Ok(Some((args, n))) => {
+                    consumed += n;
+                    if !args.is_empty() {
+                        session.exec(&svc, &args).encode(session.proto, &mut out);
+                    }
+                    if session.quit {
+                        break;
+                    }
+                }
+                Err(msg) => {
+                    err(&msg).encode(session.proto, &mut out);
+                    session.quit = true;
+                    break;
+                }
+            }
+        }
+        buf.drain(..consumed);
+
+        writer.write_all(&out)?;
+        writer.flush()?;
+        if session.quit {
+            return Ok(());
+        }
+    }
+}
+
+/// Accept RESP clients on `listener`, each served by its own thread.
+pub fn listen(svc: MemdsService, listener: TcpListener) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let svc = svc.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = serve(svc, stream) {
+                        debug!("resp client: {:?}", e);
+                    }
+                });
+            }
+            Err(e) => error!("resp accept failed: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_command, Session, Value};
+    use crate::acl::Acl;
+    use crate::rpcservice::tests::test_service;
+
+    fn args(s: &str) -> Vec<Vec<u8>> {
+        s.split(' ').map(|a| a.as_bytes().to_vec()).collect()
+    }
+
+    fn bulk(s: &str) -> Value {
+        Value::Bulk(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn parse() {
+        let buf = b"*2\r\n$3\r\nGET\r\n$1\r\na\r\nPING\r\n*1\r\n$4\r\nPI";
+        let (cmd, n) = parse_command(buf).unwrap().unwrap();
+        assert_eq!(cmd, args("GET a"));
+        let (cmd, m) = parse_command(&buf[n..]).unwrap().unwrap();
+        assert_eq!(cmd, args("PING"));
+        assert_eq!(parse_command(&buf[n + m..]).unwrap(), None);
+
+        assert!(parse_command(b"*1\r\n+PING\r\n").is_err());
+
+        // a large count, its arguments yet to arrive
+        assert_eq!(parse_command(b"*1048576\r\n$4\r\nPING\r\n").unwrap(), None);
+    }
+
+    #[test]
+    fn encode() {
+        let value = Value::Map(vec![
+            (bulk("a"), Value::Double(1.5)),
+            (bulk("b"), Value::Nil),
+        ]);
+
+        let mut out = Vec::new();
+        value.encode(2, &mut out);
+        assert_eq!(
+            out,
+            b"*4\r\n$1\r\na\r\n$3\r\n1.5\r\n$1\r\nb\r\n$-1\r\n".to_vec()
+        );
+
+        let mut out = Vec::new();
+        value.encode(3, &mut out);
+        assert_eq!(out, b"%2\r\n$1\r\na\r\n,1.5\r\n$1\r\nb\r\n_\r\n".to_vec());
+    }
+
+    #[test]
+    fn commands() {
+        let svc = test_service();
+        let mut session = Session::new();
+        let mut exec = |s: &str| session.exec(&svc, &args(s));
+
+        assert_eq!(exec("GET a"), Value::Nil);
+        assert_eq!(exec("SET a 10"), Value::Simple("OK".to_string()));
+        assert_eq!(exec("SET a 11 NX"), Value::Nil);
+        assert_eq!(exec("INCRBY a 5"), Value::Int(15));
+        assert_eq!(exec("get a"), bulk("15"));
+        assert_eq!(exec("TYPE a"), Value::Simple("string".to_string()));
+        assert_eq!(exec("TTL a"), Value::Int(-1));
+        assert_eq!(exec("TTL b"), Value::Int(-2));
+
+        assert_eq!(exec("LPUSH l x y"), Value::Int(2));
+        assert_eq!(exec("RPOP l"), bulk("x"));
+        assert_eq!(exec("LLEN nosuch"), Value::Int(0));
+
+        assert_eq!(exec("SADD s m n m"), Value::Int(2));
+        assert_eq!(exec("SISMEMBER s n"), Value::Int(1));
+        assert_eq!(exec("SCARD s"), Value::Int(2));
+
+        assert_eq!(exec("HSET h f v g w"), Value::Int(2));
+        assert_eq!(
+            exec("HMGET h f z"),
+            Value::Array(vec![bulk("v"), Value::Nil])
+        );
+
+        assert_eq!(exec("ZADD z 2 b 1 a"), Value::Int(2));
+        assert_eq!(
+            exec("ZRANGE z 0 -1 WITHSCORES"),
+            Value::Array(vec![
+                bulk("a"),
+                Value::Double(1.0),
+                bulk("b"),
+                Value::Double(2.0)
+            ])
+        );
+
+        match exec("LPUSH a x") {
+            Value::Error(msg) => assert!(msg.starts_with("WRONGTYPE")),
+            v => panic!("unexpected reply {:?}", v),
+        }
+        match exec("SET a") {
+            Value::Error(msg) => assert!(msg.starts_with("ERR wrong number")),
+            v => panic!("unexpected reply {:?}", v),
+        }
+
+        // databases are selected per connection
+        assert_eq!(exec("SELECT 1"), Value::Simple("OK".to_string()));
+        assert_eq!(exec("DBSIZE"), Value::Int(0));
+
+        match exec("HELLO 3") {
+            Value::Map(pairs) => assert!(pairs.contains(&(bulk("proto"), Value::Int(3)))),
+            v => panic!("unexpected reply {:?}", v),
+        }
+
+        // as is the user authenticated
+        assert_eq!(exec("ACL WHOAMI"), bulk("default"));
+        let ok = Value::Simple("OK".to_string());
+        assert_eq!(exec("ACL SETUSER app on >pw ~app:* +@all"), ok);
+        assert_eq!(exec("AUTH app pw"), ok);
+        assert_eq!(exec("ACL WHOAMI"), bulk("app"));
+        assert_eq!(exec("GET app:x"), Value::Nil);
+        match exec("GET a") {
+            Value::Error(msg) => assert!(msg.starts_with("NOPERM")),
+            v => panic!("unexpected reply {:?}", v),
+        }
+    }
+
+    #[test]
+    fn hello_auth() {
+        let svc = test_service();
+        *svc.acl.write().unwrap() =
+            Acl::parse("user default off\nuser app on >pw ~* +@all\n").unwrap();
+        let mut session = Session::new();
+        let mut exec = |s: &str| session.exec(&svc, &args(s));
+
+        match exec("GET a") {
+            Value::Error(msg) => assert!(msg.starts_with("NOAUTH")),
+            v => panic!("unexpected reply {:?}", v),
+        }
+
+        // failed authentication, or unknown option, leaves connection as is
+        match exec("HELLO 3 AUTH app wrong") {
+            Value::Error(msg) => assert!(msg.starts_with("WRONGPASS")),
+            v => panic!("unexpected reply {:?}", v),
+        }
+        match exec("HELLO 3 AUTH app pw BOGUS") {
+            Value::Error(msg) => assert!(msg.starts_with("ERR syntax")),
+            v => panic!("unexpected reply {:?}", v),
+        }
+        match exec("GET a") {
+            Value::Error(msg) => assert!(msg.starts_with("NOAUTH")),
+            v => panic!("unexpected reply {:?}", v),
+        }
+
+        match exec("HELLO 3 AUTH app pw SETNAME conn") {
+            Value::Map(pairs) => assert!(pairs.contains(&(bulk("proto"), Value::Int(3)))),
+            v => panic!("unexpected reply {:?}", v),
+        }
+        assert_eq!(exec("ACL WHOAMI"), bulk("app"));
+        assert_eq!(exec("GET a"), Value::Nil);
+    }
+}
@@ -1,268 +1,973 @@
-use std::collections::HashMap;
+use std::sync::mpsc::Sender;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
 
 use futures::Future;
 use grpcio::{RpcContext, UnarySink};
 
-use memds_proto::memds_api::{OpResult, OpType, RequestMsg, ResponseMsg};
+use memds_proto::memds_api::{
+    ErrorCode, OpResult, OpType, Operation, RequestMsg, RequestMsg_BatchMode, ResponseMsg,
+};
 use memds_proto::memds_api_grpc::Memds;
-use memds_proto::util::result_err;
-use memds_proto::Atom;
 
+use crate::acl::Acl;
+use crate::batch;
+use crate::db::{self, Db, DbSet, DEF_DB};
+use crate::error::OpError;
+use crate::journal::Journal;
 use crate::keys;
-use crate::list;
-use crate::server;
-use crate::set;
-use crate::string;
-
-/// The in-memory database shared amongst all clients.
+use crate::registry::{self, Handler, KeyScope, Method, ServerCtx};
+use crate::script::ScriptCache;
+use crate::server::{SaveState, ShutdownReq};
+use crate::shard::{Keyspace, Shards};
+use crate::wal::{self, Wal};
+use crate::wasm::ModuleSet;
+
+/// The in-memory databases shared amongst all clients.
 ///
-/// This database will be shared via `Arc`, so to mutate the internal map we're
-/// going to use a `Mutex` for interior mutability.
+/// These databases will be shared via `Arc`, so to mutate the internal maps
+/// we're going to use locks for interior mutability:  one per keyspace
+/// shard, so that batches touching disjoint shards execute in parallel.
 
 #[derive(Clone)]
 pub struct MemdsService {
-    pub map: Arc<Mutex<HashMap<Vec<u8>, Atom>>>,
+    pub keyspace: Arc<Keyspace>,
+    pub wal: Arc<Mutex<Option<Wal>>>,
+    pub snap: Arc<Mutex<SaveState>>,
+    pub scripts: Arc<Mutex<ScriptCache>>,
+    pub modules: Arc<Mutex<ModuleSet>>,
+    pub acl: Arc<RwLock<Acl>>,
+    pub shutdown_tx: Sender<ShutdownReq>,
 }
 
-impl Memds for MemdsService {
-    fn exec(&mut self, ctx: RpcContext, msg_req: RequestMsg, sink: UnarySink<ResponseMsg>) {
+/// Database key selected for operation:  the operation's own, else the
+/// request's, else the default database.
+pub fn selected_db<'a>(op: &'a Operation, req_db: &'a [u8]) -> &'a [u8] {
+    if !op.db.is_empty() {
+        &op.db
+    } else {
+        request_db(req_db)
+    }
+}
+
+// database key selected by request:  its own, else the default database
+fn request_db(req_db: &[u8]) -> &[u8] {
+    if !req_db.is_empty() {
+        req_db
+    } else {
+        DEF_DB
+    }
+}
+
+/// Execute a single operation against database `db_key`.  Used both to
+/// serve client requests and to replay the operation log.
+pub fn exec_op(dbs: &mut DbSet, db_key: &[u8], op: &Operation) -> OpResult {
+    match registry::validate(op) {
+        Ok(method) => exec_method(method, dbs, db_key, op),
+        Err(err) => err.into(),
+    }
+}
+
+// execute validated operation, other than server-wide methods
+fn exec_method(method: &Method, dbs: &mut DbSet, db_key: &[u8], op: &Operation) -> OpResult {
+    let op_res = match method.handler {
+        Handler::Db(f) => f(dbs.db_mut(db_key), op),
+        Handler::DbRead(f) => read_db(f, dbs, db_key, op),
+        Handler::Dbs(f) => f(dbs, db_key, op),
+        Handler::Keyspace(f) => f(&mut [&mut *dbs], db_key, op),
+        Handler::KeyspaceRead(f) => f(&[&*dbs], db_key, op),
+        Handler::Server(_) => return OpError::invalid_op().into(),
+    };
+
+    dbs.prune(db_key);
+    op_res
+}
+
+// execute read method upon database db_key, empty if missing
+fn read_db(
+    f: fn(&Db, &Operation) -> OpResult,
+    dbs: &DbSet,
+    db_key: &[u8],
+    op: &Operation,
+) -> OpResult {
+    match dbs.get(db_key) {
+        Some(db) => f(db, op),
+        None => f(&dbs.empty_db(), op),
+    }
+}
+
+/// Execute a validated operation, other than server-wide methods, upon
+/// the keyspace shards locked by its batch.  Returns the result and, if
+/// the operation wrote, the operations recording it in the operation log.
+pub fn exec_sharded(
+    shards: &mut Shards,
+    method: &Method,
+    db_key: &[u8],
+    op: &Operation,
+) -> Result<(OpResult, Vec<Operation>), OpError> {
+    let keys = match &method.arg {
+        Some(arg) => (arg.key_values)(op),
+        None => Vec::new(),
+    };
+    let is_write = method.is_write(op);
+
+    match method.handler {
+        Handler::Db(_) | Handler::Dbs(_) => shards.with_items(&keys, |dbs| {
+            let op_res = exec_method(method, dbs, db_key, op);
+            let log_ops = match op_res.ok && is_write {
+                true => wal::loggable_ops(dbs, db_key, op),
+                false => Vec::new(),
+            };
+            (op_res, log_ops)
+        }),
+
+        Handler::DbRead(f) => {
+            shards.read_items(&keys, |dbs| (read_db(f, dbs, db_key, op), Vec::new()))
+        }
+
+        Handler::Keyspace(f) => {
+            let op_res = f(&mut shards.parts_mut()?, db_key, op);
+            let log_ops = match op_res.ok && is_write {
+                true => wal::loggable_ops(&DbSet::new(), db_key, op),
+                false => Vec::new(),
+            };
+            Ok((op_res, log_ops))
+        }
+
+        Handler::KeyspaceRead(f) => Ok((f(&shards.parts(), db_key, op), Vec::new())),
+
+        Handler::Server(_) => Err(OpError::invalid_op()),
+    }
+}
+
+// SHUTDOWN awaits the final snapshot, which locks every shard:  its
+// batch must lock none, so holds it alone
+fn shutdown_alone(msg_req: &RequestMsg) -> bool {
+    let ops = msg_req.get_ops();
+    match ops.iter().any(|op| op.otype == OpType::SRV_SHUTDOWN) {
+        true => ops.len() == 1 && msg_req.get_watch().is_empty(),
+        false => true,
+    }
+}
+
+// keys whose items a batch may touch, watched keys included; None if it
+// may touch any item
+fn batch_keys(msg_req: &RequestMsg) -> Option<Vec<&[u8]>> {
+    let mut keys: Vec<&[u8]> = msg_req.get_watch().iter().map(|w| w.get_key()).collect();
+    for op in msg_req.get_ops() {
+        // invalid ops fail without touching the keyspace
+        let method = match registry::validate(op) {
+            Ok(method) => method,
+            Err(_) => continue,
+        };
+        match (method.scope(op), &method.arg) {
+            (KeyScope::None, _) => {}
+            (KeyScope::Keys, Some(arg)) => keys.extend((arg.key_values)(op)),
+            _ => return None,
+        }
+    }
+
+    Some(keys)
+}
+
+impl MemdsService {
+    /// Execute request, as served to clients of every protocol.  `user`
+    /// is the user the client connection authenticated as, if any;
+    /// updated upon authentication.
+    pub fn exec_request(&self, msg_req: &RequestMsg, user: &mut Option<String>) -> ResponseMsg {
+        if !shutdown_alone(msg_req) {
+            let mut out_resp = ResponseMsg::new();
+            out_resp.ok = true;
+            let err = OpError::new(ErrorCode::INVALID_OP, "Server.Shutdown must be sent alone");
+            batch_failed(&mut out_resp, &err.into());
+            return out_resp;
+        }
+
+        // RESTOREs lacking key name the key within their dump
+        let named = keys::name_restore_keys(msg_req);
+        let msg_req = named.as_ref().unwrap_or(msg_req);
+
+        // lock the keyspace shards holding the batch's keys, else every
+        // shard:  for reading alone, if no op writes.  then, as needed,
+        // script cache, modules, operation log and snapshot state, in
+        // that order.  users are locked briefly.
+        let keys = batch_keys(msg_req);
+        let write = msg_req
+            .get_ops()
+            .iter()
+            .any(|op| match registry::validate(op) {
+                Ok(method) => !method.reads_only(),
+                Err(_) => false,
+            });
+        let mut shards = self.keyspace.lock(keys.as_deref(), write);
+
+        self.exec_batch(msg_req, &mut shards, user)
+    }
+
+    // execute server-wide method, upon the items at the operation's keys;
+    // keyspace-wide methods see every shard instead
+    fn exec_server(
+        &self,
+        f: fn(&mut ServerCtx, &Operation) -> OpResult,
+        method: &Method,
+        shards: &mut Shards,
+        db_key: &[u8],
+        op: &Operation,
+        user: &mut Option<String>,
+    ) -> Result<OpResult, OpError> {
+        let mut exec = |dbs: &mut DbSet, parts: &[&DbSet]| {
+            let mut ctx = ServerCtx {
+                dbs,
+                parts,
+                db_key,
+                wal: &self.wal,
+                snap: &self.snap,
+                scripts: &self.scripts,
+                modules: &self.modules,
+                acl: &self.acl,
+                user: &mut *user,
+                shutdown_tx: &self.shutdown_tx,
+            };
+            f(&mut ctx, op)
+        };
+
+        match (method.scope, &method.arg) {
+            (KeyScope::All, _) => Ok(exec(&mut DbSet::new(), &shards.parts())),
+            (_, Some(arg)) => shards.with_items(&(arg.key_values)(op), |dbs| exec(dbs, &[])),
+            (_, None) => Ok(exec(&mut DbSet::new(), &[])),
+        }
+    }
+
+    // remove expired items at the keys of a writing op, logging their
+    // removal
+    fn expire_keys(
+        &self,
+        shards: &mut Shards,
+        method: &Method,
+        op: &Operation,
+    ) -> Result<(), OpError> {
+        let keys = match &method.arg {
+            Some(arg) if !method.reads_only() && method.scope(op) == KeyScope::Keys => {
+                (arg.key_values)(op)
+            }
+            _ => return Ok(()),
+        };
+        let expired = shards.expire_keys(&keys)?;
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(w) = self.wal.lock().unwrap().as_mut() {
+            if let Err(e) = w.append_batch(&wal::expired_ops(&expired)) {
+                error!("operation log write failed: {:?}", e);
+                return Err(OpError::internal("Internal error - log write"));
+            }
+        }
+
+        Ok(())
+    }
+
+    // execute batch of operations upon the shards it locked
+    fn exec_batch(
+        &self,
+        msg_req: &RequestMsg,
+        shards: &mut Shards,
+        user: &mut Option<String>,
+    ) -> ResponseMsg {
         let mut out_resp = ResponseMsg::new();
         out_resp.ok = true;
 
-        // lock db
-        let mut db = self.map.lock().unwrap();
-
-        // handle requests
+        let mode = msg_req.mode;
         let ops = msg_req.get_ops();
-        for op in ops.iter() {
-            match op.otype {
-                OpType::KEY_DUMP => {
-                    if !op.has_key() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
-                    let op_req = op.get_key();
-                    let op_res = keys::dump(&mut db, op_req);
-                    out_resp.results.push(op_res);
+
+        // credentials of request, if any, authenticate it alone
+        let mut req_user;
+        let user = if msg_req.has_auth() {
+            match self.acl.read().unwrap().authenticate(msg_req.get_auth()) {
+                Ok(name) => {
+                    req_user = Some(name);
+                    &mut req_user
                 }
+                Err(err) => {
+                    batch_failed(&mut out_resp, &err.into());
+                    return out_resp;
+                }
+            }
+        } else {
+            user
+        };
+
+        // atomic batches are validated in full before execution;
+        // server-wide methods cannot be undone
+        let mut journal = None;
+        if mode == RequestMsg_BatchMode::ATOMIC {
+            for op in ops.iter() {
+                let err = match registry::validate(op) {
+                    Err(err) => err,
+                    Ok(method) => match method.handler {
+                        Handler::Server(_) => OpError::new(
+                            ErrorCode::INVALID_OP,
+                            &format!("{} not permitted in atomic batch", method.full_name()),
+                        ),
+                        _ => continue,
+                    },
+                };
+                batch_failed(&mut out_resp, &err.into());
+                return out_resp;
+            }
+            journal = Some(Journal::new());
+        }
 
-                OpType::KEY_RESTORE => {
-                    if !op.has_set() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
+        // watched keys must be permitted the client's user, and unchanged
+        for watch in msg_req.get_watch() {
+            let db_key = match watch.get_db() {
+                b"" => request_db(msg_req.get_db()),
+                db_key => db_key,
+            };
+            let version = self
+                .acl
+                .read()
+                .unwrap()
+                .check_key(user.as_deref(), watch.get_key())
+                .and_then(|_| shards.version(db_key, watch.get_key()));
+            let err = match version {
+                Ok(version) if version == watch.version => continue,
+                Ok(_) => OpError::new(ErrorCode::CONFLICT, "Watched key changed"),
+                Err(err) => err,
+            };
+            batch_failed(&mut out_resp, &err.into());
+            return out_resp;
+        }
 
-                    let op_req = op.get_set();
-                    let op_res = keys::restore(&mut db, op_req);
+        // handle requests
+        let mut log_ops = Vec::new();
+        let mut n_writes = 0;
+        for op in ops.iter() {
+            let db_key = selected_db(op, msg_req.get_db());
+
+            // skip op if its condition fails; else take its arguments
+            // from earlier results
+            let op = match batch::condition_holds(op, &out_resp.results) {
+                Ok(false) => {
+                    let mut op_res = OpResult::new();
+                    op_res.ok = true;
+                    op_res.otype = op.otype;
+                    op_res.skipped = true;
                     out_resp.results.push(op_res);
+                    continue;
                 }
-
-                OpType::KEYS_DEL | OpType::KEYS_EXIST => {
-                    if !op.has_key_list() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
-                    let keys_req = op.get_key_list();
-                    let remove_it = op.otype == OpType::KEYS_DEL;
-                    let op_res = keys::del_exist(&mut db, keys_req, remove_it);
+                Ok(true) => batch::resolve(op, &out_resp.results),
+                Err(err) => Err(err),
+            };
+            // every op must be permitted the client's user
+            let op = op.and_then(|op| registry::validate(&op).map(|m| (op, m)));
+            let op = op.and_then(|(op, m)| {
+                let acl = self.acl.read().unwrap();
+                acl.check(user.as_deref(), m, &op).map(|_| (op, m))
+            });
+            let (op, method) = match op {
+                Ok(v) => v,
+                Err(err) => {
+                    let op_res: OpResult = err.into();
+                    batch_failed(&mut out_resp, &op_res);
                     out_resp.results.push(op_res);
-                }
-
-                OpType::KEYS_RENAME => {
-                    if !op.has_rename() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
+                    if mode == RequestMsg_BatchMode::BEST_EFFORT {
                         continue;
                     }
-                    let rn_req = op.get_rename();
-                    let op_res = keys::rename(&mut db, rn_req);
-                    out_resp.results.push(op_res);
+                    break;
                 }
-
-                OpType::KEYS_TYPE => {
-                    if !op.has_key() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
+            };
+            let op = &op;
+            let is_write = method.is_write(op);
+
+            // op's expired keys are removed, and logged, beforehand;
+            // expiry is then judged as of that time for the op's duration
+            let res = db::with_clock(db::now_ms(), || {
+                self.expire_keys(shards, method, op)?;
+                if let Some(journal) = journal.as_mut() {
+                    if is_write {
+                        journal.record(shards, db_key, method, op)?;
                     }
-                    let key_req = op.get_key();
-                    let op_res = keys::typ(&mut db, key_req);
-                    out_resp.results.push(op_res);
                 }
-
-                OpType::SET_ADD | OpType::SET_DEL | OpType::SET_ISMEMBER => {
-                    if !op.has_keyed_list() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
-                    let op_req = op.get_keyed_list();
-                    let op_res = {
-                        if op.otype == OpType::SET_ISMEMBER {
-                            set::is_member(&mut db, op_req)
-                        } else {
-                            set::add_del(&mut db, op_req, op.otype)
-                        }
-                    };
-                    out_resp.results.push(op_res);
+                match method.handler {
+                    Handler::Server(f) => self
+                        .exec_server(f, method, shards, db_key, op, user)
+                        .map(|op_res| (op_res, Vec::new())),
+                    _ => exec_sharded(shards, method, db_key, op),
                 }
-
-                OpType::SET_DIFF | OpType::SET_UNION | OpType::SET_INTERSECT => {
-                    if !op.has_cmp_stor() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
+            });
+            let (mut op_res, op_log) = match res {
+                Ok(v) => v,
+                Err(err) => (err.into(), Vec::new()),
+            };
+
+            // record successful mutations in operation log; atomic
+            // batches are logged upon completion
+            if op_res.ok && is_write {
+                if journal.is_some() {
+                    log_ops.extend(op_log);
+                    n_writes += 1;
+                } else {
+                    if let Some(w) = self.wal.lock().unwrap().as_mut() {
+                        if let Err(e) = w.append_batch(&op_log) {
+                            error!("operation log write failed: {:?}", e);
+                            op_res = OpError::internal("Internal error - log write").into();
+                        }
                     }
-                    let op_req = op.get_cmp_stor();
-                    let op_res = match op.otype {
-                        OpType::SET_DIFF => set::diff(&mut db, op_req),
-                        OpType::SET_UNION => set::union(&mut db, op_req),
-                        OpType::SET_INTERSECT => set::intersect(&mut db, op_req),
-                        _ => unreachable!(),
-                    };
-
-                    out_resp.results.push(op_res);
+                    self.snap.lock().unwrap().dirty += 1;
                 }
+            }
 
-                OpType::SET_INFO | OpType::SET_MEMBERS => {
-                    if !op.has_key() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
+            // versions of op's keys, following op
+            if op_res.ok && op.want_version {
+                if let Some(arg) = &method.arg {
+                    let acl = self.acl.read().unwrap();
+                    let versions: Result<Vec<u64>, OpError> = (arg.key_values)(op)
+                        .into_iter()
+                        .map(|key| {
+                            acl.check_key(user.as_deref(), key)?;
+                            shards.version(db_key, key)
+                        })
+                        .collect();
+                    match versions {
+                        Ok(versions) => op_res.versions = versions,
+                        Err(err) => op_res = err.into(),
                     }
-                    let op_req = op.get_key();
-                    let op_res = {
-                        if op.otype == OpType::SET_INFO {
-                            set::info(&mut db, op_req)
-                        } else {
-                            set::members(&mut db, op_req)
-                        }
-                    };
-                    out_resp.results.push(op_res);
                 }
+            }
 
-                OpType::SET_MOVE => {
-                    if !op.has_set_move() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
+            let failed = !op_res.ok;
+            if failed {
+                batch_failed(&mut out_resp, &op_res);
+            }
+            out_resp.results.push(op_res);
+            if failed && mode != RequestMsg_BatchMode::BEST_EFFORT {
+                break;
+            }
+        }
+
+        if let Some(journal) = journal {
+            // log completed batch, as a unit
+            if out_resp.ok && !log_ops.is_empty() {
+                if let Some(w) = self.wal.lock().unwrap().as_mut() {
+                    if let Err(e) = w.append_batch(&log_ops) {
+                        error!("operation log write failed: {:?}", e);
+                        let op_res = OpError::internal("Internal error - log write").into();
+                        batch_failed(&mut out_resp, &op_res);
                     }
-                    let op_req = op.get_set_move();
-                    let op_res = set::mov(&mut db, op_req);
-                    out_resp.results.push(op_res);
                 }
+            }
 
-                OpType::SRV_BGSAVE => {
-                    let op_res = server::bgsave(&mut db);
-                    out_resp.results.push(op_res);
+            if out_resp.ok {
+                self.snap.lock().unwrap().dirty += n_writes;
+            } else {
+                if let Err(err) = journal.rollback(shards) {
+                    error!("batch rollback failed: {}", err);
                 }
+                out_resp.rolled_back = true;
+            }
+        }
 
-                OpType::SRV_DBSIZE => {
-                    let op_res = server::dbsize(&mut db);
-                    out_resp.results.push(op_res);
-                }
+        out_resp
+    }
+}
 
-                OpType::SRV_FLUSHDB | OpType::SRV_FLUSHALL => {
-                    let op_res = server::flush(&mut db, op.otype);
-                    out_resp.results.push(op_res);
-                }
+// record failure of batch, at its first failed operation
+fn batch_failed(resp: &mut ResponseMsg, op_res: &OpResult) {
+    if resp.ok {
+        resp.ok = false;
+        resp.err_code = op_res.err_code;
+        resp.err_message = op_res.err_message.clone();
+    }
+}
 
-                OpType::SRV_TIME => {
-                    let op_res = server::time();
-                    out_resp.results.push(op_res);
-                }
+impl Memds for MemdsService {
+    fn exec(&mut self, ctx: RpcContext, msg_req: RequestMsg, sink: UnarySink<ResponseMsg>) {
+        let out_resp = self.exec_request(&msg_req, &mut None);
 
-                OpType::STR_GET | OpType::STR_GETRANGE => {
-                    if !op.has_get() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
-                    let get_req = op.get_get();
-                    let op_res = string::get(&mut db, get_req, op.otype);
-                    out_resp.results.push(op_res);
-                }
+        let f = sink
+            .success(out_resp)
+            .map_err(|e| error!("exec req failed: {:?}", e));
+        ctx.spawn(f)
+    }
+}
 
-                OpType::STR_SET | OpType::STR_APPEND => {
-                    if !op.has_set() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
+#[cfg(test)]
+pub mod tests {
+    use crate::acl::Acl;
+    use crate::db::{Db, DbSet, DEF_DB};
+    use crate::error::OpError;
+    use crate::keys;
+    use crate::rpcservice::MemdsService;
+    use crate::script::{self, ScriptCache};
+    use crate::server::{SaveState, ShutdownReq};
+    use crate::shard::{self, Keyspace};
+    use crate::wasm::ModuleSet;
+    use memds_proto::memds_api::{
+        AuthOp, ErrorCode, KeyListOp, KeyOp, KeyVersion, KeyedListOp, ListPopOp, NumOp,
+        OpCondition, OpCondition_Test, OpRef, OpRef_Source, OpRef_Target, OpType, Operation,
+        RequestMsg, RequestMsg_BatchMode, ShutdownOp, StrSetOp,
+    };
+    use memds_proto::Atom;
+    use std::path::PathBuf;
+    use std::sync::{mpsc, Arc, Mutex, RwLock};
+
+    pub fn test_service() -> MemdsService {
+        let (shutdown_tx, _shutdown_rx) = mpsc::channel();
+        MemdsService {
+            keyspace: Arc::new(Keyspace::new(DbSet::new(), shard::DEF_SHARDS)),
+            wal: Arc::new(Mutex::new(None)),
+            snap: Arc::new(Mutex::new(SaveState::new(
+                PathBuf::from("unused"),
+                Vec::new(),
+            ))),
+            scripts: Arc::new(Mutex::new(ScriptCache::new(script::DEF_TIME_LIMIT_MS))),
+            modules: Arc::new(Mutex::new(ModuleSet::new())),
+            acl: Arc::new(RwLock::new(Acl::new())),
+            shutdown_tx,
+        }
+    }
 
-                    let set_req = op.get_set();
-                    let op_res = {
-                        if op.otype == OpType::STR_SET {
-                            string::set(&mut db, set_req)
-                        } else {
-                            string::append(&mut db, set_req)
-                        }
-                    };
-                    out_resp.results.push(op_res);
-                }
+    fn incr_op(key: &[u8]) -> Operation {
+        let mut req = NumOp::new();
+        req.set_key(key.to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::STR_INCR;
+        op.set_num(req);
+        op
+    }
 
-                OpType::STR_DECR | OpType::STR_DECRBY | OpType::STR_INCR | OpType::STR_INCRBY => {
-                    if !op.has_num() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
+    // restore, at key, of a dump of a string at dump_key
+    fn restore_op(key: &[u8], dump_key: &[u8]) -> Operation {
+        let mut db = Db::new();
+        db.insert(dump_key.to_vec(), Atom::String(b"x".to_vec()));
+        let mut key_req = KeyOp::new();
+        key_req.set_key(dump_key.to_vec());
+        let res = keys::dump(&db, &key_req);
+
+        let mut req = StrSetOp::new();
+        req.set_key(key.to_vec());
+        req.set_value(res.get_get().get_value().to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::KEY_RESTORE;
+        op.set_set(req);
+        op
+    }
 
-                    let num_req = op.get_num();
-                    let op_res = string::incrdecr(&mut db, op.otype, num_req);
-                    out_resp.results.push(op_res);
-                }
+    // service holding dbs, in shards
+    fn service_with(dbs: DbSet) -> MemdsService {
+        let mut svc = test_service();
+        svc.keyspace = Arc::new(Keyspace::new(dbs, 8));
+        svc
+    }
 
-                OpType::LIST_PUSH => {
-                    if !op.has_lpush() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
+    // item at key, in the default database
+    fn get(svc: &MemdsService, key: &[u8]) -> Option<Atom> {
+        let keys = [key];
+        let shards = svc.keyspace.lock(Some(&keys), false);
+        let db = shards.shard(key).unwrap().get(DEF_DB)?;
+        db.peek(key).cloned()
+    }
 
-                    let lpush_req = op.get_lpush();
-                    let op_res = list::push(&mut db, lpush_req);
-                    out_resp.results.push(op_res);
-                }
+    // item count of the default database
+    fn db_len(svc: &MemdsService) -> usize {
+        let shards = svc.keyspace.read_all();
+        shards
+            .iter()
+            .filter_map(|dbs| dbs.get(DEF_DB))
+            .map(|db| db.len())
+            .sum()
+    }
 
-                OpType::LIST_POP => {
-                    if !op.has_lpop() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
+    // incr a, incr b (fails: not an integer), incr c
+    fn exec_test_batch(mode: RequestMsg_BatchMode) -> (MemdsService, RequestMsg) {
+        let mut req = RequestMsg::new();
+        req.mode = mode;
+        req.ops.push(incr_op(b"a"));
+        req.ops.push(incr_op(b"b"));
+        req.ops.push(incr_op(b"c"));
+
+        let mut dbs = DbSet::new();
+        dbs.db_mut(DEF_DB)
+            .insert(b"b".to_vec(), Atom::String(b"x".to_vec()));
+        (service_with(dbs), req)
+    }
 
-                    let lpop_req = op.get_lpop();
-                    let op_res = list::pop(&mut db, lpop_req);
-                    out_resp.results.push(op_res);
-                }
+    fn dirty(svc: &MemdsService) -> u64 {
+        svc.snap.lock().unwrap().dirty
+    }
 
-                OpType::LIST_INFO => {
-                    if !op.has_key() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
+    #[test]
+    fn batch_modes() {
+        // best effort: every op executed
+        let (svc, req) = exec_test_batch(RequestMsg_BatchMode::BEST_EFFORT);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, false);
+        assert_eq!(resp.err_code, ErrorCode::WRONGTYPE);
+        assert_eq!(resp.results.len(), 3);
+        assert_eq!(db_len(&svc), 3);
+        assert_eq!(dirty(&svc), 2);
+
+        // stop on error: later ops not executed
+        let (svc, req) = exec_test_batch(RequestMsg_BatchMode::STOP_ON_ERROR);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, false);
+        assert_eq!(resp.results.len(), 2);
+        assert!(get(&svc, b"a").is_some());
+        assert!(get(&svc, b"c").is_none());
+
+        // atomic: earlier ops undone
+        let (svc, req) = exec_test_batch(RequestMsg_BatchMode::ATOMIC);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, false);
+        assert_eq!(resp.rolled_back, true);
+        assert_eq!(resp.results.len(), 2);
+        assert_eq!(db_len(&svc), 1);
+        assert_eq!(dirty(&svc), 0);
+
+        // atomic success
+        let (svc, mut req) = exec_test_batch(RequestMsg_BatchMode::ATOMIC);
+        req.ops.remove(1);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, true);
+        assert_eq!(resp.rolled_back, false);
+        assert_eq!(db_len(&svc), 3);
+        assert_eq!(dirty(&svc), 2);
+
+        // server-wide methods refused in atomic batches
+        let (svc, mut req) = exec_test_batch(RequestMsg_BatchMode::ATOMIC);
+        let mut op = Operation::new();
+        op.otype = OpType::SRV_BGSAVE;
+        req.ops.push(op);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.err_code, ErrorCode::INVALID_OP);
+        assert_eq!(resp.results.len(), 0);
+        assert_eq!(db_len(&svc), 1);
+    }
 
-                    let key_req = op.get_key();
-                    let op_res = list::info(&mut db, key_req);
-                    out_resp.results.push(op_res);
-                }
+    #[test]
+    fn shutdown() {
+        let (shutdown_tx, shutdown_rx) = mpsc::channel::<ShutdownReq>();
+        let mut svc = test_service();
+        svc.shutdown_tx = shutdown_tx;
+        let mut op = Operation::new();
+        op.otype = OpType::SRV_SHUTDOWN;
+        op.set_shutdown(ShutdownOp::new());
+
+        // refused alongside other ops:  not sent to the main thread
+        let mut req = RequestMsg::new();
+        req.ops.push(incr_op(b"a"));
+        req.ops.push(op.clone());
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.err_code, ErrorCode::INVALID_OP);
+        assert!(get(&svc, b"a").is_none());
+        assert!(shutdown_rx.try_recv().is_err());
+
+        // alone, awaits the main thread's outcome
+        let main = std::thread::spawn(move || {
+            let sreq = shutdown_rx.recv().unwrap();
+            let err = OpError::internal("Save failed; not shutting down");
+            sreq.reply.unwrap().send(err.into()).unwrap();
+        });
+        let mut req = RequestMsg::new();
+        req.ops.push(op);
+        let resp = svc.exec_request(&req, &mut None);
+        main.join().unwrap();
+        assert_eq!(resp.ok, false);
+        assert_eq!(
+            resp.results[0].err_message,
+            "Save failed; not shutting down"
+        );
+    }
 
-                OpType::LIST_INDEX => {
-                    if !op.has_lindex() {
-                        out_resp.results.push(result_err(-400, "Invalid op"));
-                        continue;
-                    }
+    #[test]
+    fn watch() {
+        let svc = test_service();
+
+        // incr, returning new version
+        let mut req = RequestMsg::new();
+        let mut op = incr_op(b"n");
+        op.want_version = true;
+        req.ops.push(op);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, true);
+        let version = resp.results[0].versions[0];
+        let shards = svc.keyspace.lock(None, false);
+        assert_eq!(version, shards.version(DEF_DB, b"n").unwrap());
+        drop(shards);
+
+        // check-and-set succeeds at that version, then fails
+        let mut watch = KeyVersion::new();
+        watch.set_key(b"n".to_vec());
+        watch.version = version;
+        req.watch.push(watch);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, true);
+
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, false);
+        assert_eq!(resp.err_code, ErrorCode::CONFLICT);
+        assert_eq!(resp.results.len(), 0);
+        match get(&svc, b"n") {
+            Some(Atom::String(s)) => assert_eq!(s, b"2"),
+            _ => panic!("not a string"),
+        }
 
-                    let lindex_req = op.get_lindex();
-                    let op_res = list::index(&mut db, lindex_req);
-                    out_resp.results.push(op_res);
-                }
+        // watch upon an absent key fails once it is created then deleted
+        let shards = svc.keyspace.lock(None, false);
+        let absent = shards.version(DEF_DB, b"m").unwrap();
+        drop(shards);
+        let mut req = RequestMsg::new();
+        req.ops.push(incr_op(b"m"));
+        let mut del_req = KeyListOp::new();
+        del_req.keys.push(b"m".to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::KEYS_DEL;
+        op.set_key_list(del_req);
+        req.ops.push(op);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, true);
+
+        let mut req = RequestMsg::new();
+        req.ops.push(incr_op(b"m"));
+        let mut watch = KeyVersion::new();
+        watch.set_key(b"m".to_vec());
+        watch.version = absent;
+        req.watch.push(watch);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.err_code, ErrorCode::CONFLICT);
+        assert!(get(&svc, b"m").is_none());
+    }
 
-                _ => {
-                    let mut res = OpResult::new();
-                    res.ok = false;
-                    res.err_code = -400;
-                    res.err_message = String::from("Invalid op");
-                    out_resp.results.push(res);
-                }
-            }
+    #[test]
+    fn result_refs() {
+        let mut dbs = DbSet::new();
+        dbs.db_mut(DEF_DB)
+            .insert(b"a".to_vec(), Atom::List(vec![b"x".to_vec()]));
+        let svc = service_with(dbs);
+
+        // pop from list a, add popped value to set b
+        let mut req = RequestMsg::new();
+        let mut pop_req = ListPopOp::new();
+        pop_req.set_key(b"a".to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::LIST_POP;
+        op.set_lpop(pop_req);
+        req.ops.push(op);
+
+        let mut add_req = KeyedListOp::new();
+        add_req.set_key(b"b".to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::SET_ADD;
+        op.set_keyed_list(add_req);
+        let mut r = OpRef::new();
+        r.op_index = 0;
+        r.source = OpRef_Source::RES_ELEMENTS;
+        r.target = OpRef_Target::ARG_ELEMENTS;
+        op.refs.push(r);
+        let mut cond = OpCondition::new();
+        cond.op_index = 0;
+        cond.test = OpCondition_Test::COUNT_NONZERO;
+        op.set_cond(cond);
+        req.ops.push(op);
+
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, true);
+        assert_eq!(resp.results[1].skipped, false);
+        match get(&svc, b"b") {
+            Some(Atom::Set(s)) => assert!(s.contains(b"x".as_slice())),
+            _ => panic!("not a set"),
         }
 
-        let f = sink
-            .success(out_resp)
-            .map_err(|e| error!("exec req failed: {:?}", e));
-        ctx.spawn(f)
+        // list now empty: add skipped
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, true);
+        assert_eq!(resp.results.len(), 2);
+        assert_eq!(resp.results[1].skipped, true);
+    }
+
+    #[test]
+    fn restore_unnamed() {
+        let svc = test_service();
+
+        // restores the key within the dump, locked and undone as any
+        let mut req = RequestMsg::new();
+        req.mode = RequestMsg_BatchMode::ATOMIC;
+        req.ops.push(restore_op(b"", b"a"));
+        req.ops.push(restore_op(b"b", b"a"));
+        req.ops.push(incr_op(b"b"));
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.rolled_back, true);
+        assert!(get(&svc, b"a").is_none());
+
+        req.ops.pop();
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, true);
+        match get(&svc, b"a") {
+            Some(Atom::String(s)) => assert_eq!(s, b"x"),
+            _ => panic!("not a string"),
+        }
+        assert!(get(&svc, b"b").is_some());
+    }
+
+    #[test]
+    fn acl_enforced() {
+        let svc = test_service();
+        *svc.acl.write().unwrap() =
+            Acl::parse("user default off\nuser app on >pw ~app:* +@read +@write\n").unwrap();
+
+        let mut req = RequestMsg::new();
+        req.ops.push(incr_op(b"app:n"));
+
+        // unauthenticated
+        let mut user = None;
+        let resp = svc.exec_request(&req, &mut user);
+        assert_eq!(resp.err_code, ErrorCode::NOAUTH);
+
+        // credentials of request authenticate it alone
+        let mut creds = AuthOp::new();
+        creds.set_username("app".to_string());
+        creds.set_password(b"pw".to_vec());
+        let mut auth_req = req.clone();
+        auth_req.set_auth(creds.clone());
+        assert!(svc.exec_request(&auth_req, &mut user).ok);
+        assert_eq!(user, None);
+
+        // ...while AUTH authenticates the connection
+        let mut op = Operation::new();
+        op.otype = OpType::ACL_AUTH;
+        op.set_auth(creds);
+        let mut auth_req = RequestMsg::new();
+        auth_req.ops.push(op);
+        assert!(svc.exec_request(&auth_req, &mut user).ok);
+        assert_eq!(user.as_deref(), Some("app"));
+        assert!(svc.exec_request(&req, &mut user).ok);
+
+        // keys outside user's patterns, and categories not granted
+        let mut req = RequestMsg::new();
+        req.ops.push(incr_op(b"other"));
+        let resp = svc.exec_request(&req, &mut user);
+        assert_eq!(resp.err_code, ErrorCode::NOPERM);
+        let mut op = Operation::new();
+        op.otype = OpType::SRV_FLUSHALL;
+        let mut req = RequestMsg::new();
+        req.ops.push(op);
+        let resp = svc.exec_request(&req, &mut user);
+        assert_eq!(resp.err_code, ErrorCode::NOPERM);
+
+        // watched keys too, whatever their version
+        let mut req = RequestMsg::new();
+        req.ops.push(incr_op(b"app:n"));
+        let mut watch = KeyVersion::new();
+        watch.set_key(b"other".to_vec());
+        req.watch.push(watch);
+        let resp = svc.exec_request(&req, &mut user);
+        assert_eq!(resp.err_code, ErrorCode::NOPERM);
+
+        // restore without key, of a dump of another key
+        let mut req = RequestMsg::new();
+        req.ops.push(restore_op(b"", b"other"));
+        let resp = svc.exec_request(&req, &mut user);
+        assert_eq!(resp.err_code, ErrorCode::NOPERM);
+        assert!(get(&svc, b"other").is_none());
+    }
+
+    #[test]
+    fn sharded() {
+        let mut svc = test_service();
+        let mut dbs = DbSet::new();
+        dbs.db_mut(DEF_DB)
+            .insert(b"b".to_vec(), Atom::String(b"x".to_vec()));
+        svc.keyspace = Arc::new(Keyspace::new(dbs, 8));
+
+        // atomic batch spanning shards, undone in every shard
+        let mut req = RequestMsg::new();
+        req.mode = RequestMsg_BatchMode::ATOMIC;
+        for i in 0..20 {
+            req.ops.push(incr_op(format!("n{}", i).as_bytes()));
+        }
+        req.ops.push(incr_op(b"b"));
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.rolled_back, true);
+        req.ops.pop();
+        assert!(svc.exec_request(&req, &mut None).ok);
+        assert_eq!(dirty(&svc), 20);
+
+        // items spread amongst shards; keyspace-wide ops see every one
+        let n_used = svc
+            .keyspace
+            .read_all()
+            .iter()
+            .filter(|s| s.len() > 0)
+            .count();
+        assert!(n_used > 1);
+        let mut op = Operation::new();
+        op.otype = OpType::SRV_DBSIZE;
+        let mut req = RequestMsg::new();
+        req.ops.push(op);
+        let mut op = Operation::new();
+        op.otype = OpType::KEYS_SCAN;
+        op.mut_scan().count = 4;
+        req.ops.push(op);
+        let mut n_scanned = 0;
+        loop {
+            let resp = svc.exec_request(&req, &mut None);
+            assert_eq!(resp.results[0].get_count().n, 21);
+            let scan_res = resp.results[1].get_scan();
+            n_scanned += scan_res.keys.len();
+            if scan_res.cursor == 0 {
+                break;
+            }
+            req.ops[1].mut_scan().cursor = scan_res.cursor;
+        }
+        assert_eq!(n_scanned, 21);
+
+        // reads alone share the shards with other readers
+        let readers = svc.keyspace.read_all();
+        let mut req = RequestMsg::new();
+        let mut op = Operation::new();
+        op.otype = OpType::KEYS_EXIST;
+        op.mut_key_list().keys.push(b"n1".to_vec());
+        op.mut_key_list().keys.push(b"n2".to_vec());
+        req.ops.push(op);
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.results[0].get_count().n, 2);
+        drop(readers);
+
+        // keyspace-wide writes span every shard
+        let mut op = Operation::new();
+        op.otype = OpType::SRV_FLUSHDB;
+        let mut req = RequestMsg::new();
+        req.ops.push(op);
+        assert!(svc.exec_request(&req, &mut None).ok);
+        assert_eq!(db_len(&svc), 0);
+
+        // key taken from earlier result, in another shard
+        let mut req = RequestMsg::new();
+        let mut pop_req = ListPopOp::new();
+        pop_req.set_key(b"l".to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::LIST_POP;
+        op.set_lpop(pop_req);
+        req.ops.push(op);
+        let mut op = incr_op(b"");
+        let mut r = OpRef::new();
+        r.op_index = 0;
+        r.source = OpRef_Source::RES_ELEMENTS;
+        r.target = OpRef_Target::ARG_KEY;
+        op.refs.push(r);
+        req.ops.push(op);
+        let mut dbs = DbSet::new();
+        dbs.db_mut(DEF_DB)
+            .insert(b"l".to_vec(), Atom::List(vec![b"n7".to_vec()]));
+        svc.keyspace = Arc::new(Keyspace::new(dbs, 8));
+        let resp = svc.exec_request(&req, &mut None);
+        assert_eq!(resp.ok, true);
+        let shards = svc.keyspace.read_all();
+        let i = svc.keyspace.shard_of(b"n7");
+        match shards[i].get(DEF_DB).unwrap().peek(b"n7") {
+            Some(Atom::String(s)) => assert_eq!(s, b"1"),
+            _ => panic!("not a string"),
+        }
     }
 }
@@ -0,0 +1,677 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use protobuf::descriptor::{
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FieldDescriptorProto_Label,
+    FieldDescriptorProto_Type,
+};
+use protobuf::reflect::{ProtobufValue, ReflectFieldRef, ReflectValueRef};
+use protobuf::{CodedOutputStream, Message};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+
+use memds_proto::memds_api::{
+    self, ErrorCode, OpResult, OpType, Operation, ScriptOp, ScriptRes, ScriptValue,
+    ScriptValue_Type,
+};
+
+use crate::acl::User;
+use crate::db::DbSet;
+use crate::error::OpError;
+use crate::registry::{self, Access, Handler, KeyScope, Method, ServerCtx, ARG_SCRIPT, DANGEROUS};
+use crate::rpcservice;
+use crate::util;
+use crate::wal;
+
+pub const DEF_TIME_LIMIT_MS: u64 = 5000;
+
+/// Scripts loaded into the server, by SHA1 digest of their source.
+pub struct ScriptCache {
+    scripts: HashMap<String, String>,
+    time_limit: Duration,
+}
+
+impl ScriptCache {
+    pub fn new(time_limit_ms: u64) -> ScriptCache {
+        ScriptCache {
+            scripts: HashMap::new(),
+            time_limit: Duration::from_millis(time_limit_ms),
+        }
+    }
+
+    /// Cache script, returning its digest.
+    pub fn insert(&mut self, source: &str) -> String {
+        let sha1 = digest(source);
+        self.scripts.insert(sha1.clone(), source.to_string());
+        sha1
+    }
+}
+
+// SHA1 digest of script source, as hex string
+fn digest(source: &str) -> String {
+    util::sha1_hex(source.as_bytes())
+}
+
+// split fully qualified type name (".memds.Msg.Nested") into the message
+// type enclosing it, if any, and its own name
+fn type_scope(type_name: &str) -> Option<(Option<&'static DescriptorProto>, &str)> {
+    let file = memds_api::file_descriptor_proto();
+    let path = type_name
+        .strip_prefix('.')?
+        .strip_prefix(file.get_package())?
+        .strip_prefix('.')?;
+    let mut parts: Vec<&str> = path.split('.').collect();
+    let name = parts.pop()?;
+
+    let mut scope: Option<&'static DescriptorProto> = None;
+    for part in parts {
+        let types = match scope {
+            None => file.get_message_type(),
+            Some(msg) => msg.get_nested_type(),
+        };
+        scope = Some(types.iter().find(|m| m.get_name() == part)?);
+    }
+
+    Some((scope, name))
+}
+
+fn find_message(type_name: &str) -> Option<&'static DescriptorProto> {
+    let (scope, name) = type_scope(type_name)?;
+    let types = match scope {
+        None => memds_api::file_descriptor_proto().get_message_type(),
+        Some(msg) => msg.get_nested_type(),
+    };
+    types.iter().find(|m| m.get_name() == name)
+}
+
+fn find_enum(type_name: &str) -> Option<&'static EnumDescriptorProto> {
+    let (scope, name) = type_scope(type_name)?;
+    let types = match scope {
+        None => memds_api::file_descriptor_proto().get_enum_type(),
+        Some(msg) => msg.get_enum_type(),
+    };
+    types.iter().find(|e| e.get_name() == name)
+}
+
+// script value as bytes:  strings, blobs, or numbers in decimal
+fn dynamic_bytes(value: &Dynamic) -> Option<Vec<u8>> {
+    if value.is_string() {
+        Some(value.clone().into_string().ok()?.into_bytes())
+    } else if value.is_blob() {
+        value.clone().into_blob().ok()
+    } else if value.is_int() || value.is_float() {
+        Some(value.to_string().into_bytes())
+    } else {
+        None
+    }
+}
+
+fn bytes_dynamic(bytes: &[u8]) -> Dynamic {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.into(),
+        Err(_) => Dynamic::from_blob(bytes.to_vec()),
+    }
+}
+
+// enum value:  by number, or by name
+fn enum_number(field: &FieldDescriptorProto, value: &Dynamic) -> Option<i32> {
+    if let Ok(n) = value.as_int() {
+        return Some(n as i32);
+    }
+
+    let name = value.clone().into_string().ok()?;
+    let enum_desc = find_enum(field.get_type_name())?;
+    let value_desc = enum_desc
+        .get_value()
+        .iter()
+        .find(|v| v.get_name() == name)?;
+    Some(value_desc.get_number())
+}
+
+fn encode_field(
+    os: &mut CodedOutputStream,
+    field: &FieldDescriptorProto,
+    value: &Dynamic,
+) -> Result<(), String> {
+    let number = field.get_number() as u32;
+    let invalid = || format!("Invalid {} for {}", value.type_name(), field.get_name());
+    let int = || value.as_int().map_err(|_| invalid());
+    let float = || {
+        value
+            .as_float()
+            .or_else(|_| value.as_int().map(|n| n as f64))
+            .map_err(|_| invalid())
+    };
+
+    let res = match field.get_field_type() {
+        FieldDescriptorProto_Type::TYPE_BYTES | FieldDescriptorProto_Type::TYPE_STRING => {
+            os.write_bytes(number, &dynamic_bytes(value).ok_or_else(invalid)?)
+        }
+        FieldDescriptorProto_Type::TYPE_BOOL => {
+            os.write_bool(number, value.as_bool().map_err(|_| invalid())?)
+        }
+        FieldDescriptorProto_Type::TYPE_INT32
+        | FieldDescriptorProto_Type::TYPE_INT64
+        | FieldDescriptorProto_Type::TYPE_UINT32
+        | FieldDescriptorProto_Type::TYPE_UINT64 => os.write_int64(number, int()?),
+        FieldDescriptorProto_Type::TYPE_SINT32 => os.write_sint32(number, int()? as i32),
+        FieldDescriptorProto_Type::TYPE_SINT64 => os.write_sint64(number, int()?),
+        FieldDescriptorProto_Type::TYPE_DOUBLE => os.write_double(number, float()?),
+        FieldDescriptorProto_Type::TYPE_FLOAT => os.write_float(number, float()? as f32),
+        FieldDescriptorProto_Type::TYPE_ENUM => {
+            os.write_enum(number, enum_number(field, value).ok_or_else(invalid)?)
+        }
+        FieldDescriptorProto_Type::TYPE_MESSAGE => {
+            let desc = find_message(field.get_type_name()).ok_or_else(invalid)?;
+            let map = value.clone().try_cast::<Map>().ok_or_else(invalid)?;
+            os.write_bytes(number, &encode_message(desc, &map)?)
+        }
+        _ => return Err(invalid()),
+    };
+
+    res.map_err(|e| e.to_string())
+}
+
+// encode script map as protobuf message, fields named as in the .proto
+fn encode_message(desc: &DescriptorProto, map: &Map) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    let mut os = CodedOutputStream::vec(&mut buf);
+    for (name, value) in map.iter() {
+        let field = match desc
+            .get_field()
+            .iter()
+            .find(|f| f.get_name() == name.as_str())
+        {
+            None => return Err(format!("Unknown field {}.{}", desc.get_name(), name)),
+            Some(field) => field,
+        };
+
+        if field.get_label() == FieldDescriptorProto_Label::LABEL_REPEATED {
+            let values = match value.clone().into_array() {
+                Ok(values) => values,
+                Err(_) => vec![value.clone()],
+            };
+            for value in values.iter() {
+                encode_field(&mut os, field, value)?;
+            }
+        } else {
+            encode_field(&mut os, field, value)?;
+        }
+    }
+    os.flush().map_err(|e| e.to_string())?;
+    drop(os);
+
+    Ok(buf)
+}
+
+fn value_dynamic(value: ReflectValueRef) -> Dynamic {
+    match value {
+        ReflectValueRef::U32(n) => (n as i64).into(),
+        ReflectValueRef::U64(n) => (n as i64).into(),
+        ReflectValueRef::I32(n) => (n as i64).into(),
+        ReflectValueRef::I64(n) => n.into(),
+        ReflectValueRef::F32(f) => (f as f64).into(),
+        ReflectValueRef::F64(f) => f.into(),
+        ReflectValueRef::Bool(b) => b.into(),
+        ReflectValueRef::String(s) => s.into(),
+        ReflectValueRef::Bytes(b) => bytes_dynamic(b),
+        ReflectValueRef::Enum(e) => e.name().into(),
+        ReflectValueRef::Message(m) => message_dynamic(m),
+    }
+}
+
+// decode protobuf message as script map, fields named as in the .proto
+fn message_dynamic(m: &dyn Message) -> Dynamic {
+    let mut map = Map::new();
+    for field in m.descriptor().fields() {
+        let value = match field.get_reflect(m) {
+            ReflectFieldRef::Optional(Some(value)) => value_dynamic(value),
+            ReflectFieldRef::Repeated(values) => values
+                .reflect_iter()
+                .map(|v| value_dynamic(ProtobufValue::as_ref(v)))
+                .collect::<Array>()
+                .into(),
+            _ => continue,
+        };
+        map.insert(field.name().into(), value);
+    }
+
+    map.into()
+}
+
+// script return value, as returned to client
+fn script_value(value: Dynamic) -> ScriptValue {
+    let mut sv = ScriptValue::new();
+    if value.is_unit() {
+        sv.vtype = ScriptValue_Type::NIL;
+    } else if let Ok(b) = value.as_bool() {
+        sv.vtype = ScriptValue_Type::BOOL;
+        sv.b = b;
+    } else if let Ok(n) = value.as_int() {
+        sv.vtype = ScriptValue_Type::INT;
+        sv.i = n;
+    } else if let Ok(f) = value.as_float() {
+        sv.vtype = ScriptValue_Type::FLOAT;
+        sv.f = f;
+    } else if value.is_array() {
+        sv.vtype = ScriptValue_Type::ARRAY;
+        for element in value.into_array().unwrap() {
+            sv.elements.push(script_value(element));
+        }
+    } else if value.is_map() {
+        sv.vtype = ScriptValue_Type::MAP;
+        for (name, element) in value.cast::<Map>() {
+            sv.names.push(name.to_string());
+            sv.elements.push(script_value(element));
+        }
+    } else {
+        sv.vtype = ScriptValue_Type::STRING;
+        sv.set_s(dynamic_bytes(&value).unwrap_or_else(|| value.to_string().into_bytes()));
+    }
+
+    sv
+}
+
+// state shared with functions called by script, for its duration
+struct ScriptState {
+    dbs: DbSet,
+    db_key: Vec<u8>,
+    keys: Vec<Vec<u8>>,      // declared keys, the only keys accessible
+    log_ops: Vec<Operation>, // loggable effects of operations called
+    n_writes: u64,
+    user: User, // on whose behalf script runs; its operations are checked
+}
+
+// execute MODULE.METHOD, with arguments as map, on behalf of script
+fn call_method(state: &RefCell<ScriptState>, name: &str, args: Map) -> Result<OpResult, String> {
+    let method = match registry::lookup_name(name) {
+        None => return Err(format!("Unknown method {}", name)),
+        Some(method) => method,
+    };
+    if let Handler::Server(_) = method.handler {
+        return Err(format!(
+            "{} not permitted in script:  scripts may not call server-wide methods",
+            method.full_name()
+        ));
+    }
+
+    let mut op = Operation::new();
+    if let Some(arg) = &method.arg {
+        let mut op_map = Map::new();
+        op_map.insert(arg.name.into(), args.into());
+        let buf = encode_message(Operation::descriptor_static().get_proto(), &op_map)?;
+        op = Operation::parse_from_bytes(&buf).map_err(|e| e.to_string())?;
+    }
+    op.otype = method.otype;
+
+    let mut state = state.borrow_mut();
+    let state = &mut *state;
+
+    // only the items at declared keys are held for the script
+    match (method.scope(&op), &method.arg) {
+        (KeyScope::None, _) => {}
+        (KeyScope::Keys, Some(arg)) => {
+            for key in (arg.key_values)(&op) {
+                if !state.keys.iter().any(|k| k == key) {
+                    let key = String::from_utf8_lossy(key);
+                    return Err(format!(
+                        "Key {} not declared to script:  scripts may access only KEYS",
+                        key
+                    ));
+                }
+            }
+        }
+        _ => {
+            return Err(format!(
+                "{} not permitted in script:  scripts may not call keyspace-wide methods",
+                method.full_name()
+            ))
+        }
+    }
+
+    state.user.check(method, &op).map_err(|e| e.message)?;
+    let op_res = rpcservice::exec_op(&mut state.dbs, &state.db_key, &op);
+    if op_res.ok && method.is_write(&op) {
+        let log_ops = wal::loggable_ops(&state.dbs, &state.db_key, &op);
+        state.log_ops.extend(log_ops);
+        state.n_writes += 1;
+    }
+
+    Ok(op_res)
+}
+
+// compile & run script, until completion or time limit
+fn run(
+    source: &str,
+    req: &ScriptOp,
+    state: &Rc<RefCell<ScriptState>>,
+    time_limit: Duration,
+) -> Result<Dynamic, OpError> {
+    let mut engine = Engine::new();
+    let start = Instant::now();
+    engine.on_progress(move |_| {
+        if start.elapsed() > time_limit {
+            Some(Dynamic::UNIT)
+        } else {
+            None
+        }
+    });
+    engine.on_print(|s| debug!("script: {}", s));
+
+    // exec() fails the script upon operation failure; try_exec() returns
+    // the failed result
+    let exec_state = state.clone();
+    engine.register_fn(
+        "exec",
+        move |name: &str, args: Map| -> Result<Dynamic, Box<EvalAltResult>> {
+            let op_res = call_method(&exec_state, name, args)?;
+            if !op_res.ok {
+                return Err(format!("{:?}: {}", op_res.err_code, op_res.err_message).into());
+            }
+            Ok(message_dynamic(&op_res))
+        },
+    );
+    let try_state = state.clone();
+    engine.register_fn(
+        "try_exec",
+        move |name: &str, args: Map| -> Result<Dynamic, Box<EvalAltResult>> {
+            let op_res = call_method(&try_state, name, args)?;
+            Ok(message_dynamic(&op_res))
+        },
+    );
+
+    let mut scope = Scope::new();
+    let keys: Array = req.keys.iter().map(|k| bytes_dynamic(k)).collect();
+    let args: Array = req.args.iter().map(|a| bytes_dynamic(a)).collect();
+    scope.push("KEYS", keys);
+    scope.push("ARGV", args);
+
+    engine
+        .eval_with_scope::<Dynamic>(&mut scope, source)
+        .map_err(|err| match *err {
+            EvalAltResult::ErrorTerminated(..) => {
+                OpError::new(ErrorCode::TIMEOUT, "Script time limit exceeded")
+            }
+            err => OpError::new(ErrorCode::SCRIPT_ERROR, &format!("Script error: {}", err)),
+        })
+}
+
+// execute script against the selected database.  the script itself is
+// not logged; the operations it called are, as it called them.
+fn exec(ctx: &mut ServerCtx, source: &str, req: &ScriptOp, otype: OpType) -> OpResult {
+    let user = match ctx.acl.read().unwrap().user(ctx.user.as_deref()) {
+        Ok(user) => user.clone(),
+        Err(err) => return err.into(),
+    };
+
+    // operations called by script need the databases for themselves
+    let state = Rc::new(RefCell::new(ScriptState {
+        dbs: mem::replace(ctx.dbs, DbSet::new()),
+        db_key: ctx.db_key.to_vec(),
+        keys: req.keys.to_vec(),
+        log_ops: Vec::new(),
+        n_writes: 0,
+        user,
+    }));
+    let time_limit = ctx.scripts.lock().unwrap().time_limit;
+    let res = run(source, req, &state, time_limit);
+    let state = match Rc::try_unwrap(state) {
+        Ok(state) => state.into_inner(),
+        Err(_) => unreachable!(),
+    };
+    *ctx.dbs = state.dbs;
+
+    // effects persist even if script failed part way
+    let mut wal = ctx.wal.lock().unwrap();
+    ctx.snap.lock().unwrap().dirty += state.n_writes;
+    if let Some(w) = wal.as_mut() {
+        if let Err(e) = w.append_batch(&state.log_ops) {
+            error!("operation log write failed: {:?}", e);
+            return OpError::internal("Internal error - log write").into();
+        }
+    }
+
+    let value = match res {
+        Ok(value) => value,
+        Err(err) => return err.into(),
+    };
+
+    let mut script_res = ScriptRes::new();
+    script_res.set_value(script_value(value));
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = otype;
+    op_res.set_script(script_res);
+
+    op_res
+}
+
+pub fn eval(ctx: &mut ServerCtx, req: &ScriptOp) -> OpResult {
+    ctx.scripts.lock().unwrap().insert(req.get_source());
+    exec(ctx, req.get_source(), req, OpType::SCRIPT_EVAL)
+}
+
+pub fn evalsha(ctx: &mut ServerCtx, req: &ScriptOp) -> OpResult {
+    let source = {
+        let sha1 = match req.sha1.first() {
+            None => return OpError::invalid_args("Script digest required").into(),
+            Some(sha1) => sha1.to_lowercase(),
+        };
+        match ctx.scripts.lock().unwrap().scripts.get(&sha1) {
+            None => {
+                return OpError::new(ErrorCode::NOSCRIPT, "No matching script").into();
+            }
+            Some(source) => source.clone(),
+        }
+    };
+
+    exec(ctx, &source, req, OpType::SCRIPT_EVALSHA)
+}
+
+pub fn load(scripts: &mut ScriptCache, req: &ScriptOp) -> OpResult {
+    // scripts are compiled upon loading, to report syntax errors early
+    if let Err(err) = Engine::new().compile(req.get_source()) {
+        let msg = format!("Script error: {}", err);
+        return OpError::new(ErrorCode::SCRIPT_ERROR, &msg).into();
+    }
+
+    let mut script_res = ScriptRes::new();
+    script_res.set_sha1(scripts.insert(req.get_source()));
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::SCRIPT_LOAD;
+    op_res.set_script(script_res);
+
+    op_res
+}
+
+pub fn exists(scripts: &ScriptCache, req: &ScriptOp) -> OpResult {
+    let mut script_res = ScriptRes::new();
+    for sha1 in req.sha1.iter() {
+        let exists = scripts.scripts.contains_key(&sha1.to_lowercase());
+        script_res.exists.push(exists);
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::SCRIPT_EXISTS;
+    op_res.set_script(script_res);
+
+    op_res
+}
+
+pub fn flush(scripts: &mut ScriptCache) -> OpResult {
+    scripts.scripts.clear();
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::SCRIPT_FLUSH;
+
+    op_res
+}
+
+/// Script module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "Script",
+        name: "Load",
+        otype: OpType::SCRIPT_LOAD,
+        arg: Some(ARG_SCRIPT),
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::Server(|ctx, op| load(&mut ctx.scripts.lock().unwrap(), op.get_script())),
+    },
+    Method {
+        module: "Script",
+        name: "Exists",
+        otype: OpType::SCRIPT_EXISTS,
+        arg: Some(ARG_SCRIPT),
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::Server(|ctx, op| exists(&ctx.scripts.lock().unwrap(), op.get_script())),
+    },
+    Method {
+        module: "Script",
+        name: "Flush",
+        otype: OpType::SCRIPT_FLUSH,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: DANGEROUS,
+        handler: Handler::Server(|ctx, _op| flush(&mut ctx.scripts.lock().unwrap())),
+    },
+    Method {
+        module: "Script",
+        name: "Eval",
+        otype: OpType::SCRIPT_EVAL,
+        arg: Some(ARG_SCRIPT),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::Server(|ctx, op| eval(ctx, op.get_script())),
+    },
+    Method {
+        module: "Script",
+        name: "EvalSha",
+        otype: OpType::SCRIPT_EVALSHA,
+        arg: Some(ARG_SCRIPT),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::Server(|ctx, op| evalsha(ctx, op.get_script())),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::acl::Acl;
+    use crate::db::{DbSet, DEF_DB};
+    use crate::registry::ServerCtx;
+    use crate::script::{self, ScriptCache};
+    use crate::server::SaveState;
+    use crate::wasm::ModuleSet;
+    use memds_proto::memds_api::{ErrorCode, ScriptOp, ScriptValue_Type};
+    use memds_proto::Atom;
+    use std::path::PathBuf;
+    use std::sync::{mpsc, Mutex, RwLock};
+
+    #[test]
+    fn eval() {
+        let mut dbs = DbSet::new();
+        dbs.db_mut(DEF_DB)
+            .insert(b"a".to_vec(), Atom::List(vec![b"x".to_vec()]));
+        let snap = Mutex::new(SaveState::new(PathBuf::from("unused"), Vec::new()));
+        let scripts = Mutex::new(ScriptCache::new(100));
+        let modules = Mutex::new(ModuleSet::new());
+        let acl = RwLock::new(Acl::new());
+        let (shutdown_tx, _shutdown_rx) = mpsc::channel();
+        let mut ctx = ServerCtx {
+            dbs: &mut dbs,
+            parts: &[],
+            db_key: DEF_DB,
+            wal: &Mutex::new(None),
+            snap: &snap,
+            scripts: &scripts,
+            modules: &modules,
+            acl: &acl,
+            user: &mut None,
+            shutdown_tx: &shutdown_tx,
+        };
+
+        // pop from list, add to set, return popped value
+        let mut req = ScriptOp::new();
+        req.set_source(String::from(
+            r#"
+            let v = exec("List.Pop", #{ key: KEYS[0] }).list.elements[0];
+            exec("Set.Add", #{ key: KEYS[1], elements: [v] });
+            [v, ARGV[0]]
+            "#,
+        ));
+        req.keys.push(b"a".to_vec());
+        req.keys.push(b"b".to_vec());
+        req.args.push(b"y".to_vec());
+        let res = script::eval(&mut ctx, &req);
+        assert_eq!(res.ok, true);
+        let value = res.get_script().get_value();
+        assert_eq!(value.vtype, ScriptValue_Type::ARRAY);
+        assert_eq!(value.elements[0].s, b"x");
+        assert_eq!(value.elements[1].s, b"y");
+        assert_eq!(ctx.snap.lock().unwrap().dirty, 2);
+        match ctx.dbs.db_mut(DEF_DB).get(b"b") {
+            Some(Atom::Set(s)) => assert!(s.contains(&b"x".to_vec())),
+            _ => panic!("not a set"),
+        }
+
+        // cached upon eval
+        let sha1 = script::digest(req.get_source());
+        let mut exists_req = ScriptOp::new();
+        exists_req.sha1.push(sha1.clone());
+        exists_req.sha1.push(String::from("0000"));
+        let res = script::exists(&ctx.scripts.lock().unwrap(), &exists_req);
+        assert_eq!(res.get_script().exists, vec![true, false]);
+
+        // failed operation fails script; try_exec returns failure
+        let mut req = ScriptOp::new();
+        req.set_source(String::from(r#"exec("String.Incr", #{ key: "a" })"#));
+        req.keys.push(b"a".to_vec());
+        let res = script::eval(&mut ctx, &req);
+        assert_eq!(res.err_code, ErrorCode::SCRIPT_ERROR);
+        req.set_source(String::from(
+            r#"try_exec("String.Incr", #{ key: "a" }).err_code"#,
+        ));
+        let res = script::eval(&mut ctx, &req);
+        assert_eq!(res.get_script().get_value().s, b"WRONGTYPE");
+
+        // undeclared keys, and keyspace-wide methods, refused
+        req.set_source(String::from(r#"try_exec("String.Get", #{ key: "b" })"#));
+        let res = script::eval(&mut ctx, &req);
+        assert_eq!(res.err_code, ErrorCode::SCRIPT_ERROR);
+        assert!(res.err_message.contains("may access only KEYS"));
+        req.set_source(String::from(r#"try_exec("Server.DBSize", #{})"#));
+        let res = script::eval(&mut ctx, &req);
+        assert_eq!(res.err_code, ErrorCode::SCRIPT_ERROR);
+        assert!(res.err_message.contains("may not call"));
+
+        // time limit
+        req.set_source(String::from("loop {}"));
+        let res = script::eval(&mut ctx, &req);
+        assert_eq!(res.err_code, ErrorCode::TIMEOUT);
+
+        // evalsha after flush
+        script::flush(&mut ctx.scripts.lock().unwrap());
+        let mut req = ScriptOp::new();
+        req.sha1.push(sha1);
+        let res = script::evalsha(&mut ctx, &req);
+        assert_eq!(res.err_code, ErrorCode::NOSCRIPT);
+    }
+}
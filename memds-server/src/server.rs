@@ -1,19 +1,152 @@
 use bytes::BytesMut;
-use nix::unistd::{fork, ForkResult};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
-use std::time::{Duration, SystemTime};
+use nix::sys::signal::{kill, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{fork, ForkResult, Pid};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::time::{Duration, Instant, SystemTime};
 use tokio_util::codec::Encoder;
 
+use crate::db::DbSet;
+use crate::error::OpError;
 use crate::keys;
+use crate::registry::{self, Access, Handler, KeyScope, Method, ADMIN, ARG_COMMAND, DANGEROUS};
+use crate::util;
+use crate::wal::Wal;
 use memds_proto::memds_api::{
-    CountRes, MemdsMessage, MemdsMessage_MsgType, OpResult, OpType, TimeRes,
+    CommandInfo, CommandOp, CommandRes, CountRes, ErrorCode, LogPos, MemdsMessage,
+    MemdsMessage_MsgType, OpResult, OpType, SaveStatusRes, ShutdownOp, ShutdownOp_Mode, TimeRes,
 };
-use memds_proto::util::result_err;
-use memds_proto::{Atom, MemdsCodec};
+use memds_proto::MemdsCodec;
+
+/// A `save <secs> <changes>` rule:  snapshot automatically once `secs`
+/// seconds have elapsed since the last save, if at least `changes`
+/// write operations occurred meanwhile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SaveRule {
+    pub secs: u64,
+    pub changes: u64,
+}
+
+impl SaveRule {
+    /// Parse rule from "<secs> <changes>" string.
+    pub fn parse(s: &str) -> Option<SaveRule> {
+        let mut words = s.split_whitespace();
+        let secs = words.next()?.parse().ok()?;
+        let changes = words.next()?.parse().ok()?;
+        if words.next().is_some() {
+            return None;
+        }
+
+        Some(SaveRule { secs, changes })
+    }
+}
+
+/// Seconds to wait, following a failed save, before an automatic retry.
+pub const SAVE_RETRY_SECS: u64 = 5;
+
+// background save in progress
+struct SaveChild {
+    pid: Pid,
+    start: Instant,
+    dirty: u64, // write operations included in snapshot
+}
+
+/// Snapshot configuration and status, shared amongst all clients.
+pub struct SaveState {
+    pub path: PathBuf,
+    pub rules: Vec<SaveRule>,
+    pub dirty: u64,         // write operations since last save
+    pub last_save: u64,     // time of last successful save, secs since epoch
+    pub last_attempt: u64,  // time of last save attempt, secs since epoch
+    pub last_ok: bool,      // last save succeeded?
+    pub last_duration: u64, // duration of last save, in milliseconds
+    child: Option<SaveChild>,
+}
+
+impl SaveState {
+    pub fn new(path: PathBuf, rules: Vec<SaveRule>) -> SaveState {
+        let now = systime().as_secs();
+        SaveState {
+            path,
+            rules,
+            dirty: 0,
+            last_save: now,
+            last_attempt: now,
+            last_ok: true,
+            last_duration: 0,
+            child: None,
+        }
+    }
 
-const EXPORT_FN: &'static str = "memds-export.dat";
+    pub fn in_progress(&self) -> bool {
+        self.child.is_some()
+    }
+
+    /// Is an automatic snapshot due, according to the save rules?
+    pub fn save_due(&self, now: u64) -> bool {
+        if self.child.is_some() {
+            return false;
+        }
+        if !self.last_ok && now < self.last_attempt + SAVE_RETRY_SECS {
+            return false;
+        }
+
+        let elapsed = now.saturating_sub(self.last_save);
+        self.rules
+            .iter()
+            .any(|rule| self.dirty >= rule.changes && self.dirty > 0 && elapsed >= rule.secs)
+    }
+
+    /// Record outcome of a save, begun at `start`, which included
+    /// `dirty` write operations.
+    pub fn record(&mut self, ok: bool, start: Instant, dirty: u64) {
+        self.last_ok = ok;
+        self.last_duration = start.elapsed().as_millis() as u64;
+        if ok {
+            self.last_save = systime().as_secs();
+            self.dirty = self.dirty.saturating_sub(dirty);
+        }
+    }
+
+    /// Terminate background save, if any, and remove its temporary file.
+    pub fn abort_child(&mut self) {
+        if let Some(child) = self.child.take() {
+            warn!("terminating background save");
+            let _ = kill(child.pid, Signal::SIGKILL);
+            let _ = waitpid(child.pid, None);
+            let _ = fs::remove_file(snapshot_temp_path(&self.path, child.pid.as_raw() as u32));
+        }
+    }
+
+    /// Check for completion of background save; if complete, reap the
+    /// child process and record its outcome.
+    pub fn poll_child(&mut self) {
+        let pid = match &self.child {
+            None => return,
+            Some(child) => child.pid,
+        };
+
+        let status = waitpid(pid, Some(WaitPidFlag::WNOHANG));
+        let ok = match status {
+            Ok(WaitStatus::Exited(_, code)) => code == 0,
+            Ok(WaitStatus::Signaled(..)) | Err(_) => false,
+
+            // still running
+            Ok(_) => return,
+        };
+
+        let child = self.child.take().unwrap();
+        self.record(ok, child.start, child.dirty);
+        if ok {
+            info!("background save complete, {} ms", self.last_duration);
+        } else {
+            error!("background save failed: {:?}", status);
+        }
+    }
+}
 
 fn systime() -> Duration {
     SystemTime::now()
@@ -21,10 +154,14 @@ fn systime() -> Duration {
         .unwrap()
 }
 
-pub fn dbsize(db: &mut HashMap<Vec<u8>, Atom>) -> OpResult {
-    // query db item count
+pub fn dbsize(parts: &[&DbSet], db_key: &[u8]) -> OpResult {
+    // query db item count, summed across parts
     let mut info_res = CountRes::new();
-    info_res.n = db.len() as u64;
+    info_res.n = parts
+        .iter()
+        .filter_map(|dbs| dbs.get(db_key))
+        .map(|db| db.len() as u64)
+        .sum();
 
     // standard operation result assignment & final return
     let mut op_res = OpResult::new();
@@ -36,10 +173,16 @@ pub fn dbsize(db: &mut HashMap<Vec<u8>, Atom>) -> OpResult {
     op_res
 }
 
-pub fn flush(db: &mut HashMap<Vec<u8>, Atom>, otype: OpType) -> OpResult {
-    // clear entire db; we only have 1 db right now,
-    // making flush [one] db operation equivalent to flush-all-dbs.
-    db.clear();
+pub fn flush(parts: &mut [&mut DbSet], db_key: &[u8], otype: OpType) -> OpResult {
+    // clear selected db, in every part
+    for dbs in parts.iter_mut() {
+        if let Some(db) = dbs.get(db_key) {
+            if db.len() > 0 {
+                dbs.db_mut(db_key).clear();
+            }
+        }
+        dbs.prune(db_key);
+    }
 
     // standard operation result assignment & final return
     let mut op_res = OpResult::new();
@@ -50,6 +193,94 @@ pub fn flush(db: &mut HashMap<Vec<u8>, Atom>, otype: OpType) -> OpResult {
     op_res
 }
 
+pub fn flushall(parts: &mut [&mut DbSet]) -> OpResult {
+    // clear all dbs, in every part
+    for dbs in parts.iter_mut() {
+        dbs.clear();
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::SRV_FLUSHALL;
+
+    op_res
+}
+
+// describe registered method
+fn command_desc(method: &Method) -> CommandInfo {
+    let mut info = CommandInfo::new();
+    info.set_name(method.full_name());
+    info.otype = method.otype;
+    if let Some(arg) = &method.arg {
+        info.set_arg(arg.name.to_string());
+        for key in arg.keys.iter() {
+            info.key_fields.push(key.to_string());
+        }
+    }
+
+    let flag = match method.access {
+        Access::Read => "readonly",
+        Access::Write | Access::WriteIf(_) => "write",
+    };
+    info.flags.push(flag.to_string());
+    for name in method.flag_names() {
+        info.flags.push(name.to_string());
+    }
+
+    info
+}
+
+pub fn command() -> OpResult {
+    let mut cmd_res = CommandRes::new();
+    for method in registry::methods() {
+        cmd_res.commands.push(command_desc(method));
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::SRV_COMMAND;
+    op_res.set_command(cmd_res);
+
+    op_res
+}
+
+pub fn command_count() -> OpResult {
+    let mut count_res = CountRes::new();
+    count_res.n = registry::methods().count() as u64;
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::SRV_COMMAND_COUNT;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn command_info(req: &CommandOp) -> OpResult {
+    let mut cmd_res = CommandRes::new();
+    for name in req.names.iter() {
+        match registry::lookup_name(name) {
+            None => {
+                return OpError::new(ErrorCode::NOT_FOUND, &format!("Unknown command {}", name))
+                    .into()
+            }
+            Some(method) => cmd_res.commands.push(command_desc(method)),
+        }
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::SRV_COMMAND_INFO;
+    op_res.set_command(cmd_res);
+
+    op_res
+}
+
 pub fn time() -> OpResult {
     // query system time
     let now = systime();
@@ -68,86 +299,434 @@ pub fn time() -> OpResult {
     op_res
 }
 
-pub fn bgsave(db: &mut HashMap<Vec<u8>, Atom>) -> OpResult {
-    match fork() {
-        Ok(ForkResult::Parent { child: _, .. }) => {
-            // standard operation result assignment & final return
-            let mut op_res = OpResult::new();
+pub fn lastsave(snap: &SaveState) -> OpResult {
+    let mut time_res = TimeRes::new();
+    time_res.secs = snap.last_save;
 
-            op_res.ok = true;
-            op_res.otype = OpType::SRV_BGSAVE;
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
 
-            return op_res;
-        }
-        Ok(ForkResult::Child) => {}
+    op_res.ok = true;
+    op_res.otype = OpType::SRV_LASTSAVE;
+    op_res.set_srv_time(time_res);
 
-        Err(_) => {
-            return result_err(-500, "Internal error - fork");
-        }
-    }
+    op_res
+}
 
-    // child continues...
+pub fn save_status(snap: &SaveState) -> OpResult {
+    let mut status_res = SaveStatusRes::new();
+    status_res.in_progress = snap.in_progress();
+    status_res.last_ok = snap.last_ok;
+    status_res.last_save = snap.last_save;
+    status_res.last_duration_ms = snap.last_duration;
 
-    let f_res = File::create(EXPORT_FN);
-    if f_res.is_err() {
-        println!("Internal error I/O - create");
-        std::process::exit(1);
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::SRV_SAVE_STATUS;
+    op_res.set_save_status(status_res);
+
+    op_res
+}
+
+/// Fork, and rewrite the operation log from the child process.  The
+/// dataset may be in parts, such as keyspace shards.  Refused while a
+/// background save is in progress:  one forked child at a time.
+pub fn bgrewriteaof(parts: &[&DbSet], wal: Option<&mut Wal>, snap: &SaveState) -> OpResult {
+    let wal = match wal {
+        None => return OpError::new(ErrorCode::DISABLED, "Operation log disabled").into(),
+        Some(w) => w,
+    };
+    if wal.rewrite_in_progress() {
+        return OpError::new(ErrorCode::BUSY, "Rewrite in progress").into();
     }
-    let mut f = f_res.unwrap();
-    let mut codec = MemdsCodec::new();
+    if snap.in_progress() {
+        return OpError::new(ErrorCode::BUSY, "Background save in progress").into();
+    }
+    if wal.start_rewrite(parts).is_err() {
+        return OpError::internal("Internal error - fork").into();
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
 
-    for key in db.keys() {
-        // serialize key+value into protobuf message
-        let dbv = keys::export_dbv(db, key).unwrap();
-        let mut msg = MemdsMessage::new();
-        msg.mtype = MemdsMessage_MsgType::DBVAL;
-        msg.set_dbv(dbv);
+    op_res.ok = true;
+    op_res.otype = OpType::SRV_BGREWRITEAOF;
 
-        // encode message into checksummed stream
-        let msg_raw = &mut BytesMut::new();
-        codec.encode(msg, msg_raw).unwrap();
+    op_res
+}
 
-        // write packet to file
-        let res = f.write_all(msg_raw);
-        if res.is_err() {
-            println!("Internal error I/O - write");
-            std::process::exit(1);
+/// Write a snapshot of the dataset, in one or more `parts`, to `path`,
+/// atomically replacing any previous snapshot:  the data is written to a
+/// temporary file, flushed to disk, and renamed into place.  `log_pos`
+/// is the operation log position matching the dataset, if logging is
+/// enabled.
+pub fn write_snapshot(parts: &[&DbSet], path: &Path, log_pos: Option<LogPos>) -> io::Result<()> {
+    let tmp_path = snapshot_temp_path(path, std::process::id());
+    let mut f = File::create(&tmp_path)?;
+    let mut codec = MemdsCodec::new();
+
+    for (db_key, db) in parts.iter().flat_map(|dbs| dbs.iter()) {
+        for key in db.keys() {
+            // serialize key+value into protobuf message; skip expired keys
+            let mut dbv = match keys::export_dbv(db, key) {
+                None => continue,
+                Some(dbv) => dbv,
+            };
+            dbv.set_db(db_key.clone());
+            let mut msg = MemdsMessage::new();
+            msg.mtype = MemdsMessage_MsgType::DBVAL;
+            msg.set_dbv(dbv);
+
+            // encode message into checksummed stream
+            let msg_raw = &mut BytesMut::new();
+            codec.encode(msg, msg_raw).unwrap();
+
+            // write packet to file
+            f.write_all(msg_raw)?;
         }
     }
 
     // stream terminator
     let mut end_msg = MemdsMessage::new();
     end_msg.mtype = MemdsMessage_MsgType::END;
+    if let Some(pos) = log_pos {
+        end_msg.set_log_pos(pos);
+    }
 
     // encode terminator message into checksummed stream
     let end_msg_raw = &mut BytesMut::new();
     codec.encode(end_msg, end_msg_raw).unwrap();
 
     // write terminating packet to file
-    let res = f.write_all(end_msg_raw);
-    if res.is_err() {
-        println!("Internal error I/O - write end");
-        std::process::exit(1);
-    }
+    f.write_all(end_msg_raw)?;
 
     // flush to disk
-    if f.sync_data().is_err() {
-        println!("Internal error I/O - sync");
+    f.sync_all()?;
+
+    // replace previous snapshot
+    fs::rename(&tmp_path, path)?;
+    util::sync_parent_dir(path)
+}
+
+// temporary file, into which a snapshot is written by process `pid`
+fn snapshot_temp_path(path: &Path, pid: u32) -> PathBuf {
+    let mut s = path.as_os_str().to_os_string();
+    s.push(format!(".tmp-{}", pid));
+    PathBuf::from(s)
+}
+
+// operation log position matching db, recorded in snapshot for replay
+// at startup.  log is flushed to disk first.
+fn snapshot_log_pos(wal: Option<&mut Wal>) -> io::Result<Option<LogPos>> {
+    match wal {
+        None => Ok(None),
+        Some(w) => {
+            w.sync()?;
+            Ok(Some(w.position()))
+        }
+    }
+}
+
+/// Write a snapshot of db in the foreground, blocking all clients.
+pub fn save(parts: &[&DbSet], wal: Option<&mut Wal>, snap: &mut SaveState) -> OpResult {
+    if snap.in_progress() {
+        return OpError::new(ErrorCode::BUSY, "Background save already in progress").into();
+    }
+
+    let log_pos = match snapshot_log_pos(wal) {
+        Ok(pos) => pos,
+        Err(e) => {
+            error!("operation log sync failed: {:?}", e);
+            return OpError::internal("Internal error - log sync").into();
+        }
+    };
+
+    let start = Instant::now();
+    snap.last_attempt = systime().as_secs();
+    let res = write_snapshot(parts, &snap.path, log_pos);
+    snap.record(res.is_ok(), start, snap.dirty);
+    if let Err(e) = res {
+        error!("save failed: {:?}", e);
+        return OpError::internal("Internal error - save").into();
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::SRV_SAVE;
+
+    op_res
+}
+
+/// Should shutdown in `mode` write a snapshot?
+pub fn shutdown_saves(mode: ShutdownOp_Mode, snap: &SaveState) -> bool {
+    match mode {
+        ShutdownOp_Mode::DEFAULT => !snap.rules.is_empty(),
+        ShutdownOp_Mode::SAVE => true,
+        ShutdownOp_Mode::NOSAVE => false,
+    }
+}
+
+/// Request to the main thread to shut down the server.
+pub struct ShutdownReq {
+    pub mode: ShutdownOp_Mode,
+    pub reply: Option<Sender<OpResult>>, // SHUTDOWN caller, awaiting outcome
+}
+
+/// Ask the main thread to shut down the server, awaiting the outcome of
+/// its final snapshot, if any.  If the snapshot fails, the server
+/// continues running.
+pub fn shutdown(req: &ShutdownOp, shutdown_tx: &Sender<ShutdownReq>) -> OpResult {
+    let (reply_tx, reply_rx) = mpsc::channel();
+    let sreq = ShutdownReq {
+        mode: req.mode,
+        reply: Some(reply_tx),
+    };
+    if shutdown_tx.send(sreq).is_err() {
+        return OpError::internal("Internal error - shutdown").into();
+    }
+
+    match reply_rx.recv() {
+        Ok(op_res) => op_res,
+        Err(_) => OpError::internal("Internal error - shutdown").into(),
+    }
+}
+
+/// Final snapshot, of the dataset in `parts`, preceding shutdown in
+/// `mode`, if it saves.  Fails if the server should continue running.
+pub fn shutdown_save(
+    parts: &[&DbSet],
+    wal: Option<&mut Wal>,
+    snap: &mut SaveState,
+    mode: ShutdownOp_Mode,
+) -> OpResult {
+    if shutdown_saves(mode, snap) {
+        snap.abort_child();
+        let res = save(parts, wal, snap);
+        if !res.ok {
+            return OpError::internal("Save failed; not shutting down").into();
+        }
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::SRV_SHUTDOWN;
+
+    op_res
+}
+
+/// Fork, and write a snapshot of db from the child process.  Refused
+/// while an operation log rewrite is in progress.
+pub fn bgsave(parts: &[&DbSet], wal: Option<&mut Wal>, snap: &mut SaveState) -> OpResult {
+    if snap.in_progress() {
+        return OpError::new(ErrorCode::BUSY, "Background save already in progress").into();
+    }
+    if wal.as_ref().is_some_and(|w| w.rewrite_in_progress()) {
+        return OpError::new(ErrorCode::BUSY, "Rewrite in progress").into();
+    }
+
+    let log_pos = match snapshot_log_pos(wal) {
+        Ok(pos) => pos,
+        Err(e) => {
+            error!("operation log sync failed: {:?}", e);
+            return OpError::internal("Internal error - log sync").into();
+        }
+    };
+
+    match fork() {
+        Ok(ForkResult::Parent { child, .. }) => {
+            snap.last_attempt = systime().as_secs();
+            snap.child = Some(SaveChild {
+                pid: child,
+                start: Instant::now(),
+                dirty: snap.dirty,
+            });
+
+            // standard operation result assignment & final return
+            let mut op_res = OpResult::new();
+
+            op_res.ok = true;
+            op_res.otype = OpType::SRV_BGSAVE;
+
+            return op_res;
+        }
+        Ok(ForkResult::Child) => {}
+
+        Err(_) => {
+            return OpError::internal("Internal error - fork").into();
+        }
+    }
+
+    // child continues...
+
+    if let Err(e) = write_snapshot(parts, &snap.path, log_pos) {
+        println!("Internal error I/O - snapshot: {:?}", e);
         std::process::exit(1);
     }
 
     std::process::exit(0);
 }
 
+/// Server module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "Server",
+        name: "DBSize",
+        otype: OpType::SRV_DBSIZE,
+        arg: None,
+        scope: KeyScope::All,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::KeyspaceRead(|parts, db_key, _op| dbsize(parts, db_key)),
+    },
+    Method {
+        module: "Server",
+        name: "FlushDB",
+        otype: OpType::SRV_FLUSHDB,
+        arg: None,
+        scope: KeyScope::All,
+        access: Access::Write,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Keyspace(|parts, db_key, op| flush(parts, db_key, op.otype)),
+    },
+    Method {
+        module: "Server",
+        name: "FlushAll",
+        otype: OpType::SRV_FLUSHALL,
+        arg: None,
+        scope: KeyScope::All,
+        access: Access::Write,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Keyspace(|parts, _db_key, _op| flushall(parts)),
+    },
+    Method {
+        module: "Server",
+        name: "Command",
+        otype: OpType::SRV_COMMAND,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|_db, _op| command()),
+    },
+    Method {
+        module: "Server",
+        name: "CommandCount",
+        otype: OpType::SRV_COMMAND_COUNT,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|_db, _op| command_count()),
+    },
+    Method {
+        module: "Server",
+        name: "CommandInfo",
+        otype: OpType::SRV_COMMAND_INFO,
+        arg: Some(ARG_COMMAND),
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|_db, op| command_info(op.get_command())),
+    },
+    Method {
+        module: "Server",
+        name: "Time",
+        otype: OpType::SRV_TIME,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|_db, _op| time()),
+    },
+    Method {
+        module: "Server",
+        name: "BGSave",
+        otype: OpType::SRV_BGSAVE,
+        arg: None,
+        scope: KeyScope::All,
+        access: Access::Read,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Server(|ctx, _op| {
+            let mut wal = ctx.wal.lock().unwrap();
+            bgsave(ctx.parts, wal.as_mut(), &mut ctx.snap.lock().unwrap())
+        }),
+    },
+    Method {
+        module: "Server",
+        name: "BGRewriteAOF",
+        otype: OpType::SRV_BGREWRITEAOF,
+        arg: None,
+        scope: KeyScope::All,
+        access: Access::Read,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Server(|ctx, _op| {
+            let mut wal = ctx.wal.lock().unwrap();
+            bgrewriteaof(ctx.parts, wal.as_mut(), &ctx.snap.lock().unwrap())
+        }),
+    },
+    Method {
+        module: "Server",
+        name: "LastSave",
+        otype: OpType::SRV_LASTSAVE,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::Server(|ctx, _op| lastsave(&ctx.snap.lock().unwrap())),
+    },
+    Method {
+        module: "Server",
+        name: "SaveStatus",
+        otype: OpType::SRV_SAVE_STATUS,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::Server(|ctx, _op| save_status(&ctx.snap.lock().unwrap())),
+    },
+    Method {
+        module: "Server",
+        name: "Save",
+        otype: OpType::SRV_SAVE,
+        arg: None,
+        scope: KeyScope::All,
+        access: Access::Read,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Server(|ctx, _op| {
+            let mut wal = ctx.wal.lock().unwrap();
+            save(ctx.parts, wal.as_mut(), &mut ctx.snap.lock().unwrap())
+        }),
+    },
+    Method {
+        module: "Server",
+        name: "Shutdown",
+        otype: OpType::SRV_SHUTDOWN,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Server(|ctx, op| shutdown(op.get_shutdown(), ctx.shutdown_tx)),
+    },
+];
+
 #[cfg(test)]
 mod tests {
+    use crate::db::{Db, DbSet, DEF_DB};
     use crate::server;
-    use memds_proto::memds_api::OpType;
+    use crate::wal::{FsyncPolicy, Wal};
+    use memds_proto::memds_api::{CommandOp, ErrorCode, OpType, ShutdownOp, ShutdownOp_Mode};
     use memds_proto::Atom;
-    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::mpsc;
 
-    fn get_test_db() -> HashMap<Vec<u8>, Atom> {
-        let mut db: HashMap<Vec<u8>, Atom> = HashMap::new();
+    fn get_test_db() -> Db {
+        let mut db = Db::new();
         db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
         db.insert(b"name".to_vec(), Atom::String(b"Jane Doe".to_vec()));
         db.insert(b"age".to_vec(), Atom::String(b"25".to_vec()));
@@ -157,9 +736,12 @@ mod tests {
 
     #[test]
     fn dbsize() {
-        let mut db = get_test_db();
+        let mut dbs = DbSet::new();
+        *dbs.db_mut(DEF_DB) = get_test_db();
+        let parts = dbs.split(2, |key| key.len() % 2);
+        let parts: Vec<&DbSet> = parts.iter().collect();
 
-        let res = server::dbsize(&mut db);
+        let res = server::dbsize(&parts, DEF_DB);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::SRV_DBSIZE);
@@ -170,25 +752,197 @@ mod tests {
     }
 
     #[test]
-    fn flush() {
-        let mut db = get_test_db();
+    fn command() {
+        let res = server::command();
+        assert_eq!(res.ok, true);
+        let commands = res.get_command().get_commands();
 
-        let res = server::flush(&mut db, OpType::SRV_FLUSHALL);
+        let res = server::command_count();
+        assert_eq!(res.get_count().n, commands.len() as u64);
 
+        let mut req = CommandOp::new();
+        req.names.push(String::from("set.union"));
+        req.names.push(String::from("Server.FlushAll"));
+        let res = server::command_info(&req);
         assert_eq!(res.ok, true);
-        assert_eq!(res.otype, OpType::SRV_FLUSHALL);
+        let commands = res.get_command().get_commands();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].name, "Set.Union");
+        assert_eq!(commands[0].otype, OpType::SET_UNION);
+        assert_eq!(commands[0].arg, "cmp_stor");
+        assert_eq!(commands[0].key_fields.to_vec(), vec!["keys", "store_key"]);
+        assert_eq!(
+            commands[1].flags.to_vec(),
+            vec!["write", "admin", "dangerous"]
+        );
+
+        req.names.push(String::from("Server.Nope"));
+        let res = server::command_info(&req);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
+    }
 
-        let res = server::dbsize(&mut db);
-        let count_res = res.get_count();
-        assert_eq!(count_res.n, 0);
+    #[test]
+    fn bgsave_reap() {
+        let mut dbs = DbSet::new();
+        *dbs.db_mut(DEF_DB) = get_test_db();
+        let mut path = std::env::temp_dir();
+        path.push(format!("memds-test-bgsave-{}.dat", std::process::id()));
+        let mut snap = server::SaveState::new(path.clone(), Vec::new());
+        snap.dirty = 3;
+
+        let res = server::bgsave(&[&dbs], None, &mut snap);
+        assert_eq!(res.ok, true);
+        assert!(snap.in_progress());
+
+        // concurrent save, or rewrite, refused
+        let res = server::bgsave(&[&dbs], None, &mut snap);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::BUSY);
+        let mut wal_path = path.clone();
+        wal_path.set_extension("aof");
+        let mut wal = Wal::create(&wal_path, FsyncPolicy::Never, &dbs).unwrap();
+        let res = server::bgrewriteaof(&[&dbs], Some(&mut wal), &snap);
+        assert_eq!(res.err_code, ErrorCode::BUSY);
+        assert!(!wal.rewrite_in_progress());
+
+        while snap.in_progress() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            snap.poll_child();
+        }
+
+        let res = server::save_status(&snap);
+        assert_eq!(res.ok, true);
+        let status_res = res.get_save_status();
+        assert_eq!(status_res.in_progress, false);
+        assert_eq!(status_res.last_ok, true);
+        assert_eq!(snap.dirty, 0);
+        assert!(path.exists());
+
+        // save refused while rewriting
+        let res = server::bgrewriteaof(&[&dbs], Some(&mut wal), &snap);
+        assert_eq!(res.ok, true);
+        let res = server::bgsave(&[&dbs], Some(&mut wal), &mut snap);
+        assert_eq!(res.err_code, ErrorCode::BUSY);
+        assert!(!snap.in_progress());
+        while wal.rewrite_in_progress() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            wal.poll_rewrite();
+        }
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&wal_path).unwrap();
+    }
 
-        let res = server::flush(&mut db, OpType::SRV_FLUSHDB);
+    #[test]
+    fn save_shutdown() {
+        let mut dbs = DbSet::new();
+        *dbs.db_mut(DEF_DB) = get_test_db();
+        let mut path = std::env::temp_dir();
+        path.push(format!("memds-test-save-{}.dat", std::process::id()));
+        let mut snap = server::SaveState::new(path.clone(), Vec::new());
+        snap.dirty = 3;
+
+        let res = server::save(&[&dbs], None, &mut snap);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.otype, OpType::SRV_SAVE);
+        assert_eq!(snap.dirty, 0);
+        assert_eq!(snap.last_ok, true);
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+
+        // shutdown awaits the main thread's final save
+        let (tx, rx) = mpsc::channel::<server::ShutdownReq>();
+        let main = std::thread::spawn(move || {
+            for req in rx.iter() {
+                let res = server::shutdown_save(&[&dbs], None, &mut snap, req.mode);
+                req.reply.unwrap().send(res).unwrap();
+            }
+            snap
+        });
+
+        // no save rules:  default shutdown does not save
+        let mut req = ShutdownOp::new();
+        req.mode = ShutdownOp_Mode::DEFAULT;
+        let res = server::shutdown(&req, &tx);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.otype, OpType::SRV_SHUTDOWN);
+        assert!(!path.exists());
+
+        req.mode = ShutdownOp_Mode::SAVE;
+        let res = server::shutdown(&req, &tx);
+        assert_eq!(res.ok, true);
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+        drop(tx);
+
+        // failed save:  server continues running
+        let mut snap = main.join().unwrap();
+        snap.path = PathBuf::from("/nonexistent/memds-test.dat");
+        let res = server::shutdown_save(&[], None, &mut snap, ShutdownOp_Mode::SAVE);
+        assert_eq!(res.ok, false);
+        assert_eq!(snap.last_ok, false);
+    }
+
+    #[test]
+    fn save_rules() {
+        assert_eq!(
+            server::SaveRule::parse("900 1"),
+            Some(server::SaveRule {
+                secs: 900,
+                changes: 1
+            })
+        );
+        assert!(server::SaveRule::parse("900").is_none());
+        assert!(server::SaveRule::parse("900 1 2").is_none());
+        assert!(server::SaveRule::parse("x 1").is_none());
+
+        let rules = vec![
+            server::SaveRule::parse("900 1").unwrap(),
+            server::SaveRule::parse("60 100").unwrap(),
+        ];
+        let mut snap = server::SaveState::new(std::path::PathBuf::from("x"), rules);
+        let t = snap.last_save;
+
+        // no changes:  never due
+        assert!(!snap.save_due(t + 1000));
+
+        snap.dirty = 1;
+        assert!(!snap.save_due(t + 60));
+        assert!(snap.save_due(t + 900));
+
+        snap.dirty = 100;
+        assert!(!snap.save_due(t + 59));
+        assert!(snap.save_due(t + 60));
+    }
+
+    #[test]
+    fn flush() {
+        let mut dbs = DbSet::new();
+        *dbs.db_mut(b"0") = get_test_db();
+        *dbs.db_mut(b"1") = get_test_db();
+        let mut parts = dbs.split(2, |key| key.len() % 2);
+        let mut parts: Vec<&mut DbSet> = parts.iter_mut().collect();
+
+        // flushdb clears selected db only, in every part
+        let res = server::flush(&mut parts, b"0", OpType::SRV_FLUSHDB);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::SRV_FLUSHDB);
 
-        let res = server::dbsize(&mut db);
+        let all: Vec<&DbSet> = parts.iter().map(|dbs| &**dbs).collect();
+        let res = server::dbsize(&all, b"0");
         let count_res = res.get_count();
         assert_eq!(count_res.n, 0);
+
+        let res = server::dbsize(&all, b"1");
+        let count_res = res.get_count();
+        assert_eq!(count_res.n, 3);
+
+        let res = server::flushall(&mut parts);
+
+        assert_eq!(res.ok, true);
+        assert_eq!(res.otype, OpType::SRV_FLUSHALL);
+        assert!(parts.iter().all(|dbs| dbs.len() == 0));
     }
 }
@@ -1,13 +1,18 @@
-use std::collections::HashMap;
 use std::collections::HashSet;
 
 use memds_proto::memds_api::{
-    CmpStoreOp, CountRes, KeyOp, KeyedListOp, ListRes, OpResult, OpType, SetInfoRes, SetMoveOp,
+    CmpStoreOp, CountRes, KeyOp, KeyedListOp, ListRes, OpResult, OpType, Operation, SetInfoRes,
+    SetMoveOp,
 };
-use memds_proto::util::result_err;
 use memds_proto::Atom;
 
-pub fn add_del(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyedListOp, otype: OpType) -> OpResult {
+use crate::db::Db;
+use crate::error::OpError;
+use crate::registry::{
+    Access, Handler, KeyScope, Method, ARG_CMP_STOR, ARG_KEY, ARG_KEYED_LIST, ARG_SET_MOVE,
+};
+
+pub fn add_del(db: &mut Db, req: &KeyedListOp, otype: OpType) -> OpResult {
     let do_delete = match otype {
         OpType::SET_DEL => true,
         _ => false,
@@ -20,7 +25,7 @@ pub fn add_del(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyedListOp, otype: OpType
             None => {
                 // if we're deleting, go no further
                 if do_delete {
-                    return result_err(-404, "Not Found");
+                    return OpError::not_found().into();
                 }
 
                 // does not exist; create empty set
@@ -38,7 +43,7 @@ pub fn add_del(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyedListOp, otype: OpType
             Some(atom) => match atom {
                 Atom::Set(st) => st,
                 _ => {
-                    return result_err(-400, "not a list");
+                    return OpError::wrong_type("list").into();
                 }
             },
         }
@@ -72,17 +77,17 @@ pub fn add_del(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyedListOp, otype: OpType
     op_res
 }
 
-pub fn mov(db: &mut HashMap<Vec<u8>, Atom>, req: &SetMoveOp) -> OpResult {
+pub fn mov(db: &mut Db, req: &SetMoveOp) -> OpResult {
     // test sets
     let src_key = req.get_src_key();
     match db.get(src_key) {
         None => {
-            return result_err(-404, "Not Found");
+            return OpError::not_found().into();
         }
         Some(atom) => match atom {
             Atom::Set(_st) => {}
             _ => {
-                return result_err(-400, "not a set");
+                return OpError::wrong_type("set").into();
             }
         },
     }
@@ -94,7 +99,7 @@ pub fn mov(db: &mut HashMap<Vec<u8>, Atom>, req: &SetMoveOp) -> OpResult {
         Some(atom) => match atom {
             Atom::Set(_st) => {}
             _ => {
-                return result_err(-400, "not a set");
+                return OpError::wrong_type("set").into();
             }
         },
     }
@@ -142,18 +147,18 @@ pub fn mov(db: &mut HashMap<Vec<u8>, Atom>, req: &SetMoveOp) -> OpResult {
     op_res
 }
 
-pub fn info(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
+pub fn info(db: &Db, req: &KeyOp) -> OpResult {
     // get set to query
     let st = {
         let key = req.get_key();
-        match db.get(key) {
+        match db.peek(key) {
             None => {
-                return result_err(-404, "Not Found");
+                return OpError::not_found().into();
             }
             Some(atom) => match atom {
                 Atom::Set(st) => st,
                 _ => {
-                    return result_err(-400, "not a set");
+                    return OpError::wrong_type("set").into();
                 }
             },
         }
@@ -173,18 +178,18 @@ pub fn info(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
     op_res
 }
 
-pub fn members(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
+pub fn members(db: &Db, req: &KeyOp) -> OpResult {
     // get set to query
     let st = {
         let key = req.get_key();
-        match db.get(key) {
+        match db.peek(key) {
             None => {
-                return result_err(-404, "Not Found");
+                return OpError::not_found().into();
             }
             Some(atom) => match atom {
                 Atom::Set(st) => st,
                 _ => {
-                    return result_err(-400, "not a set");
+                    return OpError::wrong_type("set").into();
                 }
             },
         }
@@ -207,9 +212,9 @@ pub fn members(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyOp) -> OpResult {
     op_res
 }
 
-pub fn intersect(db: &mut HashMap<Vec<u8>, Atom>, req: &CmpStoreOp) -> OpResult {
+pub fn intersect(db: &mut Db, req: &CmpStoreOp) -> OpResult {
     if req.keys.len() < 1 {
-        return result_err(-400, "at least one key required");
+        return OpError::invalid_args("at least one key required").into();
     }
 
     // iterate through list of provided keys
@@ -284,9 +289,9 @@ pub fn intersect(db: &mut HashMap<Vec<u8>, Atom>, req: &CmpStoreOp) -> OpResult
     op_res
 }
 
-pub fn diff(db: &mut HashMap<Vec<u8>, Atom>, req: &CmpStoreOp) -> OpResult {
+pub fn diff(db: &mut Db, req: &CmpStoreOp) -> OpResult {
     if req.keys.len() < 1 {
-        return result_err(-400, "at least one key required");
+        return OpError::invalid_args("at least one key required").into();
     }
 
     // iterate through list of provided keys
@@ -350,9 +355,9 @@ pub fn diff(db: &mut HashMap<Vec<u8>, Atom>, req: &CmpStoreOp) -> OpResult {
     op_res
 }
 
-pub fn union(db: &mut HashMap<Vec<u8>, Atom>, req: &CmpStoreOp) -> OpResult {
+pub fn union(db: &mut Db, req: &CmpStoreOp) -> OpResult {
     if req.keys.len() < 1 {
-        return result_err(-400, "at least one key required");
+        return OpError::invalid_args("at least one key required").into();
     }
 
     // iterate through list of provided keys
@@ -398,18 +403,18 @@ pub fn union(db: &mut HashMap<Vec<u8>, Atom>, req: &CmpStoreOp) -> OpResult {
     op_res
 }
 
-pub fn is_member(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyedListOp) -> OpResult {
+pub fn is_member(db: &Db, req: &KeyedListOp) -> OpResult {
     // get set to query
     let st = {
         let key = req.get_key();
-        match db.get(key) {
+        match db.peek(key) {
             None => {
-                return result_err(-404, "Not Found");
+                return OpError::not_found().into();
             }
             Some(atom) => match atom {
                 Atom::Set(st) => st,
                 _ => {
-                    return result_err(-400, "not a set");
+                    return OpError::wrong_type("set").into();
                 }
             },
         }
@@ -437,16 +442,115 @@ pub fn is_member(db: &mut HashMap<Vec<u8>, Atom>, req: &KeyedListOp) -> OpResult
     op_res
 }
 
+// set comparisons mutate only if storing their result
+fn is_store(op: &Operation) -> bool {
+    !op.get_cmp_stor().store_key.is_empty()
+}
+
+/// Set module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "Set",
+        name: "Add",
+        otype: OpType::SET_ADD,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| add_del(db, op.get_keyed_list(), op.otype)),
+    },
+    Method {
+        module: "Set",
+        name: "Remove",
+        otype: OpType::SET_DEL,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| add_del(db, op.get_keyed_list(), op.otype)),
+    },
+    Method {
+        module: "Set",
+        name: "IsMember",
+        otype: OpType::SET_ISMEMBER,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| is_member(db, op.get_keyed_list())),
+    },
+    Method {
+        module: "Set",
+        name: "Info",
+        otype: OpType::SET_INFO,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| info(db, op.get_key())),
+    },
+    Method {
+        module: "Set",
+        name: "Members",
+        otype: OpType::SET_MEMBERS,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| members(db, op.get_key())),
+    },
+    Method {
+        module: "Set",
+        name: "Diff",
+        otype: OpType::SET_DIFF,
+        arg: Some(ARG_CMP_STOR),
+        scope: KeyScope::Keys,
+        access: Access::WriteIf(is_store),
+        flags: 0,
+        handler: Handler::Db(|db, op| diff(db, op.get_cmp_stor())),
+    },
+    Method {
+        module: "Set",
+        name: "Union",
+        otype: OpType::SET_UNION,
+        arg: Some(ARG_CMP_STOR),
+        scope: KeyScope::Keys,
+        access: Access::WriteIf(is_store),
+        flags: 0,
+        handler: Handler::Db(|db, op| union(db, op.get_cmp_stor())),
+    },
+    Method {
+        module: "Set",
+        name: "Intersect",
+        otype: OpType::SET_INTERSECT,
+        arg: Some(ARG_CMP_STOR),
+        scope: KeyScope::Keys,
+        access: Access::WriteIf(is_store),
+        flags: 0,
+        handler: Handler::Db(|db, op| intersect(db, op.get_cmp_stor())),
+    },
+    Method {
+        module: "Set",
+        name: "Move",
+        otype: OpType::SET_MOVE,
+        arg: Some(ARG_SET_MOVE),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| mov(db, op.get_set_move())),
+    },
+];
+
 #[cfg(test)]
 mod tests {
+    use crate::db::Db;
     use crate::set;
     use memds_proto::memds_api::{CmpStoreOp, KeyOp, KeyedListOp, OpType, SetMoveOp};
     use memds_proto::Atom;
-    use std::collections::HashMap;
     use std::collections::HashSet;
 
-    fn get_test_db() -> HashMap<Vec<u8>, Atom> {
-        let mut db: HashMap<Vec<u8>, Atom> = HashMap::new();
+    fn get_test_db() -> Db {
+        let mut db = Db::new();
         db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
         db.insert(b"name".to_vec(), Atom::String(b"Jane Doe".to_vec()));
         db.insert(b"age".to_vec(), Atom::String(b"25".to_vec()));
@@ -495,7 +599,7 @@ mod tests {
         let mut req = KeyOp::new();
         req.set_key(b"a_set".to_vec());
 
-        let res = set::info(&mut db, &req);
+        let res = set::info(&db, &req);
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::SET_INFO);
         assert!(res.has_set_info());
@@ -542,7 +646,7 @@ mod tests {
         let mut req = KeyOp::new();
         req.set_key(b"a_set".to_vec());
 
-        let res = set::info(&mut db, &req);
+        let res = set::info(&db, &req);
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::SET_INFO);
         assert!(res.has_set_info());
@@ -575,7 +679,7 @@ mod tests {
         let mut req = KeyOp::new();
         req.set_key(b"a_set".to_vec());
 
-        let mut res = set::members(&mut db, &req);
+        let mut res = set::members(&db, &req);
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::SET_MEMBERS);
         assert!(res.has_list());
@@ -613,7 +717,7 @@ mod tests {
         req.elements.push(b"one".to_vec());
         req.elements.push(b"does-not-exist".to_vec());
 
-        let res = set::is_member(&mut db, &req);
+        let res = set::is_member(&db, &req);
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::SET_ISMEMBER);
         assert!(res.has_count());
@@ -716,7 +820,7 @@ mod tests {
         req.set_key(b"setx".to_vec());
         req.elements.push(b"d".to_vec());
 
-        let res = set::is_member(&mut db, &req);
+        let res = set::is_member(&db, &req);
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::SET_ISMEMBER);
         assert!(res.has_count());
@@ -729,7 +833,7 @@ mod tests {
         req.set_key(b"set1".to_vec());
         req.elements.push(b"d".to_vec());
 
-        let res = set::is_member(&mut db, &req);
+        let res = set::is_member(&db, &req);
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::SET_ISMEMBER);
         assert!(res.has_count());
@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Deref;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use memds_proto::scan_hash;
+
+use crate::db::{DbSet, ItemKey};
+use crate::error::OpError;
+
+/// Default number of keyspace shards.
+pub const DEF_SHARDS: usize = 64;
+
+/// The databases, partitioned by hash of item key into shards, each
+/// behind its own lock.  A key's items, in every database, share a shard,
+/// so batches touching disjoint shards execute in parallel.
+///
+/// Shards are always locked in ascending order, so batches locking
+/// overlapping sets of shards cannot deadlock.
+pub struct Keyspace {
+    shards: Vec<RwLock<DbSet>>,
+}
+
+impl Keyspace {
+    /// Partition `dbs` into `n_shards` shards.
+    pub fn new(dbs: DbSet, n_shards: usize) -> Keyspace {
+        assert!(n_shards > 0);
+        let parts = dbs.split(n_shards, |key| shard_index(key, n_shards));
+        Keyspace {
+            shards: parts.into_iter().map(RwLock::new).collect(),
+        }
+    }
+
+    pub fn n_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Shard holding key, in every database.
+    pub fn shard_of(&self, key: &[u8]) -> usize {
+        shard_index(key, self.shards.len())
+    }
+
+    /// Lock the shards holding `keys`, else every shard:  for writing,
+    /// if `write`, otherwise for reading, shared with other readers.
+    pub fn lock(&self, keys: Option<&[&[u8]]>, write: bool) -> Shards<'_> {
+        let indices: BTreeSet<usize> = match keys {
+            Some(keys) => keys.iter().map(|key| self.shard_of(key)).collect(),
+            None => (0..self.shards.len()).collect(),
+        };
+
+        // in ascending order
+        let guards = indices
+            .into_iter()
+            .map(|i| {
+                let guard = match write {
+                    true => Guard::Write(self.shards[i].write().unwrap()),
+                    false => Guard::Read(self.shards[i].read().unwrap()),
+                };
+                (i, guard)
+            })
+            .collect();
+
+        Shards {
+            keyspace: self,
+            guards,
+        }
+    }
+
+    /// Read-lock every shard, in ascending order:  a consistent view of
+    /// the dataset, in parts, excluding writers meanwhile.
+    pub fn read_all(&self) -> Vec<RwLockReadGuard<'_, DbSet>> {
+        self.shards.iter().map(|s| s.read().unwrap()).collect()
+    }
+
+    /// Active expiry, one shard at a time.  `log` is passed the database
+    /// key and key of the items removed from each shard, while it
+    /// remains locked.  Returns number removed.
+    pub fn active_expire(
+        &self,
+        now: u64,
+        max_keys: usize,
+        mut log: impl FnMut(&[ItemKey]),
+    ) -> usize {
+        let mut n_expired = 0;
+        for shard in self.shards.iter() {
+            if n_expired >= max_keys {
+                break;
+            }
+            let mut shard = shard.write().unwrap();
+            let expired = shard.active_expire(now, max_keys - n_expired);
+            if !expired.is_empty() {
+                log(&expired);
+            }
+            n_expired += expired.len();
+        }
+
+        n_expired
+    }
+}
+
+fn shard_index(key: &[u8], n_shards: usize) -> usize {
+    (scan_hash(key) % n_shards as u64) as usize
+}
+
+// lock held upon a shard
+enum Guard<'a> {
+    Read(RwLockReadGuard<'a, DbSet>),
+    Write(RwLockWriteGuard<'a, DbSet>),
+}
+
+impl Deref for Guard<'_> {
+    type Target = DbSet;
+
+    fn deref(&self) -> &DbSet {
+        match self {
+            Guard::Read(g) => g,
+            Guard::Write(g) => g,
+        }
+    }
+}
+
+// key lies in a shard the batch did not lock
+fn not_locked() -> OpError {
+    OpError::internal("Internal error - key outside locked shards")
+}
+
+/// Shards locked on behalf of a batch.
+pub struct Shards<'a> {
+    keyspace: &'a Keyspace,
+    guards: BTreeMap<usize, Guard<'a>>,
+}
+
+impl Shards<'_> {
+    /// Shard holding key, in every database.
+    pub fn shard(&self, key: &[u8]) -> Result<&DbSet, OpError> {
+        match self.guards.get(&self.keyspace.shard_of(key)) {
+            None => Err(not_locked()),
+            Some(guard) => Ok(guard),
+        }
+    }
+
+    // shard `i`, locked for writing
+    fn shard_mut(&mut self, i: usize) -> Result<&mut DbSet, OpError> {
+        match self.guards.get_mut(&i) {
+            None => Err(not_locked()),
+            Some(Guard::Read(_)) => Err(OpError::internal("Internal error - shard read-locked")),
+            Some(Guard::Write(g)) => Ok(g),
+        }
+    }
+
+    // shards holding keys, each locked
+    fn indices(&self, keys: &[&[u8]]) -> Result<BTreeSet<usize>, OpError> {
+        let indices: BTreeSet<usize> = keys.iter().map(|k| self.keyspace.shard_of(k)).collect();
+        match indices.iter().all(|i| self.guards.contains_key(i)) {
+            true => Ok(indices),
+            false => Err(not_locked()),
+        }
+    }
+
+    /// Version of key, in database `db_key`.  See `Db::version()`.
+    pub fn version(&self, db_key: &[u8], key: &[u8]) -> Result<u64, OpError> {
+        let dbs = self.shard(key)?;
+        Ok(dbs.version(db_key, key))
+    }
+
+    /// The shards locked, in ascending order.
+    pub fn parts(&self) -> Vec<&DbSet> {
+        self.guards.values().map(|g| &**g).collect()
+    }
+
+    /// The shards locked, in ascending order, for modification.  Fails
+    /// unless every shard is locked for writing.
+    pub fn parts_mut(&mut self) -> Result<Vec<&mut DbSet>, OpError> {
+        let mut parts = Vec::new();
+        for guard in self.guards.values_mut() {
+            match guard {
+                Guard::Read(_) => {
+                    return Err(OpError::internal("Internal error - shard read-locked"))
+                }
+                Guard::Write(g) => parts.push(&mut **g),
+            }
+        }
+
+        Ok(parts)
+    }
+
+    /// Remove the items at `keys`, in every database, that have expired.
+    /// See `DbSet::expire_keys()`.
+    pub fn expire_keys(&mut self, keys: &[&[u8]]) -> Result<Vec<ItemKey>, OpError> {
+        let mut expired = Vec::new();
+        for i in self.indices(keys)? {
+            expired.extend(self.shard_mut(i)?.expire_keys(keys));
+        }
+
+        Ok(expired)
+    }
+
+    /// Call `f` upon the items at `keys`:  in place, if they share a
+    /// shard; otherwise upon copies, gathered from their shards.
+    pub fn read_items<R>(&self, keys: &[&[u8]], f: impl FnOnce(&DbSet) -> R) -> Result<R, OpError> {
+        let indices = self.indices(keys)?;
+        if indices.len() == 1 {
+            let i = indices.into_iter().next().unwrap();
+            return Ok(f(&self.guards[&i]));
+        }
+
+        let mut dbs = DbSet::new();
+        for i in indices {
+            dbs.absorb(self.guards[&i].copy(keys));
+        }
+
+        Ok(f(&dbs))
+    }
+
+    /// Call `f` upon the items at `keys`:  in place, if they share a
+    /// shard; otherwise gathered from their shards into one set, then
+    /// returned.  Items `f` creates must lie at `keys`.
+    pub fn with_items<R>(
+        &mut self,
+        keys: &[&[u8]],
+        f: impl FnOnce(&mut DbSet) -> R,
+    ) -> Result<R, OpError> {
+        let indices = self.indices(keys)?;
+        for i in indices.iter() {
+            self.shard_mut(*i)?;
+        }
+        if indices.len() == 1 {
+            let i = indices.into_iter().next().unwrap();
+            return Ok(f(self.shard_mut(i)?));
+        }
+
+        let mut dbs = DbSet::new();
+        for i in indices.iter() {
+            let shard = self.shard_mut(*i)?;
+            dbs.absorb(shard.extract(keys));
+        }
+
+        let res = f(&mut dbs);
+
+        let keyspace = self.keyspace;
+        let parts = dbs.split(keyspace.n_shards(), |key| keyspace.shard_of(key));
+        for (i, part) in parts.into_iter().enumerate() {
+            if part.len() > 0 {
+                match indices.contains(&i) {
+                    true => self.shard_mut(i)?.absorb(part),
+                    false => return Err(not_locked()),
+                }
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{DbSet, DEF_DB};
+    use crate::shard::Keyspace;
+    use memds_proto::Atom;
+
+    #[test]
+    fn shards() {
+        let mut dbs = DbSet::new();
+        for i in 0..100 {
+            let key = format!("key{}", i).into_bytes();
+            dbs.db_mut(DEF_DB)
+                .insert(key, Atom::String(b"value".to_vec()));
+        }
+        let keyspace = Keyspace::new(dbs, 8);
+
+        // items partitioned amongst shards, by key
+        let parts = keyspace.read_all();
+        assert_eq!(parts.iter().map(|p| p.len()).sum::<usize>(), 100);
+        assert!(parts.iter().all(|p| p.len() < 100));
+        let i = keyspace.shard_of(b"key1");
+        assert!(parts[i].get(DEF_DB).unwrap().peek(b"key1").is_some());
+        drop(parts);
+
+        // keys gathered from their shards, and returned
+        let (a, b) = (b"key1".as_ref(), b"key2".as_ref());
+        let keys = [a, b, b"new".as_ref()];
+        let mut shards = keyspace.lock(Some(&keys), true);
+        let n = shards
+            .with_items(&keys, |dbs| {
+                let db = dbs.db_mut(DEF_DB);
+                db.remove(a);
+                db.insert(b"new".to_vec(), Atom::String(b"value".to_vec()));
+                dbs.len()
+            })
+            .unwrap();
+        assert_eq!(n, 2);
+
+        // items outside the shards locked:  an error, not a panic
+        let other = (0..100)
+            .map(|i| format!("key{}", i).into_bytes())
+            .find(|k| {
+                keys.iter()
+                    .all(|key| keyspace.shard_of(key) != keyspace.shard_of(k))
+            })
+            .unwrap();
+        assert!(shards
+            .with_items(&[other.as_slice()], |dbs| dbs.len())
+            .is_err());
+        assert!(shards.shard(&other).is_err());
+        let res = shards.read_items(&keys, |dbs| dbs.len()).unwrap();
+        assert_eq!(res, 2);
+        drop(shards);
+
+        // read locks are shared, and refuse modification
+        let mut shards = keyspace.lock(None, false);
+        let readers = keyspace.lock(Some(&keys), false);
+        assert_eq!(shards.parts().iter().map(|p| p.len()).sum::<usize>(), 100);
+        assert!(shards.parts_mut().is_err());
+        assert!(shards.with_items(&keys, |_| ()).is_err());
+        drop(readers);
+        drop(shards);
+
+        let i = keyspace.shard_of(b"new");
+        assert!(keyspace.read_all()[i]
+            .get(DEF_DB)
+            .unwrap()
+            .peek(b"new")
+            .is_some());
+    }
+}
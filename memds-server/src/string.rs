@@ -1,13 +1,15 @@
-use std::collections::HashMap;
 use std::str;
 
 use memds_proto::memds_api::{
-    NumOp, NumRes, OpResult, OpType, StrGetOp, StrGetRes, StrSetOp, StrSetRes,
+    ErrorCode, NumOp, NumRes, OpResult, OpType, StrGetOp, StrGetRes, StrSetOp, StrSetRes,
 };
-use memds_proto::util::result_err;
 use memds_proto::Atom;
 
-pub fn incrdecr(db: &mut HashMap<Vec<u8>, Atom>, otype: OpType, req: &NumOp) -> OpResult {
+use crate::db::{self, Db};
+use crate::error::OpError;
+use crate::registry::{Access, Handler, KeyScope, Method, ARG_GET, ARG_NUM, ARG_SET};
+
+pub fn incrdecr(db: &mut Db, otype: OpType, req: &NumOp) -> OpResult {
     // parameterize based on operation
     let (has_n, is_incr) = match otype {
         OpType::STR_DECR => (false, false),
@@ -26,19 +28,19 @@ pub fn incrdecr(db: &mut HashMap<Vec<u8>, Atom>, otype: OpType, req: &NumOp) ->
                     Atom::String(val) => {
                         let s_res = str::from_utf8(val);
                         if s_res.is_err() {
-                            return result_err(-400, "value not a string");
+                            return OpError::new(ErrorCode::WRONGTYPE, "value not a string").into();
                         }
                         s_res.unwrap()
                     }
                     _ => {
-                        return result_err(-400, "value not a string");
+                        return OpError::new(ErrorCode::WRONGTYPE, "value not a string").into();
                     }
                 }
             };
 
             let sv_res = s.parse::<i64>();
             if sv_res.is_err() {
-                return result_err(-400, "value not i64");
+                return OpError::new(ErrorCode::WRONGTYPE, "value not i64").into();
             }
 
             sv_res.unwrap()
@@ -57,8 +59,8 @@ pub fn incrdecr(db: &mut HashMap<Vec<u8>, Atom>, otype: OpType, req: &NumOp) ->
         false => old_val - n,
     };
 
-    // store value in database as string
-    db.insert(
+    // store value in database as string, retaining expiration
+    db.insert_keepttl(
         req.get_key().to_vec(),
         Atom::String(new_val.to_string().as_bytes().to_vec()),
     );
@@ -107,9 +109,9 @@ fn sanitize_range(in_start: i32, in_end: i32, value_len: i32) -> (usize, usize)
     (start, end)
 }
 
-pub fn get(db: &mut HashMap<Vec<u8>, Atom>, req: &StrGetOp, otype: OpType) -> OpResult {
+pub fn get(db: &Db, req: &StrGetOp, otype: OpType) -> OpResult {
     // get item by key
-    match db.get(req.get_key()) {
+    match db.peek(req.get_key()) {
         Some(atom) => match atom {
             Atom::String(value) => {
                 let mut get_res = StrGetRes::new();
@@ -132,22 +134,62 @@ pub fn get(db: &mut HashMap<Vec<u8>, Atom>, req: &StrGetOp, otype: OpType) -> Op
 
                 op_res
             }
-            _ => result_err(-400, "not a string"),
+            _ => OpError::wrong_type("string").into(),
         },
-        None => result_err(-404, "Not Found"),
+        None => OpError::not_found().into(),
     }
 }
 
-pub fn set(db: &mut HashMap<Vec<u8>, Atom>, req: &StrSetOp) -> OpResult {
+pub fn set(db: &mut Db, req: &StrSetOp) -> OpResult {
     let key = req.get_key();
 
+    // validate option combinations
+    if req.create_excl && req.require_exist {
+        return OpError::invalid_args("create_excl and require_exist are exclusive").into();
+    }
+    if req.expire_ms < 0 || (req.keep_ttl && req.expire_ms != 0) {
+        return OpError::invalid_args("invalid expiration").into();
+    }
+
     // option test: create iff key does not exist
-    if req.create_excl && db.contains_key(key) {
-        return result_err(-412, "Precondition failed: key exists");
+    let exists = db.contains_key(key);
+    if req.create_excl && exists {
+        return OpError::new(ErrorCode::EXISTS, "Precondition failed: key exists").into();
+    }
+
+    // option test: store iff key exists
+    if req.require_exist && !exists {
+        return OpError::new(
+            ErrorCode::NOT_FOUND,
+            "Precondition failed: key does not exist",
+        )
+        .into();
+    }
+
+    // option test: if old-value requested, it must be a string
+    if req.return_old {
+        match db.get(key) {
+            None | Some(Atom::String(_)) => {}
+            _ => {
+                return OpError::wrong_type("string").into();
+            }
+        }
     }
 
     // insert, and return previous item stored at key (if any)
-    let previous = db.insert(key.to_vec(), Atom::String(req.get_value().to_vec()));
+    let value = Atom::String(req.get_value().to_vec());
+    let previous = {
+        if req.keep_ttl {
+            db.insert_keepttl(key.to_vec(), value)
+        } else {
+            db.insert(key.to_vec(), value)
+        }
+    };
+
+    // option: set expiration
+    if req.expire_ms > 0 {
+        db.set_expire(key, db::clock() + req.expire_ms as u64);
+    }
 
     // if old-value requested, return it
     let mut set_res = StrSetRes::new();
@@ -168,14 +210,14 @@ pub fn set(db: &mut HashMap<Vec<u8>, Atom>, req: &StrSetOp) -> OpResult {
     op_res
 }
 
-pub fn append(db: &mut HashMap<Vec<u8>, Atom>, req: &StrSetOp) -> OpResult {
+pub fn append(db: &mut Db, req: &StrSetOp) -> OpResult {
     // get old value, or use "" if none
     let res = db.get(req.get_key());
     let mut value: Vec<u8> = match res {
         Some(atom) => match atom {
             Atom::String(s) => s.to_vec(),
             _ => {
-                return result_err(-400, "not a string");
+                return OpError::wrong_type("string").into();
             }
         },
         None => Vec::new(),
@@ -187,9 +229,9 @@ pub fn append(db: &mut HashMap<Vec<u8>, Atom>, req: &StrSetOp) -> OpResult {
         set_res.set_old_value(value.clone());
     }
 
-    // append to value
+    // append to value, retaining expiration
     value.extend_from_slice(req.get_value());
-    db.insert(req.get_key().to_vec(), Atom::String(value));
+    db.insert_keepttl(req.get_key().to_vec(), Atom::String(value));
 
     // standard operation result assignment & final return
     let mut op_res = OpResult::new();
@@ -200,15 +242,99 @@ pub fn append(db: &mut HashMap<Vec<u8>, Atom>, req: &StrSetOp) -> OpResult {
     op_res
 }
 
+/// String module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "String",
+        name: "Get",
+        otype: OpType::STR_GET,
+        arg: Some(ARG_GET),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| get(db, op.get_get(), op.otype)),
+    },
+    Method {
+        module: "String",
+        name: "GetRange",
+        otype: OpType::STR_GETRANGE,
+        arg: Some(ARG_GET),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| get(db, op.get_get(), op.otype)),
+    },
+    Method {
+        module: "String",
+        name: "Set",
+        otype: OpType::STR_SET,
+        arg: Some(ARG_SET),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| set(db, op.get_set())),
+    },
+    Method {
+        module: "String",
+        name: "Append",
+        otype: OpType::STR_APPEND,
+        arg: Some(ARG_SET),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| append(db, op.get_set())),
+    },
+    Method {
+        module: "String",
+        name: "Decr",
+        otype: OpType::STR_DECR,
+        arg: Some(ARG_NUM),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| incrdecr(db, op.otype, op.get_num())),
+    },
+    Method {
+        module: "String",
+        name: "DecrBy",
+        otype: OpType::STR_DECRBY,
+        arg: Some(ARG_NUM),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| incrdecr(db, op.otype, op.get_num())),
+    },
+    Method {
+        module: "String",
+        name: "Incr",
+        otype: OpType::STR_INCR,
+        arg: Some(ARG_NUM),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| incrdecr(db, op.otype, op.get_num())),
+    },
+    Method {
+        module: "String",
+        name: "IncrBy",
+        otype: OpType::STR_INCRBY,
+        arg: Some(ARG_NUM),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| incrdecr(db, op.otype, op.get_num())),
+    },
+];
+
 #[cfg(test)]
 mod tests {
+    use crate::db::Db;
     use crate::string;
-    use memds_proto::memds_api::{NumOp, OpType, StrGetOp, StrSetOp};
+    use memds_proto::memds_api::{ErrorCode, NumOp, OpType, StrGetOp, StrSetOp};
     use memds_proto::Atom;
-    use std::collections::HashMap;
 
-    fn get_test_db() -> HashMap<Vec<u8>, Atom> {
-        let mut db: HashMap<Vec<u8>, Atom> = HashMap::new();
+    fn get_test_db() -> Db {
+        let mut db = Db::new();
         db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
         db.insert(b"name".to_vec(), Atom::String(b"Jane Doe".to_vec()));
         db.insert(b"age".to_vec(), Atom::String(b"25".to_vec()));
@@ -218,13 +344,13 @@ mod tests {
 
     #[test]
     fn basic_get() {
-        let mut db = get_test_db();
+        let db = get_test_db();
 
         let mut req = StrGetOp::new();
         req.set_key(b"foo".to_vec());
         req.set_want_length(false);
 
-        let res = string::get(&mut db, &req, OpType::STR_GET);
+        let res = string::get(&db, &req, OpType::STR_GET);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::STR_GET);
@@ -237,13 +363,13 @@ mod tests {
 
     #[test]
     fn get_length() {
-        let mut db = get_test_db();
+        let db = get_test_db();
 
         let mut req = StrGetOp::new();
         req.set_key(b"foo".to_vec());
         req.set_want_length(true);
 
-        let res = string::get(&mut db, &req, OpType::STR_GET);
+        let res = string::get(&db, &req, OpType::STR_GET);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::STR_GET);
@@ -256,7 +382,7 @@ mod tests {
 
     #[test]
     fn get_range() {
-        let mut db = get_test_db();
+        let db = get_test_db();
 
         // testing: (0,4) substr of "Jane Doe"
         let mut req = StrGetOp::new();
@@ -265,7 +391,7 @@ mod tests {
         req.range_start = 0;
         req.range_end = -4;
 
-        let res = string::get(&mut db, &req, OpType::STR_GETRANGE);
+        let res = string::get(&db, &req, OpType::STR_GETRANGE);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::STR_GETRANGE);
@@ -282,7 +408,7 @@ mod tests {
         req.range_start = 0;
         req.range_end = -1;
 
-        let res = string::get(&mut db, &req, OpType::STR_GETRANGE);
+        let res = string::get(&db, &req, OpType::STR_GETRANGE);
 
         assert_eq!(res.ok, true);
         assert_eq!(res.otype, OpType::STR_GETRANGE);
@@ -295,17 +421,17 @@ mod tests {
 
     #[test]
     fn get_not_found() {
-        let mut db = get_test_db();
+        let db = get_test_db();
 
         let mut req = StrGetOp::new();
         req.set_key(b"does not exist".to_vec());
         req.set_want_length(false);
 
-        let res = string::get(&mut db, &req, OpType::STR_GET);
+        let res = string::get(&db, &req, OpType::STR_GET);
 
         assert_eq!(res.ok, false);
         assert_eq!(res.otype, OpType::NOOP);
-        assert_eq!(res.err_code, -404);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
         assert!(!res.has_get());
         assert!(!res.has_set());
     }
@@ -417,7 +543,7 @@ mod tests {
         let mut req = StrGetOp::new();
         req.set_key(b"num".to_vec());
 
-        let res = string::get(&mut db, &req, OpType::STR_GET);
+        let res = string::get(&db, &req, OpType::STR_GET);
         assert_eq!(res.ok, true);
         let get_res = res.get_get();
         assert_eq!(get_res.value, b"0".to_vec());
@@ -461,9 +587,97 @@ mod tests {
         let mut req = StrGetOp::new();
         req.set_key(b"app".to_vec());
 
-        let res = string::get(&mut db, &req, OpType::STR_GET);
+        let res = string::get(&db, &req, OpType::STR_GET);
         assert_eq!(res.ok, true);
         let get_res = res.get_get();
         assert_eq!(get_res.value, b"doordoor".to_vec());
     }
+
+    #[test]
+    fn set_options() {
+        let mut db = get_test_db();
+
+        // require_exist: fails for missing key
+        let mut req = StrSetOp::new();
+        req.set_key(b"barn".to_vec());
+        req.set_value(b"door".to_vec());
+        req.require_exist = true;
+
+        let res = string::set(&mut db, &req);
+
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
+
+        // create_excl: fails for existing key
+        let mut req = StrSetOp::new();
+        req.set_key(b"foo".to_vec());
+        req.set_value(b"door".to_vec());
+        req.create_excl = true;
+
+        let res = string::set(&mut db, &req);
+
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::EXISTS);
+
+        // require_exist + expiration + old value
+        let mut req = StrSetOp::new();
+        req.set_key(b"foo".to_vec());
+        req.set_value(b"door".to_vec());
+        req.require_exist = true;
+        req.return_old = true;
+        req.expire_ms = 60000;
+
+        let res = string::set(&mut db, &req);
+
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_set().old_value, b"bar".to_vec());
+        assert!(db.expire_time(b"foo").is_some());
+
+        // keep_ttl retains expiration
+        let mut req = StrSetOp::new();
+        req.set_key(b"foo".to_vec());
+        req.set_value(b"bar".to_vec());
+        req.keep_ttl = true;
+
+        let res = string::set(&mut db, &req);
+
+        assert_eq!(res.ok, true);
+        assert!(db.expire_time(b"foo").is_some());
+
+        // plain set clears expiration
+        let mut req = StrSetOp::new();
+        req.set_key(b"foo".to_vec());
+        req.set_value(b"bar".to_vec());
+
+        let res = string::set(&mut db, &req);
+
+        assert_eq!(res.ok, true);
+        assert!(db.expire_time(b"foo").is_none());
+
+        // invalid option combinations
+        let mut req = StrSetOp::new();
+        req.set_key(b"foo".to_vec());
+        req.set_value(b"bar".to_vec());
+        req.keep_ttl = true;
+        req.expire_ms = 1000;
+
+        let res = string::set(&mut db, &req);
+
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::INVALID_ARGUMENT);
+
+        // old value of wrong type: error, and value untouched
+        db.insert(b"lst".to_vec(), Atom::List(vec![b"a".to_vec()]));
+
+        let mut req = StrSetOp::new();
+        req.set_key(b"lst".to_vec());
+        req.set_value(b"bar".to_vec());
+        req.return_old = true;
+
+        let res = string::set(&mut db, &req);
+
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::WRONGTYPE);
+        assert!(matches!(db.get(b"lst"), Some(Atom::List(_))));
+    }
 }
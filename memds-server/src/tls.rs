@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use grpcio::{CertificateRequestType, ServerCredentialsBuilder, ServerCredentialsFetcher};
+
+use crate::config::TlsConfig;
+
+/// Server certificate, key and CA, read from their files initially, and
+/// again whenever reload is requested.  Upon failure to reload, the
+/// certificates previously read remain in use.
+pub struct CertFetcher {
+    cfg: TlsConfig,
+    reload: Arc<AtomicBool>,
+}
+
+impl CertFetcher {
+    pub fn new(cfg: TlsConfig) -> CertFetcher {
+        CertFetcher {
+            cfg,
+            reload: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Client certificate policy:  verified if CA configured, and
+    /// required if so configured.
+    pub fn cert_request_type(&self) -> CertificateRequestType {
+        match (&self.cfg.ca, self.cfg.require_client_cert) {
+            (None, _) => CertificateRequestType::DontRequestClientCertificate,
+            (Some(_), false) => CertificateRequestType::RequestClientCertificateAndVerify,
+            (Some(_), true) => CertificateRequestType::RequestAndRequireClientCertificateAndVerify,
+        }
+    }
+
+    /// Flag set to request reload, upon the next connection accepted.
+    pub fn reload_flag(&self) -> Arc<AtomicBool> {
+        self.reload.clone()
+    }
+
+    fn read(&self) -> Result<ServerCredentialsBuilder, Box<dyn Error>> {
+        let read = |path| fs::read(path).map_err(|e| format!("{:?}: {}", path, e));
+        let mut builder =
+            ServerCredentialsBuilder::new().add_cert(read(&self.cfg.cert)?, read(&self.cfg.key)?);
+        if let Some(ca) = &self.cfg.ca {
+            builder = builder.root_cert(read(ca)?, self.cert_request_type());
+        }
+        Ok(builder)
+    }
+}
+
+impl ServerCredentialsFetcher for CertFetcher {
+    fn fetch(&self) -> Result<Option<ServerCredentialsBuilder>, Box<dyn Error>> {
+        if !self.reload.swap(false, Ordering::SeqCst) {
+            return Ok(None);
+        }
+
+        match self.read() {
+            Ok(builder) => {
+                info!("TLS certificates loaded");
+                Ok(Some(builder))
+            }
+            Err(e) => {
+                error!("TLS certificate load failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CertFetcher;
+    use crate::config::TlsConfig;
+    use grpcio::ServerCredentialsFetcher;
+    use std::fs;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn reload() {
+        let dir = std::env::temp_dir();
+        let cert = dir.join(format!("memds-test-{}.crt", std::process::id()));
+        let key = dir.join(format!("memds-test-{}.key", std::process::id()));
+        fs::write(&cert, b"cert").unwrap();
+        fs::write(&key, b"key").unwrap();
+
+        let fetcher = CertFetcher::new(TlsConfig {
+            cert: cert.clone(),
+            key: key.clone(),
+            ca: None,
+            require_client_cert: false,
+        });
+        let reload = fetcher.reload_flag();
+
+        // read initially; thereafter, only upon request
+        assert!(fetcher.fetch().unwrap().is_some());
+        assert!(fetcher.fetch().unwrap().is_none());
+        reload.store(true, Ordering::SeqCst);
+        assert!(fetcher.fetch().unwrap().is_some());
+
+        // unreadable files fail the reload
+        fs::remove_file(&key).unwrap();
+        reload.store(true, Ordering::SeqCst);
+        assert!(fetcher.fetch().is_err());
+        assert!(fetcher.fetch().unwrap().is_none());
+
+        fs::remove_file(&cert).unwrap();
+    }
+}
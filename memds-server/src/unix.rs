@@ -0,0 +1,138 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use crate::framed;
+use crate::rpcservice::MemdsService;
+
+pub const DEF_SOCKET_MODE: u32 = 0o660;
+
+/// Socket file bound by this server, removed at shutdown.
+pub struct SocketFile {
+    path: PathBuf,
+    dev: u64,
+    ino: u64,
+}
+
+impl SocketFile {
+    /// Remove socket file, unless since replaced by another's.
+    pub fn remove(&self) {
+        match fs::symlink_metadata(&self.path) {
+            Ok(md) if md.dev() == self.dev && md.ino() == self.ino => {
+                if let Err(e) = fs::remove_file(&self.path) {
+                    error!("{}: socket removal failed: {:?}", self.path.display(), e);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// remove socket file left by a server no longer running.  Refuses to
+// remove anything other than a socket, or a socket accepting connections.
+fn remove_stale(path: &Path) -> io::Result<()> {
+    let md = match fs::symlink_metadata(path) {
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+        Ok(md) => md,
+    };
+    if !md.file_type().is_socket() {
+        let msg = format!("{}: exists, and is not a socket", path.display());
+        return Err(Error::new(ErrorKind::AlreadyExists, msg));
+    }
+
+    match UnixStream::connect(path) {
+        Ok(_) => {
+            let msg = format!("{}: socket in use", path.display());
+            Err(Error::new(ErrorKind::AddrInUse, msg))
+        }
+        Err(ref e) if e.kind() == ErrorKind::ConnectionRefused => {
+            info!("{}: removing stale socket", path.display());
+            fs::remove_file(path)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Bind socket at `path` with permissions `mode`, removing a stale socket
+/// file if present.
+pub fn bind(path: &Path, mode: u32) -> io::Result<(UnixListener, SocketFile)> {
+    remove_stale(path)?;
+
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+
+    let md = fs::symlink_metadata(path)?;
+    let file = SocketFile {
+        path: path.to_path_buf(),
+        dev: md.dev(),
+        ino: md.ino(),
+    };
+    Ok((listener, file))
+}
+
+/// Accept framed protocol clients on `listener`, each served by its own
+/// thread.
+pub fn listen(svc: MemdsService, listener: UnixListener) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let svc = svc.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = framed::serve(svc, stream) {
+                        debug!("unix socket client: {:?}", e);
+                    }
+                });
+            }
+            Err(e) => error!("unix socket accept failed: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rpcservice::tests::test_service;
+    use memds_proto::memds_api::{OpType, Operation, RequestMsg};
+    use memds_proto::FramedClient;
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn socket_lifecycle() {
+        let path = std::env::temp_dir().join(format!("memds-test-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        // stale socket is replaced
+        drop(super::bind(&path, 0o600).unwrap());
+        let (listener, file) = super::bind(&path, 0o600).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // live socket is not
+        let err = super::bind(&path, 0o600).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::AddrInUse);
+
+        let svc = test_service();
+        std::thread::spawn(move || super::listen(svc, listener));
+
+        let mut op = Operation::new();
+        op.otype = OpType::SRV_DBSIZE;
+        let mut req = RequestMsg::new();
+        req.ops.push(op);
+        let mut client = FramedClient::new(UnixStream::connect(&path).unwrap());
+        let resp = client.exec(&req).unwrap();
+        assert!(resp.ok);
+
+        file.remove();
+        assert!(!path.exists());
+
+        // nor is anything other than a socket
+        fs::write(&path, b"").unwrap();
+        assert!(super::bind(&path, 0o600).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
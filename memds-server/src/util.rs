@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+/// Cursor-based incremental iteration:  from `parts`, each yielding
+/// (scan hash, item) in `scan_hash()` order from `cursor`, select at
+/// least `count` items with lowest hash, plus any hash ties.  Returns
+/// the items selected and the cursor at which to resume; 0 when
+/// iteration is complete.  Parts are read only as far as the selection.
+///
+/// Items present for the entire iteration are returned exactly once,
+/// however the underlying collection is resized between calls.
+pub fn scan_select<T, I>(parts: impl IntoIterator<Item = I>, count: usize) -> (u64, Vec<T>)
+where
+    I: Iterator<Item = (u64, T)>,
+{
+    // from each part, its first `count` items plus any hash ties:  every
+    // item the selection may include
+    let count = count.max(1);
+    let mut candidates: Vec<(u64, T)> = Vec::new();
+    let mut more = false;
+    for part in parts {
+        let mut part = part.peekable();
+        let (mut n, mut last_hash) = (0, 0);
+        while let Some((h, item)) = part.next_if(|(h, _)| n < count || *h == last_hash) {
+            candidates.push((h, item));
+            n += 1;
+            last_hash = h;
+        }
+        more |= part.peek().is_some();
+    }
+
+    // select `count` items with lowest hash, plus any hash ties
+    candidates.sort_by_key(|(h, _)| *h);
+    let mut next_cursor = 0;
+    if candidates.len() > count || more {
+        let last_hash = candidates[count - 1].0;
+        candidates.retain(|(h, _)| *h <= last_hash);
+        next_cursor = last_hash.wrapping_add(1);
+    }
+
+    let selected = candidates.into_iter().map(|(_, item)| item).collect();
+
+    (next_cursor, selected)
+}
+
+// match byte c against the character class beginning at pattern[p] == '['.
+// returns (matched, position following the class)
+fn match_class(pattern: &[u8], mut p: usize, c: u8) -> (bool, usize) {
+    p += 1;
+    let negate = p < pattern.len() && pattern[p] == b'^';
+    if negate {
+        p += 1;
+    }
+
+    let mut matched = false;
+    while p < pattern.len() && pattern[p] != b']' {
+        if pattern[p] == b'\\' && p + 1 < pattern.len() {
+            // escaped literal
+            matched |= pattern[p + 1] == c;
+            p += 2;
+        } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' && pattern[p + 2] != b']' {
+            // range, in either order
+            let (lo, hi) = {
+                if pattern[p] <= pattern[p + 2] {
+                    (pattern[p], pattern[p + 2])
+                } else {
+                    (pattern[p + 2], pattern[p])
+                }
+            };
+            matched |= lo <= c && c <= hi;
+            p += 3;
+        } else {
+            matched |= pattern[p] == c;
+            p += 1;
+        }
+    }
+
+    // skip closing bracket, if present
+    if p < pattern.len() {
+        p += 1;
+    }
+
+    (matched != negate, p)
+}
+
+/// SHA1 digest of `data`, as 40 hex digits.
+pub fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// SHA-256 digest of `data`, as 64 hex digits.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Compare `a` with `b` in time independent of where they differ, so
+/// that comparing secrets reveals nothing of them.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a
+        .iter()
+        .zip(b)
+        .fold(0u8, |acc, (x, y)| std::hint::black_box(acc | (x ^ y)));
+    diff == 0
+}
+
+/// Binary-safe glob-style match of `s` against `pattern`.
+///
+/// Supports `*`, `?`, `[abc]`, `[^abc]`, `[a-z]` and `\x` escapes.
+pub fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    let mut p = 0;
+    let mut i = 0;
+    let mut star: Option<(usize, usize)> = None; // (pattern pos after '*', string pos)
+
+    while i < s.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    star = Some((p + 1, i));
+                    p += 1;
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    i += 1;
+                    continue;
+                }
+                b'[' => {
+                    let (matched, next_p) = match_class(pattern, p, s[i]);
+                    if matched {
+                        p = next_p;
+                        i += 1;
+                        continue;
+                    }
+                }
+                b'\\' if p + 1 < pattern.len() => {
+                    if pattern[p + 1] == s[i] {
+                        p += 2;
+                        i += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == s[i] {
+                        p += 1;
+                        i += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // mismatch:  backtrack to most recent '*', consuming one more byte
+        match star {
+            None => return false,
+            Some((star_p, star_i)) => {
+                p = star_p;
+                i = star_i + 1;
+                star = Some((star_p, i));
+            }
+        }
+    }
+
+    // remaining pattern must be all '*'
+    while p < pattern.len() && pattern[p] == b'*' {
+        p += 1;
+    }
+
+    p == pattern.len()
+}
+
+/// Flush the directory containing `path` to stable storage, making a
+/// newly created or renamed file durable.
+pub fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{ct_eq, glob_match, scan_select, sha256_hex};
+
+    #[test]
+    fn scan_parts() {
+        // two parts, in hash order, with a tie across them at hash 3
+        let a = [(1, "a1"), (3, "a3"), (5, "a5")];
+        let b = [(2, "b2"), (3, "b3"), (4, "b4"), (6, "b6")];
+        let parts = || [a.iter().copied(), b.iter().copied()];
+
+        // at least count items, plus ties; resume past the last hash
+        let (cursor, items) = scan_select(parts(), 3);
+        assert_eq!(cursor, 4);
+        assert_eq!(items, ["a1", "b2", "a3", "b3"]);
+
+        let rest = |cursor| parts().map(move |p| p.filter(move |(h, _)| *h >= cursor));
+        let (cursor, items) = scan_select(rest(cursor), 2);
+        assert_eq!((cursor, items), (6, vec!["b4", "a5"]));
+        let (cursor, items) = scan_select(rest(cursor), 2);
+        assert_eq!((cursor, items), (0, vec!["b6"]));
+
+        // count exceeding the items:  complete
+        assert_eq!(scan_select(parts(), 100).0, 0);
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"*", b"anything"));
+        assert!(glob_match(b"foo", b"foo"));
+        assert!(!glob_match(b"foo", b"food"));
+        assert!(glob_match(b"foo*", b"food"));
+        assert!(glob_match(b"*od", b"food"));
+        assert!(glob_match(b"f*o*d", b"food"));
+        assert!(!glob_match(b"f*o*x", b"food"));
+        assert!(glob_match(b"h?llo", b"hello"));
+        assert!(!glob_match(b"h?llo", b"hllo"));
+        assert!(glob_match(b"h[ae]llo", b"hallo"));
+        assert!(!glob_match(b"h[ae]llo", b"hillo"));
+        assert!(glob_match(b"h[^e]llo", b"hallo"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-b]llo", b"hbllo"));
+        assert!(glob_match(b"h[b-a]llo", b"hallo"));
+        assert!(!glob_match(b"h[a-b]llo", b"hcllo"));
+        assert!(glob_match(b"h\\*llo", b"h*llo"));
+        assert!(!glob_match(b"h\\*llo", b"hello"));
+        assert!(glob_match(b"[\\]]", b"]"));
+
+        // binary safe
+        assert!(glob_match(b"a*\x00", b"abc\x00"));
+        assert!(glob_match(b"\xff?", b"\xff\x00"));
+    }
+
+    #[test]
+    fn digests() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(ct_eq(b"secret", b"secret"));
+        assert!(!ct_eq(b"secret", b"secreT"));
+        assert!(!ct_eq(b"secret", b"secrets"));
+        assert!(ct_eq(b"", b""));
+    }
+}
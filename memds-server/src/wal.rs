@@ -0,0 +1,933 @@
+//
+// Operation log (aka write-ahead log, append-only file)
+//
+// Every successful mutating Operation is appended to the log, framed by
+// the chained-CRC MemdsCodec.  The log begins with a LOGHDR record
+// carrying a unique log id, followed by the dataset as of log creation,
+// making each log self-contained.  Snapshots record the log position
+// (id, offset, checksum chain) at which they were taken, permitting
+// startup to replay only the log records following the snapshot.
+// Keys removed upon expiry are logged as deletions, and replay expires
+// no keys itself, so that replay at a later time reproduces the dataset.
+//
+// A background rewrite (BGREWRITEAOF) forks, and the child writes a new
+// log containing only the current dataset.  Writes arriving meanwhile
+// are buffered by the parent, appended to the new log once the child
+// completes, and the new log atomically replaces the old.
+//
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bytes::BytesMut;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{fork, ForkResult, Pid};
+use tokio_util::codec::{Decoder, Encoder};
+
+use memds_proto::memds_api::{
+    KeyExpireOp, KeyListOp, LogPos, MemdsMessage, MemdsMessage_MsgType, OpType, Operation, StrSetOp,
+};
+use memds_proto::{MemdsCodec, MemdsError};
+
+use crate::db::{self, Db, DbSet, ItemKey, DEF_DB};
+use crate::keys;
+use crate::rpcservice::{exec_op, selected_db};
+use crate::util;
+
+/// Milliseconds between fsyncs, under the "everysec" policy.
+pub const FSYNC_INTERVAL_MS: u64 = 1000;
+
+/// Default automatic rewrite trigger:  log grown by this percentage
+/// since the last rewrite (or startup).  Zero disables.
+pub const DEF_REWRITE_PCT: u64 = 100;
+
+/// Default minimum log size for automatic rewrite.
+pub const DEF_REWRITE_MIN_SIZE: u64 = 64 * 1024 * 1024;
+
+/// When to flush operation log writes to stable storage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FsyncPolicy {
+    Always,   // after every write
+    EverySec, // once per FSYNC_INTERVAL_MS, by background thread
+    Never,    // leave it to the OS
+}
+
+impl FsyncPolicy {
+    pub fn parse(s: &str) -> Option<FsyncPolicy> {
+        match s {
+            "always" => Some(FsyncPolicy::Always),
+            "everysec" => Some(FsyncPolicy::EverySec),
+            "never" => Some(FsyncPolicy::Never),
+            _ => None,
+        }
+    }
+}
+
+fn codec_err(e: MemdsError) -> Error {
+    Error::new(ErrorKind::Other, format!("log codec: {:?}", e))
+}
+
+// unique log id:  current time, in nanoseconds
+fn new_log_id() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64
+}
+
+// temporary file, into which a rewritten log is written
+fn rewrite_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_os_string();
+    s.push(".rewrite");
+    PathBuf::from(s)
+}
+
+// absolute-time expiration of key, as applied to db:  the key's
+// expiration time if any, else deletion of the key
+fn expire_op(db: Option<&Db>, key: &[u8]) -> Operation {
+    let expires = match db.and_then(|db| db.expire_time(key)) {
+        None => return del_op(key),
+        Some(t) => t,
+    };
+
+    let mut req = KeyExpireOp::new();
+    req.set_key(key.to_vec());
+    req.absolute = true;
+    req.expire_ms = expires as i64;
+
+    let mut op = Operation::new();
+    op.otype = OpType::KEY_EXPIRE;
+    op.set_expire(req);
+    op
+}
+
+fn del_op(key: &[u8]) -> Operation {
+    let mut req = KeyListOp::new();
+    req.keys.push(key.to_vec());
+
+    let mut op = Operation::new();
+    op.otype = OpType::KEYS_DEL;
+    op.set_key_list(req);
+    op
+}
+
+/// Operations to log, given items removed upon expiry:  the database key
+/// and key of each.
+pub fn expired_ops(expired: &[ItemKey]) -> Vec<Operation> {
+    expired
+        .iter()
+        .map(|(db_key, key)| {
+            let mut op = del_op(key);
+            op.set_db(db_key.clone());
+            op
+        })
+        .collect()
+}
+
+// operations to log, given an operation just applied to db `db_key`.
+// relative expiration times are converted to absolute, so that replay at
+// a later time reproduces the same expiration.  each operation records
+// the database it applies to.
+pub fn loggable_ops(dbs: &DbSet, db_key: &[u8], op: &Operation) -> Vec<Operation> {
+    let db = dbs.get(db_key);
+    let mut ops = match op.otype {
+        // as of the clock when applied:  an expiration already past
+        // deleted the key
+        OpType::KEY_EXPIRE => vec![expire_op(db, op.get_expire().get_key())],
+
+        OpType::STR_SET if op.get_set().expire_ms > 0 => {
+            let mut set_op = op.clone();
+            set_op.mut_set().expire_ms = 0;
+            vec![set_op, expire_op(db, op.get_set().get_key())]
+        }
+
+        _ => vec![op.clone()],
+    };
+
+    for log_op in ops.iter_mut() {
+        log_op.set_db(db_key.to_vec());
+    }
+
+    ops
+}
+
+// operation recreating key's current value and expiration, if key exists
+fn restore_op(db: &Db, db_key: &[u8], key: &[u8]) -> Option<Operation> {
+    let dbv = keys::export_dbv(db, key)?;
+
+    // encode DbValue, as KEY_DUMP does
+    let mut msg = MemdsMessage::new();
+    msg.mtype = MemdsMessage_MsgType::DBVAL;
+    msg.set_dbv(dbv);
+    let msg_raw = &mut BytesMut::new();
+    MemdsCodec::new().encode(msg, msg_raw).unwrap();
+
+    let mut req = StrSetOp::new();
+    req.set_key(key.to_vec());
+    req.set_value(msg_raw.to_vec());
+
+    let mut op = Operation::new();
+    op.otype = OpType::KEY_RESTORE;
+    op.set_set(req);
+    op.set_db(db_key.to_vec());
+    Some(op)
+}
+
+// background rewrite in progress
+struct Rewrite {
+    child: Pid,
+    log_id: u64,
+    buf: Vec<Operation>, // operations logged since fork
+}
+
+pub struct Wal {
+    path: PathBuf,
+    f: File,
+    codec: MemdsCodec,
+    log_id: u64,
+    offset: u64,    // length of valid log data
+    base_size: u64, // length following last rewrite, or startup
+    policy: FsyncPolicy,
+    dirty: bool, // written, but not yet fsync'd
+    rewrite: Option<Rewrite>,
+}
+
+impl Wal {
+    // create log with the given id, containing the dataset, in one or
+    // more parts
+    fn create_with_id(
+        path: &Path,
+        policy: FsyncPolicy,
+        log_id: u64,
+        parts: &[&DbSet],
+    ) -> io::Result<Wal> {
+        let f = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        let mut wal = Wal {
+            path: path.to_path_buf(),
+            f,
+            codec: MemdsCodec::new(),
+            log_id,
+            offset: 0,
+            base_size: 0,
+            policy: FsyncPolicy::Never, // sync once, below
+            dirty: false,
+            rewrite: None,
+        };
+
+        // write log header
+        let mut pos = LogPos::new();
+        pos.log_id = log_id;
+        let mut msg = MemdsMessage::new();
+        msg.mtype = MemdsMessage_MsgType::LOGHDR;
+        msg.set_log_pos(pos);
+        wal.write_msg(msg)?;
+
+        // write dataset
+        for (db_key, db) in parts.iter().flat_map(|dbs| dbs.iter()) {
+            for key in db.keys() {
+                if let Some(op) = restore_op(db, db_key, key) {
+                    wal.write_op(op)?;
+                }
+            }
+        }
+
+        wal.f.sync_all()?;
+        util::sync_parent_dir(path)?;
+        wal.dirty = false;
+        wal.policy = policy;
+        wal.base_size = wal.offset;
+
+        Ok(wal)
+    }
+
+    /// Create a new operation log containing the dataset of dbs,
+    /// replacing any existing file.
+    pub fn create(path: &Path, policy: FsyncPolicy, dbs: &DbSet) -> io::Result<Wal> {
+        Wal::create_with_id(path, policy, new_log_id(), &[dbs])
+    }
+
+    /// Open existing operation log, replaying its records into dbs, and
+    /// prepare to append to it.  If `base` is the log position of the
+    /// snapshot already loaded into dbs, replay begins at that position;
+    /// otherwise, dbs is emptied and the entire log is replayed.
+    ///
+    /// A torn tail record -- incomplete, or failing verification with
+    /// nothing but zeroes following -- is truncated.  A missing log is
+    /// created.
+    pub fn open(
+        path: &Path,
+        policy: FsyncPolicy,
+        dbs: &mut DbSet,
+        base: Option<&LogPos>,
+    ) -> io::Result<Wal> {
+        // remove remnant of interrupted rewrite, if any
+        let _ = fs::remove_file(rewrite_path(path));
+
+        match Wal::load(path, policy, Some(dbs), base) {
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Wal::create(path, policy, dbs),
+            res => res,
+        }
+    }
+
+    // read log, verifying it and applying its records to dbs (if any)
+    fn load(
+        path: &Path,
+        policy: FsyncPolicy,
+        mut dbs: Option<&mut DbSet>,
+        base: Option<&LogPos>,
+    ) -> io::Result<Wal> {
+        let mut f = OpenOptions::new().read(true).append(true).open(path)?;
+        let file_len = f.metadata()?.len();
+
+        let mut codec = MemdsCodec::new();
+        let mut accum = BytesMut::with_capacity(4096);
+        let mut buf = [0; 4096];
+        let mut n_read: u64 = 0; // bytes read from file
+        let mut good_offset: u64 = 0; // end of last valid record
+        let mut good_crc = codec.dec_chain(); // checksum chain at good_offset
+        let mut log_id: Option<u64> = None;
+        let mut corrupt = false;
+
+        'read: loop {
+            // read next chunk of data from the file
+            let n = f.read(&mut buf)?;
+            if n == 0 {
+                // EOF
+                break;
+            }
+            n_read += n as u64;
+            accum.extend_from_slice(&buf[0..n]);
+
+            // for each decodable record...
+            loop {
+                let msg = match codec.decode(&mut accum) {
+                    Err(_) => {
+                        corrupt = true;
+                        break 'read;
+                    }
+
+                    // incomplete record
+                    Ok(None) => break,
+
+                    Ok(Some(msg)) => msg,
+                };
+
+                match log_id {
+                    // first record: log header
+                    None => {
+                        if msg.mtype != MemdsMessage_MsgType::LOGHDR || !msg.has_log_pos() {
+                            return Err(Error::new(ErrorKind::Other, "not an operation log"));
+                        }
+                        let hdr_log_id = msg.get_log_pos().log_id;
+                        log_id = Some(hdr_log_id);
+                        good_offset = n_read - accum.len() as u64;
+                        good_crc = codec.dec_chain();
+
+                        // skip to snapshot position within this log, if any
+                        if let Some(pos) = base {
+                            if pos.log_id == hdr_log_id && pos.offset >= good_offset {
+                                // log lost data included in snapshot;
+                                // snapshot becomes the base of a new log
+                                if pos.offset > file_len {
+                                    warn!("operation log shorter than snapshot; recreating");
+                                    return match dbs {
+                                        None => Err(Error::new(ErrorKind::Other, "short log")),
+                                        Some(dbs) => Wal::create(path, policy, dbs),
+                                    };
+                                }
+
+                                f.seek(SeekFrom::Start(pos.offset))?;
+                                n_read = pos.offset;
+                                accum.clear();
+                                codec = MemdsCodec::with_chain(pos.crc);
+                                good_offset = pos.offset;
+                                good_crc = pos.crc;
+                                continue 'read;
+                            }
+                        }
+
+                        // log is self-contained; replay it entirely
+                        if let Some(dbs) = dbs.as_mut() {
+                            if dbs.len() > 0 {
+                                info!(
+                                    "snapshot not taken from operation log; replaying entire log"
+                                );
+                                dbs.clear();
+                            }
+                        }
+                    }
+
+                    // following records: logged operations
+                    Some(_) => {
+                        if msg.mtype != MemdsMessage_MsgType::OPLOG || !msg.has_op() {
+                            return Err(Error::new(ErrorKind::Other, "unexpected record type"));
+                        }
+
+                        // keys expire only as logged
+                        if let Some(dbs) = dbs.as_mut() {
+                            let op = msg.get_op();
+                            let db_key = selected_db(op, DEF_DB);
+                            let op_res = db::with_clock(0, || exec_op(dbs, db_key, op));
+                            if !op_res.ok {
+                                warn!(
+                                    "operation log replay: {:?} failed: {}",
+                                    msg.get_op().otype,
+                                    op_res.err_message
+                                );
+                            }
+                        }
+
+                        good_offset = n_read - accum.len() as u64;
+                        good_crc = codec.dec_chain();
+                    }
+                }
+            }
+        }
+
+        // anything following the last valid record must be a torn tail:
+        // an incomplete record, or bytes past a failed record all zero
+        if good_offset < file_len {
+            let consumed = n_read - accum.len() as u64;
+            if corrupt && !tail_is_zero(&mut f, consumed.max(good_offset))? {
+                let msg = format!("operation log corrupt at offset {}", good_offset);
+                return Err(Error::new(ErrorKind::Other, msg));
+            }
+
+            warn!(
+                "operation log: truncating {} byte torn tail at offset {}",
+                file_len - good_offset,
+                good_offset
+            );
+            f.set_len(good_offset)?;
+            f.sync_all()?;
+        }
+
+        // header itself torn or missing: start anew
+        let log_id = match (log_id, dbs) {
+            (Some(id), _) => id,
+            (None, Some(dbs)) => return Wal::create(path, policy, dbs),
+            (None, None) => return Err(Error::new(ErrorKind::Other, "missing log header")),
+        };
+
+        Ok(Wal {
+            path: path.to_path_buf(),
+            f,
+            codec: MemdsCodec::with_chain(good_crc),
+            log_id,
+            offset: good_offset,
+            base_size: good_offset,
+            policy,
+            dirty: false,
+            rewrite: None,
+        })
+    }
+
+    fn write_msg(&mut self, msg: MemdsMessage) -> io::Result<()> {
+        // encode message into checksummed stream
+        let saved_codec = self.codec;
+        let msg_raw = &mut BytesMut::new();
+        self.codec.encode(msg, msg_raw).map_err(codec_err)?;
+
+        // append to log; upon failure, remove any partial record
+        if let Err(e) = self.f.write_all(msg_raw) {
+            self.codec = saved_codec;
+            let _ = self.f.set_len(self.offset);
+            return Err(e);
+        }
+        self.offset += msg_raw.len() as u64;
+        self.dirty = true;
+
+        if self.policy == FsyncPolicy::Always {
+            self.sync()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_op(&mut self, op: Operation) -> io::Result<()> {
+        let mut msg = MemdsMessage::new();
+        msg.mtype = MemdsMessage_MsgType::OPLOG;
+        msg.set_op(op);
+        self.write_msg(msg)
+    }
+
+    /// Append the loggable operations of an operation or batch, as
+    /// returned by `loggable_ops()`, all or none:  upon failure, any records written
+    /// are removed.
+    pub fn append_batch(&mut self, ops: &[Operation]) -> io::Result<()> {
+        let saved_codec = self.codec;
+        let saved_offset = self.offset;
+        for log_op in ops.iter() {
+            if let Err(e) = self.write_op(log_op.clone()) {
+                self.codec = saved_codec;
+                self.offset = saved_offset;
+                let _ = self.f.set_len(saved_offset);
+                return Err(e);
+            }
+        }
+
+        // buffer for new log, if being rewritten
+        if let Some(rw) = self.rewrite.as_mut() {
+            rw.buf.extend_from_slice(ops);
+        }
+
+        Ok(())
+    }
+
+    /// Flush log writes to stable storage, if any are pending.
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.dirty {
+            self.f.sync_data()?;
+            self.dirty = false;
+        }
+
+        Ok(())
+    }
+
+    /// Current end of log, for recording in a snapshot.
+    pub fn position(&self) -> LogPos {
+        let mut pos = LogPos::new();
+        pos.log_id = self.log_id;
+        pos.offset = self.offset;
+        pos.crc = self.codec.enc_chain();
+        pos
+    }
+
+    pub fn rewrite_in_progress(&self) -> bool {
+        self.rewrite.is_some()
+    }
+
+    /// Fork, and write a new log containing the dataset, in one or more
+    /// `parts`, from the child process.  Completed by `poll_rewrite()`.
+    pub fn start_rewrite(&mut self, parts: &[&DbSet]) -> io::Result<()> {
+        assert!(self.rewrite.is_none());
+
+        let log_id = new_log_id();
+        match fork() {
+            Ok(ForkResult::Parent { child, .. }) => {
+                self.rewrite = Some(Rewrite {
+                    child,
+                    log_id,
+                    buf: Vec::new(),
+                });
+                Ok(())
+            }
+
+            Ok(ForkResult::Child) => {
+                // child writes new log, then exits
+                let tmp_path = rewrite_path(&self.path);
+                let code = match Wal::create_with_id(&tmp_path, FsyncPolicy::Never, log_id, parts) {
+                    Ok(_) => 0,
+                    Err(e) => {
+                        println!("Internal error I/O - log rewrite: {:?}", e);
+                        1
+                    }
+                };
+                std::process::exit(code);
+            }
+
+            Err(_) => Err(Error::new(ErrorKind::Other, "fork")),
+        }
+    }
+
+    // append buffered writes to the rewritten log, then atomically
+    // replace the old log with it
+    fn finish_rewrite(&mut self, rw: Rewrite) -> io::Result<()> {
+        let tmp_path = rewrite_path(&self.path);
+        let mut new_wal = Wal::load(&tmp_path, FsyncPolicy::Never, None, None)?;
+        if new_wal.log_id != rw.log_id {
+            return Err(Error::new(ErrorKind::Other, "rewritten log id mismatch"));
+        }
+
+        for op in rw.buf {
+            new_wal.write_op(op)?;
+        }
+        new_wal.f.sync_all()?;
+
+        fs::rename(&tmp_path, &self.path)?;
+        util::sync_parent_dir(&self.path)?;
+
+        new_wal.path = self.path.clone();
+        new_wal.base_size = new_wal.offset;
+        new_wal.policy = self.policy;
+        new_wal.dirty = false;
+        *self = new_wal;
+
+        Ok(())
+    }
+
+    /// Check for completion of background rewrite; if complete, switch
+    /// to the new log.
+    pub fn poll_rewrite(&mut self) {
+        let child = match &self.rewrite {
+            None => return,
+            Some(rw) => rw.child,
+        };
+
+        let status = waitpid(child, Some(WaitPidFlag::WNOHANG));
+        match status {
+            Ok(WaitStatus::Exited(_, 0)) => {
+                let rw = self.rewrite.take().unwrap();
+                match self.finish_rewrite(rw) {
+                    Ok(()) => info!("operation log rewrite complete, {} bytes", self.offset),
+                    Err(e) => {
+                        error!("operation log rewrite failed: {:?}", e);
+                        let _ = fs::remove_file(rewrite_path(&self.path));
+                    }
+                }
+            }
+
+            Ok(WaitStatus::Exited(..)) | Ok(WaitStatus::Signaled(..)) | Err(_) => {
+                error!("operation log rewrite child failed: {:?}", status);
+                self.rewrite = None;
+                let _ = fs::remove_file(rewrite_path(&self.path));
+            }
+
+            // still running
+            Ok(_) => {}
+        }
+    }
+
+    /// Has the log grown enough, since the last rewrite, to rewrite it
+    /// automatically?  `pct` of zero disables.
+    pub fn rewrite_due(&self, pct: u64, min_size: u64) -> bool {
+        pct > 0
+            && self.rewrite.is_none()
+            && self.offset >= min_size
+            && self.offset - self.base_size >= self.base_size.saturating_mul(pct) / 100
+    }
+
+    /// Periodic maintenance, every FSYNC_INTERVAL_MS:  fsync under the
+    /// "everysec" policy, and rewrite completion.
+    pub fn cron(&mut self) {
+        if self.policy == FsyncPolicy::EverySec {
+            if let Err(e) = self.sync() {
+                error!("operation log sync failed: {:?}", e);
+            }
+        }
+
+        self.poll_rewrite();
+    }
+
+    /// Automatic rewrite, of the dataset in `parts`, if due.
+    pub fn auto_rewrite(&mut self, parts: &[&DbSet], rewrite_pct: u64, rewrite_min_size: u64) {
+        if self.rewrite_due(rewrite_pct, rewrite_min_size) {
+            info!("operation log grown to {} bytes; rewriting", self.offset);
+            if let Err(e) = self.start_rewrite(parts) {
+                error!("operation log rewrite failed: {:?}", e);
+            }
+        }
+    }
+}
+
+// are all bytes of file, from offset to EOF, zero?
+fn tail_is_zero(f: &mut File, offset: u64) -> io::Result<bool> {
+    f.seek(SeekFrom::Start(offset))?;
+    let mut buf = [0; 4096];
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        if buf[0..n].iter().any(|b| *b != 0) {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{self, now_ms, DbSet, DEF_DB};
+    use crate::wal::{expired_ops, loggable_ops, FsyncPolicy, Wal};
+    use memds_proto::memds_api::{KeyExpireOp, KeyedListOp, NumOp, OpType, Operation, StrSetOp};
+    use memds_proto::Atom;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn test_path(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("memds-test-{}-{}.aof", name, std::process::id()));
+        path
+    }
+
+    fn incr_op(key: &[u8]) -> Operation {
+        let mut req = NumOp::new();
+        req.set_key(key.to_vec());
+        let mut op = Operation::new();
+        op.otype = OpType::STR_INCR;
+        op.set_num(req);
+        op
+    }
+
+    // log operation, just applied to db `db_key`
+    fn append(wal: &mut Wal, dbs: &DbSet, db_key: &[u8], op: &Operation) {
+        wal.append_batch(&loggable_ops(dbs, db_key, op)).unwrap();
+    }
+
+    fn get_str(dbs: &mut DbSet, db_key: &[u8], key: &[u8]) -> Vec<u8> {
+        match dbs.db_mut(db_key).get(key) {
+            Some(Atom::String(s)) => s.clone(),
+            _ => panic!("not a string"),
+        }
+    }
+
+    #[test]
+    fn append_replay() {
+        let path = test_path("replay");
+        let mut dbs = DbSet::new();
+        let mut wal = Wal::create(&path, FsyncPolicy::Always, &dbs).unwrap();
+
+        // set with relative expiration, then incr
+        let mut req = StrSetOp::new();
+        req.set_key(b"foo".to_vec());
+        req.set_value(b"1".to_vec());
+        req.expire_ms = 60000;
+        let mut op = Operation::new();
+        op.otype = OpType::STR_SET;
+        op.set_set(req);
+        crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+        append(&mut wal, &dbs, DEF_DB, &op);
+        let expires = dbs.db_mut(DEF_DB).expire_time(b"foo").unwrap();
+
+        let op = incr_op(b"foo");
+        crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+        append(&mut wal, &dbs, DEF_DB, &op);
+        let pos = wal.position();
+        drop(wal);
+
+        // replay entire log, replacing unrelated snapshot
+        let mut dbs = DbSet::new();
+        dbs.db_mut(DEF_DB)
+            .insert(b"stale".to_vec(), Atom::String(b"x".to_vec()));
+        let mut wal = Wal::open(&path, FsyncPolicy::Always, &mut dbs, None).unwrap();
+        assert_eq!(get_str(&mut dbs, DEF_DB, b"foo"), b"2");
+        assert!(!dbs.db_mut(DEF_DB).contains_key(b"stale"));
+        assert_eq!(dbs.db_mut(DEF_DB).expire_time(b"foo"), Some(expires));
+
+        // replay from snapshot position:  only later records applied
+        let op = incr_op(b"foo");
+        crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+        append(&mut wal, &dbs, DEF_DB, &op);
+        drop(wal);
+
+        let mut dbs = DbSet::new();
+        dbs.db_mut(DEF_DB)
+            .insert(b"foo".to_vec(), Atom::String(b"2".to_vec()));
+        Wal::open(&path, FsyncPolicy::Always, &mut dbs, Some(&pos)).unwrap();
+        assert_eq!(get_str(&mut dbs, DEF_DB, b"foo"), b"3");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replay_expiry() {
+        let path = test_path("replay-expiry");
+        let mut dbs = DbSet::new();
+        let mut wal = Wal::create(&path, FsyncPolicy::Always, &dbs).unwrap();
+        let expires = now_ms() - 1000;
+
+        // before expiry: set "a" and "b", expiring; incr "a"
+        db::with_clock(expires - 1, || {
+            for key in [b"a", b"b"] {
+                let mut req = StrSetOp::new();
+                req.set_key(key.to_vec());
+                req.set_value(b"1".to_vec());
+                let mut op = Operation::new();
+                op.otype = OpType::STR_SET;
+                op.set_set(req);
+                crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+                append(&mut wal, &dbs, DEF_DB, &op);
+
+                let mut req = KeyExpireOp::new();
+                req.set_key(key.to_vec());
+                req.expire_ms = expires as i64;
+                req.absolute = true;
+                let mut op = Operation::new();
+                op.otype = OpType::KEY_EXPIRE;
+                op.set_expire(req);
+                crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+                append(&mut wal, &dbs, DEF_DB, &op);
+            }
+
+            let op = incr_op(b"a");
+            crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+            append(&mut wal, &dbs, DEF_DB, &op);
+        });
+
+        // after expiry: "b" removed, then recreated by incr
+        let expired = dbs.expire_keys(&[b"b"]);
+        assert_eq!(expired.len(), 1);
+        wal.append_batch(&expired_ops(&expired)).unwrap();
+        let op = incr_op(b"b");
+        crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+        append(&mut wal, &dbs, DEF_DB, &op);
+        drop(wal);
+
+        // replay, long after expiry:  "a" remains expired, "b" does not
+        let mut dbs = DbSet::new();
+        Wal::open(&path, FsyncPolicy::Always, &mut dbs, None).unwrap();
+        let db = dbs.db_mut(DEF_DB);
+        assert_eq!(db.expire_time(b"a"), Some(expires));
+        assert!(db.peek(b"a").is_none());
+        assert_eq!(db.expire_time(b"b"), None);
+        assert_eq!(get_str(&mut dbs, DEF_DB, b"b"), b"1");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replay_past_expireat() {
+        let path = test_path("replay-expireat");
+        let mut dbs = DbSet::new();
+        let mut wal = Wal::create(&path, FsyncPolicy::Always, &dbs).unwrap();
+
+        // sadd, expireat in the past (deleting set), sadd
+        let sadd_op = |elem: &[u8]| {
+            let mut req = KeyedListOp::new();
+            req.set_key(b"s".to_vec());
+            req.elements.push(elem.to_vec());
+            let mut op = Operation::new();
+            op.otype = OpType::SET_ADD;
+            op.set_keyed_list(req);
+            op
+        };
+        let op = sadd_op(b"x");
+        crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+        append(&mut wal, &dbs, DEF_DB, &op);
+
+        let mut req = KeyExpireOp::new();
+        req.set_key(b"s".to_vec());
+        req.expire_ms = (now_ms() - 1000) as i64;
+        req.absolute = true;
+        let mut op = Operation::new();
+        op.otype = OpType::KEY_EXPIRE;
+        op.set_expire(req);
+        assert!(crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op).ok);
+        assert!(!dbs.db_mut(DEF_DB).contains_key(b"s"));
+        append(&mut wal, &dbs, DEF_DB, &op);
+
+        let op = sadd_op(b"y");
+        crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+        append(&mut wal, &dbs, DEF_DB, &op);
+        drop(wal);
+
+        // replay:  the set holds the later element alone
+        let mut dbs = DbSet::new();
+        Wal::open(&path, FsyncPolicy::Always, &mut dbs, None).unwrap();
+        match dbs.db_mut(DEF_DB).get(b"s") {
+            Some(Atom::Set(s)) => {
+                assert_eq!(s.len(), 1);
+                assert!(s.contains(b"y".as_slice()));
+            }
+            _ => panic!("not a set"),
+        }
+        assert_eq!(dbs.db_mut(DEF_DB).expire_time(b"s"), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn torn_tail() {
+        let path = test_path("torn");
+        let mut dbs = DbSet::new();
+        let mut wal = Wal::create(&path, FsyncPolicy::Never, &dbs).unwrap();
+        let op = incr_op(b"n");
+        append(&mut wal, &dbs, DEF_DB, &op);
+        append(&mut wal, &dbs, DEF_DB, &op);
+        drop(wal);
+
+        // simulate partial write of a third record
+        let good_len = std::fs::metadata(&path).unwrap().len();
+        let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+        f.write_all(&[0x4d, 0, 0, 20, 1, 2]).unwrap();
+        drop(f);
+
+        // replay truncates tail, and appends continue the chain
+        let mut wal = Wal::open(&path, FsyncPolicy::Never, &mut dbs, None).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), good_len);
+        assert_eq!(get_str(&mut dbs, DEF_DB, b"n"), b"2");
+        append(&mut wal, &dbs, DEF_DB, &op);
+        drop(wal);
+
+        let mut dbs = DbSet::new();
+        Wal::open(&path, FsyncPolicy::Never, &mut dbs, None).unwrap();
+        assert_eq!(get_str(&mut dbs, DEF_DB, b"n"), b"3");
+
+        // corruption followed by non-zero data is an error
+        let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+        f.write_all(&[0xff; 16]).unwrap();
+        drop(f);
+        let mut dbs = DbSet::new();
+        assert!(Wal::open(&path, FsyncPolicy::Never, &mut dbs, None).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rewrite() {
+        let path = test_path("rewrite");
+        let mut dbs = DbSet::new();
+        let mut wal = Wal::create(&path, FsyncPolicy::Never, &dbs).unwrap();
+        let op = incr_op(b"n");
+        for _ in 0..100 {
+            crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+            append(&mut wal, &dbs, DEF_DB, &op);
+        }
+        let old_len = wal.position().offset;
+        assert!(wal.rewrite_due(100, 0));
+
+        // writes during rewrite are carried over to new log
+        wal.start_rewrite(&[&dbs]).unwrap();
+        assert!(wal.rewrite_in_progress());
+        crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+        append(&mut wal, &dbs, DEF_DB, &op);
+        while wal.rewrite_in_progress() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            wal.poll_rewrite();
+        }
+        assert!(wal.position().offset < old_len);
+        assert!(!wal.rewrite_due(100, 0));
+
+        // appends continue on new log, in any database
+        crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+        append(&mut wal, &dbs, DEF_DB, &op);
+        crate::rpcservice::exec_op(&mut dbs, b"other", &op);
+        append(&mut wal, &dbs, b"other", &op);
+        drop(wal);
+
+        let mut dbs = DbSet::new();
+        Wal::open(&path, FsyncPolicy::Never, &mut dbs, None).unwrap();
+        assert_eq!(get_str(&mut dbs, DEF_DB, b"n"), b"102");
+        assert_eq!(get_str(&mut dbs, b"other", b"n"), b"1");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn expire_absolute() {
+        let mut dbs = DbSet::new();
+        dbs.db_mut(DEF_DB)
+            .insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+
+        let mut req = KeyExpireOp::new();
+        req.set_key(b"foo".to_vec());
+        req.expire_ms = 60000;
+        let mut op = Operation::new();
+        op.otype = OpType::KEY_EXPIRE;
+        op.set_expire(req);
+        crate::rpcservice::exec_op(&mut dbs, DEF_DB, &op);
+
+        let ops = super::loggable_ops(&dbs, DEF_DB, &op);
+        assert_eq!(ops.len(), 1);
+        assert!(ops[0].get_expire().absolute);
+        assert!(ops[0].get_expire().expire_ms as u64 > now_ms());
+    }
+}
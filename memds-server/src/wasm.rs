@@ -0,0 +1,767 @@
+use std::mem;
+use std::path::Path;
+
+use protobuf::Message;
+use wasmi::core::TrapCode;
+use wasmi::{Caller, Config, Engine, Error, Extern, Instance, Linker, Module, Store};
+
+use memds_proto::memds_api::{
+    DbValue, ErrorCode, KeyListOp, KeyOp, ListRes, ModuleCallOp, ModuleInfo, ModuleOp, ModuleRes,
+    OpResult, OpType, Operation, StrSetOp,
+};
+
+use crate::acl::{self, User};
+use crate::db::DbSet;
+use crate::error::OpError;
+use crate::keys;
+use crate::registry::{
+    Access, Handler, KeyScope, Method, ServerCtx, ADMIN, ARG_MODULE, ARG_MODULE_CALL, DANGEROUS,
+};
+use crate::wal;
+
+// execution limit of a single module call, in wasmi fuel units
+const FUEL_PER_CALL: u64 = 100_000_000;
+
+// module export called upon load, to register the module's methods
+const INIT_EXPORT: &str = "memds_init";
+
+// method flags, as passed to register()
+const METHOD_WRITE: i32 = 1 << 0;
+
+struct ModuleMethod {
+    name: String,
+    write: bool,
+}
+
+// module instance state, visible to host API
+struct Host {
+    dbs: DbSet, // moved in for the duration of each call
+    db_key: Vec<u8>,
+    write: bool, // method called may write to database
+    keys: Vec<Vec<u8>>,
+    args: Vec<Vec<u8>>,
+    replies: Vec<Vec<u8>>,
+    error: Option<String>,
+    touched: Vec<Vec<u8>>, // keys written, in order first written
+    user: User,            // on whose behalf method runs
+
+    // methods registered; only during initialization
+    methods: Option<Vec<ModuleMethod>>,
+}
+
+impl Host {
+    fn new() -> Host {
+        Host {
+            dbs: DbSet::new(),
+            db_key: Vec::new(),
+            write: false,
+            keys: Vec::new(),
+            args: Vec::new(),
+            replies: Vec::new(),
+            error: None,
+            touched: Vec::new(),
+            user: User::new(""),
+            methods: None,
+        }
+    }
+
+    // reads or writes of key must be of a declared key, whose keyspace
+    // shard is held for the call, and permitted the calling user
+    fn check(&self, cat: u32, key: &[u8]) -> Result<(), Error> {
+        if !self.keys.iter().any(|k| k == key) {
+            let msg = format!(
+                "Key {} not declared:  methods may access only the keys passed to them",
+                String::from_utf8_lossy(key)
+            );
+            return Err(Error::new(msg));
+        }
+        if self.user.categories & cat == 0 {
+            let access = if cat == acl::CAT_READ {
+                "read"
+            } else {
+                "write"
+            };
+            let msg = format!(
+                "User {} has no permissions to {} keys",
+                self.user.name, access
+            );
+            return Err(Error::new(msg));
+        }
+        self.user.check_key(key).map_err(|e| Error::new(e.message))
+    }
+
+    fn touch(&mut self, key: Vec<u8>) {
+        if !self.touched.contains(&key) {
+            self.touched.push(key);
+        }
+    }
+}
+
+struct WasmModule {
+    name: String,
+    path: String,
+    methods: Vec<ModuleMethod>,
+    store: Store<Host>,
+    instance: Instance,
+}
+
+/// WASM modules loaded into the server, each registering methods under
+/// its own namespace.
+pub struct ModuleSet {
+    engine: Engine,
+    linker: Linker<Host>,
+    modules: Vec<WasmModule>,
+}
+
+// module memory range, as bytes
+fn read_mem(caller: &Caller<Host>, ptr: i32, len: i32) -> Result<Vec<u8>, Error> {
+    let memory = match caller.get_export("memory").and_then(Extern::into_memory) {
+        None => return Err(Error::new("module exports no memory")),
+        Some(memory) => memory,
+    };
+
+    // bounds checked before copying:  the range is the module's to name
+    let data = memory.data(caller);
+    let start = ptr as u32 as usize;
+    let range = start
+        .checked_add(len as u32 as usize)
+        .and_then(|end| data.get(start..end));
+    match range {
+        None => Err(Error::new("out of bounds memory access")),
+        Some(bytes) => Ok(bytes.to_vec()),
+    }
+}
+
+// copy value into module buffer if it fits, returning value length
+fn write_mem(caller: &mut Caller<Host>, ptr: i32, cap: i32, value: &[u8]) -> Result<i64, Error> {
+    let memory = match caller.get_export("memory").and_then(Extern::into_memory) {
+        None => return Err(Error::new("module exports no memory")),
+        Some(memory) => memory,
+    };
+
+    if value.len() <= cap as u32 as usize {
+        memory
+            .write(caller, ptr as u32 as usize, value)
+            .map_err(|e| Error::new(e.to_string()))?;
+    }
+    Ok(value.len() as i64)
+}
+
+// host API, imported by modules from "memds".  keys, arguments and values
+// are passed as (pointer, length) into module memory; values returned
+// are copied into a module buffer, returning their length, or -1 if none.
+fn host_api(engine: &Engine) -> Linker<Host> {
+    let mut linker = Linker::new(engine);
+
+    // register(name, flags): register method, during initialization
+    linker
+        .func_wrap(
+            "memds",
+            "register",
+            |mut caller: Caller<Host>, ptr: i32, len: i32, flags: i32| -> Result<i32, Error> {
+                let name = read_mem(&caller, ptr, len)?;
+                let name = String::from_utf8_lossy(&name).to_string();
+                match caller.data_mut().methods.as_mut() {
+                    None => Ok(-1),
+                    Some(methods) => {
+                        let write = (flags & METHOD_WRITE) != 0;
+                        methods.push(ModuleMethod { name, write });
+                        Ok(0)
+                    }
+                }
+            },
+        )
+        .unwrap();
+
+    // key_count(), arg_count()
+    linker
+        .func_wrap("memds", "key_count", |caller: Caller<Host>| -> i32 {
+            caller.data().keys.len() as i32
+        })
+        .unwrap();
+    linker
+        .func_wrap("memds", "arg_count", |caller: Caller<Host>| -> i32 {
+            caller.data().args.len() as i32
+        })
+        .unwrap();
+
+    // key(index, buf, cap), arg(index, buf, cap)
+    linker
+        .func_wrap(
+            "memds",
+            "key",
+            |mut caller: Caller<Host>, index: i32, ptr: i32, cap: i32| -> Result<i64, Error> {
+                match caller.data().keys.get(index as u32 as usize).cloned() {
+                    None => Ok(-1),
+                    Some(key) => write_mem(&mut caller, ptr, cap, &key),
+                }
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "memds",
+            "arg",
+            |mut caller: Caller<Host>, index: i32, ptr: i32, cap: i32| -> Result<i64, Error> {
+                match caller.data().args.get(index as u32 as usize).cloned() {
+                    None => Ok(-1),
+                    Some(arg) => write_mem(&mut caller, ptr, cap, &arg),
+                }
+            },
+        )
+        .unwrap();
+
+    // get(key, buf, cap): item at key, as encoded DbValue
+    linker
+        .func_wrap(
+            "memds",
+            "get",
+            |mut caller: Caller<Host>,
+             key_ptr: i32,
+             key_len: i32,
+             ptr: i32,
+             cap: i32|
+             -> Result<i64, Error> {
+                let key = read_mem(&caller, key_ptr, key_len)?;
+                let host = caller.data_mut();
+                host.check(acl::CAT_READ, &key)?;
+                let dbv = match host.dbs.get(&host.db_key) {
+                    None => None,
+                    Some(db) => keys::export_dbv(db, &key),
+                };
+                match dbv {
+                    None => Ok(-1),
+                    Some(dbv) => {
+                        let value = dbv
+                            .write_to_bytes()
+                            .map_err(|e| Error::new(e.to_string()))?;
+                        write_mem(&mut caller, ptr, cap, &value)
+                    }
+                }
+            },
+        )
+        .unwrap();
+
+    // put(key, value): store item at key, from encoded DbValue
+    linker
+        .func_wrap(
+            "memds",
+            "put",
+            |mut caller: Caller<Host>,
+             key_ptr: i32,
+             key_len: i32,
+             ptr: i32,
+             len: i32|
+             -> Result<i32, Error> {
+                let key = read_mem(&caller, key_ptr, key_len)?;
+                let value = read_mem(&caller, ptr, len)?;
+                let host = caller.data_mut();
+                if !host.write || key.is_empty() {
+                    return Ok(-1);
+                }
+                host.check(acl::CAT_WRITE, &key)?;
+                let dbv = match DbValue::parse_from_bytes(&value) {
+                    Err(_) => return Ok(-1),
+                    Ok(dbv) => dbv,
+                };
+                if !keys::import_dbv(host.dbs.db_mut(&host.db_key), Some(&key), &dbv) {
+                    return Ok(-1);
+                }
+                host.touch(key);
+                Ok(0)
+            },
+        )
+        .unwrap();
+
+    // del(key): remove item at key, returning 1 if removed
+    linker
+        .func_wrap(
+            "memds",
+            "del",
+            |mut caller: Caller<Host>, key_ptr: i32, key_len: i32| -> Result<i32, Error> {
+                let key = read_mem(&caller, key_ptr, key_len)?;
+                let host = caller.data_mut();
+                if !host.write {
+                    return Ok(-1);
+                }
+                host.check(acl::CAT_WRITE, &key)?;
+                if host.dbs.db_mut(&host.db_key).remove(&key).is_none() {
+                    return Ok(0);
+                }
+                host.touch(key);
+                Ok(1)
+            },
+        )
+        .unwrap();
+
+    // reply(value): append value to method result
+    linker
+        .func_wrap(
+            "memds",
+            "reply",
+            |mut caller: Caller<Host>, ptr: i32, len: i32| -> Result<(), Error> {
+                let value = read_mem(&caller, ptr, len)?;
+                caller.data_mut().replies.push(value);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+    // error(message): error message, upon method failure
+    linker
+        .func_wrap(
+            "memds",
+            "error",
+            |mut caller: Caller<Host>, ptr: i32, len: i32| -> Result<(), Error> {
+                let message = read_mem(&caller, ptr, len)?;
+                caller.data_mut().error = Some(String::from_utf8_lossy(&message).to_string());
+                Ok(())
+            },
+        )
+        .unwrap();
+
+    linker
+}
+
+// failure of module code:  trap, or fuel exhausted
+fn module_error(err: Error) -> OpError {
+    match err.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => OpError::new(ErrorCode::TIMEOUT, "Module fuel exhausted"),
+        _ => OpError::new(ErrorCode::MODULE_ERROR, &format!("Module error: {}", err)),
+    }
+}
+
+// compile and instantiate module, calling its initialization export
+fn instantiate(
+    engine: &Engine,
+    linker: &Linker<Host>,
+    wasm: &[u8],
+) -> Result<(Store<Host>, Instance, Vec<ModuleMethod>), OpError> {
+    let module = Module::new(engine, wasm).map_err(module_error)?;
+    let mut store = Store::new(engine, Host::new());
+    store.set_fuel(FUEL_PER_CALL).unwrap();
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|pre| pre.start(&mut store))
+        .map_err(module_error)?;
+
+    store.data_mut().methods = Some(Vec::new());
+    let rc = instance
+        .get_typed_func::<(), i32>(&store, INIT_EXPORT)
+        .and_then(|init| init.call(&mut store, ()))
+        .map_err(module_error)?;
+    let methods = store.data_mut().methods.take().unwrap();
+    if rc != 0 {
+        let msg = format!("Module initialization failed: {}", rc);
+        return Err(OpError::new(ErrorCode::MODULE_ERROR, &msg));
+    }
+
+    Ok((store, instance, methods))
+}
+
+impl ModuleSet {
+    pub fn new() -> ModuleSet {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let linker = host_api(&engine);
+
+        ModuleSet {
+            engine,
+            linker,
+            modules: Vec::new(),
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.modules
+            .iter()
+            .position(|m| m.name.eq_ignore_ascii_case(name))
+    }
+}
+
+pub fn load(modules: &mut ModuleSet, req: &ModuleOp) -> OpResult {
+    // namespace:  as requested, else file stem
+    let path = Path::new(req.get_path());
+    let name = match req.get_name() {
+        "" => match path.file_stem() {
+            None => return OpError::invalid_args("Module name required").into(),
+            Some(stem) => stem.to_string_lossy().to_string(),
+        },
+        name => name.to_string(),
+    };
+    if modules.position(&name).is_some() {
+        let msg = format!("Module {} already loaded", name);
+        return OpError::new(ErrorCode::EXISTS, &msg).into();
+    }
+
+    let wasm = match std::fs::read(path) {
+        Err(e) => {
+            let msg = format!("{}: {}", path.display(), e);
+            return OpError::invalid_args(&msg).into();
+        }
+        Ok(wasm) => wasm,
+    };
+    let (store, instance, methods) = match instantiate(&modules.engine, &modules.linker, &wasm) {
+        Err(err) => return err.into(),
+        Ok(res) => res,
+    };
+
+    modules.modules.push(WasmModule {
+        name,
+        path: req.get_path().to_string(),
+        methods,
+        store,
+        instance,
+    });
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::MODULE_LOAD;
+
+    op_res
+}
+
+pub fn unload(modules: &mut ModuleSet, req: &ModuleOp) -> OpResult {
+    match modules.position(req.get_name()) {
+        None => return OpError::not_found().into(),
+        Some(pos) => modules.modules.remove(pos),
+    };
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::MODULE_UNLOAD;
+
+    op_res
+}
+
+pub fn list(modules: &ModuleSet) -> OpResult {
+    let mut module_res = ModuleRes::new();
+    for module in modules.modules.iter() {
+        let mut info = ModuleInfo::new();
+        info.set_name(module.name.clone());
+        info.set_path(module.path.clone());
+        for method in module.methods.iter() {
+            info.methods
+                .push(format!("{}.{}", module.name, method.name));
+            info.write.push(method.write);
+        }
+        module_res.modules.push(info);
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::MODULE_LIST;
+    op_res.set_module(module_res);
+
+    op_res
+}
+
+// operation log records of keys written by module:  their final state
+fn touched_ops(dbs: &mut DbSet, db_key: &[u8], touched: &[Vec<u8>]) -> Vec<Operation> {
+    let mut log_ops = Vec::new();
+    for key in touched.iter() {
+        let mut key_req = KeyOp::new();
+        key_req.set_key(key.clone());
+        let dump_res = keys::dump(dbs.db_mut(db_key), &key_req);
+
+        let mut op = Operation::new();
+        if dump_res.ok {
+            let mut restore_req = StrSetOp::new();
+            restore_req.set_key(key.clone());
+            restore_req.set_value(dump_res.get_get().value.clone());
+            op.otype = OpType::KEY_RESTORE;
+            op.set_set(restore_req);
+        } else {
+            let mut del_req = KeyListOp::new();
+            del_req.keys.push(key.clone());
+            op.otype = OpType::KEYS_DEL;
+            op.set_key_list(del_req);
+        }
+        log_ops.extend(wal::loggable_ops(dbs, db_key, &op));
+    }
+    dbs.prune(db_key);
+
+    log_ops
+}
+
+/// Call module method against the selected database.  The call itself
+/// is not logged; the final state of the keys it wrote is.
+pub fn call(ctx: &mut ServerCtx, req: &ModuleCallOp) -> OpResult {
+    let (ns, method_name) = match req.get_method().split_once('.') {
+        None => return OpError::invalid_args("Method name NAMESPACE.METHOD required").into(),
+        Some(names) => names,
+    };
+    let mut modules = ctx.modules.lock().unwrap();
+    let module = match modules.position(ns) {
+        None => return OpError::not_found().into(),
+        Some(pos) => &mut modules.modules[pos],
+    };
+    let method = match module.methods.iter().find(|m| m.name == method_name) {
+        None => return OpError::not_found().into(),
+        Some(method) => method,
+    };
+
+    let user = match ctx.acl.read().unwrap().user(ctx.user.as_deref()) {
+        Ok(user) => user.clone(),
+        Err(err) => return err.into(),
+    };
+
+    // module needs the databases for itself, for the duration of call
+    let host = module.store.data_mut();
+    host.user = user;
+    host.dbs = mem::replace(ctx.dbs, DbSet::new());
+    host.db_key = ctx.db_key.to_vec();
+    host.write = method.write;
+    host.keys = req.keys.to_vec();
+    host.args = req.args.to_vec();
+    host.replies.clear();
+    host.error = None;
+    host.touched.clear();
+
+    module.store.set_fuel(FUEL_PER_CALL).unwrap();
+    let res = module
+        .instance
+        .get_typed_func::<(), i32>(&module.store, &method.name)
+        .and_then(|f| f.call(&mut module.store, ()));
+
+    let host = module.store.data_mut();
+    *ctx.dbs = mem::replace(&mut host.dbs, DbSet::new());
+
+    // effects persist even if method failed part way
+    let log_ops = touched_ops(ctx.dbs, ctx.db_key, &host.touched);
+    let mut wal = ctx.wal.lock().unwrap();
+    ctx.snap.lock().unwrap().dirty += host.touched.len() as u64;
+    if let Some(w) = wal.as_mut() {
+        if let Err(e) = w.append_batch(&log_ops) {
+            error!("operation log write failed: {:?}", e);
+            return OpError::internal("Internal error - log write").into();
+        }
+    }
+
+    match res {
+        Err(err) => return module_error(err).into(),
+        Ok(0) => {}
+        Ok(rc) => {
+            let msg = match host.error.take() {
+                None => format!("Module method failed: {}", rc),
+                Some(msg) => msg,
+            };
+            return OpError::new(ErrorCode::MODULE_ERROR, &msg).into();
+        }
+    }
+
+    let mut list_res = ListRes::new();
+    for reply in host.replies.drain(..) {
+        list_res.elements.push(reply);
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+    op_res.ok = true;
+    op_res.otype = OpType::MODULE_CALL;
+    op_res.set_list(list_res);
+
+    op_res
+}
+
+/// Module module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "Module",
+        name: "Load",
+        otype: OpType::MODULE_LOAD,
+        arg: Some(ARG_MODULE),
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Server(|ctx, op| load(&mut ctx.modules.lock().unwrap(), op.get_module())),
+    },
+    Method {
+        module: "Module",
+        name: "Unload",
+        otype: OpType::MODULE_UNLOAD,
+        arg: Some(ARG_MODULE),
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: ADMIN | DANGEROUS,
+        handler: Handler::Server(|ctx, op| {
+            unload(&mut ctx.modules.lock().unwrap(), op.get_module())
+        }),
+    },
+    Method {
+        module: "Module",
+        name: "List",
+        otype: OpType::MODULE_LIST,
+        arg: None,
+        scope: KeyScope::None,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::Server(|ctx, _op| list(&ctx.modules.lock().unwrap())),
+    },
+    Method {
+        module: "Module",
+        name: "Call",
+        otype: OpType::MODULE_CALL,
+        arg: Some(ARG_MODULE_CALL),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::Server(|ctx, op| call(ctx, op.get_module_call())),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::acl::Acl;
+    use crate::db::{DbSet, DEF_DB};
+    use crate::registry::ServerCtx;
+    use crate::script::{self, ScriptCache};
+    use crate::server::SaveState;
+    use crate::wasm::{self, ModuleSet};
+    use memds_proto::memds_api::{ErrorCode, ModuleCallOp, ModuleOp};
+    use memds_proto::Atom;
+    use std::path::PathBuf;
+    use std::sync::{mpsc, Mutex, RwLock};
+
+    // echo: reply with first argument
+    // copy: copy item at first key to second key
+    // spin: loop forever
+    // huge: reply with a range beyond memory
+    const TEST_MODULE: &str = r#"
+        (module
+          (import "memds" "register" (func $register (param i32 i32 i32) (result i32)))
+          (import "memds" "key" (func $key (param i32 i32 i32) (result i64)))
+          (import "memds" "arg" (func $arg (param i32 i32 i32) (result i64)))
+          (import "memds" "get" (func $get (param i32 i32 i32 i32) (result i64)))
+          (import "memds" "put" (func $put (param i32 i32 i32 i32) (result i32)))
+          (import "memds" "reply" (func $reply (param i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "echo")
+          (data (i32.const 8) "copy")
+          (data (i32.const 16) "spin")
+          (data (i32.const 24) "huge")
+          (func (export "memds_init") (result i32)
+            (drop (call $register (i32.const 0) (i32.const 4) (i32.const 0)))
+            (drop (call $register (i32.const 8) (i32.const 4) (i32.const 1)))
+            (drop (call $register (i32.const 16) (i32.const 4) (i32.const 0)))
+            (drop (call $register (i32.const 24) (i32.const 4) (i32.const 0)))
+            (i32.const 0))
+          (func (export "echo") (result i32)
+            (local $n i32)
+            (local.set $n (i32.wrap_i64 (call $arg (i32.const 0) (i32.const 1024) (i32.const 1024))))
+            (call $reply (i32.const 1024) (local.get $n))
+            (i32.const 0))
+          (func (export "copy") (result i32)
+            (local $k0 i32) (local $k1 i32) (local $v i32)
+            (local.set $k0 (i32.wrap_i64 (call $key (i32.const 0) (i32.const 256) (i32.const 64))))
+            (local.set $k1 (i32.wrap_i64 (call $key (i32.const 1) (i32.const 320) (i32.const 64))))
+            (local.set $v (i32.wrap_i64
+              (call $get (i32.const 256) (local.get $k0) (i32.const 2048) (i32.const 4096))))
+            (if (i32.lt_s (local.get $v) (i32.const 0)) (then (return (i32.const 1))))
+            (call $put (i32.const 320) (local.get $k1) (i32.const 2048) (local.get $v)))
+          (func (export "spin") (result i32)
+            (loop $l (br $l))
+            (i32.const 0))
+          (func (export "huge") (result i32)
+            (call $reply (i32.const 16) (i32.const -1))
+            (i32.const 0)))
+    "#;
+
+    #[test]
+    fn modules() {
+        let path = std::env::temp_dir().join(format!("memds-test-{}.wasm", std::process::id()));
+        std::fs::write(&path, wat::parse_str(TEST_MODULE).unwrap()).unwrap();
+
+        let mut dbs = DbSet::new();
+        dbs.db_mut(DEF_DB)
+            .insert(b"a".to_vec(), Atom::List(vec![b"x".to_vec()]));
+        let snap = Mutex::new(SaveState::new(PathBuf::from("unused"), Vec::new()));
+        let scripts = Mutex::new(ScriptCache::new(script::DEF_TIME_LIMIT_MS));
+        let modules = Mutex::new(ModuleSet::new());
+        let acl = RwLock::new(Acl::new());
+        let (shutdown_tx, _shutdown_rx) = mpsc::channel();
+        let mut ctx = ServerCtx {
+            dbs: &mut dbs,
+            parts: &[],
+            db_key: DEF_DB,
+            wal: &Mutex::new(None),
+            snap: &snap,
+            scripts: &scripts,
+            modules: &modules,
+            acl: &acl,
+            user: &mut None,
+            shutdown_tx: &shutdown_tx,
+        };
+
+        // load, registering methods under namespace
+        let mut req = ModuleOp::new();
+        req.set_path(path.to_string_lossy().to_string());
+        req.set_name(String::from("test"));
+        assert_eq!(wasm::load(&mut ctx.modules.lock().unwrap(), &req).ok, true);
+        assert_eq!(
+            wasm::load(&mut ctx.modules.lock().unwrap(), &req).err_code,
+            ErrorCode::EXISTS
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let res = wasm::list(&ctx.modules.lock().unwrap());
+        let info = &res.get_module().modules[0];
+        assert_eq!(
+            info.methods.to_vec(),
+            vec!["test.echo", "test.copy", "test.spin", "test.huge"]
+        );
+        assert_eq!(info.write, vec![false, true, false, false]);
+
+        let mut call_req = ModuleCallOp::new();
+        call_req.set_method(String::from("test.echo"));
+        call_req.args.push(b"hello".to_vec());
+        let res = wasm::call(&mut ctx, &call_req);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_list().elements.to_vec(), vec![b"hello".to_vec()]);
+
+        // write through host API
+        let mut call_req = ModuleCallOp::new();
+        call_req.set_method(String::from("test.copy"));
+        call_req.keys.push(b"a".to_vec());
+        call_req.keys.push(b"b".to_vec());
+        let res = wasm::call(&mut ctx, &call_req);
+        assert_eq!(res.ok, true);
+        assert_eq!(ctx.snap.lock().unwrap().dirty, 1);
+        match ctx.dbs.db_mut(DEF_DB).get(b"b") {
+            Some(Atom::List(l)) => assert_eq!(l, &vec![b"x".to_vec()]),
+            _ => panic!("not a list"),
+        }
+
+        // fuel exhausted
+        call_req.set_method(String::from("test.spin"));
+        let res = wasm::call(&mut ctx, &call_req);
+        assert_eq!(res.err_code, ErrorCode::TIMEOUT);
+
+        // memory range out of bounds traps, allocating nothing
+        call_req.set_method(String::from("test.huge"));
+        let res = wasm::call(&mut ctx, &call_req);
+        assert_eq!(res.err_code, ErrorCode::MODULE_ERROR);
+
+        // unknown method; unload
+        call_req.set_method(String::from("test.nope"));
+        let res = wasm::call(&mut ctx, &call_req);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
+        let mut req = ModuleOp::new();
+        req.set_name(String::from("test"));
+        assert_eq!(
+            wasm::unload(&mut ctx.modules.lock().unwrap(), &req).ok,
+            true
+        );
+        assert_eq!(
+            wasm::list(&ctx.modules.lock().unwrap())
+                .get_module()
+                .modules
+                .len(),
+            0
+        );
+    }
+}
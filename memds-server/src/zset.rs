@@ -0,0 +1,996 @@
+use std::collections::HashMap;
+
+use memds_proto::memds_api::{
+    CountRes, ErrorCode, KeyOp, KeyedListOp, OpResult, OpType, ScoredMember, ZAddOp, ZPopOp,
+    ZRangeOp, ZRangeOp_RangeBy, ZSetRes, ZStoreOp, ZStoreOp_Aggregate,
+};
+use memds_proto::{Atom, SortedSet};
+
+use crate::db::Db;
+use crate::error::OpError;
+use crate::registry::{
+    Access, Handler, KeyScope, Method, ARG_KEY, ARG_KEYED_LIST, ARG_ZADD, ARG_ZPOP, ARG_ZRANGE,
+    ARG_ZSTORE,
+};
+
+fn scored_member(member: &[u8], score: f64) -> ScoredMember {
+    let mut sm = ScoredMember::new();
+    sm.set_member(member.to_vec());
+    sm.score = score;
+    sm
+}
+
+pub fn add(db: &mut Db, req: &ZAddOp) -> OpResult {
+    // validate request
+    if req.members.is_empty()
+        || (req.create_excl && req.require_exist)
+        || (req.create_excl && (req.only_greater || req.only_less))
+        || (req.only_greater && req.only_less)
+        || (req.incr && req.members.len() != 1)
+    {
+        return OpError::invalid_args("Invalid args").into();
+    }
+    for sm in req.members.iter() {
+        if sm.score.is_nan() {
+            return OpError::invalid_args("score is not a number").into();
+        }
+    }
+
+    // get sorted set to mutate.
+    let zs = {
+        let key = req.get_key();
+        match db.get_mut(key) {
+            None => {
+                // if we only update existing members, go no further
+                if req.require_exist {
+                    let mut op_res = OpResult::new();
+                    op_res.ok = true;
+                    op_res.otype = OpType::ZSET_ADD;
+                    if req.incr {
+                        op_res.set_zset(ZSetRes::new());
+                    } else {
+                        op_res.set_count(CountRes::new());
+                    }
+                    return op_res;
+                }
+
+                // does not exist; create empty sorted set
+                db.insert(key.to_vec(), Atom::SortedSet(SortedSet::new()));
+                match db.get_mut(key) {
+                    None => unreachable!(),
+                    Some(atom) => match atom {
+                        Atom::SortedSet(zs) => zs,
+                        _ => unreachable!(),
+                    },
+                }
+            }
+
+            // found the key.  grab ref.
+            Some(atom) => match atom {
+                Atom::SortedSet(zs) => zs,
+                _ => {
+                    return OpError::wrong_type("sorted set").into();
+                }
+            },
+        }
+    };
+
+    // iterate through each member in request, adding/updating
+    let mut n_added = 0;
+    let mut n_changed = 0;
+    let mut zset_res = ZSetRes::new();
+    for sm in req.members.iter() {
+        let member = sm.get_member();
+        let old_score = zs.score(member);
+
+        // calculate new score
+        let score = match (req.incr, old_score) {
+            (true, Some(old)) => old + sm.score,
+            _ => sm.score,
+        };
+        if score.is_nan() {
+            return OpError::new(ErrorCode::OVERFLOW, "resulting score is not a number").into();
+        }
+
+        // apply NX, XX, GT, LT conditions
+        let skip = match old_score {
+            None => req.require_exist,
+            Some(old) => {
+                req.create_excl
+                    || (req.only_greater && score <= old)
+                    || (req.only_less && score >= old)
+            }
+        };
+        if skip {
+            continue;
+        }
+
+        match old_score {
+            None => n_added += 1,
+            Some(old) => {
+                if old != score {
+                    n_changed += 1;
+                }
+            }
+        }
+        zs.insert(member.to_vec(), score);
+
+        if req.incr {
+            zset_res.members.push(scored_member(member, score));
+        }
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::ZSET_ADD;
+
+    // INCR: return new score, or no members if update was not applied.
+    // otherwise return number of members added (CH: plus updated)
+    if req.incr {
+        op_res.set_zset(zset_res);
+    } else {
+        let mut count_res = CountRes::new();
+        count_res.n = match req.count_changed {
+            true => n_added + n_changed,
+            false => n_added,
+        };
+        op_res.set_count(count_res);
+    }
+
+    op_res
+}
+
+pub fn rem(db: &mut Db, req: &KeyedListOp) -> OpResult {
+    // get sorted set to mutate
+    let zs = {
+        match db.get_mut(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::SortedSet(zs) => zs,
+                _ => {
+                    return OpError::wrong_type("sorted set").into();
+                }
+            },
+        }
+    };
+
+    // iterate through each member in request, removing
+    let mut n_removed = 0;
+    for member in req.elements.iter() {
+        if zs.remove(member).is_some() {
+            n_removed += 1;
+        }
+    }
+
+    // return number of members removed
+    let mut count_res = CountRes::new();
+    count_res.n = n_removed;
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::ZSET_REM;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn card(db: &Db, req: &KeyOp) -> OpResult {
+    // get sorted set to query
+    let zs = {
+        match db.peek(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::SortedSet(zs) => zs,
+                _ => {
+                    return OpError::wrong_type("sorted set").into();
+                }
+            },
+        }
+    };
+
+    // return number of members
+    let mut count_res = CountRes::new();
+    count_res.n = zs.len() as u64;
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = OpType::ZSET_CARD;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+pub fn score(db: &Db, req: &KeyedListOp, otype: OpType) -> OpResult {
+    // validate request
+    if req.elements.len() != 1 {
+        return OpError::invalid_args("Invalid args").into();
+    }
+
+    // get sorted set to query
+    let zs = {
+        match db.peek(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::SortedSet(zs) => zs,
+                _ => {
+                    return OpError::wrong_type("sorted set").into();
+                }
+            },
+        }
+    };
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+
+    let member = &req.elements[0];
+    match otype {
+        // ZSET_SCORE: return member and its score
+        OpType::ZSET_SCORE => {
+            let score = match zs.score(member) {
+                None => {
+                    return OpError::not_found().into();
+                }
+                Some(score) => score,
+            };
+
+            let mut zset_res = ZSetRes::new();
+            zset_res.members.push(scored_member(member, score));
+            op_res.set_zset(zset_res);
+        }
+
+        // ZSET_RANK, ZSET_REVRANK: return position of member
+        OpType::ZSET_RANK | OpType::ZSET_REVRANK => {
+            let rank = match zs.rank(member) {
+                None => {
+                    return OpError::not_found().into();
+                }
+                Some(rank) => rank,
+            };
+
+            let mut count_res = CountRes::new();
+            count_res.n = match otype {
+                OpType::ZSET_REVRANK => (zs.len() - 1 - rank) as u64,
+                _ => rank as u64,
+            };
+            op_res.set_count(count_res);
+        }
+
+        _ => unreachable!(),
+    }
+
+    op_res
+}
+
+// number of members ordered before a lex range bound:
+// "-", "+", "[member" (inclusive) or "(member" (exclusive)
+fn lex_index(zs: &SortedSet, bound: &[u8], is_max: bool) -> Option<usize> {
+    if bound == b"-" {
+        return Some(0);
+    }
+    if bound == b"+" {
+        return Some(zs.len());
+    }
+    if bound.is_empty() {
+        return None;
+    }
+
+    let m = &bound[1..];
+    let inclusive = match bound[0] {
+        b'[' => true,
+        b'(' => false,
+        _ => return None,
+    };
+
+    // a max bound includes the member itself iff inclusive;
+    // a min bound excludes the member itself iff exclusive
+    if inclusive == is_max {
+        Some(zs.count_while(&|_, member| member <= m))
+    } else {
+        Some(zs.count_while(&|_, member| member < m))
+    }
+}
+
+// number of members ordered before a score range bound
+fn score_index(zs: &SortedSet, bound: f64, exclusive: bool, is_max: bool) -> usize {
+    if exclusive != is_max {
+        zs.count_while(&|score, _| score <= bound)
+    } else {
+        zs.count_while(&|score, _| score < bound)
+    }
+}
+
+pub fn range(db: &Db, req: &ZRangeOp, otype: OpType) -> OpResult {
+    // validate request
+    if req.has_limit && req.by == ZRangeOp_RangeBy::INDEX {
+        return OpError::invalid_args("LIMIT requires a score or lex range").into();
+    }
+    if req.by == ZRangeOp_RangeBy::SCORE && (req.min.is_nan() || req.max.is_nan()) {
+        return OpError::invalid_args("score is not a number").into();
+    }
+
+    // get sorted set to query
+    let zs = {
+        match db.peek(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::SortedSet(zs) => zs,
+                _ => {
+                    return OpError::wrong_type("sorted set").into();
+                }
+            },
+        }
+    };
+
+    // determine range [lo, hi) of matching members, by ascending index
+    let n = zs.len() as i64;
+    let (lo, hi) = match req.by {
+        ZRangeOp_RangeBy::INDEX => {
+            // negative indices count from end
+            let mut start = req.start;
+            let mut stop = req.stop;
+            if start < 0 {
+                start += n;
+            }
+            if stop < 0 {
+                stop += n;
+            }
+            if start < 0 {
+                start = 0;
+            }
+            if stop >= n {
+                stop = n - 1;
+            }
+
+            // reverse: indices count from highest score
+            if start > stop {
+                (0, 0)
+            } else if req.reverse {
+                ((n - 1 - stop) as usize, (n - start) as usize)
+            } else {
+                (start as usize, (stop + 1) as usize)
+            }
+        }
+
+        ZRangeOp_RangeBy::SCORE => (
+            score_index(zs, req.min, req.min_exclusive, false),
+            score_index(zs, req.max, req.max_exclusive, true),
+        ),
+
+        ZRangeOp_RangeBy::LEX => {
+            let lo = lex_index(zs, req.get_min_lex(), false);
+            let hi = lex_index(zs, req.get_max_lex(), true);
+            match (lo, hi) {
+                (Some(lo), Some(hi)) => (lo, hi),
+                _ => {
+                    return OpError::invalid_args("invalid lex range bound").into();
+                }
+            }
+        }
+    };
+    let hi = hi.max(lo);
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+
+    // ZSET_COUNT: return number of members in range
+    if otype == OpType::ZSET_COUNT {
+        let mut count_res = CountRes::new();
+        count_res.n = (hi - lo) as u64;
+        op_res.set_count(count_res);
+        return op_res;
+    }
+
+    // ZSET_RANGE: return members in range, applying LIMIT
+    let mut indices: Box<dyn Iterator<Item = usize>> = match req.reverse {
+        true => Box::new((lo..hi).rev()),
+        false => Box::new(lo..hi),
+    };
+    if req.has_limit {
+        indices = Box::new(indices.skip(req.offset as usize));
+        if req.count >= 0 {
+            indices = Box::new(indices.take(req.count as usize));
+        }
+    }
+
+    let mut zset_res = ZSetRes::new();
+    for i in indices {
+        let (member, score) = zs.get_index(i).unwrap();
+        zset_res.members.push(scored_member(member, score));
+    }
+    op_res.set_zset(zset_res);
+
+    op_res
+}
+
+pub fn pop(db: &mut Db, req: &ZPopOp, otype: OpType) -> OpResult {
+    let pop_max = match otype {
+        OpType::ZSET_POPMAX => true,
+        _ => false,
+    };
+
+    // get sorted set to mutate
+    let zs = {
+        match db.get_mut(req.get_key()) {
+            None => {
+                return OpError::not_found().into();
+            }
+            Some(atom) => match atom {
+                Atom::SortedSet(zs) => zs,
+                _ => {
+                    return OpError::wrong_type("sorted set").into();
+                }
+            },
+        }
+    };
+
+    // remove members from lowest (or highest) end
+    let count = {
+        if req.count == 0 {
+            1
+        } else {
+            req.count as usize
+        }
+    };
+
+    let mut zset_res = ZSetRes::new();
+    for _ in 0..count {
+        if zs.is_empty() {
+            break;
+        }
+
+        let index = match pop_max {
+            true => zs.len() - 1,
+            false => 0,
+        };
+        let member = zs.get_index(index).unwrap().0.clone();
+        let score = zs.remove(&member).unwrap();
+        zset_res.members.push(scored_member(&member, score));
+    }
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+    op_res.set_zset(zset_res);
+
+    op_res
+}
+
+// aggregate two weighted scores.  inf - inf is treated as 0.
+fn aggregate(agg: ZStoreOp_Aggregate, a: f64, b: f64) -> f64 {
+    match agg {
+        ZStoreOp_Aggregate::SUM => {
+            let sum = a + b;
+            if sum.is_nan() {
+                0.0
+            } else {
+                sum
+            }
+        }
+        ZStoreOp_Aggregate::MIN => a.min(b),
+        ZStoreOp_Aggregate::MAX => a.max(b),
+    }
+}
+
+pub fn store(db: &mut Db, req: &ZStoreOp, otype: OpType) -> OpResult {
+    let is_union = match otype {
+        OpType::ZSET_UNIONSTORE => true,
+        _ => false,
+    };
+
+    // validate request
+    if req.keys.is_empty()
+        || req.store_key.is_empty()
+        || (!req.weights.is_empty() && req.weights.len() != req.keys.len())
+    {
+        return OpError::invalid_args("Invalid args").into();
+    }
+    for weight in req.weights.iter() {
+        if weight.is_nan() {
+            return OpError::invalid_args("weight is not a number").into();
+        }
+    }
+
+    // read each input, applying weights.  sets are accepted, with each
+    // member scored 1.0.  missing keys are treated as empty.
+    let mut inputs: Vec<HashMap<Vec<u8>, f64>> = Vec::new();
+    for (i, key) in req.keys.iter().enumerate() {
+        let weight = match req.weights.is_empty() {
+            true => 1.0,
+            false => req.weights[i],
+        };
+
+        // weighted score; inf * 0 is treated as 0
+        let weighted = |score: f64| {
+            let ws = score * weight;
+            if ws.is_nan() {
+                0.0
+            } else {
+                ws
+            }
+        };
+
+        let mut input = HashMap::new();
+        match db.get(key) {
+            None => {}
+            Some(Atom::SortedSet(zs)) => {
+                for (member, score) in zs.iter() {
+                    input.insert(member.clone(), weighted(score));
+                }
+            }
+            Some(Atom::Set(st)) => {
+                for member in st.iter() {
+                    input.insert(member.clone(), weighted(1.0));
+                }
+            }
+            Some(_) => {
+                return OpError::wrong_type("sorted set").into();
+            }
+        }
+        inputs.push(input);
+    }
+
+    // union: aggregate every member of every input.
+    // intersect: aggregate members of first input present in all others.
+    let mut result: HashMap<Vec<u8>, f64> = HashMap::new();
+    if is_union {
+        for input in inputs.iter() {
+            for (member, score) in input.iter() {
+                let new_score = match result.get(member) {
+                    None => *score,
+                    Some(old) => aggregate(req.aggregate, *old, *score),
+                };
+                result.insert(member.clone(), new_score);
+            }
+        }
+    } else {
+        for (member, score) in inputs[0].iter() {
+            let mut agg_score = *score;
+            let mut in_all = true;
+            for input in inputs[1..].iter() {
+                match input.get(member) {
+                    None => {
+                        in_all = false;
+                        break;
+                    }
+                    Some(s) => agg_score = aggregate(req.aggregate, agg_score, *s),
+                }
+            }
+            if in_all {
+                result.insert(member.clone(), agg_score);
+            }
+        }
+    }
+
+    // store result, returning count stored
+    let mut zs = SortedSet::new();
+    for (member, score) in result {
+        zs.insert(member, score);
+    }
+
+    let mut count_res = CountRes::new();
+    count_res.n = zs.len() as u64;
+
+    db.insert(req.store_key.to_vec(), Atom::SortedSet(zs));
+
+    // standard operation result assignment & final return
+    let mut op_res = OpResult::new();
+
+    op_res.ok = true;
+    op_res.otype = otype;
+    op_res.set_count(count_res);
+
+    op_res
+}
+
+/// SortedSet module methods.
+pub const METHODS: &[Method] = &[
+    Method {
+        module: "SortedSet",
+        name: "Add",
+        otype: OpType::ZSET_ADD,
+        arg: Some(ARG_ZADD),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| add(db, op.get_zadd())),
+    },
+    Method {
+        module: "SortedSet",
+        name: "Remove",
+        otype: OpType::ZSET_REM,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| rem(db, op.get_keyed_list())),
+    },
+    Method {
+        module: "SortedSet",
+        name: "Score",
+        otype: OpType::ZSET_SCORE,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| score(db, op.get_keyed_list(), op.otype)),
+    },
+    Method {
+        module: "SortedSet",
+        name: "Rank",
+        otype: OpType::ZSET_RANK,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| score(db, op.get_keyed_list(), op.otype)),
+    },
+    Method {
+        module: "SortedSet",
+        name: "RevRank",
+        otype: OpType::ZSET_REVRANK,
+        arg: Some(ARG_KEYED_LIST),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| score(db, op.get_keyed_list(), op.otype)),
+    },
+    Method {
+        module: "SortedSet",
+        name: "Card",
+        otype: OpType::ZSET_CARD,
+        arg: Some(ARG_KEY),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| card(db, op.get_key())),
+    },
+    Method {
+        module: "SortedSet",
+        name: "Range",
+        otype: OpType::ZSET_RANGE,
+        arg: Some(ARG_ZRANGE),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| range(db, op.get_zrange(), op.otype)),
+    },
+    Method {
+        module: "SortedSet",
+        name: "Count",
+        otype: OpType::ZSET_COUNT,
+        arg: Some(ARG_ZRANGE),
+        scope: KeyScope::Keys,
+        access: Access::Read,
+        flags: 0,
+        handler: Handler::DbRead(|db, op| range(db, op.get_zrange(), op.otype)),
+    },
+    Method {
+        module: "SortedSet",
+        name: "PopMin",
+        otype: OpType::ZSET_POPMIN,
+        arg: Some(ARG_ZPOP),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| pop(db, op.get_zpop(), op.otype)),
+    },
+    Method {
+        module: "SortedSet",
+        name: "PopMax",
+        otype: OpType::ZSET_POPMAX,
+        arg: Some(ARG_ZPOP),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| pop(db, op.get_zpop(), op.otype)),
+    },
+    Method {
+        module: "SortedSet",
+        name: "UnionStore",
+        otype: OpType::ZSET_UNIONSTORE,
+        arg: Some(ARG_ZSTORE),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| store(db, op.get_zstore(), op.otype)),
+    },
+    Method {
+        module: "SortedSet",
+        name: "InterStore",
+        otype: OpType::ZSET_INTERSTORE,
+        arg: Some(ARG_ZSTORE),
+        scope: KeyScope::Keys,
+        access: Access::Write,
+        flags: 0,
+        handler: Handler::Db(|db, op| store(db, op.get_zstore(), op.otype)),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::db::Db;
+    use crate::zset;
+    use memds_proto::memds_api::{
+        ErrorCode, KeyedListOp, OpType, ScoredMember, ZAddOp, ZPopOp, ZRangeOp, ZRangeOp_RangeBy,
+        ZStoreOp, ZStoreOp_Aggregate,
+    };
+    use memds_proto::{Atom, SortedSet};
+    use std::collections::HashSet;
+
+    fn get_test_db() -> Db {
+        let mut db = Db::new();
+        db.insert(b"foo".to_vec(), Atom::String(b"bar".to_vec()));
+
+        let mut zs = SortedSet::new();
+        zs.insert(b"a".to_vec(), 1.0);
+        zs.insert(b"b".to_vec(), 2.0);
+        zs.insert(b"c".to_vec(), 3.0);
+        zs.insert(b"d".to_vec(), 4.0);
+        db.insert(b"zset1".to_vec(), Atom::SortedSet(zs));
+
+        let mut zs = SortedSet::new();
+        zs.insert(b"c".to_vec(), 10.0);
+        zs.insert(b"e".to_vec(), 20.0);
+        db.insert(b"zset2".to_vec(), Atom::SortedSet(zs));
+
+        let mut st = HashSet::new();
+        st.insert(b"a".to_vec());
+        st.insert(b"c".to_vec());
+        db.insert(b"set1".to_vec(), Atom::Set(st));
+
+        db
+    }
+
+    fn sm(member: &[u8], score: f64) -> ScoredMember {
+        let mut sm = ScoredMember::new();
+        sm.set_member(member.to_vec());
+        sm.score = score;
+        sm
+    }
+
+    fn members(res: &memds_proto::memds_api::OpResult) -> Vec<Vec<u8>> {
+        res.get_zset()
+            .members
+            .iter()
+            .map(|sm| sm.member.clone())
+            .collect()
+    }
+
+    #[test]
+    fn add() {
+        let mut db = get_test_db();
+
+        // add one new, update one existing
+        let mut req = ZAddOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.members.push(sm(b"a", 5.0));
+        req.members.push(sm(b"z", 0.5));
+
+        let res = zset::add(&mut db, &req);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.otype, OpType::ZSET_ADD);
+        assert_eq!(res.get_count().n, 1);
+
+        // CH counts updated members
+        req.members.clear();
+        req.members.push(sm(b"a", 6.0));
+        req.count_changed = true;
+        let res = zset::add(&mut db, &req);
+        assert_eq!(res.get_count().n, 1);
+
+        // GT: lower score not applied
+        let mut req = ZAddOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.members.push(sm(b"a", 1.0));
+        req.only_greater = true;
+        req.count_changed = true;
+        let res = zset::add(&mut db, &req);
+        assert_eq!(res.get_count().n, 0);
+
+        // NX: existing member not updated
+        let mut req = ZAddOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.members.push(sm(b"a", 1.0));
+        req.create_excl = true;
+        let res = zset::add(&mut db, &req);
+        assert_eq!(res.get_count().n, 0);
+
+        // INCR returns new score
+        let mut req = ZAddOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.members.push(sm(b"a", 1.5));
+        req.incr = true;
+        let res = zset::add(&mut db, &req);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_zset().members[0].score, 7.5);
+
+        // XX of missing key creates nothing
+        let mut req = ZAddOp::new();
+        req.set_key(b"zset9".to_vec());
+        req.members.push(sm(b"a", 1.0));
+        req.require_exist = true;
+        let res = zset::add(&mut db, &req);
+        assert_eq!(res.ok, true);
+        assert!(db.get(b"zset9").is_none());
+
+        // invalid combination; wrong type
+        let mut req = ZAddOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.members.push(sm(b"a", 1.0));
+        req.create_excl = true;
+        req.only_less = true;
+        let res = zset::add(&mut db, &req);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::INVALID_ARGUMENT);
+
+        req.set_key(b"foo".to_vec());
+        req.only_less = false;
+        let res = zset::add(&mut db, &req);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::WRONGTYPE);
+    }
+
+    #[test]
+    fn score_rank_rem() {
+        let mut db = get_test_db();
+
+        let mut req = KeyedListOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.elements.push(b"c".to_vec());
+
+        let res = zset::score(&db, &req, OpType::ZSET_SCORE);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_zset().members[0].score, 3.0);
+
+        let res = zset::score(&db, &req, OpType::ZSET_RANK);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 2);
+
+        let res = zset::score(&db, &req, OpType::ZSET_REVRANK);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 1);
+
+        req.elements.push(b"x".to_vec());
+        let res = zset::rem(&mut db, &req);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 1);
+
+        req.elements.clear();
+        req.elements.push(b"c".to_vec());
+        let res = zset::score(&db, &req, OpType::ZSET_SCORE);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn range() {
+        let db = get_test_db();
+
+        // by index
+        let mut req = ZRangeOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.start = 1;
+        req.stop = -1;
+        let res = zset::range(&db, &req, OpType::ZSET_RANGE);
+        assert_eq!(res.ok, true);
+        assert_eq!(
+            members(&res),
+            vec![b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]
+        );
+
+        req.reverse = true;
+        req.start = 0;
+        req.stop = 1;
+        let res = zset::range(&db, &req, OpType::ZSET_RANGE);
+        assert_eq!(members(&res), vec![b"d".to_vec(), b"c".to_vec()]);
+
+        // by score, with exclusive bound and limit
+        let mut req = ZRangeOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.by = ZRangeOp_RangeBy::SCORE;
+        req.min = 1.0;
+        req.min_exclusive = true;
+        req.max = f64::INFINITY;
+        let res = zset::range(&db, &req, OpType::ZSET_COUNT);
+        assert_eq!(res.get_count().n, 3);
+
+        req.has_limit = true;
+        req.offset = 1;
+        req.count = 1;
+        let res = zset::range(&db, &req, OpType::ZSET_RANGE);
+        assert_eq!(members(&res), vec![b"c".to_vec()]);
+
+        // by lex
+        let mut req = ZRangeOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.by = ZRangeOp_RangeBy::LEX;
+        req.set_min_lex(b"(a".to_vec());
+        req.set_max_lex(b"[c".to_vec());
+        let res = zset::range(&db, &req, OpType::ZSET_RANGE);
+        assert_eq!(members(&res), vec![b"b".to_vec(), b"c".to_vec()]);
+
+        req.set_max_lex(b"c".to_vec());
+        let res = zset::range(&db, &req, OpType::ZSET_RANGE);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn pop() {
+        let mut db = get_test_db();
+
+        let mut req = ZPopOp::new();
+        req.set_key(b"zset1".to_vec());
+        req.count = 2;
+        let res = zset::pop(&mut db, &req, OpType::ZSET_POPMAX);
+        assert_eq!(res.ok, true);
+        assert_eq!(members(&res), vec![b"d".to_vec(), b"c".to_vec()]);
+
+        req.count = 0;
+        let res = zset::pop(&mut db, &req, OpType::ZSET_POPMIN);
+        assert_eq!(members(&res), vec![b"a".to_vec()]);
+    }
+
+    #[test]
+    fn store() {
+        let mut db = get_test_db();
+
+        let mut req = ZStoreOp::new();
+        req.keys.push(b"zset1".to_vec());
+        req.keys.push(b"zset2".to_vec());
+        req.keys.push(b"set1".to_vec());
+        req.set_store_key(b"dest".to_vec());
+
+        let res = zset::store(&mut db, &req, OpType::ZSET_INTERSTORE);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 1);
+        match db.get(b"dest") {
+            Some(Atom::SortedSet(zs)) => assert_eq!(zs.score(b"c"), Some(14.0)),
+            _ => panic!("not a sorted set"),
+        }
+
+        // weighted union, aggregate max
+        req.weights.push(1.0);
+        req.weights.push(2.0);
+        req.weights.push(1.0);
+        req.aggregate = ZStoreOp_Aggregate::MAX;
+        let res = zset::store(&mut db, &req, OpType::ZSET_UNIONSTORE);
+        assert_eq!(res.ok, true);
+        assert_eq!(res.get_count().n, 5);
+        match db.get(b"dest") {
+            Some(Atom::SortedSet(zs)) => {
+                assert_eq!(zs.score(b"c"), Some(20.0));
+                assert_eq!(zs.score(b"e"), Some(40.0));
+            }
+            _ => panic!("not a sorted set"),
+        }
+
+        // weights must match keys
+        req.weights.pop();
+        let res = zset::store(&mut db, &req, OpType::ZSET_UNIONSTORE);
+        assert_eq!(res.ok, false);
+        assert_eq!(res.err_code, ErrorCode::INVALID_ARGUMENT);
+    }
+}